@@ -1,16 +1,23 @@
-use std::path::PathBuf;
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use gallivant::ErrorPolicy;
 
 ////////////////////////////////////////////////////////////////
 // types
 ////////////////////////////////////////////////////////////////
 
 #[derive(Parser, Debug, Clone, Default)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 pub struct Args {
-    #[arg(short, long)]
-    pub script: PathBuf,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[arg(short, long, required = true)]
+    pub script: Option<PathBuf>,
 
     #[arg(short, long)]
     pub tcu: Option<String>,
@@ -20,6 +27,181 @@ pub struct Args {
 
     #[arg(short, long)]
     pub debug: bool,
+
+    /// Don't print messages from COMMENT or checkpoints. Warnings and dialogs are still shown.
+    #[arg(short, long, conflicts_with = "debug")]
+    pub quiet: bool,
+
+    /// Print the requests the script would make without communicating with any devices.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Write the results of test commands to a JUnit XML file.
+    #[arg(long, value_name = "PATH")]
+    pub output_junit: Option<PathBuf>,
+
+    /// Write the results of test commands to stdout in the Test Anything Protocol format.
+    #[arg(long)]
+    pub output_tap: bool,
+
+    /// Write a CSV row for each TCUTEST, TCUEXACT, PRINTERTEST, USBPRINTERTEST, USBTEST or USBEXACT
+    /// as it completes.
+    #[arg(long, value_name = "PATH")]
+    pub output_csv: Option<PathBuf>,
+
+    /// Write a standalone HTML report of the test results, highlighting any failed lines of the
+    /// script.
+    #[arg(long, value_name = "PATH")]
+    pub report_html: Option<PathBuf>,
+
+    /// Append a timestamped line to a file for each request made and error encountered while
+    /// running the script, including comments, dialogs and checkpoints.
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Define a variable that the script can reference, or insert into strings as ${NAME}. May be
+    /// given multiple times.
+    #[arg(long = "param", value_name = "NAME=VALUE", value_parser = parse_param)]
+    pub params: Vec<(String, u32)>,
+
+    /// Record the bytes sent to and received from the TCU to a file.
+    #[arg(long, value_name = "PATH", conflicts_with = "playback")]
+    pub record: Option<PathBuf>,
+
+    /// Replay the TCU's responses from a file previously written using --record instead of
+    /// communicating with a TCU.
+    #[arg(long, value_name = "PATH")]
+    pub playback: Option<PathBuf>,
+
+    /// Warn when the measurements taken while retrying a test have a standard deviation greater
+    /// than this.
+    #[arg(long, value_name = "VALUE", value_parser = parse_max_std_dev)]
+    pub max_std_dev: Option<f32>,
+
+    /// Time in milliseconds to wait for the TCU's port to flush before giving up.
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    pub flush_timeout: u64,
+
+    /// Maximum number of bytes a single USBPRINT may send to the printer.
+    #[arg(long, value_name = "BYTES")]
+    pub usb_print_limit: Option<u32>,
+
+    /// Stop the script after this many commands have been executed, counting each iteration of a
+    /// loop.
+    #[arg(long, value_name = "COUNT")]
+    pub max_commands: Option<u64>,
+
+    /// Stop the script before the next command if this file exists, then delete it. The summary
+    /// is still printed and the exit code is 2.
+    #[arg(long, value_name = "PATH")]
+    pub stop_file: Option<PathBuf>,
+
+    /// Save the script's progress to a file each time a CHECKPOINT, TESTGROUP or ENDTESTGROUP is
+    /// reached so that it can be resumed using --resume.
+    #[arg(long, value_name = "PATH")]
+    pub checkpoint_file: Option<PathBuf>,
+
+    /// Resume a script from the progress saved in a file by --checkpoint-file. The script is
+    /// taken from the file and resumed from the last checkpoint reached, with the state the script
+    /// had there. Parameters aren't applied since the file contains the values of every variable.
+    #[arg(long, value_name = "PATH")]
+    pub resume: Option<PathBuf>,
+
+    /// Print how long each WAIT actually took.
+    #[arg(long)]
+    pub verbose_timing: bool,
+
+    /// Which errors to continue running the script after. One of 'abort', 'continue-on-io' or
+    /// 'continue-on-all'.
+    #[arg(long, value_name = "POLICY", value_parser = parse_error_policy, default_value = "abort")]
+    pub error_policy: ErrorPolicy,
+
+    /// Highlight commands and comments using ANSI colors. Enabled by default when stdout is a
+    /// terminal.
+    #[arg(long, overrides_with = "no_color")]
+    pub color: bool,
+
+    /// Disable highlighting.
+    #[arg(long, overrides_with = "color")]
+    pub no_color: bool,
+}
+
+////////////////////////////////////////////////////////////////
+
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// List the serial ports available on the system.
+    ListPorts,
+}
+
+////////////////////////////////////////////////////////////////
+// methods
+////////////////////////////////////////////////////////////////
+
+impl Args {
+    /// Return the path of the script to run. Only valid when no subcommand is given.
+    ///
+    pub fn script(&self) -> &Path {
+        self.script
+            .as_deref()
+            .expect("Script is required when no subcommand is given")
+    }
+
+    /// Return whether output should be highlighted.
+    ///
+    pub fn color(&self) -> bool {
+        match (self.color, self.no_color) {
+            (true, _) => true,
+            (_, true) => false,
+            _ => std::io::stdout().is_terminal(),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Parse a parameter given in the form `NAME=VALUE`. Values may be given in hex by prefixing them
+/// with `$`, the same as in scripts.
+///
+fn parse_param(param: &str) -> Result<(String, u32), String> {
+    let Some((name, value)) = param.split_once('=') else {
+        return Err(format!("Expected NAME=VALUE but found '{param}'"));
+    };
+
+    let value = match value.strip_prefix('$') {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|error| format!("Invalid value '{value}': {error}"))?;
+
+    Ok((name.to_owned(), value))
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Parse a maximum standard deviation, which must be a finite number.
+///
+fn parse_max_std_dev(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(max_std_dev) if max_std_dev.is_finite() => Ok(max_std_dev),
+        Ok(_) => Err(format!("Expected a finite number but found '{value}'")),
+        Err(error) => Err(format!("Invalid value '{value}': {error}")),
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Parse an error policy from its name.
+///
+fn parse_error_policy(policy: &str) -> Result<ErrorPolicy, String> {
+    match policy {
+        "abort" => Ok(ErrorPolicy::AbortOnFirst),
+        "continue-on-io" => Ok(ErrorPolicy::ContinueOnIO),
+        "continue-on-all" => Ok(ErrorPolicy::ContinueOnAll),
+        _ => Err(format!(
+            "Expected 'abort', 'continue-on-io' or 'continue-on-all' but found '{policy}'"
+        )),
+    }
 }
 
 ////////////////////////////////////////////////////////////////
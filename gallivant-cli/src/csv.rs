@@ -0,0 +1,183 @@
+use std::{io::Write, ops::RangeInclusive};
+
+use chrono::Utc;
+use gallivant::{ErrorReason, Expr, FrontendRequest, ParsedExpr, ScriptHooks};
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Writes a CSV row for each TCUTEST, TCUEXACT, PRINTERTEST, USBPRINTERTEST, USBTEST or USBEXACT as
+/// it completes, whether it passed or failed. Measured values are left empty if the test didn't
+/// get as far as taking a measurement e.g. because the device stopped responding.
+///
+#[derive(Debug)]
+pub struct CsvWriter<W: Write> {
+    writer: W,
+
+    /// Whether to flush the writer after each row so that the output remains readable if the
+    /// process doesn't exit cleanly.
+    flush_on_each_row: bool,
+
+    /// Expected range of the test performed by the request currently being handled, if any.
+    pending: Option<RangeInclusive<i64>>,
+
+    /// Measurement that passed the test performed by the request currently being handled, if
+    /// reported.
+    measurement: Option<u32>,
+}
+
+////////////////////////////////////////////////////////////////
+
+const HEADER: &str = "timestamp,command,channel,measured_hex,measured_dec,min,max,passed";
+
+////////////////////////////////////////////////////////////////
+// construction / conversion
+////////////////////////////////////////////////////////////////
+
+impl<W: Write> CsvWriter<W> {
+    /// Create a new writer, immediately writing the header row.
+    ///
+    /// # Arguments
+    /// * `writer` - Destination for the CSV output.
+    ///
+    pub fn new(mut writer: W) -> std::io::Result<Self> {
+        writeln!(writer, "{HEADER}")?;
+
+        Ok(Self {
+            writer,
+            flush_on_each_row: false,
+            pending: None,
+            measurement: None,
+        })
+    }
+
+    /// Flush the writer after each row is written.
+    ///
+    pub fn with_flush_on_each_row(mut self, flush_on_each_row: bool) -> Self {
+        self.flush_on_each_row = flush_on_each_row;
+        self
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// methods
+////////////////////////////////////////////////////////////////
+
+impl<W: Write> CsvWriter<W> {
+    fn write_row(&mut self, row: &[String]) -> std::io::Result<()> {
+        writeln!(self.writer, "{}", row.join(","))?;
+
+        if self.flush_on_each_row {
+            self.writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// traits
+////////////////////////////////////////////////////////////////
+
+impl<W: Write> ScriptHooks for CsvWriter<W> {
+    fn before_request(&mut self, _: &ParsedExpr, request: &FrontendRequest) {
+        self.pending = match request {
+            FrontendRequest::TCUTransact(transaction)
+            | FrontendRequest::PrinterTransact(transaction) => {
+                transaction.test().map(|test| test.expected.clone())
+            }
+            _ => None,
+        };
+        self.measurement = None;
+    }
+
+    fn after_measurement(&mut self, _: &ParsedExpr, measurement: u32) {
+        self.measurement = Some(measurement);
+    }
+
+    fn after_request(&mut self, expression: &ParsedExpr, result: Result<(), &gallivant::Error>) {
+        let Some(expected) = self.pending.take() else {
+            return;
+        };
+
+        let (command, channel) = match expression.expression() {
+            Expr::TCUTest { channel, .. } => ("TCUTEST", channel),
+            Expr::PrinterTest { channel, .. } => ("PRINTERTEST", channel),
+            Expr::USBPrinterTest { channel, .. } => ("USBPRINTERTEST", channel),
+            Expr::USBTest { channel, .. } => ("USBTEST", channel),
+            Expr::TCUExact { channel, .. } => ("TCUEXACT", channel),
+            Expr::USBExact { channel, .. } => ("USBEXACT", channel),
+            _ => return,
+        };
+
+        let channel = match channel.expression() {
+            Expr::UInt(channel) => channel.to_string(),
+            Expr::Variable(name) => name.to_owned(),
+            _ => String::new(),
+        };
+
+        let measurement = match result.map_err(|error| error.reason()) {
+            Ok(()) => self.measurement.take(),
+            Err(ErrorReason::TestFailure { test, .. }) => Some(test.measurement),
+            Err(_) => None,
+        };
+
+        let row = [
+            Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            command.to_owned(),
+            channel,
+            measurement.map_or_else(String::new, |value| format!("${value:X}")),
+            measurement.map_or_else(String::new, |value| value.to_string()),
+            expected.start().to_string(),
+            expected.end().to_string(),
+            result.is_ok().to_string(),
+        ];
+
+        self.write_row(&row).expect("Failed to write CSV output");
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use gallivant::Interpreter;
+
+    use super::*;
+    use crate::testing::{self, SharedBuffer};
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_two_tcutests() {
+        let script = "TCUTEST 1, 1000, 12000, 0, \"FAIL 1\"\nTCUTEST 2, $10, $20, 0, \"FAIL 2\"";
+
+        let buffer = SharedBuffer::default();
+        let csv = CsvWriter::new(buffer.clone()).unwrap();
+        let mut interpreter = Interpreter::try_from_str(script).unwrap().with_hooks(csv);
+
+        // The first test passes and the second fails.
+        testing::run(&mut interpreter, &[b"3E8\r", b"30\r"]);
+
+        let output = buffer.contents();
+        let rows: Vec<Vec<&str>> = output.lines().map(|row| row.split(',').collect()).collect();
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], HEADER.split(',').collect::<Vec<_>>());
+
+        // Timestamps depend on when the test is run so only their format is checked.
+        for row in &rows[1..] {
+            assert!(chrono::DateTime::parse_from_rfc3339(row[0]).is_ok());
+        }
+
+        assert_eq!(
+            rows[1][1..],
+            ["TCUTEST", "1", "$3E8", "1000", "1000", "12000", "true"]
+        );
+        assert_eq!(
+            rows[2][1..],
+            ["TCUTEST", "2", "$30", "48", "16", "32", "false"]
+        );
+    }
+}
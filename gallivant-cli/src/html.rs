@@ -0,0 +1,334 @@
+use std::{
+    collections::BTreeSet,
+    io::Write,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use gallivant::{line_col, ErrorReason, Expr, FrontendRequest, ParsedExpr, ScriptHooks};
+
+use crate::junit::escape;
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Collects the results of test commands as a script is executed and writes them to a standalone
+/// HTML report once dropped. The report contains an overall pass/fail banner, a table of every
+/// test and the script itself with the lines of any failed commands highlighted.
+///
+#[derive(Debug)]
+pub struct HtmlReportWriter {
+    path: PathBuf,
+    title: String,
+    script: String,
+
+    /// Expected range of the test performed by the request currently being handled, if any.
+    pending: Option<RangeInclusive<i64>>,
+
+    tests: Vec<TestRow>,
+
+    /// Lines of the script, starting from 1, containing commands that failed.
+    failed_lines: BTreeSet<usize>,
+}
+
+////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug)]
+struct TestRow {
+    command: String,
+    expected: RangeInclusive<i64>,
+    outcome: Outcome,
+}
+
+////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug)]
+enum Outcome {
+    Passed,
+
+    /// A WARNTEST failed with the given measurement.
+    Warning(u32),
+
+    /// The test failed with the given measurement.
+    Failed(u32),
+
+    /// The test couldn't be completed e.g. due to an IO error.
+    Error(String),
+}
+
+////////////////////////////////////////////////////////////////
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 2em; color: #212121; }
+h1 { font-size: 1.4em; }
+.banner { padding: 0.8em 1em; font-size: 1.4em; font-weight: bold; color: #ffffff; }
+.banner.passed { background: #2e7d32; }
+.banner.failed { background: #c62828; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #bdbdbd; padding: 0.3em 0.6em; text-align: left; }
+th { background: #eeeeee; }
+td.command { font-family: monospace; }
+tr.failed td.outcome { color: #c62828; font-weight: bold; }
+tr.warning td.outcome { color: #ef6c00; font-weight: bold; }
+tr.passed td.outcome { color: #2e7d32; }
+pre { background: #fafafa; border: 1px solid #e0e0e0; padding: 1em; }
+pre .number { color: #9e9e9e; }
+pre .failed { background: #ffcdd2; color: #b71c1c; }
+"#;
+
+////////////////////////////////////////////////////////////////
+// construction / conversion
+////////////////////////////////////////////////////////////////
+
+impl HtmlReportWriter {
+    /// Create a new writer.
+    ///
+    /// # Arguments
+    /// * `path` - File the report is written to once the writer is dropped.
+    /// * `title` - Title of the report e.g. the name of the script.
+    /// * `script` - Script being executed.
+    ///
+    pub fn new(path: &Path, title: impl Into<String>, script: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+            title: title.into(),
+            script: script.to_owned(),
+            pending: None,
+            tests: Vec::new(),
+            failed_lines: BTreeSet::new(),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// methods
+////////////////////////////////////////////////////////////////
+
+impl HtmlReportWriter {
+    /// Return true if every test passed and no other command failed.
+    ///
+    fn passed(&self) -> bool {
+        self.failed_lines.is_empty()
+    }
+
+    /// Write the report as HTML.
+    ///
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let title = escape(&self.title);
+
+        writeln!(writer, "<!DOCTYPE html>")?;
+        writeln!(writer, r#"<html lang="en">"#)?;
+        writeln!(writer, "<head>")?;
+        writeln!(writer, r#"<meta charset="utf-8">"#)?;
+        writeln!(writer, "<title>{title}</title>")?;
+        writeln!(writer, "<style>{STYLE}</style>")?;
+        writeln!(writer, "</head>")?;
+        writeln!(writer, "<body>")?;
+        writeln!(writer, "<h1>{title}</h1>")?;
+
+        let passed = self
+            .tests
+            .iter()
+            .filter(|test| matches!(test.outcome, Outcome::Passed | Outcome::Warning(_)))
+            .count();
+
+        let (class, verdict) = if self.passed() {
+            ("passed", "PASSED")
+        } else {
+            ("failed", "FAILED")
+        };
+
+        writeln!(
+            writer,
+            r#"<div class="banner {class}">{verdict} - {passed} of {} tests passed</div>"#,
+            self.tests.len()
+        )?;
+
+        writeln!(writer, "<h2>Tests</h2>")?;
+        writeln!(writer, "<table>")?;
+        writeln!(
+            writer,
+            "<tr><th>Command</th><th>Expected</th><th>Measured</th><th>Result</th></tr>"
+        )?;
+
+        for test in self.tests.iter() {
+            let (class, measured, result) = match &test.outcome {
+                Outcome::Passed => ("passed", String::from("-"), String::from("Passed")),
+                Outcome::Warning(measurement) => {
+                    ("warning", measurement.to_string(), String::from("Warning"))
+                }
+                Outcome::Failed(measurement) => {
+                    ("failed", measurement.to_string(), String::from("Failed"))
+                }
+                Outcome::Error(message) => ("failed", String::from("-"), escape(message)),
+            };
+
+            writeln!(
+                writer,
+                r#"<tr class="{class}"><td class="command">{}</td><td>{}..{}</td><td>{measured}</td><td class="outcome">{result}</td></tr>"#,
+                escape(&test.command),
+                test.expected.start(),
+                test.expected.end(),
+            )?;
+        }
+
+        writeln!(writer, "</table>")?;
+
+        writeln!(writer, "<h2>Script</h2>")?;
+        write!(writer, "<pre>")?;
+
+        let lines: Vec<&str> = self.script.lines().collect();
+        let width = lines.len().to_string().len();
+        for (i, line) in lines.iter().enumerate() {
+            let number = i + 1;
+            let class = if self.failed_lines.contains(&number) {
+                "line failed"
+            } else {
+                "line"
+            };
+
+            writeln!(
+                writer,
+                r#"<span class="{class}"><span class="number">{number:>width$}</span>  {}</span>"#,
+                escape(line)
+            )?;
+        }
+
+        writeln!(writer, "</pre>")?;
+        writeln!(writer, "</body>")?;
+        writeln!(writer, "</html>")
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// traits
+////////////////////////////////////////////////////////////////
+
+impl ScriptHooks for HtmlReportWriter {
+    fn before_request(&mut self, _: &ParsedExpr, request: &FrontendRequest) {
+        self.pending = match request {
+            FrontendRequest::TCUTransact(transaction)
+            | FrontendRequest::PrinterTransact(transaction) => {
+                transaction.test().map(|test| test.expected.clone())
+            }
+            _ => None,
+        };
+    }
+
+    fn after_request(&mut self, expression: &ParsedExpr, result: Result<(), &gallivant::Error>) {
+        let warning = matches!(expression.expression(), Expr::WarnTest { .. });
+        let span = expression.span();
+
+        if result.is_err() && !warning {
+            let (first, _) = line_col(span, &self.script);
+            let (last, _) = line_col(
+                &(span.end.saturating_sub(1).max(span.start)..span.end),
+                &self.script,
+            );
+            self.failed_lines.extend(first..=last);
+        }
+
+        let Some(expected) = self.pending.take() else {
+            return;
+        };
+
+        let outcome = match result.map_err(|error| error.reason()) {
+            Ok(()) => Outcome::Passed,
+            Err(ErrorReason::TestFailure { test, .. }) if warning => {
+                Outcome::Warning(test.measurement)
+            }
+            Err(ErrorReason::TestFailure { test, .. }) => Outcome::Failed(test.measurement),
+            Err(reason) => Outcome::Error(reason.message()),
+        };
+
+        let command = self
+            .script
+            .chars()
+            .skip(span.start)
+            .take(span.len())
+            .collect::<String>();
+
+        self.tests.push(TestRow {
+            command: command.trim().to_owned(),
+            expected,
+            outcome,
+        });
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl Drop for HtmlReportWriter {
+    fn drop(&mut self) {
+        let result = std::fs::File::create(&self.path).and_then(|mut file| self.write(&mut file));
+
+        if let Err(error) = result {
+            eprintln!(
+                "Failed to write HTML report '{}': {error}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use gallivant::Interpreter;
+
+    use super::*;
+    use crate::testing;
+
+    ////////////////////////////////////////////////////////////////
+
+    /// Write the report of a script run with a mock TCU and return it.
+    ///
+    fn report(name: &str, script: &str, measurements: &[&'static [u8]]) -> String {
+        let path =
+            std::env::temp_dir().join(format!("gallivant-{name}-{}.html", std::process::id()));
+
+        // The report is only written once the writer is dropped along with the interpreter.
+        let html = HtmlReportWriter::new(&path, name, script);
+        let mut interpreter = Interpreter::try_from_str(script).unwrap().with_hooks(html);
+        testing::run(&mut interpreter, measurements);
+        drop(interpreter);
+
+        let output = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        output
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_two_tcutests() {
+        let script = "TCUTEST 1, 1000, 12000, 0, \"FAIL 1\"\nTCUTEST 2, $10, $20, 0, \"<FAIL 2>\"";
+
+        // The first test passes and the second fails.
+        let output = report("two-tcutests", script, &[b"3E8\r", b"30\r"]);
+
+        assert!(output.contains(r#"<div class="banner failed">FAILED - 1 of 2 tests passed</div>"#));
+        assert!(output.contains(
+            r#"<tr class="passed"><td class="command">TCUTEST 1, 1000, 12000, 0, &quot;FAIL 1&quot;</td><td>1000..12000</td><td>-</td><td class="outcome">Passed</td></tr>"#
+        ));
+        assert!(output.contains(
+            r#"<tr class="failed"><td class="command">TCUTEST 2, $10, $20, 0, &quot;&lt;FAIL 2&gt;&quot;</td><td>16..32</td><td>48</td><td class="outcome">Failed</td></tr>"#
+        ));
+        assert!(output.contains(r#"<span class="line"><span class="number">1</span>  TCUTEST 1"#));
+        assert!(output
+            .contains(r#"<span class="line failed"><span class="number">2</span>  TCUTEST 2"#));
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_all_passed() {
+        let script = "TCUTEST 1, 1000, 12000, 0, \"FAIL 1\"\nTCUTEST 2, $10, $20, 0, \"FAIL 2\"";
+        let output = report("all-passed", script, &[b"3E8\r", b"18\r"]);
+
+        assert!(output.contains(r#"<div class="banner passed">PASSED - 2 of 2 tests passed</div>"#));
+        assert!(!output.contains("failed\""));
+    }
+}
@@ -0,0 +1,202 @@
+use std::{io::Write, time::Duration};
+
+use gallivant::{ErrorReason, FailedTest};
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Collects the results of test commands and writes them out in the JUnit XML format.
+///
+#[derive(Clone, Debug, Default)]
+pub struct JUnitReport {
+    classname: String,
+    cases: Vec<TestCase>,
+}
+
+////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug)]
+struct TestCase {
+    name: String,
+    time: Duration,
+    outcome: Outcome,
+}
+
+////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug)]
+enum Outcome {
+    Passed,
+    Failed(FailedTest),
+
+    /// The test couldn't be completed e.g. due to an IO error.
+    Error(String),
+}
+
+////////////////////////////////////////////////////////////////
+// construction / conversion
+////////////////////////////////////////////////////////////////
+
+impl JUnitReport {
+    pub fn new(classname: impl Into<String>) -> Self {
+        Self {
+            classname: classname.into(),
+            cases: Vec::new(),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// methods
+////////////////////////////////////////////////////////////////
+
+impl JUnitReport {
+    /// Record the result of a test command.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the test.
+    /// * `time` - Time taken to complete the test.
+    /// * `result` - Result of handling the test command.
+    ///
+    pub fn push(&mut self, name: String, time: Duration, result: Result<(), &gallivant::Error>) {
+        let outcome = match result {
+            Ok(()) => Outcome::Passed,
+            Err(error) => match error.reason() {
+                ErrorReason::TestFailure { test, .. } => Outcome::Failed(test.clone()),
+                reason => Outcome::Error(reason.message()),
+            },
+        };
+
+        self.cases.push(TestCase {
+            name,
+            time,
+            outcome,
+        });
+    }
+
+    /// Write the report as JUnit XML.
+    ///
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let failures = self
+            .cases
+            .iter()
+            .filter(|case| matches!(case.outcome, Outcome::Failed(_)))
+            .count();
+
+        let errors = self
+            .cases
+            .iter()
+            .filter(|case| matches!(case.outcome, Outcome::Error(_)))
+            .count();
+
+        let time: Duration = self.cases.iter().map(|case| case.time).sum();
+
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            writer,
+            r#"<testsuite name="{}" tests="{}" failures="{failures}" errors="{errors}" time="{:.3}">"#,
+            escape(&self.classname),
+            self.cases.len(),
+            time.as_secs_f64(),
+        )?;
+
+        for case in self.cases.iter() {
+            let open_tag = format!(
+                r#"  <testcase name="{}" classname="{}" time="{:.3}""#,
+                escape(&case.name),
+                escape(&self.classname),
+                case.time.as_secs_f64(),
+            );
+
+            match &case.outcome {
+                Outcome::Passed => writeln!(writer, "{open_tag}/>")?,
+                Outcome::Failed(test) => {
+                    let message = format!(
+                        "Expected between {} and {} but measured {}",
+                        test.expected.start(),
+                        test.expected.end(),
+                        test.measurement
+                    );
+
+                    writeln!(writer, "{open_tag}>")?;
+                    writeln!(
+                        writer,
+                        r#"    <failure message="{}">{}</failure>"#,
+                        escape(&message),
+                        escape(&test.message)
+                    )?;
+                    writeln!(writer, "  </testcase>")?;
+                }
+                Outcome::Error(message) => {
+                    writeln!(writer, "{open_tag}>")?;
+                    writeln!(writer, r#"    <error message="{}"/>"#, escape(message))?;
+                    writeln!(writer, "  </testcase>")?;
+                }
+            }
+        }
+
+        writeln!(writer, "</testsuite>")
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Escape characters that can't appear as-is in XML or HTML text or attribute values.
+///
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use gallivant::Interpreter;
+
+    use super::*;
+    use crate::testing;
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_two_tcutests() {
+        let script = "TCUTEST 1, 1000, 12000, 0, \"FAIL 1\"\nTCUTEST 2, $10, $20, 0, \"<FAIL 2>\"";
+
+        // The first test passes and the second fails.
+        let mut interpreter = Interpreter::try_from_str(script).unwrap();
+        let errors = testing::run(&mut interpreter, &[b"3E8\r", b"30\r"]);
+        assert_eq!(errors.len(), 1);
+
+        let mut report = JUnitReport::new("tests/\"power\".txt");
+        report.push(String::from("FAIL 1"), Duration::ZERO, Ok(()));
+        report.push(String::from("<FAIL 2>"), Duration::ZERO, Err(&errors[0]));
+
+        let mut output = Vec::new();
+        report.write(&mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuite name="tests/&quot;power&quot;.txt" tests="2" failures="1" errors="0" time="0.000">
+  <testcase name="FAIL 1" classname="tests/&quot;power&quot;.txt" time="0.000"/>
+  <testcase name="&lt;FAIL 2&gt;" classname="tests/&quot;power&quot;.txt" time="0.000">
+    <failure message="Expected between 16 and 32 but measured 48">&lt;FAIL 2&gt;</failure>
+  </testcase>
+</testsuite>
+"#
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::io::Write;
+
+use chrono::Utc;
+use gallivant::{FrontendRequest, ParsedExpr, ScriptHooks};
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Writes everything that happens during script execution to a log, one timestamped line per
+/// request or error. Each line is flushed as it's written so that the log remains readable if the
+/// process doesn't exit cleanly.
+///
+#[derive(Debug)]
+pub struct LogWriter<W: Write> {
+    writer: W,
+}
+
+////////////////////////////////////////////////////////////////
+// construction / conversion
+////////////////////////////////////////////////////////////////
+
+impl<W: Write> LogWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// methods
+////////////////////////////////////////////////////////////////
+
+impl<W: Write> LogWriter<W> {
+    /// Write a line to the log, prefixed with the current time.
+    ///
+    /// # Arguments
+    /// * `tag` - Kind of event being logged.
+    /// * `message` - Details of the event.
+    ///
+    fn write_line(&mut self, tag: &str, message: &str) {
+        let time = Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+
+        writeln!(self.writer, "{time} [{tag}] {message}").expect("Failed to write log");
+        self.writer.flush().expect("Failed to write log");
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// traits
+////////////////////////////////////////////////////////////////
+
+impl<W: Write> ScriptHooks for LogWriter<W> {
+    fn before_request(&mut self, _: &ParsedExpr, request: &FrontendRequest) {
+        let (tag, message) = match request {
+            FrontendRequest::None | FrontendRequest::Progress { .. } => return,
+            FrontendRequest::Wait(time) => ("Wait", format!("{}ms", time.as_millis())),
+
+            FrontendRequest::GuiPrint(message) => {
+                if let Some(warning) = message.strip_prefix("[WARNING] ") {
+                    ("Warning", warning.to_owned())
+                } else {
+                    ("GuiPrint", message.to_owned())
+                }
+            }
+            FrontendRequest::Checkpoint(name) => ("Checkpoint", name.to_owned()),
+            FrontendRequest::GuiDialogue { kind, message } => {
+                ("GuiDialogue", format!("{kind}: {message}"))
+            }
+            FrontendRequest::Notification { key, value } => {
+                ("Notification", format!("{key} = {value}"))
+            }
+
+            FrontendRequest::TCUTransact(transaction) => (
+                "TCUTransact",
+                transaction.bytes().escape_ascii().to_string(),
+            ),
+            FrontendRequest::TCUFlush => ("TCUFlush", String::new()),
+            FrontendRequest::SetBaud(baud) => ("SetBaud", baud.to_string()),
+
+            FrontendRequest::PrinterOpen => ("PrinterOpen", String::new()),
+            FrontendRequest::PrinterClose => ("PrinterClose", String::new()),
+            FrontendRequest::PrinterTransact(transaction) => (
+                "PrinterTransact",
+                transaction.bytes().escape_ascii().to_string(),
+            ),
+        };
+
+        self.write_line(tag, &message);
+    }
+
+    fn after_request(&mut self, _: &ParsedExpr, result: Result<(), &gallivant::Error>) {
+        if let Err(error) = result {
+            self.write_line("Error", &error.reason().message());
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use gallivant::Interpreter;
+
+    use super::*;
+    use crate::testing::{self, SharedBuffer};
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_two_tcutests() {
+        let script = r#"
+COMMENT "Starting"
+CHECKPOINT "phase 1"
+OPENDIALOG "Connect the printer"
+TCUTEST 1, 1000, 12000, 0, "FAIL 1"
+TCUTEST 2, $10, $20, 0, "FAIL 2"
+        "#;
+
+        let buffer = SharedBuffer::default();
+        let log = LogWriter::new(buffer.clone());
+        let mut interpreter = Interpreter::try_from_str(script).unwrap().with_hooks(log);
+
+        // The first test passes and the second fails.
+        testing::run(&mut interpreter, &[b"3E8\r", b"30\r"]);
+
+        // Timestamps depend on when the test is run so only their format is checked.
+        let output = buffer.contents();
+        let messages: Vec<&str> = output
+            .lines()
+            .map(|line| {
+                let (time, message) = line.split_once(' ').unwrap();
+                assert!(chrono::DateTime::parse_from_rfc3339(time).is_ok());
+                message
+            })
+            .collect();
+
+        assert_eq!(
+            messages,
+            [
+                "[GuiPrint] Starting",
+                "[Checkpoint] phase 1",
+                "[GuiDialogue] Notification: Connect the printer",
+                "[TCUTransact] M01\\r",
+                "[TCUTransact] M02\\r",
+                "[Error] Test failed - FAIL 2",
+            ]
+        );
+    }
+}
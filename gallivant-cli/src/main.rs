@@ -1,34 +1,58 @@
 use std::{
-    io::{ErrorKind, Write},
-    time::Duration,
+    io::{BufRead, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
 };
 
-use ariadne::{Report, Source};
+use ariadne::Source;
 use clap::Parser;
 use serialport::{self, SerialPort};
 
-use gallivant::{FrontendRequest, Interpreter, Transaction, TransactionStatus};
-use gallivant_serial::{CommPort, MockTCUPort};
+use gallivant::{
+    ErrorReason, Expr, FrontendRequest, Interpreter, ParsedExpr, Transaction, TransactionStatus,
+};
+use gallivant_serial::{CommPort, MockTCUPort, PlaybackPort, TransactionRecorder};
 
 mod args;
-use args::Args;
+mod csv;
+mod html;
+mod junit;
+mod log;
+mod summary;
+mod tap;
+#[cfg(test)]
+mod testing;
+mod timing;
+mod trace;
+use args::{Args, Command};
+use csv::CsvWriter;
+use html::HtmlReportWriter;
+use junit::JUnitReport;
+use log::LogWriter;
+use summary::TestSummary;
+use tap::TapWriter;
+use timing::WaitTiming;
+use trace::{CommandTrace, RequestTrace};
 
 ////////////////////////////////////////////////////////////////
 
 enum Error {
-    ParseErrors(Vec<gallivant::Error>),
-    RuntimeError(gallivant::Error),
+    Parse(Vec<gallivant::Error>),
+    Runtime(gallivant::Error),
+
+    /// The script was stopped because the file given by --stop-file was found.
+    Stopped(PathBuf),
 }
 
 impl From<Vec<gallivant::Error>> for Error {
     fn from(errors: Vec<gallivant::Error>) -> Self {
-        Self::ParseErrors(errors)
+        Self::Parse(errors)
     }
 }
 
 impl From<gallivant::Error> for Error {
     fn from(error: gallivant::Error) -> Self {
-        Self::RuntimeError(error)
+        Self::Runtime(error)
     }
 }
 
@@ -37,131 +61,520 @@ impl From<gallivant::Error> for Error {
 fn main() {
     let args = Args::parse();
 
-    let mut tcu = args.tcu.map(|port| {
-        if port == "mock" {
-            CommPort::Open(Box::new(MockTCUPort::new()))
-        } else {
-            CommPort::from(
+    if let Some(Command::ListPorts) = args.command {
+        list_ports();
+        return;
+    }
+
+    if args.dry_run {
+        if !dry_run(&args) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let color = args.color();
+
+    let tcu = match &args.playback {
+        Some(path) => {
+            let playback = PlaybackPort::from_file(path).expect("Failed to read playback file");
+            Some(Box::new(playback) as Box<dyn SerialPort>)
+        }
+        None => args.tcu.as_ref().map(|port| {
+            if port == "mock" {
+                Box::new(MockTCUPort::new()) as Box<dyn SerialPort>
+            } else {
                 serialport::new(port, 9600)
                     .timeout(Duration::from_millis(100))
                     .open()
-                    .expect("Failed to open TCU port"),
-            )
+                    .expect("Failed to open TCU port")
+            }
+        }),
+    };
+
+    let mut tcu = tcu
+        .map(|port| match &args.record {
+            Some(path) => Box::new(TransactionRecorder::new(port).with_autosave(path)),
+            None => port,
+        })
+        .map(CommPort::from);
+
+    let mut printer = args
+        .printer
+        .as_deref()
+        .map(|port| CommPort::from(CommPort::builder(port, 9600).clear_after_write(true)));
+
+    let name = args.script().display().to_string();
+    let mut junit = args
+        .output_junit
+        .as_ref()
+        .map(|_| JUnitReport::new(name.as_str()));
+
+    let mut summary = TestSummary::new();
+
+    let run_script = |i| run_script(i, &args, &mut tcu, &mut printer, &mut junit, &mut summary);
+
+    let (script, interpreter) = match &args.resume {
+        Some(path) => {
+            let interpreter = std::fs::File::open(path)
+                .map_err(|error| {
+                    vec![gallivant::Error::from_state_restore(
+                        Some(path.clone()),
+                        error,
+                    )]
+                })
+                .and_then(|mut file| Interpreter::restore_state(&mut file))
+                .map(|interpreter| interpreter.with_filename(name.as_str()));
+
+            // A resumed script is taken from the saved state rather than the script file, so
+            // reports must use the interpreter's copy of the script for their spans to line up.
+            let script = match &interpreter {
+                Ok(interpreter) => interpreter.script().to_owned(),
+                Err(_) => String::new(),
+            };
+
+            (script, interpreter)
         }
-    });
-
-    let mut printer = args.printer.map(|port| {
-        CommPort::from(CommPort::builder(port, 9600).timeout(Duration::from_millis(100)))
-    });
 
-    let script = std::fs::read_to_string(&args.script).expect("Failed to read script");
+        None => {
+            let (script, interpreter) = load_script(&args, &name);
 
-    let run_script = |i| run_script(i, args.debug, &mut tcu, &mut printer);
+            // Problems that don't prevent the script from running are reported before it starts.
+            for warning in interpreter.iter().flat_map(Interpreter::script_warnings) {
+                warning
+                    .to_report(&script)
+                    .eprint(Source::from(&script))
+                    .expect("Failed to create warning report");
+            }
 
-    match gallivant::Interpreter::try_from_str(&script)
+            (script, interpreter)
+        }
+    };
+
+    let result = interpreter
+        .map(|interpreter| interpreter.with_error_policy(args.error_policy))
+        .map(|interpreter| match args.usb_print_limit {
+            Some(limit) => interpreter.with_usb_print_limit(limit),
+            None => interpreter,
+        })
+        .map(|interpreter| match args.max_commands {
+            Some(limit) => interpreter.with_max_commands(limit),
+            None => interpreter,
+        })
+        .map(|interpreter| interpreter.with_progress_reports(args.debug))
+        .map(|interpreter| {
+            if args.output_tap {
+                interpreter.with_hooks(TapWriter::new(std::io::stdout()))
+            } else {
+                interpreter
+            }
+        })
+        .map(|interpreter| match &args.output_csv {
+            Some(path) => {
+                let file = std::fs::File::create(path).expect("Failed to create CSV file");
+                let csv = CsvWriter::new(std::io::BufWriter::new(file))
+                    .expect("Failed to write CSV file")
+                    .with_flush_on_each_row(true);
+
+                interpreter.with_hooks(csv)
+            }
+            None => interpreter,
+        })
+        .map(|interpreter| match &args.report_html {
+            Some(path) => {
+                interpreter.with_hooks(HtmlReportWriter::new(path, name.as_str(), &script))
+            }
+            None => interpreter,
+        })
+        .map(|interpreter| match &args.log_file {
+            Some(path) => {
+                let file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .expect("Failed to open log file");
+
+                interpreter.with_hooks(LogWriter::new(file))
+            }
+            None => interpreter,
+        })
+        .map(|interpreter| {
+            if args.verbose_timing {
+                interpreter.with_hooks(WaitTiming)
+            } else {
+                interpreter
+            }
+        })
+        .map(|interpreter| {
+            if args.debug {
+                interpreter.with_hooks(CommandTrace::new(&script, color))
+            } else {
+                interpreter
+            }
+        })
+        .map(|interpreter| interpreter.with_hooks(RequestTrace))
         .map_err(Error::from)
-        .and_then(run_script)
-    {
-        Ok(()) => (),
-        Err(Error::ParseErrors(errors)) => {
+        .and_then(run_script);
+
+    if let (Some(path), Some(junit)) = (&args.output_junit, &junit) {
+        let mut file = std::fs::File::create(path).expect("Failed to create JUnit file");
+        junit.write(&mut file).expect("Failed to write JUnit file");
+    }
+
+    let tests_run = !matches!(result, Err(Error::Parse(_)));
+    let stop_file_found = matches!(result, Err(Error::Stopped(_)));
+    let stopped = matches!(
+        &result,
+        Err(Error::Runtime(error)) if matches!(
+            error.reason(),
+            ErrorReason::GlobalTimeout { .. }
+                | ErrorReason::MaxFailuresExceeded { .. }
+                | ErrorReason::MaxCommandsExceeded { .. }
+                | ErrorReason::UserCancelled
+        )
+    );
+
+    match result {
+        // Errors the script continued after are reported once it's finished.
+        Ok(interpreter) => {
+            for error in interpreter.errors() {
+                error
+                    .to_report(&script)
+                    .eprint(Source::from(&script))
+                    .expect("Failed to create error report");
+            }
+        }
+        Err(Error::Parse(errors)) => {
             for error in errors {
-                Report::from(error)
+                error
+                    .to_report(&script)
                     .eprint(Source::from(&script))
                     .expect("Failed to create error report");
             }
         }
-        Err(Error::RuntimeError(error)) => {
-            Report::from(error)
+        Err(Error::Runtime(error)) if matches!(error.reason(), ErrorReason::UserCancelled) => {
+            eprintln!("{}", error.reason().message());
+        }
+        Err(Error::Runtime(error)) => {
+            error
+                .to_report(&script)
                 .eprint(Source::from(&script))
                 .expect("Failed to create error report");
         }
+        Err(Error::Stopped(path)) => {
+            eprintln!("Script stopped - found stop file '{}'", path.display());
+        }
+    }
+
+    // Removed so that the next run isn't stopped immediately.
+    if let Some(path) = args.stop_file.as_ref().filter(|path| path.exists()) {
+        std::fs::remove_file(path).expect("Failed to remove stop file");
+    }
+
+    if tests_run {
+        println!("{summary}");
+    }
+
+    if args.debug {
+        if let Some(stats) = tcu.as_ref().and_then(CommPort::stats) {
+            println!("TCU port: {stats}");
+        }
+
+        if let Some(stats) = printer.as_ref().and_then(CommPort::stats) {
+            println!("Printer port: {stats}");
+        }
+    }
+
+    // Exiting skips destructors, so close the ports first to let any recording be written.
+    drop(tcu);
+    drop(printer);
+
+    if stop_file_found {
+        std::process::exit(2);
+    }
+
+    if stopped {
+        std::process::exit(1);
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+fn list_ports() {
+    let ports = CommPort::list_available().expect("Failed to list serial ports");
+    if ports.is_empty() {
+        println!("No serial ports found");
+        return;
+    }
+
+    let rows: Vec<[&str; 3]> = ports
+        .iter()
+        .map(|port| {
+            [
+                port.path.as_str(),
+                port.product.as_deref().unwrap_or("-"),
+                port.manufacturer.as_deref().unwrap_or("-"),
+            ]
+        })
+        .collect();
+
+    let header = ["PATH", "PRODUCT", "MANUFACTURER"];
+    let width = |column: usize| {
+        rows.iter()
+            .chain([&header])
+            .map(|row| row[column].chars().count())
+            .max()
+            .unwrap_or_default()
+    };
+    let (path_width, product_width) = (width(0), width(1));
+
+    for [path, product, manufacturer] in [header].iter().chain(rows.iter()) {
+        println!("{path:path_width$}  {product:product_width$}  {manufacturer}");
     }
 }
 
 ////////////////////////////////////////////////////////////////
 
+/// Print the requests a script would make without opening any ports.
+///
+/// # Returns
+/// True if the script was executed without any errors.
+///
+fn dry_run(args: &Args) -> bool {
+    let name = args.script().display().to_string();
+    let (script, interpreter) = load_script(args, &name);
+
+    let interpreter = match interpreter {
+        Ok(interpreter) => interpreter,
+        Err(errors) => {
+            for error in errors {
+                error
+                    .to_report(&script)
+                    .eprint(Source::from(&script))
+                    .expect("Failed to create error report");
+            }
+            return false;
+        }
+    };
+
+    let mut passed = true;
+    for result in interpreter.dry_run() {
+        match result {
+            Ok(request) => println!("{request}"),
+            Err(error) => {
+                passed = false;
+                error
+                    .to_report(&script)
+                    .eprint(Source::from(&script))
+                    .expect("Failed to create error report");
+            }
+        }
+    }
+
+    passed
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Read the script given by the arguments and create an interpreter for it with the parameters
+/// given. Every error is given the same filename, including those occuring once the script is
+/// running.
+///
+/// # Arguments
+/// * `args` - Arguments giving the script and its parameters.
+/// * `name` - Name of the script to include in errors.
+///
+/// # Returns
+/// The normalised script, which the spans of any errors refer to, and the interpreter.
+///
+fn load_script(args: &Args, name: &str) -> (String, Result<Interpreter, Vec<gallivant::Error>>) {
+    let script = match std::fs::read_to_string(args.script()) {
+        Ok(script) => gallivant::normalize_script(&script).into_owned(),
+        Err(error) => {
+            let error = gallivant::Error::from_script_read(Some(args.script().to_owned()), error);
+            return (String::new(), Err(vec![error]));
+        }
+    };
+
+    let interpreter = Interpreter::try_from_str(&script)
+        .map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|error| error.with_filename(name))
+                .collect()
+        })
+        .and_then(|interpreter| {
+            let params = args.params.iter().cloned().collect();
+            interpreter
+                .with_filename(name)
+                .with_params(params)
+                .map_err(|error| vec![error.with_filename(name)])
+        });
+
+    (script, interpreter)
+}
+
+////////////////////////////////////////////////////////////////
+
 fn run_script(
-    interpreter: Interpreter,
-    debug: bool,
+    mut interpreter: Interpreter,
+    args: &Args,
     tcu: &mut Option<CommPort>,
     printer: &mut Option<CommPort>,
-) -> Result<(), Error> {
-    for current_request in interpreter {
-        let mut current_request = Some(current_request?);
+    junit: &mut Option<JUnitReport>,
+    summary: &mut TestSummary,
+) -> Result<Interpreter, Error> {
+    loop {
+        // Checked before each command so that the current request is always finished.
+        if let Some(path) = args.stop_file.as_ref().filter(|path| path.exists()) {
+            return Err(Error::Stopped(path.clone()));
+        }
+
+        let Some(current_request) = interpreter.next() else {
+            break;
+        };
 
-        while let Some(request) = current_request {
-            current_request = handle_request(request, debug, tcu, printer)?;
+        // Errors from evaluation e.g. a failed ASSERT, may still be handled by the script.
+        let current_request = match current_request {
+            Ok(request) => request,
+            Err(error) => {
+                interpreter.complete(Err(error))?;
+                continue;
+            }
+        };
+        let test = test_name(&current_request);
+        let start = Instant::now();
+
+        let mut result = Ok(Some(current_request));
+        while let Ok(Some(request)) = result {
+            result = handle_request(request, args, tcu, printer, &mut interpreter);
+        }
+
+        if let Some(name) = test {
+            let error = match &result {
+                Err(Error::Runtime(error)) => Err(error),
+                _ => Ok(()),
+            };
+
+            // A failed WARNTEST doesn't count as a failure, but any other error e.g. losing the
+            // connection to the device still does.
+            let warning = matches!(
+                interpreter.current_expression().map(ParsedExpr::expression),
+                Some(Expr::WarnTest { .. })
+            ) && matches!(
+                error,
+                Err(error) if matches!(error.reason(), ErrorReason::TestFailure { .. })
+            );
+
+            if warning {
+                summary.push_warning(interpreter.test_group(), start.elapsed());
+            } else {
+                summary.push(interpreter.test_group(), start.elapsed(), error);
+            }
+
+            if let Some(junit) = junit.as_mut() {
+                let error = if warning { Ok(()) } else { error };
+                junit.push(name, start.elapsed(), error);
+            }
+        }
+
+        match result {
+            Ok(_) => interpreter.complete(Ok(()))?,
+            Err(Error::Runtime(error)) => interpreter.complete(Err(error))?,
+            Err(error) => return Err(error),
+        }
+
+        let checkpoint = matches!(
+            interpreter.current_expression().map(ParsedExpr::expression),
+            Some(Expr::Checkpoint(_) | Expr::TestGroup(_) | Expr::EndTestGroup)
+        );
+
+        if let (true, Some(path)) = (checkpoint, &args.checkpoint_file) {
+            std::fs::File::create(path)
+                .and_then(|mut file| interpreter.save_state(&mut file))
+                .expect("Failed to write checkpoint file");
         }
     }
 
-    Ok(())
+    Ok(interpreter)
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Return the name of the test performed by a request if it performs one.
+///
+pub fn test_name(request: &FrontendRequest) -> Option<String> {
+    match request {
+        FrontendRequest::TCUTransact(transaction)
+        | FrontendRequest::PrinterTransact(transaction) => transaction
+            .test()
+            .map(|test| test.failure_message.to_owned()),
+        _ => None,
+    }
 }
 
 ////////////////////////////////////////////////////////////////
 
 fn handle_request(
     request: FrontendRequest,
-    debug: bool,
+    args: &Args,
     tcu: &mut Option<CommPort>,
     printer: &mut Option<CommPort>,
+    interpreter: &mut Interpreter,
 ) -> Result<Option<FrontendRequest>, Error> {
-    if debug {
+    let global_timeout = interpreter.global_timeout();
+
+    if args.debug {
         println!("{request:?}")
     }
 
     match request {
         FrontendRequest::None => (),
+        FrontendRequest::Progress { current, total } => {
+            println!("PROGRESS: {}/{total}", current + 1)
+        }
         FrontendRequest::Wait(time) => std::thread::sleep(time),
 
-        FrontendRequest::GuiPrint(message) => println!("COMMENT: {message}"),
-        FrontendRequest::GuiDialogue { kind, message } => match kind {
-            gallivant::Dialog::ManualInput => {
-                println!("DIALOG:  {message}");
-
-                loop {
-                    print!("INPUT:   ");
-                    std::io::stdout().flush().expect("std out flush error");
-
-                    let mut input = String::new();
-                    std::io::stdin()
-                        .read_line(&mut input)
-                        .expect("Dialog input error");
-
-                    let input = input.trim();
-                    if input.starts_with("STOP") || input.starts_with(['S', 's']) {
-                        panic!("Test cancelled")
-                    }
+        FrontendRequest::GuiPrint(_)
+        | FrontendRequest::Checkpoint(_)
+        | FrontendRequest::GuiDialogue { .. } => print_message(
+            &request,
+            args,
+            &mut std::io::stdout(),
+            &mut std::io::stdin().lock(),
+        )?,
+        FrontendRequest::Notification { key, value } => println!(
+            r#"{{"event":"notify","key":"{}","value":{value},"ts":"{}"}}"#,
+            json_escape(&key),
+            chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ")
+        ),
 
-                    if input.starts_with("CONTINUE")
-                        || input.starts_with(['C', 'c'])
-                        || input.is_empty()
-                    {
-                        break;
-                    }
+        FrontendRequest::TCUTransact(transaction) => {
+            if let Some(tcu @ CommPort::Open { .. }) = tcu {
+                let measurement =
+                    handle_transaction(transaction, tcu, args.max_std_dev, global_timeout)?;
+                if let Some(measurement) = measurement {
+                    interpreter.report_measurement(measurement);
                 }
+            } else {
+                panic!("TCU port required but none given");
             }
-            gallivant::Dialog::Notification => println!("DIALOG:  {message}"),
-        },
+        }
 
-        FrontendRequest::TCUTransact(transaction) => {
-            if let Some(CommPort::Open(tcu)) = tcu {
-                handle_transaction(transaction, tcu)?;
+        FrontendRequest::TCUFlush => {
+            if let Some(tcu @ CommPort::Open { .. }) = tcu {
+                tcu.flush_with_timeout(Duration::from_millis(args.flush_timeout))
+                    .expect("TCU transmit error");
+                tcu.drain_input().expect("TCU receive error");
             } else {
                 panic!("TCU port required but none given");
             }
         }
 
-        FrontendRequest::TCUFlush => {
-            if let Some(CommPort::Open(tcu)) = tcu {
-                tcu.flush().expect("TCU transmit error");
-                let mut buffer = Vec::new();
-                match tcu.read_to_end(&mut buffer) {
-                    Ok(_) => (),
-                    Err(error) => match error.kind() {
-                        ErrorKind::TimedOut => (),
-                        _ => panic!("TCU receive error"),
-                    },
-                };
+        FrontendRequest::SetBaud(baud) => {
+            if let Some(tcu) = tcu {
+                tcu.set_baud_rate(baud)
+                    .expect("Failed to set TCU baud rate");
             } else {
                 panic!("TCU port required but none given");
             }
@@ -184,8 +597,12 @@ fn handle_request(
         }
 
         FrontendRequest::PrinterTransact(transaction) => match printer {
-            Some(CommPort::Open(port)) => {
-                handle_transaction(transaction, port)?;
+            Some(port @ CommPort::Open { .. }) => {
+                let measurement =
+                    handle_transaction(transaction, port, args.max_std_dev, global_timeout)?;
+                if let Some(measurement) = measurement {
+                    interpreter.report_measurement(measurement);
+                }
             }
 
             Some(CommPort::Closed(_)) => {
@@ -200,19 +617,211 @@ fn handle_request(
 
 ////////////////////////////////////////////////////////////////
 
+/// Print a message from a COMMENT, checkpoint, warning or dialog. Manual input dialogs wait for
+/// the user to either continue or stop the script. Comments and checkpoints aren't printed when
+/// quiet but warnings and dialogs always are.
+///
+/// # Arguments
+/// * `request` - Request to print. Any other kind of request is ignored.
+/// * `args` - Command line arguments.
+/// * `output` - Writer to print to e.g. stdout.
+/// * `input` - Reader that user input to dialogs is read from e.g. stdin.
+///
+/// # Returns
+/// Result where the Err value indicates the user stopped the script.
+///
+fn print_message(
+    request: &FrontendRequest,
+    args: &Args,
+    output: &mut impl Write,
+    input: &mut impl BufRead,
+) -> Result<(), Error> {
+    let message = match request {
+        FrontendRequest::GuiPrint(message) => match message.strip_prefix("[WARNING] ") {
+            Some(warning) => format!("WARNING: {warning}"),
+            None if args.quiet => return Ok(()),
+            // Messages are highlighted the same as the string literals they came from.
+            None if args.color() => {
+                let color = gallivant::ColorScheme::default().string.escape_code();
+                format!("COMMENT: {color}{message}\x1b[0m")
+            }
+            None => format!("COMMENT: {message}"),
+        },
+        FrontendRequest::Checkpoint(_) if args.quiet => return Ok(()),
+        FrontendRequest::Checkpoint(name) => format!("CHECKPOINT: {name}"),
+        FrontendRequest::GuiDialogue { message, .. } => format!("DIALOG:  {message}"),
+        _ => return Ok(()),
+    };
+    writeln!(output, "{message}").expect("std out write error");
+
+    let FrontendRequest::GuiDialogue {
+        kind: gallivant::Dialog::ManualInput,
+        ..
+    } = request
+    else {
+        return Ok(());
+    };
+
+    loop {
+        write!(output, "INPUT:   ").expect("std out write error");
+        output.flush().expect("std out flush error");
+
+        let mut line = String::new();
+        input.read_line(&mut line).expect("Dialog input error");
+
+        let line = line.trim();
+        if line.starts_with("STOP") || line.starts_with(['S', 's']) {
+            let error = gallivant::Error::from_user_cancelled();
+            return Err(Error::Runtime(error));
+        }
+
+        if line.starts_with("CONTINUE") || line.starts_with(['C', 'c']) || line.is_empty() {
+            return Ok(());
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Escape characters that can't appear as-is in a JSON string.
+///
+fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Process a transaction until it completes.
+///
+/// # Arguments
+/// * `transaction` - Transaction to process.
+/// * `port` - Port of the device the transaction is with.
+/// * `max_std_dev` - Maximum standard deviation allowed across a test's retries, if any.
+/// * `global_timeout` - Start and length of the time limit set by GLOBALTIMEOUT, if any. Waiting
+///   on the device is abandoned once it's exceeded.
+///
+/// # Returns
+/// The measurement that passed the transaction's test, if it performed one.
+///
 fn handle_transaction(
     mut transaction: Transaction,
-    port: &mut Box<dyn SerialPort>,
-) -> Result<(), Error> {
+    port: &mut CommPort,
+    max_std_dev: Option<f32>,
+    global_timeout: Option<(Instant, Duration)>,
+) -> Result<Option<u32>, Error> {
+    if let Some(max_std_dev) = max_std_dev {
+        transaction = transaction.with_max_std_dev(max_std_dev);
+    }
+
+    let expects_response = transaction.expects_response();
+
     // Send bytes.
-    loop {
+    let measurement = loop {
         transaction = match transaction.process(port)? {
-            TransactionStatus::Success => break,
-            TransactionStatus::Ongoing(transaction) => transaction,
+            TransactionStatus::Success(measurement) => break measurement,
+            TransactionStatus::Ongoing(transaction) => {
+                if let Some((start, timeout)) = global_timeout {
+                    let elapsed = start.elapsed();
+                    if elapsed >= timeout {
+                        port.transaction_timed_out();
+                        return Err(gallivant::Error::from_global_timeout(elapsed).into());
+                    }
+                }
+
+                transaction
+            }
+            TransactionStatus::Warning(transaction, request) => {
+                if let FrontendRequest::GuiPrint(message) = *request {
+                    println!("WARNING: {message}");
+                }
+                transaction
+            }
         }
+    };
+
+    if !expects_response {
+        port.write_complete()
+            .expect("Failed to clear unsolicited bytes from comm port");
     }
 
-    Ok(())
+    Ok(measurement)
 }
 
 ////////////////////////////////////////////////////////////////
+// tests
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ////////////////////////////////////////////////////////////////
+
+    /// Print the messages of a script that comments, reaches a checkpoint, fails a WARNTEST and
+    /// then waits on a dialog, which is continued.
+    ///
+    fn print_messages(args: &[&str]) -> String {
+        let script = r#"
+COMMENT "Starting"
+CHECKPOINT "phase 1"
+WARNTEST 3, 1000, 2000, 0, "Low voltage"
+WAITDIALOG "Connect the printer"
+        "#;
+
+        let args = Args::parse_from(
+            ["gallivant-cli", "--script", "test.txt", "--no-color"]
+                .iter()
+                .chain(args),
+        );
+
+        let mut output = Vec::new();
+        let mut interpreter = Interpreter::try_from_str(script).unwrap();
+        let errors = testing::run_with(&mut interpreter, &[b"30\r"], |request| {
+            let result = print_message(request, &args, &mut output, &mut &b"C\n"[..]);
+            assert!(result.is_ok());
+        });
+        assert!(errors.is_empty(), "{errors:?}");
+
+        String::from_utf8(output).unwrap()
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_print_messages() {
+        assert_eq!(
+            print_messages(&[]),
+            "COMMENT: Starting\n\
+             CHECKPOINT: phase 1\n\
+             WARNING: Low voltage\n\
+             DIALOG:  Connect the printer\n\
+             INPUT:   "
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_print_messages_quiet() {
+        // Only comments and checkpoints are hidden.
+        assert_eq!(
+            print_messages(&["--quiet"]),
+            "WARNING: Low voltage\n\
+             DIALOG:  Connect the printer\n\
+             INPUT:   "
+        );
+    }
+}
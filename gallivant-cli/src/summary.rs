@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Counts of the test commands run by a script and whether they passed.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TestSummary {
+    passed: usize,
+    failed: usize,
+
+    /// Tests that failed but only produced a warning. Counted separately from failures.
+    warnings: usize,
+    elapsed: Duration,
+
+    /// Counts for each test group, in the order they were run.
+    groups: Vec<GroupSummary>,
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Counts of the test commands run within a single test group.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GroupSummary {
+    name: String,
+    passed: usize,
+    failed: usize,
+    warnings: usize,
+}
+
+////////////////////////////////////////////////////////////////
+// construction / conversion
+////////////////////////////////////////////////////////////////
+
+impl TestSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// methods
+////////////////////////////////////////////////////////////////
+
+impl TestSummary {
+    /// Record the result of a test command.
+    ///
+    /// # Arguments
+    /// * `group` - Test group the test command is within, if any.
+    /// * `time` - Time taken to complete the test.
+    /// * `result` - Result of handling the test command.
+    ///
+    pub fn push(
+        &mut self,
+        group: Option<&str>,
+        time: Duration,
+        result: Result<(), &gallivant::Error>,
+    ) {
+        let counts = |passed: &mut usize, failed: &mut usize| match result {
+            Ok(()) => *passed += 1,
+            Err(_) => *failed += 1,
+        };
+
+        counts(&mut self.passed, &mut self.failed);
+        self.elapsed += time;
+
+        if let Some(last) = self.group(group) {
+            counts(&mut last.passed, &mut last.failed);
+        }
+    }
+
+    /// Record a test command that failed but only produced a warning.
+    ///
+    /// # Arguments
+    /// * `group` - Test group the test command is within, if any.
+    /// * `time` - Time taken to complete the test.
+    ///
+    pub fn push_warning(&mut self, group: Option<&str>, time: Duration) {
+        self.warnings += 1;
+        self.elapsed += time;
+
+        if let Some(last) = self.group(group) {
+            last.warnings += 1;
+        }
+    }
+
+    /// Return the summary for a test group, creating it if it's a new group.
+    ///
+    fn group(&mut self, group: Option<&str>) -> Option<&mut GroupSummary> {
+        let name = group?;
+
+        // Tests from the same group are always run consecutively since groups can't be nested.
+        if self.groups.last().is_none_or(|last| last.name != name) {
+            self.groups.push(GroupSummary {
+                name: name.to_owned(),
+                ..Default::default()
+            });
+        }
+
+        self.groups.last_mut()
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// ...
+////////////////////////////////////////////////////////////////
+
+impl std::fmt::Display for TestSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.passed + self.failed + self.warnings;
+        let seconds = self.elapsed.as_secs_f64();
+
+        if self.failed == 0 {
+            write!(
+                f,
+                "PASS: {}/{total} tests passed in {seconds:.1}s",
+                self.passed
+            )?;
+        } else {
+            write!(
+                f,
+                "FAIL: {}/{total} tests failed in {seconds:.1}s",
+                self.failed
+            )?;
+        }
+
+        match self.warnings {
+            0 => (),
+            1 => write!(f, " (1 warning)")?,
+            n => write!(f, " ({n} warnings)")?,
+        }
+
+        if self.groups.is_empty() {
+            return Ok(());
+        }
+
+        let header = "GROUP";
+        let width = self
+            .groups
+            .iter()
+            .map(|group| group.name.chars().count())
+            .chain([header.len()])
+            .max()
+            .unwrap_or_default();
+
+        write!(f, "\n\n{header:width$}  PASSED  FAILED  WARNINGS")?;
+        for group in self.groups.iter() {
+            write!(
+                f,
+                "\n{:width$}  {:<6}  {:<6}  {}",
+                group.name, group.passed, group.failed, group.warnings
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////
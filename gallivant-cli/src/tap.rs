@@ -0,0 +1,115 @@
+use std::io::Write;
+
+use gallivant::{ErrorReason, FrontendRequest, ParsedExpr, ScriptHooks};
+
+use crate::test_name;
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Writes the results of test commands in the Test Anything Protocol format. The plan is written
+/// after the results when the writer is dropped, since how many tests are run depends on the
+/// script's conditionals and loops.
+///
+#[derive(Debug)]
+pub struct TapWriter<W: Write> {
+    writer: W,
+    count: usize,
+    pending: Option<String>,
+}
+
+////////////////////////////////////////////////////////////////
+// construction / conversion
+////////////////////////////////////////////////////////////////
+
+impl<W: Write> TapWriter<W> {
+    /// Create a new writer.
+    ///
+    /// # Arguments
+    /// * `writer` - Destination for the TAP output.
+    ///
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            count: 0,
+            pending: None,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// traits
+////////////////////////////////////////////////////////////////
+
+impl<W: Write> ScriptHooks for TapWriter<W> {
+    fn before_request(&mut self, _: &ParsedExpr, request: &FrontendRequest) {
+        self.pending = test_name(request);
+    }
+
+    fn after_request(&mut self, _: &ParsedExpr, result: Result<(), &gallivant::Error>) {
+        let Some(description) = self.pending.take() else {
+            return;
+        };
+
+        self.count += 1;
+        let count = self.count;
+
+        let line = match result.map_err(|error| error.reason()) {
+            Ok(()) => format!("ok {count} - {description}"),
+            Err(ErrorReason::TestFailure { test, .. }) => format!(
+                "not ok {count} - {description} # FAIL expected {}..{} got {}",
+                test.expected.start(),
+                test.expected.end(),
+                test.measurement
+            ),
+            Err(reason) => format!("not ok {count} - {description} # {}", reason.message()),
+        };
+
+        writeln!(self.writer, "{line}").expect("Failed to write TAP output");
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl<W: Write> Drop for TapWriter<W> {
+    fn drop(&mut self) {
+        if let Err(error) = writeln!(self.writer, "1..{}", self.count) {
+            eprintln!("Failed to write TAP plan: {error}");
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use gallivant::Interpreter;
+
+    use super::*;
+    use crate::testing::{self, SharedBuffer};
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_two_tcutests() {
+        let script = "TCUTEST 1, 1000, 12000, 0, \"FAIL 1\"\nTCUTEST 2, $10, $20, 0, \"FAIL 2\"";
+
+        let buffer = SharedBuffer::default();
+        let tap = TapWriter::new(buffer.clone());
+        let mut interpreter = Interpreter::try_from_str(script).unwrap().with_hooks(tap);
+
+        // The first test passes and the second fails.
+        testing::run(&mut interpreter, &[b"3E8\r", b"30\r"]);
+
+        // The plan is only written once the writer is dropped along with the interpreter.
+        drop(interpreter);
+
+        assert_eq!(
+            buffer.contents(),
+            "ok 1 - FAIL 1\n\
+             not ok 2 - FAIL 2 # FAIL expected 16..32 got 48\n\
+             1..2\n"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{Read, Write},
+    rc::Rc,
+};
+
+use gallivant::{FrontendRequest, Interpreter, TransactionStatus};
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Buffer that can still be read once the writer using it has been given to an interpreter.
+///
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+////////////////////////////////////////////////////////////////
+
+/// TCU that echoes each command followed by a fixed measurement.
+///
+#[derive(Default)]
+pub struct TcuMock {
+    measurement: &'static [u8],
+    rxdata: VecDeque<u8>,
+}
+
+////////////////////////////////////////////////////////////////
+// methods
+////////////////////////////////////////////////////////////////
+
+impl SharedBuffer {
+    /// Return everything written to the buffer so far.
+    ///
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// traits
+////////////////////////////////////////////////////////////////
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl Read for TcuMock {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = buf.len().min(self.rxdata.len());
+        for (byte, rxbyte) in buf.iter_mut().zip(self.rxdata.drain(..count)) {
+            *byte = rxbyte;
+        }
+        Ok(count)
+    }
+}
+
+impl Write for TcuMock {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.rxdata.extend(buf);
+        self.rxdata.extend(self.measurement);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// functions
+////////////////////////////////////////////////////////////////
+
+/// Run a script to completion, handling each TCU transaction with a mock TCU and ignoring every
+/// other request.
+///
+/// # Arguments
+/// * `interpreter` - Interpreter for the script.
+/// * `measurements` - Response given to each TCU transaction after its echo, in order.
+///
+/// # Returns
+/// Errors returned when completing each request.
+///
+pub fn run(interpreter: &mut Interpreter, measurements: &[&'static [u8]]) -> Vec<gallivant::Error> {
+    run_with(interpreter, measurements, |_| ())
+}
+
+/// Run a script to completion, handling each TCU transaction with a mock TCU and passing every
+/// other request to a handler.
+///
+/// # Arguments
+/// * `interpreter` - Interpreter for the script.
+/// * `measurements` - Response given to each TCU transaction after its echo, in order.
+/// * `handler` - Called with each request that isn't a TCU transaction.
+///
+/// # Returns
+/// Errors returned when completing each request.
+///
+pub fn run_with(
+    interpreter: &mut Interpreter,
+    measurements: &[&'static [u8]],
+    mut handler: impl FnMut(&FrontendRequest),
+) -> Vec<gallivant::Error> {
+    let mut port = TcuMock::default();
+    let mut measurements = measurements.iter();
+    let mut errors = Vec::new();
+
+    while let Some(request) = interpreter.next() {
+        let mut transaction = match request.unwrap() {
+            FrontendRequest::TCUTransact(transaction) => transaction,
+            request => {
+                handler(&request);
+                interpreter.complete(Ok(())).unwrap();
+                continue;
+            }
+        };
+
+        port.measurement = measurements.next().unwrap();
+        let result = loop {
+            transaction = match transaction.process(&mut port) {
+                Ok(TransactionStatus::Success(measurement)) => break Ok(measurement),
+                Ok(TransactionStatus::Ongoing(transaction)) => transaction,
+                Ok(TransactionStatus::Warning(transaction, _)) => transaction,
+                Err(error) => break Err(error),
+            };
+        };
+
+        if let Ok(Some(measurement)) = result {
+            interpreter.report_measurement(measurement);
+        }
+        if let Err(error) = interpreter.complete(result.map(|_| ())) {
+            errors.push(error);
+        }
+    }
+
+    errors
+}
+
+////////////////////////////////////////////////////////////////
@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+use gallivant::{ParsedExpr, ScriptHooks};
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Prints how long each WAIT actually took, which may be longer than requested due to OS
+/// scheduling.
+///
+#[derive(Debug, Default)]
+pub struct WaitTiming;
+
+////////////////////////////////////////////////////////////////
+// traits
+////////////////////////////////////////////////////////////////
+
+impl ScriptHooks for WaitTiming {
+    fn after_wait(&mut self, _: &ParsedExpr, requested: Duration, elapsed: Duration) {
+        println!(
+            "WAITED:  {}ms (requested {}ms)",
+            elapsed.as_millis(),
+            requested.as_millis()
+        );
+    }
+}
+
+////////////////////////////////////////////////////////////////
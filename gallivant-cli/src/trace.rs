@@ -0,0 +1,67 @@
+use gallivant::{FrontendRequest, ParsedExpr, ScriptHooks};
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Prints each command as it's executed.
+///
+#[derive(Debug)]
+pub struct CommandTrace {
+    script: Vec<char>,
+    color: bool,
+}
+
+////////////////////////////////////////////////////////////////
+// construction / conversion
+////////////////////////////////////////////////////////////////
+
+impl CommandTrace {
+    /// # Arguments
+    /// * `script` - Script being executed.
+    /// * `color` - Whether to highlight the commands.
+    ///
+    pub fn new(script: &str, color: bool) -> Self {
+        Self {
+            script: script.chars().collect(),
+            color,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// traits
+////////////////////////////////////////////////////////////////
+
+impl ScriptHooks for CommandTrace {
+    fn before_request(&mut self, expression: &ParsedExpr, _: &FrontendRequest) {
+        let Some(command) = self.script.get(expression.span().clone()) else {
+            return;
+        };
+
+        let command = String::from_iter(command);
+        let command = command.trim();
+        if self.color {
+            println!("COMMAND: {}", gallivant::highlight(command));
+        } else {
+            println!("COMMAND: {command}");
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Prints the request of each command executed between TRACE ON and TRACE OFF to stderr.
+///
+#[derive(Debug, Default)]
+pub struct RequestTrace;
+
+////////////////////////////////////////////////////////////////
+
+impl ScriptHooks for RequestTrace {
+    fn trace_request(&mut self, expression: &ParsedExpr, request: &FrontendRequest) {
+        eprintln!("TRACE:   {expression} => {request:?}");
+    }
+}
+
+////////////////////////////////////////////////////////////////
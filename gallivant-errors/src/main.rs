@@ -1,6 +1,6 @@
 use std::fmt::Write;
 
-use ariadne::{Report, Source};
+use ariadne::Source;
 
 use gallivant::Interpreter;
 
@@ -23,9 +23,10 @@ fn invalid_command(output: &mut String) {
 
     let script = "CMMENT";
     if let Err(errors) = Interpreter::try_from_str(script) {
-        for report in errors.into_iter().map(Report::from) {
+        for error in errors {
             let mut buffer = Vec::new();
-            report
+            error
+                .to_report(script)
                 .write_for_stdout(Source::from(script), &mut buffer)
                 .unwrap();
             output.push_str(&String::from_utf8(buffer).unwrap());
@@ -40,9 +41,10 @@ fn invalid_argument_type(output: &mut String) {
 
     let script = r#"TCUCLOSE "arg""#;
     if let Err(errors) = Interpreter::try_from_str(script) {
-        for report in errors.into_iter().map(Report::from) {
+        for error in errors {
             let mut buffer = Vec::new();
-            report
+            error
+                .to_report(script)
                 .write_for_stdout(Source::from(script), &mut buffer)
                 .unwrap();
             output.push_str(&String::from_utf8(buffer).unwrap());
@@ -57,9 +59,10 @@ fn invalid_argument_value(output: &mut String) {
 
     let script = r#"TCUCLOSE 256"#;
     if let Err(errors) = Interpreter::try_from_str(script) {
-        for report in errors.into_iter().map(Report::from) {
+        for error in errors {
             let mut buffer = Vec::new();
-            report
+            error
+                .to_report(script)
                 .write_for_stdout(Source::from(script), &mut buffer)
                 .unwrap();
             output.push_str(&String::from_utf8(buffer).unwrap());
@@ -1,9 +1,13 @@
 mod mock;
+mod playback;
 mod port;
+mod recorder;
 
 ////////////////////////////////////////////////////////////////
 
-pub use mock::MockTCUPort;
-pub use port::{CommPort, CommPortBuilder};
+pub use mock::{MockPrinterPort, MockPrinterPortBuilder, MockTCUPort, MockTCUPortBuilder};
+pub use playback::PlaybackPort;
+pub use port::{CommPort, CommPortBuilder, CommPortInfo, CommPortStats};
+pub use recorder::{Direction, TransactionRecord, TransactionRecorder};
 
 ////////////////////////////////////////////////////////////////
@@ -1,6 +1,7 @@
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, HashSet, VecDeque},
     io::{Read, Write},
+    time::Duration,
 };
 
 use serialport::SerialPort;
@@ -9,9 +10,77 @@ use serialport::SerialPort;
 // types
 ////////////////////////////////////////////////////////////////
 
+/// Serial port that responds to commands in the same way as a TCU, without requiring any hardware.
+///
+#[derive(Clone)]
 pub struct MockTCUPort {
     write: VecDeque<u8>,
     read: VecDeque<u8>,
+    baud_rate: u32,
+
+    /// Value returned when measuring each TCU channel.
+    channels: HashMap<u8, u16>,
+
+    /// Value returned when measuring each printer channel.
+    printer_channels: HashMap<u8, u16>,
+
+    /// Commands that respond with garbled data.
+    command_errors: HashSet<u8>,
+
+    /// TCU channels that respond with an error instead of a measurement.
+    error_channels: HashSet<u8>,
+
+    /// Time taken to flush the port.
+    flush_delay: Duration,
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Serial port that responds to commands in the same way as a printer connected over USB, without
+/// requiring any hardware. Anything written that isn't a command is treated as data to print and
+/// kept so that it can be inspected.
+///
+#[derive(Clone)]
+pub struct MockPrinterPort {
+    write: VecDeque<u8>,
+    read: VecDeque<u8>,
+    baud_rate: u32,
+
+    /// Value returned when measuring each channel.
+    channels: HashMap<u8, u16>,
+
+    /// Data received for printing.
+    printed: Vec<u8>,
+
+    /// Setting of each option set so far.
+    options: HashMap<u8, u8>,
+
+    time_format: Option<u8>,
+    time: Option<String>,
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Builder for configuring how a mock printer responds. Channels not configured by the builder
+/// return the same values as a typical printer.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockPrinterPortBuilder {
+    channels: HashMap<u8, u16>,
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Builder for configuring how a mock TCU responds. Channels not configured by the builder return
+/// the same values as a typical TCU and printer.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockTCUPortBuilder {
+    channels: HashMap<u8, u16>,
+    printer_channels: HashMap<u8, u16>,
+    command_errors: HashSet<u8>,
+    error_channels: HashSet<u8>,
+    flush_delay: Duration,
 }
 
 ////////////////////////////////////////////////////////////////
@@ -20,15 +89,218 @@ pub struct MockTCUPort {
 
 impl MockTCUPort {
     pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    pub fn builder() -> MockTCUPortBuilder {
+        MockTCUPortBuilder::default()
+    }
+}
+
+impl Default for MockTCUPort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl MockPrinterPort {
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    pub fn builder() -> MockPrinterPortBuilder {
+        MockPrinterPortBuilder::default()
+    }
+}
+
+impl Default for MockPrinterPort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl MockTCUPortBuilder {
+    /// Set the value returned when measuring a TCU channel.
+    ///
+    /// # Arguments
+    /// * `channel` - Channel to set the value of.
+    /// * `value` - Value to return when the channel is measured.
+    ///
+    pub fn channel(mut self, channel: u8, value: u16) -> Self {
+        self.channels.insert(channel, value);
+        self
+    }
+
+    /// Set the value returned when measuring a printer channel.
+    ///
+    /// # Arguments
+    /// * `channel` - Channel to set the value of.
+    /// * `value` - Value to return when the channel is measured.
+    ///
+    pub fn printer_channel(mut self, channel: u8, value: u16) -> Self {
+        self.printer_channels.insert(channel, value);
+        self
+    }
+
+    /// Make a command respond with garbled data. Commands that return a measurement echo correctly
+    /// but return a garbled measurement. Other commands return a garbled echo.
+    ///
+    /// # Arguments
+    /// * `cmd` - First character of the command e.g. 'M' for measurements.
+    ///
+    pub fn command_error(mut self, cmd: char) -> Self {
+        let cmd = u8::try_from(cmd).expect("TCU commands are ASCII");
+        self.command_errors.insert(cmd);
+        self
+    }
+
+    /// Make measuring a TCU channel respond with "ERROR\r" instead of a measurement, as a TCU
+    /// does when a measurement fails.
+    ///
+    /// # Arguments
+    /// * `channel` - Channel to respond with an error.
+    ///
+    pub fn respond_with_error(mut self, channel: u8) -> Self {
+        self.error_channels.insert(channel);
+        self
+    }
+
+    /// Make flushing the port take a given time e.g. to simulate a device that's been
+    /// disconnected.
+    ///
+    /// # Arguments
+    /// * `delay` - Time each flush blocks for.
+    ///
+    pub fn flush_delay(mut self, delay: Duration) -> Self {
+        self.flush_delay = delay;
+        self
+    }
+
+    pub fn build(self) -> MockTCUPort {
+        MockTCUPort {
+            write: VecDeque::new(),
+            read: VecDeque::new(),
+            baud_rate: 9600,
+            channels: self.channels,
+            printer_channels: self.printer_channels,
+            command_errors: self.command_errors,
+            error_channels: self.error_channels,
+            flush_delay: self.flush_delay,
+        }
+    }
+}
+
+impl Default for MockTCUPortBuilder {
+    fn default() -> Self {
+        let channels = HashMap::from([
+            (0x00, 100),  // battery current
+            (0x01, 100),  // charger current
+            (0x02, 1),    // cts level
+            (0x03, 5000), // vbatt
+            (0x04, 100),  // vdrop 100ms
+            (0x05, 200),  // vdrop 500ms
+            (0x06, 300),  // vdrop 2s
+            (0x07, 400),  // vdrop 5s
+            (0x08, 41),   // tcu software version
+            (0x09, 5000), // vref
+            (0x0A, 5000), // battery voltage internal
+            (0x0B, 5000), // charger voltage internal
+            (0x10, 0),    // BT address high
+            (0x11, 0),    // BT address mid
+            (0x12, 0),    // BT address low
+            (0x13, 1),    // BT address ready
+            (0x14, 1),    // BT connect pin=0
+            (0x15, 1),    // BT connect pin=1234
+            (0x16, 1),    // BT connect pin=4254
+        ]);
+
         Self {
+            channels,
+            printer_channels: default_printer_channels(),
+            command_errors: HashSet::new(),
+            error_channels: HashSet::new(),
+            flush_delay: Duration::ZERO,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl MockPrinterPortBuilder {
+    /// Set the value returned when measuring a channel.
+    ///
+    /// # Arguments
+    /// * `channel` - Channel to set the value of.
+    /// * `value` - Value to return when the channel is measured.
+    ///
+    pub fn channel(mut self, channel: u8, value: u16) -> Self {
+        self.channels.insert(channel, value);
+        self
+    }
+
+    pub fn build(self) -> MockPrinterPort {
+        MockPrinterPort {
             write: VecDeque::new(),
             read: VecDeque::new(),
+            baud_rate: 9600,
+            channels: self.channels,
+            printed: Vec::new(),
+            options: HashMap::new(),
+            time_format: None,
+            time: None,
+        }
+    }
+}
+
+impl Default for MockPrinterPortBuilder {
+    fn default() -> Self {
+        Self {
+            channels: default_printer_channels(),
         }
     }
 }
 
 ////////////////////////////////////////////////////////////////
 
+/// Return the value of each channel of a typical printer.
+///
+fn default_printer_channels() -> HashMap<u8, u16> {
+    let channels = HashMap::from([
+        (0, 5000), // battery voltage
+        (1, 5000), // charger voltage
+        (2, 5000), // vcc voltage
+        (3, 5000), // mech voltage
+        (4, 25),   // head temp
+        (5, 0x40), // paper sensor
+        (6, 1),    // wake signal
+        (7, 0),    // unused
+        (8, 0),    // feed button
+        (9, 0),    // unused
+        (10, 0),   // rtc present
+        (11, 0),   // unused
+        (12, 0),   // checksum
+        (13, 0),   // mech busy status
+        (14, 0),   // unused
+        (15, 0),   // unused
+        (16, 0),   // BT address
+        (17, 0),   // BT address
+        (18, 0),   // BT address
+        (19, 0),   // font lib present
+    ]);
+
+    // Options.
+    channels
+        .into_iter()
+        .chain((32..=63).map(|channel| (channel, 0)))
+        .collect()
+}
+
+////////////////////////////////////////////////////////////////
+
 fn tcu_decode_byte(bytes: &[u8]) -> u8 {
     let bytes = std::str::from_utf8(bytes).unwrap();
     u8::from_str_radix(bytes, 16).unwrap()
@@ -38,6 +310,15 @@ fn tcu_format_measurement(measurement: u16) -> Vec<u8> {
     format!("{measurement:04X}\r").into_bytes()
 }
 
+/// Replace every byte of a response other than its terminator with one that can't be parsed.
+///
+fn garble(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .map(|&b| if b == b'\r' { b } else { b'?' })
+        .collect()
+}
+
 ////////////////////////////////////////////////////////////////
 
 impl Write for MockTCUPort {
@@ -46,69 +327,47 @@ impl Write for MockTCUPort {
         if let Some(pos) = self.write.iter().position(|b| *b == b'\r') {
             let command: Vec<u8> = self.write.drain(0..=pos).collect();
 
-            self.read.extend(&command);
-
-            match &command[..] {
-                [b'P', ..] => (), // Print
-                [b'C', ..] => (), // Close relay
-                [b'O', ..] => (), // Open relay
+            let measurement = match &command[..] {
+                [b'P', ..] => None, // Print
+                [b'C', ..] => None, // Close relay
+                [b'O', ..] => None, // Open relay
 
                 // Measure
-                [b'M', channel @ .., b'\r'] => match tcu_decode_byte(channel) {
-                    0x00 => self.read.extend(tcu_format_measurement(100)), // battery current
-                    0x01 => self.read.extend(tcu_format_measurement(100)), // charger current
-                    0x02 => self.read.extend(tcu_format_measurement(1)),   // cts level
-                    0x03 => self.read.extend(tcu_format_measurement(5000)), // vbatt
-                    0x04 => self.read.extend(tcu_format_measurement(100)), // vdrop 100ms
-                    0x05 => self.read.extend(tcu_format_measurement(200)), // vdrop 500ms
-                    0x06 => self.read.extend(tcu_format_measurement(300)), // vdrop 2s
-                    0x07 => self.read.extend(tcu_format_measurement(400)), // vdrop 5s
-                    0x08 => self.read.extend(tcu_format_measurement(41)),  // tcu software version
-                    0x09 => self.read.extend(tcu_format_measurement(5000)), // vref
-                    0x0A => self.read.extend(tcu_format_measurement(5000)), // battery voltage internal
-                    0x0B => self.read.extend(tcu_format_measurement(5000)), // charger voltage internal
-                    0x10 => self.read.extend(tcu_format_measurement(0)),    // BT address high
-                    0x11 => self.read.extend(tcu_format_measurement(0)),    // BT address mid
-                    0x12 => self.read.extend(tcu_format_measurement(0)),    // BT address low
-                    0x13 => self.read.extend(tcu_format_measurement(1)),    // BT address ready
-                    0x14 => self.read.extend(tcu_format_measurement(1)),    // BT connect pin=0
-                    0x15 => self.read.extend(tcu_format_measurement(1)),    // BT connect pin=1234
-                    0x16 => self.read.extend(tcu_format_measurement(1)),    // BT connect pin=4254
-                    _ => panic!(
-                        "Unhandled TCU measure channel: {:?}",
-                        tcu_decode_byte(channel)
-                    ),
-                },
+                [b'M', channel @ .., b'\r'] => {
+                    let channel = tcu_decode_byte(channel);
+                    match self.channels.get(&channel) {
+                        _ if self.error_channels.contains(&channel) => Some(b"ERROR\r".to_vec()),
+                        Some(value) => Some(tcu_format_measurement(*value)),
+                        None => panic!("Unhandled TCU measure channel: {channel:?}"),
+                    }
+                }
 
                 // Measure from printer.
-                [b'W', .., b1, b2, b'\r'] => match tcu_decode_byte(&[*b1, *b2]) {
-                    0 => self.read.extend(tcu_format_measurement(5000)), // battery voltage
-                    1 => self.read.extend(tcu_format_measurement(5000)), // charger voltage
-                    2 => self.read.extend(tcu_format_measurement(5000)), // vcc voltage
-                    3 => self.read.extend(tcu_format_measurement(5000)), // mech voltage
-                    4 => self.read.extend(tcu_format_measurement(25)),   // head temp
-                    5 => self.read.extend(tcu_format_measurement(0x40)), // paper sensor
-                    6 => self.read.extend(tcu_format_measurement(1)),    // wake signal
-                    7 => self.read.extend(tcu_format_measurement(0)),    // unused
-                    8 => self.read.extend(tcu_format_measurement(0)),    // feed button
-                    9 => self.read.extend(tcu_format_measurement(0)),    // unused
-                    10 => self.read.extend(tcu_format_measurement(0)),   // rtc present
-                    11 => self.read.extend(tcu_format_measurement(0)),   // unused
-                    12 => self.read.extend(tcu_format_measurement(0)),   // checksum
-                    13 => self.read.extend(tcu_format_measurement(0)),   // mech busy status
-                    14 => self.read.extend(tcu_format_measurement(0)),   // unused
-                    15 => self.read.extend(tcu_format_measurement(0)),   // unused
-                    16 => self.read.extend(tcu_format_measurement(0)),   // BT address
-                    17 => self.read.extend(tcu_format_measurement(0)),   // BT address
-                    18 => self.read.extend(tcu_format_measurement(0)),   // BT address
-                    19 => self.read.extend(tcu_format_measurement(0)),   // font lib present
-                    32..=63 => self.read.extend(tcu_format_measurement(0)), // option
-                    _ => panic!(
-                        "Unhandled printer measure channel: {:?}",
-                        tcu_decode_byte(&[*b1, *b2])
-                    ),
-                },
+                [b'W', .., b1, b2, b'\r'] => {
+                    let channel = tcu_decode_byte(&[*b1, *b2]);
+                    match self.printer_channels.get(&channel) {
+                        Some(value) => Some(tcu_format_measurement(*value)),
+                        None => panic!("Unhandled printer measure channel: {channel:?}"),
+                    }
+                }
                 _ => panic!("Unhandled TCU command {command:?}"),
+            };
+
+            let garbled = command
+                .first()
+                .is_some_and(|cmd| self.command_errors.contains(cmd));
+
+            match measurement {
+                Some(measurement) if garbled => {
+                    self.read.extend(&command);
+                    self.read.extend(garble(&measurement));
+                }
+                Some(measurement) => {
+                    self.read.extend(&command);
+                    self.read.extend(measurement);
+                }
+                None if garbled => self.read.extend(garble(&command)),
+                None => self.read.extend(&command),
             }
         }
 
@@ -116,126 +375,364 @@ impl Write for MockTCUPort {
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
+        std::thread::sleep(self.flush_delay);
         Ok(())
     }
 }
 
 ////////////////////////////////////////////////////////////////
 
-impl Read for MockTCUPort {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let bytes = std::cmp::min(self.read.len(), buf.len());
+impl MockPrinterPort {
+    /// Return the data received for printing so far i.e. everything written that wasn't a
+    /// command.
+    ///
+    pub fn printed(&self) -> &[u8] {
+        &self.printed
+    }
+
+    /// Return the setting of an option, if it's been set.
+    ///
+    pub fn option(&self, option: u8) -> Option<u8> {
+        self.options.get(&option).copied()
+    }
+
+    /// Return the most recently set time format, if any.
+    ///
+    pub fn time_format(&self) -> Option<u8> {
+        self.time_format
+    }
+
+    /// Return the most recently set time, if any, as sent by the host e.g. "12:30:00,01/02/24".
+    ///
+    pub fn time(&self) -> Option<&str> {
+        self.time.as_deref()
+    }
+
+    /// Handle every complete command written so far. Commands begin with an ESC byte, which may
+    /// be followed by up to two zeros depending on whether HPMODE is active.
+    ///
+    /// # Returns
+    /// An error of kind InvalidInput if a command isn't recognised, in which case everything
+    /// written so far is discarded.
+    ///
+    fn process_written(&mut self) -> std::io::Result<()> {
+        loop {
+            let data = self.write.iter().position(|b| *b == ESC);
+            let data = data.unwrap_or(self.write.len());
+            self.printed.extend(self.write.drain(..data));
+
+            let zeros = self.write.iter().skip(1).take_while(|b| **b == 0).count();
+            let command: Vec<u8> = self.write.iter().skip(1 + zeros).copied().collect();
+
+            let length = match &command[..] {
+                [] | [b't'] => return Ok(()),
+                [b'M', ..] | [b'S', ..] => 2,
+                [b'O', ..] | [b't', b'f', ..] => 3,
+                [b't', b's', ..] => 2 + DATETIME_LENGTH,
+                _ => return Err(self.invalid_command(&format!("Unhandled command {command:?}"))),
+            };
+
+            if command.len() < length {
+                return Ok(());
+            }
+
+            match &command[..length] {
+                [b'M', channel] => match self.channels.get(channel) {
+                    Some(value) => self.read.extend(tcu_format_measurement(*value)),
+                    None => {
+                        let message = format!("Unhandled measure channel {channel}");
+                        return Err(self.invalid_command(&message));
+                    }
+                },
+                [b'S', _] => (),
+                [b'O', option, setting] => {
+                    self.options.insert(*option, *setting);
+                }
+                [b't', b'f', format] => self.time_format = Some(*format),
+                [b't', b's', time @ ..] => {
+                    self.time = Some(String::from_utf8_lossy(time).into_owned());
+                }
+                _ => unreachable!("Command length is determined by the command"),
+            }
 
-        for b in buf.iter_mut().take(bytes) {
-            *b = self.read.pop_front().unwrap();
+            self.write.drain(..1 + zeros + length);
         }
+    }
 
-        Ok(bytes)
+    /// Discard everything written so far and create an error for a command that isn't recognised.
+    ///
+    fn invalid_command(&mut self, message: &str) -> std::io::Error {
+        self.write.clear();
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
     }
 }
 
 ////////////////////////////////////////////////////////////////
 
-impl SerialPort for MockTCUPort {
-    fn name(&self) -> Option<String> {
-        todo!()
-    }
+const ESC: u8 = 0x1B;
 
-    fn baud_rate(&self) -> serialport::Result<u32> {
-        todo!()
-    }
+/// Length of the time sent with the set time command e.g. "12:30:00,01/02/24".
+const DATETIME_LENGTH: usize = 17;
 
-    fn data_bits(&self) -> serialport::Result<serialport::DataBits> {
-        todo!()
-    }
+////////////////////////////////////////////////////////////////
 
-    fn flow_control(&self) -> serialport::Result<serialport::FlowControl> {
-        todo!()
+impl Write for MockPrinterPort {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write.extend(buf);
+        self.process_written()?;
+        Ok(buf.len())
     }
 
-    fn parity(&self) -> serialport::Result<serialport::Parity> {
-        todo!()
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
+}
 
-    fn stop_bits(&self) -> serialport::Result<serialport::StopBits> {
-        todo!()
-    }
+////////////////////////////////////////////////////////////////
 
-    fn timeout(&self) -> std::time::Duration {
-        todo!()
-    }
+/// Implement Read and SerialPort for a mock port. The port must have `read: VecDeque<u8>` and
+/// `baud_rate: u32` fields. Settings that the mocks don't simulate are left unimplemented.
+///
+macro_rules! impl_mock_port {
+    ($port:ty) => {
+        impl Read for $port {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let bytes = std::cmp::min(self.read.len(), buf.len());
 
-    fn set_baud_rate(&mut self, _: u32) -> serialport::Result<()> {
-        todo!()
-    }
+                for b in buf.iter_mut().take(bytes) {
+                    *b = self.read.pop_front().unwrap();
+                }
 
-    fn set_data_bits(&mut self, _: serialport::DataBits) -> serialport::Result<()> {
-        todo!()
-    }
+                Ok(bytes)
+            }
+        }
 
-    fn set_flow_control(&mut self, _: serialport::FlowControl) -> serialport::Result<()> {
-        todo!()
-    }
+        impl SerialPort for $port {
+            fn name(&self) -> Option<String> {
+                Some(String::from("mock"))
+            }
 
-    fn set_parity(&mut self, _: serialport::Parity) -> serialport::Result<()> {
-        todo!()
-    }
+            fn baud_rate(&self) -> serialport::Result<u32> {
+                Ok(self.baud_rate)
+            }
 
-    fn set_stop_bits(&mut self, _: serialport::StopBits) -> serialport::Result<()> {
-        todo!()
-    }
+            fn data_bits(&self) -> serialport::Result<serialport::DataBits> {
+                todo!()
+            }
 
-    fn set_timeout(&mut self, _: std::time::Duration) -> serialport::Result<()> {
-        todo!()
-    }
+            fn flow_control(&self) -> serialport::Result<serialport::FlowControl> {
+                todo!()
+            }
 
-    fn write_request_to_send(&mut self, _: bool) -> serialport::Result<()> {
-        todo!()
-    }
+            fn parity(&self) -> serialport::Result<serialport::Parity> {
+                todo!()
+            }
 
-    fn write_data_terminal_ready(&mut self, _: bool) -> serialport::Result<()> {
-        todo!()
-    }
+            fn stop_bits(&self) -> serialport::Result<serialport::StopBits> {
+                todo!()
+            }
 
-    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
-        todo!()
-    }
+            fn timeout(&self) -> std::time::Duration {
+                todo!()
+            }
 
-    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
-        todo!()
-    }
+            fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+                self.baud_rate = baud_rate;
+                Ok(())
+            }
 
-    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
-        todo!()
-    }
+            fn set_data_bits(&mut self, _: serialport::DataBits) -> serialport::Result<()> {
+                todo!()
+            }
+
+            fn set_flow_control(&mut self, _: serialport::FlowControl) -> serialport::Result<()> {
+                todo!()
+            }
+
+            fn set_parity(&mut self, _: serialport::Parity) -> serialport::Result<()> {
+                todo!()
+            }
 
-    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
-        todo!()
+            fn set_stop_bits(&mut self, _: serialport::StopBits) -> serialport::Result<()> {
+                todo!()
+            }
+
+            fn set_timeout(&mut self, _: std::time::Duration) -> serialport::Result<()> {
+                todo!()
+            }
+
+            fn write_request_to_send(&mut self, _: bool) -> serialport::Result<()> {
+                todo!()
+            }
+
+            fn write_data_terminal_ready(&mut self, _: bool) -> serialport::Result<()> {
+                todo!()
+            }
+
+            fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+                todo!()
+            }
+
+            fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+                todo!()
+            }
+
+            fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+                todo!()
+            }
+
+            fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+                todo!()
+            }
+
+            fn bytes_to_read(&self) -> serialport::Result<u32> {
+                Ok(self.read.len() as u32)
+            }
+
+            fn bytes_to_write(&self) -> serialport::Result<u32> {
+                todo!()
+            }
+
+            fn clear(&self, _: serialport::ClearBuffer) -> serialport::Result<()> {
+                Err(serialport::Error::new(
+                    serialport::ErrorKind::Unknown,
+                    "Clearing buffers isn't supported by the mock port",
+                ))
+            }
+
+            /// The clone responds in the same way but doesn't share any data waiting to be read.
+            fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+                Ok(Box::new(self.clone()))
+            }
+
+            fn set_break(&self) -> serialport::Result<()> {
+                todo!()
+            }
+
+            fn clear_break(&self) -> serialport::Result<()> {
+                todo!()
+            }
+        }
+    };
+}
+
+impl_mock_port!(MockTCUPort);
+impl_mock_port!(MockPrinterPort);
+
+////////////////////////////////////////////////////////////////
+// tests
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CommPort;
+
+    ////////////////////////////////////////////////////////////////
+
+    /// Write a command to the port and return everything it responds with.
+    ///
+    fn transact(port: &mut MockTCUPort, command: &[u8]) -> Vec<u8> {
+        port.write_all(command).unwrap();
+
+        let mut buffer = [0; 256];
+        let count = port.read(&mut buffer).unwrap();
+        buffer[..count].to_vec()
     }
 
-    fn bytes_to_read(&self) -> serialport::Result<u32> {
-        Ok(self.read.len() as u32)
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_builder_channels() {
+        let baseline = MockTCUPort::builder().channel(0x03, 4000);
+        let mut port = baseline.clone().printer_channel(4, 30).build();
+
+        assert_eq!(transact(&mut port, b"M03\r"), b"M03\r0FA0\r");
+        assert_eq!(transact(&mut port, b"M01\r"), b"M01\r0064\r");
+        assert_eq!(transact(&mut port, b"W04\r"), b"W04\r001E\r");
+
+        let mut port = baseline.build();
+        assert_eq!(transact(&mut port, b"W04\r"), b"W04\r0019\r");
     }
 
-    fn bytes_to_write(&self) -> serialport::Result<u32> {
-        todo!()
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_builder_command_error() {
+        let mut port = MockTCUPort::builder()
+            .command_error('M')
+            .command_error('C')
+            .build();
+
+        assert_eq!(transact(&mut port, b"M03\r"), b"M03\r????\r");
+        assert_eq!(transact(&mut port, b"C05\r"), b"???\r");
+        assert_eq!(transact(&mut port, b"O05\r"), b"O05\r");
     }
 
-    fn clear(&self, _: serialport::ClearBuffer) -> serialport::Result<()> {
-        todo!()
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_set_baud_rate() {
+        let mut port = CommPort::from(Box::new(MockTCUPort::new()) as Box<dyn SerialPort>);
+        assert_eq!(port.baud_rate().unwrap(), 9600);
+
+        port.set_baud_rate(115200).unwrap();
+        assert_eq!(port.baud_rate().unwrap(), 115200);
     }
 
-    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
-        todo!()
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_builder_respond_with_error() {
+        let mut port = MockTCUPort::builder().respond_with_error(0x03).build();
+
+        assert_eq!(transact(&mut port, b"M03\r"), b"M03\rERROR\r");
+        assert_eq!(transact(&mut port, b"M01\r"), b"M01\r0064\r");
     }
 
-    fn set_break(&self) -> serialport::Result<()> {
-        todo!()
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_printer_commands() {
+        let mut port = MockPrinterPort::builder().channel(4, 30).build();
+
+        port.write_all(b"Hello\x1B\x00O\x05\x01, world").unwrap();
+        port.write_all(b"\x1B\x00\x00S\x02\x1Btf\x03").unwrap();
+        assert_eq!(port.printed(), b"Hello, world");
+        assert_eq!(port.option(5), Some(1));
+        assert_eq!(port.time_format(), Some(3));
+
+        // Commands may be split across writes.
+        port.write_all(b"\x1B\x00ts12:30:00").unwrap();
+        assert_eq!(port.time(), None);
+        port.write_all(b",01/02/24").unwrap();
+        assert_eq!(port.time(), Some("12:30:00,01/02/24"));
+
+        let mut buffer = [0; 256];
+        port.write_all(b"\x1B\x00M\x04\x1B\x00\x00M\x00").unwrap();
+        let count = port.read(&mut buffer).unwrap();
+        assert_eq!(&buffer[..count], b"001E\r1388\r");
+        assert_eq!(port.printed(), b"Hello, world");
     }
 
-    fn clear_break(&self) -> serialport::Result<()> {
-        todo!()
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_printer_unknown_command() {
+        let mut port = MockPrinterPort::builder().build();
+
+        let error = port.write_all(b"\x1B\x00Z").unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        let error = port.write_all(b"\x1B\x00M\x7F").unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        // The port recovers once the invalid command is discarded.
+        port.write_all(b"\x1B\x00O\x05\x01").unwrap();
+        assert_eq!(port.option(5), Some(1));
     }
+
+    ////////////////////////////////////////////////////////////////
 }
 
 ////////////////////////////////////////////////////////////////
@@ -0,0 +1,249 @@
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    path::Path,
+    time::Duration,
+};
+
+use serialport::{DataBits, FlowControl, Parity, SerialPort, StopBits};
+
+use crate::recorder::{Direction, TransactionRecord};
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Serial port that replays the records saved by a TransactionRecorder. Recorded responses are only
+/// made available once the bytes sent before them have been written, so that a script sees the
+/// same exchange it did when recording.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlaybackPort {
+    records: VecDeque<TransactionRecord>,
+}
+
+////////////////////////////////////////////////////////////////
+// construction / conversion
+////////////////////////////////////////////////////////////////
+
+impl PlaybackPort {
+    pub fn new(records: impl IntoIterator<Item = TransactionRecord>) -> Self {
+        Self {
+            records: records.into_iter().collect(),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> std::io::Result<Self> {
+        TransactionRecord::load_from_file(path).map(Self::new)
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// traits
+////////////////////////////////////////////////////////////////
+
+impl Read for PlaybackPort {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some(record) = self
+            .records
+            .front_mut()
+            .filter(|record| record.direction == Direction::Received)
+        else {
+            return Err(std::io::ErrorKind::TimedOut.into());
+        };
+
+        let count = std::cmp::min(buf.len(), record.bytes.len());
+        for (byte, recorded) in buf.iter_mut().zip(record.bytes.drain(..count)) {
+            *byte = recorded;
+        }
+
+        if record.bytes.is_empty() {
+            self.records.pop_front();
+        }
+
+        Ok(count)
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl Write for PlaybackPort {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // Consume the recorded bytes that were sent, regardless of whether they match, so that the
+        // responses following them become available.
+        let mut remaining = buf.len();
+        while remaining > 0 {
+            let Some(record) = self
+                .records
+                .front_mut()
+                .filter(|record| record.direction == Direction::Sent)
+            else {
+                break;
+            };
+
+            let count = std::cmp::min(remaining, record.bytes.len());
+            record.bytes.drain(..count);
+            remaining -= count;
+
+            if record.bytes.is_empty() {
+                self.records.pop_front();
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl SerialPort for PlaybackPort {
+    fn name(&self) -> Option<String> {
+        Some(String::from("playback"))
+    }
+
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(9600)
+    }
+
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        Ok(DataBits::Eight)
+    }
+
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        Ok(FlowControl::None)
+    }
+
+    fn parity(&self) -> serialport::Result<Parity> {
+        Ok(Parity::None)
+    }
+
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        Ok(StopBits::One)
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn set_baud_rate(&mut self, _: u32) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_data_bits(&mut self, _: DataBits) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_flow_control(&mut self, _: FlowControl) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_parity(&mut self, _: Parity) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_stop_bits(&mut self, _: StopBits) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, _: Duration) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn write_request_to_send(&mut self, _: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn write_data_terminal_ready(&mut self, _: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        let count = self
+            .records
+            .front()
+            .filter(|record| record.direction == Direction::Received)
+            .map_or(0, |record| record.bytes.len());
+
+        Ok(count as u32)
+    }
+
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+
+    fn clear(&self, _: serialport::ClearBuffer) -> serialport::Result<()> {
+        Err(serialport::Error::new(
+            serialport::ErrorKind::Unknown,
+            "Clearing buffers isn't supported by the playback port",
+        ))
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(self.clone()))
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// tests
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use crate::{MockTCUPort, TransactionRecorder};
+
+    use super::*;
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_record_and_playback() {
+        let mut recorder = TransactionRecorder::new(Box::new(MockTCUPort::new()));
+        recorder.write_all(b"M03\r").unwrap();
+
+        let mut buffer = [0; 256];
+        let count = recorder.read(&mut buffer).unwrap();
+        assert_eq!(&buffer[..count], b"M03\r1388\r");
+
+        let path = std::env::temp_dir().join("gallivant_test_record_and_playback.txt");
+        recorder.save_to_file(&path).unwrap();
+        let mut playback = PlaybackPort::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // Nothing to read until the recorded command has been sent.
+        assert!(playback.read(&mut buffer).is_err());
+
+        playback.write_all(b"M03\r").unwrap();
+        let count = playback.read(&mut buffer).unwrap();
+        assert_eq!(&buffer[..count], b"M03\r1388\r");
+    }
+}
+
+////////////////////////////////////////////////////////////////
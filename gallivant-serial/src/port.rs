@@ -1,7 +1,11 @@
-use std::time::Duration;
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
 
 use serialport::{
-    self, DataBits, Error, ErrorKind, FlowControl, Parity, SerialPort, SerialPortBuilder, StopBits,
+    self, ClearBuffer, DataBits, Error, ErrorKind, FlowControl, Parity, SerialPort,
+    SerialPortBuilder, SerialPortInfo, SerialPortType, StopBits,
 };
 
 ////////////////////////////////////////////////////////////////
@@ -11,7 +15,8 @@ use serialport::{
 /// Re-implementation of serialport::SerialPortBuilder because we would like provide read access to
 /// the fields please and thank you.
 /// Unlike serialport::SerialPortBuilder, this doesn't allow changing the timeout period as we use
-/// that internally to determine if the stream is empty or not when reading from it.
+/// that internally to determine if the stream is empty or not when reading from it. The session
+/// timeout may be set instead, which is what CommPort::session_timeout returns.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct CommPortBuilder {
     path: String,
@@ -20,7 +25,15 @@ pub struct CommPortBuilder {
     flow_control: FlowControl,
     parity: Parity,
     stop_bits: StopBits,
+
+    /// Read polling period of the underlying port.
     timeout: Duration,
+
+    /// Time users of the port should allow for a response. Never applied to the underlying port.
+    session_timeout: Duration,
+
+    /// Whether to discard unsolicited bytes received after writes that expect no response.
+    clear_after_write: bool,
 }
 
 ////////////////////////////////////////////////////////////////
@@ -30,9 +43,62 @@ pub struct CommPortBuilder {
 #[derive(Debug)]
 pub enum CommPort {
     Closed(CommPortBuilder),
-    Open(Box<dyn SerialPort>),
+    Open {
+        port: Box<dyn SerialPort>,
+        stats: CommPortStats,
+
+        /// Path and baud rate the port was opened with. These aren't updated if the port's
+        /// settings are changed while open.
+        path: String,
+        baud_rate: u32,
+
+        /// Whether to discard unsolicited bytes received after writes that expect no response.
+        clear_after_write: bool,
+
+        /// Time users of the port should allow for a response.
+        session_timeout: Duration,
+    },
 }
 
+////////////////////////////////////////////////////////////////
+
+/// Counts of the traffic and errors seen by an open port.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CommPortStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+
+    /// Bytes received but discarded by drain_input rather than read.
+    pub bytes_discarded: u64,
+    pub read_errors: u64,
+    pub write_errors: u64,
+
+    /// Writes and flushes that timed out and transactions abandoned because the device didn't
+    /// respond in time. Reads time out whenever the device has nothing more to send so they aren't
+    /// counted.
+    pub timeouts: u64,
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Description of a serial port available on the system.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommPortInfo {
+    pub path: String,
+
+    /// Product and manufacturer are only known for USB ports.
+    pub product: Option<String>,
+    pub manufacturer: Option<String>,
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Session timeout of ports created by a builder unless set otherwise.
+///
+const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(1);
+
 ////////////////////////////////////////////////////////////////
 // construction / conversion
 ////////////////////////////////////////////////////////////////
@@ -48,22 +114,197 @@ impl CommPort {
     pub fn open(&mut self) -> Result<(), Error> {
         match self {
             Self::Closed(port) => {
-                *self = Self::Open(SerialPortBuilder::from(port.clone()).open()?);
+                *self = Self::Open {
+                    port: SerialPortBuilder::from(port.clone()).open()?,
+                    stats: CommPortStats::default(),
+                    path: port.path.clone(),
+                    baud_rate: port.baud_rate,
+                    clear_after_write: port.clear_after_write,
+                    session_timeout: port.session_timeout,
+                };
                 Ok(())
             }
-            Self::Open(_) => Ok(()),
+            Self::Open { .. } => Ok(()),
         }
     }
 
     pub fn close(&mut self) -> Result<(), Error> {
         match self {
             Self::Closed(_) => Ok(()),
-            Self::Open(port) => {
-                *self = Self::Closed(CommPortBuilder::from_serial_port(port.as_ref())?);
+            Self::Open {
+                port,
+                clear_after_write,
+                session_timeout,
+                ..
+            } => {
+                let builder = CommPortBuilder::from_serial_port(port.as_ref())?
+                    .clear_after_write(*clear_after_write)
+                    .session_timeout(*session_timeout);
+                *self = Self::Closed(builder);
                 Ok(())
             }
         }
     }
+
+    /// Discard any bytes waiting in the port's receive buffer. Where the port doesn't support
+    /// clearing it's buffer, bytes are read and discarded until the port times out instead.
+    ///
+    /// # Returns
+    /// The number of bytes discarded.
+    ///
+    pub fn drain_input(&mut self) -> Result<usize, Error> {
+        let Self::Open { port, stats, .. } = self else {
+            return Err(Error::new(ErrorKind::NoDevice, "Port closed"));
+        };
+
+        let pending = port.bytes_to_read()?;
+        if port.clear(ClearBuffer::Input).is_ok() {
+            stats.bytes_discarded += u64::from(pending);
+            return Ok(pending as usize);
+        }
+
+        let mut count = 0;
+        let mut buffer = [0; 256];
+        loop {
+            match port.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => count += read,
+                Err(error) if error.kind() == std::io::ErrorKind::TimedOut => break,
+                Err(error) => {
+                    stats.read_errors += 1;
+                    return Err(error.into());
+                }
+            }
+        }
+
+        stats.bytes_discarded += count as u64;
+        Ok(count)
+    }
+
+    /// Record that a transaction was abandoned because the device didn't respond in time. Has no
+    /// effect if the port is closed.
+    ///
+    pub fn transaction_timed_out(&mut self) {
+        if let Self::Open { stats, .. } = self {
+            stats.timeouts += 1;
+        }
+    }
+
+    /// Flush the port, giving up if it doesn't complete in time e.g. because the device has been
+    /// disconnected. The flush is carried out on a clone of the port by a separate thread, which
+    /// is left blocked if it times out.
+    ///
+    /// # Arguments
+    /// * `timeout` - Time to wait for the flush to complete.
+    ///
+    /// # Returns
+    /// An error of kind TimedOut if the flush didn't complete in time.
+    ///
+    pub fn flush_with_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        let Self::Open { port, stats, .. } = self else {
+            return Err(Error::new(ErrorKind::NoDevice, "Port closed"));
+        };
+
+        let mut clone = port.try_clone()?;
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || sender.send(clone.flush()));
+
+        match receiver.recv_timeout(timeout) {
+            Ok(result) => result.map_err(|error| {
+                stats.write_errors += 1;
+                Error::from(error)
+            }),
+            Err(RecvTimeoutError::Timeout) => {
+                stats.timeouts += 1;
+                Err(Error::new(
+                    ErrorKind::Io(std::io::ErrorKind::TimedOut),
+                    "Flush timed out",
+                ))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(Error::new(
+                ErrorKind::Unknown,
+                "Flush thread exited without completing",
+            )),
+        }
+    }
+
+    /// Set whether to discard unsolicited bytes received after writes that expect no response. Some
+    /// devices e.g. printers may send bytes that would otherwise corrupt the response to the next
+    /// transaction. Disabled by default.
+    ///
+    pub fn clear_after_write(&mut self, clear: bool) {
+        match self {
+            Self::Closed(port) => port.clear_after_write = clear,
+            Self::Open {
+                clear_after_write, ..
+            } => *clear_after_write = clear,
+        }
+    }
+
+    /// Discard any bytes waiting in the port's receive buffer if the port is set to clear after
+    /// writes. Should be called once a write that expects no response has completed.
+    ///
+    /// # Returns
+    /// The number of bytes discarded.
+    ///
+    pub fn write_complete(&mut self) -> Result<usize, Error> {
+        match self {
+            Self::Open {
+                clear_after_write: true,
+                ..
+            } => self.drain_input(),
+            _ => Ok(0),
+        }
+    }
+
+    /// Return the traffic and errors seen by the port since it was opened, or None if it's closed.
+    ///
+    pub fn stats(&self) -> Option<&CommPortStats> {
+        match self {
+            Self::Closed(_) => None,
+            Self::Open { stats, .. } => Some(stats),
+        }
+    }
+
+    /// Return the time users of the port should allow for a response, whether it's open or closed.
+    /// This is set by CommPortBuilder::session_timeout or set_timeout, and is separate from the
+    /// timeout of the underlying port returned by timeout.
+    ///
+    pub fn session_timeout(&self) -> Duration {
+        match self {
+            Self::Closed(port) => port.session_timeout,
+            Self::Open {
+                session_timeout, ..
+            } => *session_timeout,
+        }
+    }
+
+    /// Return the path of the port, whether it's open or closed. Ports opened from an existing
+    /// serial port that doesn't report a name have an empty path.
+    ///
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Closed(port) => &port.path,
+            Self::Open { path, .. } => path,
+        }
+    }
+
+    /// Return the baud rate the port was configured with, whether it's open or closed. Unlike
+    /// SerialPort::baud_rate, this doesn't reflect changes made to an open port.
+    ///
+    pub fn configured_baud_rate(&self) -> u32 {
+        match self {
+            Self::Closed(port) => port.baud_rate,
+            Self::Open { baud_rate, .. } => *baud_rate,
+        }
+    }
+
+    /// Return every serial port available on the system.
+    ///
+    pub fn list_available() -> Result<Vec<CommPortInfo>, Error> {
+        let ports = serialport::available_ports()?;
+        Ok(ports.into_iter().map(CommPortInfo::from).collect())
+    }
 }
 
 ////////////////////////////////////////////////////////////////
@@ -91,6 +332,8 @@ impl CommPortBuilder {
             parity: Parity::None,
             stop_bits: StopBits::One,
             timeout: Duration::from_millis(1),
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+            clear_after_write: false,
         }
     }
 
@@ -110,6 +353,8 @@ impl CommPortBuilder {
             parity: port.parity()?,
             stop_bits: port.stop_bits()?,
             timeout: port.timeout(),
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+            clear_after_write: false,
         })
     }
 }
@@ -159,8 +404,67 @@ impl CommPortBuilder {
         self
     }
 
-    pub fn build(self) -> CommPort {
-        CommPort::from(self)
+    /// Set the time users of the port should allow for a response. This is what
+    /// CommPort::session_timeout returns. It isn't applied to the underlying port, whose timeout is
+    /// used internally when reading.
+    ///
+    #[must_use]
+    pub fn session_timeout(mut self, timeout: Duration) -> Self {
+        self.session_timeout = timeout;
+        self
+    }
+
+    /// Set whether to discard unsolicited bytes received after writes that expect no response.
+    ///
+    #[must_use]
+    pub fn clear_after_write(mut self, clear: bool) -> Self {
+        self.clear_after_write = clear;
+        self
+    }
+
+    /// Check that the builder describes a port that could be opened i.e. that the path isn't empty
+    /// and the baud rate isn't 0.
+    ///
+    /// # Returns
+    /// An error of kind InvalidInput describing the first invalid parameter found.
+    ///
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.path.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Invalid path - path must not be empty",
+            ));
+        }
+
+        if self.baud_rate == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Invalid baud rate - baud rate must be greater than 0",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Create a closed port described by the builder.
+    ///
+    /// # Returns
+    /// The closed port or an error if the builder is invalid.
+    ///
+    pub fn build(self) -> Result<CommPort, Error> {
+        self.validate()?;
+        Ok(CommPort::from(self))
+    }
+
+    /// Open the port described by the builder.
+    ///
+    /// # Returns
+    /// The open port or an error if the builder is invalid or the port couldn't be opened.
+    ///
+    pub fn open(self) -> Result<CommPort, Error> {
+        let mut port = self.build()?;
+        port.open()?;
+        Ok(port)
     }
 }
 
@@ -172,11 +476,35 @@ impl<T: Into<CommPortBuilder>> From<T> for CommPort {
     }
 }
 
-////////////////////////////////////////////////////////////////ru
+////////////////////////////////////////////////////////////////
 
 impl From<Box<dyn SerialPort>> for CommPort {
     fn from(port: Box<dyn SerialPort>) -> Self {
-        Self::Open(port)
+        Self::Open {
+            path: port.name().unwrap_or_default(),
+            baud_rate: port.baud_rate().unwrap_or_default(),
+            port,
+            stats: CommPortStats::default(),
+            clear_after_write: false,
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl From<SerialPortInfo> for CommPortInfo {
+    fn from(info: SerialPortInfo) -> Self {
+        let (product, manufacturer) = match info.port_type {
+            SerialPortType::UsbPort(usb) => (usb.product, usb.manufacturer),
+            _ => (None, None),
+        };
+
+        Self {
+            path: info.port_name,
+            product,
+            manufacturer,
+        }
     }
 }
 
@@ -191,7 +519,7 @@ impl std::io::Read for CommPort {
                 ErrorKind::NoDevice,
                 "Port closed",
             ))),
-            CommPort::Open(port) => {
+            CommPort::Open { port, stats, .. } => {
                 // There's no guarantee that there'll be a termination character in the stream so
                 // we need to read one byte at a time and use timeout to determine if the stream's
                 // empty. Otherwise we'll either get a timeout error or get blocked here forever if
@@ -200,17 +528,22 @@ impl std::io::Read for CommPort {
                 for byte in buf {
                     let mut byte_read = [0; 1];
                     match port.read(&mut byte_read) {
+                        Ok(0) => break,
                         Ok(_) => {
                             *byte = byte_read[0];
                             count += 1;
                         }
                         Err(error) => match error.kind() {
                             std::io::ErrorKind::TimedOut => break,
-                            _ => return Err(error),
+                            _ => {
+                                stats.read_errors += 1;
+                                return Err(error);
+                            }
                         },
                     }
                 }
 
+                stats.bytes_received += count as u64;
                 Ok(count)
             }
         }
@@ -223,14 +556,27 @@ impl std::io::Write for CommPort {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         match self {
             CommPort::Closed(_) => Err(Error::new(ErrorKind::NoDevice, "Port closed").into()),
-            CommPort::Open(port) => port.write(buf),
+            CommPort::Open { port, stats, .. } => match port.write(buf) {
+                Ok(count) => {
+                    stats.bytes_sent += count as u64;
+                    Ok(count)
+                }
+                Err(error) => {
+                    match error.kind() {
+                        std::io::ErrorKind::TimedOut => stats.timeouts += 1,
+                        _ => stats.write_errors += 1,
+                    }
+
+                    Err(error)
+                }
+            },
         }
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
         match self {
             CommPort::Closed(_) => Err(Error::new(ErrorKind::NoDevice, "Port closed").into()),
-            CommPort::Open(port) => port.flush(),
+            CommPort::Open { port, .. } => port.flush(),
         }
     }
 }
@@ -241,49 +587,52 @@ impl SerialPort for CommPort {
     fn name(&self) -> Option<String> {
         match self {
             CommPort::Closed(port) => Some(port.path.clone()),
-            CommPort::Open(port) => port.name(),
+            CommPort::Open { port, .. } => port.name(),
         }
     }
 
     fn baud_rate(&self) -> serialport::Result<u32> {
         match self {
             CommPort::Closed(port) => Ok(port.baud_rate),
-            CommPort::Open(port) => port.baud_rate(),
+            CommPort::Open { port, .. } => port.baud_rate(),
         }
     }
 
     fn data_bits(&self) -> serialport::Result<serialport::DataBits> {
         match self {
             CommPort::Closed(port) => Ok(port.data_bits),
-            CommPort::Open(port) => port.data_bits(),
+            CommPort::Open { port, .. } => port.data_bits(),
         }
     }
 
     fn flow_control(&self) -> serialport::Result<serialport::FlowControl> {
         match self {
             CommPort::Closed(port) => Ok(port.flow_control),
-            CommPort::Open(port) => port.flow_control(),
+            CommPort::Open { port, .. } => port.flow_control(),
         }
     }
 
     fn parity(&self) -> serialport::Result<serialport::Parity> {
         match self {
             CommPort::Closed(port) => Ok(port.parity),
-            CommPort::Open(port) => port.parity(),
+            CommPort::Open { port, .. } => port.parity(),
         }
     }
 
     fn stop_bits(&self) -> serialport::Result<serialport::StopBits> {
         match self {
             CommPort::Closed(port) => Ok(port.stop_bits),
-            CommPort::Open(port) => port.stop_bits(),
+            CommPort::Open { port, .. } => port.stop_bits(),
         }
     }
 
+    /// Return the timeout of the underlying port, which is used internally when reading. See
+    /// CommPort::session_timeout for the time users of the port should allow for a response.
+    ///
     fn timeout(&self) -> Duration {
         match self {
             CommPort::Closed(port) => port.timeout,
-            CommPort::Open(port) => port.timeout(),
+            CommPort::Open { port, .. } => port.timeout(),
         }
     }
 
@@ -293,7 +642,7 @@ impl SerialPort for CommPort {
                 *port = port.clone().baud_rate(baud_rate);
                 Ok(())
             }
-            CommPort::Open(port) => port.set_baud_rate(baud_rate),
+            CommPort::Open { port, .. } => port.set_baud_rate(baud_rate),
         }
     }
 
@@ -303,7 +652,7 @@ impl SerialPort for CommPort {
                 *port = port.clone().data_bits(data_bits);
                 Ok(())
             }
-            CommPort::Open(port) => port.set_data_bits(data_bits),
+            CommPort::Open { port, .. } => port.set_data_bits(data_bits),
         }
     }
 
@@ -316,7 +665,7 @@ impl SerialPort for CommPort {
                 *port = port.clone().flow_control(flow_control);
                 Ok(())
             }
-            CommPort::Open(port) => port.set_flow_control(flow_control),
+            CommPort::Open { port, .. } => port.set_flow_control(flow_control),
         }
     }
 
@@ -326,7 +675,7 @@ impl SerialPort for CommPort {
                 *port = port.clone().parity(parity);
                 Ok(())
             }
-            CommPort::Open(port) => port.set_parity(parity),
+            CommPort::Open { port, .. } => port.set_parity(parity),
         }
     }
 
@@ -336,99 +685,371 @@ impl SerialPort for CommPort {
                 *port = port.clone().stop_bits(stop_bits);
                 Ok(())
             }
-            CommPort::Open(port) => port.set_stop_bits(stop_bits),
+            CommPort::Open { port, .. } => port.set_stop_bits(stop_bits),
         }
     }
 
-    /// Changing timeout is not allowed >:(
-    /// It's required to internally to make reading more erganomic.
-    fn set_timeout(&mut self, _: Duration) -> serialport::Result<()> {
+    /// Set the session timeout, as returned by CommPort::session_timeout. The timeout of the
+    /// underlying port can't be changed as it's used internally to make reading more ergonomic.
+    ///
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        match self {
+            CommPort::Closed(port) => port.session_timeout = timeout,
+            CommPort::Open {
+                session_timeout, ..
+            } => *session_timeout = timeout,
+        }
+
         Ok(())
     }
 
     fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
         match self {
             CommPort::Closed(_) => Err(Error::new(ErrorKind::NoDevice, "Port closed")),
-            CommPort::Open(port) => port.write_request_to_send(level),
+            CommPort::Open { port, .. } => port.write_request_to_send(level),
         }
     }
 
     fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
         match self {
             CommPort::Closed(_) => Err(Error::new(ErrorKind::NoDevice, "Port closed")),
-            CommPort::Open(port) => port.write_data_terminal_ready(level),
+            CommPort::Open { port, .. } => port.write_data_terminal_ready(level),
         }
     }
 
     fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
         match self {
             CommPort::Closed(_) => Err(Error::new(ErrorKind::NoDevice, "Port closed")),
-            CommPort::Open(port) => port.read_clear_to_send(),
+            CommPort::Open { port, .. } => port.read_clear_to_send(),
         }
     }
 
     fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
         match self {
             CommPort::Closed(_) => Err(Error::new(ErrorKind::NoDevice, "Port closed")),
-            CommPort::Open(port) => port.read_data_set_ready(),
+            CommPort::Open { port, .. } => port.read_data_set_ready(),
         }
     }
 
     fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
         match self {
             CommPort::Closed(_) => Err(Error::new(ErrorKind::NoDevice, "Port closed")),
-            CommPort::Open(port) => port.read_ring_indicator(),
+            CommPort::Open { port, .. } => port.read_ring_indicator(),
         }
     }
 
     fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
         match self {
             CommPort::Closed(_) => Err(Error::new(ErrorKind::NoDevice, "Port closed")),
-            CommPort::Open(port) => port.read_carrier_detect(),
+            CommPort::Open { port, .. } => port.read_carrier_detect(),
         }
     }
 
     fn bytes_to_read(&self) -> serialport::Result<u32> {
         match self {
             CommPort::Closed(_) => Err(Error::new(ErrorKind::NoDevice, "Port closed")),
-            CommPort::Open(port) => port.bytes_to_read(),
+            CommPort::Open { port, .. } => port.bytes_to_read(),
         }
     }
 
     fn bytes_to_write(&self) -> serialport::Result<u32> {
         match self {
             CommPort::Closed(_) => Err(Error::new(ErrorKind::NoDevice, "Port closed")),
-            CommPort::Open(port) => port.bytes_to_write(),
+            CommPort::Open { port, .. } => port.bytes_to_write(),
         }
     }
 
     fn clear(&self, buffer_to_clear: serialport::ClearBuffer) -> serialport::Result<()> {
         match self {
             CommPort::Closed(_) => Err(Error::new(ErrorKind::NoDevice, "Port closed")),
-            CommPort::Open(port) => port.clear(buffer_to_clear),
+            CommPort::Open { port, .. } => port.clear(buffer_to_clear),
         }
     }
 
+    /// Cloning an open port creates a second handle to the same physical port. Both handles share
+    /// the same kernel buffers so bytes read through one won't be seen by the other. Stats are
+    /// tracked separately for each handle.
+    ///
+    /// Cloning a closed port creates an independent port with the same settings which can be
+    /// opened separately.
+    ///
     fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
         match self {
-            CommPort::Closed(_) => Err(Error::new(ErrorKind::NoDevice, "Port closed")),
-            CommPort::Open(port) => port.try_clone(),
+            CommPort::Closed(port) => Ok(Box::new(CommPort::Closed(port.clone()))),
+            CommPort::Open {
+                port,
+                session_timeout,
+                ..
+            } => {
+                let mut clone = CommPort::from(port.try_clone()?);
+                clone.set_timeout(*session_timeout)?;
+                Ok(Box::new(clone))
+            }
         }
     }
 
     fn set_break(&self) -> serialport::Result<()> {
         match self {
             CommPort::Closed(_) => Err(Error::new(ErrorKind::NoDevice, "Port closed")),
-            CommPort::Open(port) => port.set_break(),
+            CommPort::Open { port, .. } => port.set_break(),
         }
     }
 
     fn clear_break(&self) -> serialport::Result<()> {
         match self {
             CommPort::Closed(_) => Err(Error::new(ErrorKind::NoDevice, "Port closed")),
-            CommPort::Open(port) => port.clear_break(),
+            CommPort::Open { port, .. } => port.clear_break(),
         }
     }
 }
 
 ////////////////////////////////////////////////////////////////
+
+impl std::fmt::Display for CommPortStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} bytes sent, {} bytes received, {} bytes discarded, {} read errors, {} write errors, \
+            {} timeouts",
+            self.bytes_sent,
+            self.bytes_received,
+            self.bytes_discarded,
+            self.read_errors,
+            self.write_errors,
+            self.timeouts
+        )
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// tests
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::MockTCUPort;
+
+    use super::*;
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_stats() {
+        let mut port = CommPort::from(Box::new(MockTCUPort::new()) as Box<dyn SerialPort>);
+
+        port.write_all(b"M03\r").unwrap();
+        let mut buffer = [0; 256];
+        let count = port.read(&mut buffer).unwrap();
+
+        // Echo followed by the measurement.
+        assert_eq!(&buffer[..count], b"M03\r1388\r");
+        assert_eq!(
+            port.stats(),
+            Some(&CommPortStats {
+                bytes_sent: 4,
+                bytes_received: 9,
+                ..Default::default()
+            })
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_clear_after_write() {
+        let mut port = CommPort::from(Box::new(MockTCUPort::new()) as Box<dyn SerialPort>);
+
+        // Bytes the mock responds with stand in for stray bytes sent by a device.
+        port.write_all(b"C06\r").unwrap();
+        assert_eq!(port.write_complete().unwrap(), 0);
+
+        let mut buffer = [0; 256];
+        assert_eq!(port.read(&mut buffer).unwrap(), 4);
+
+        port.clear_after_write(true);
+        port.write_all(b"C06\r").unwrap();
+        assert_eq!(port.write_complete().unwrap(), 4);
+        assert_eq!(port.read(&mut buffer).unwrap(), 0);
+
+        // Discarded bytes are counted separately from those read.
+        assert_eq!(
+            port.stats(),
+            Some(&CommPortStats {
+                bytes_sent: 8,
+                bytes_received: 4,
+                bytes_discarded: 4,
+                ..Default::default()
+            })
+        );
+
+        port.transaction_timed_out();
+        assert_eq!(port.stats().map(|stats| stats.timeouts), Some(1));
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_flush_with_timeout() {
+        let port = MockTCUPort::builder()
+            .flush_delay(Duration::from_millis(500))
+            .build();
+        let mut port = CommPort::from(Box::new(port) as Box<dyn SerialPort>);
+
+        let error = port
+            .flush_with_timeout(Duration::from_millis(10))
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Io(std::io::ErrorKind::TimedOut));
+        assert_eq!(port.stats().map(|stats| stats.timeouts), Some(1));
+
+        assert!(port.flush_with_timeout(Duration::from_secs(5)).is_ok());
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_path_and_configured_baud_rate() {
+        let port = CommPort::builder("/dev/gallivant-missing", 9600)
+            .build()
+            .unwrap();
+        assert_eq!(port.path(), "/dev/gallivant-missing");
+        assert_eq!(port.configured_baud_rate(), 9600);
+
+        let mut port = CommPort::from(Box::new(MockTCUPort::new()) as Box<dyn SerialPort>);
+        let baud_rate = port.configured_baud_rate();
+        assert_eq!(port.path(), "mock");
+
+        port.set_baud_rate(baud_rate * 2).unwrap();
+        assert_eq!(port.configured_baud_rate(), baud_rate);
+        assert_eq!(port.baud_rate().unwrap(), baud_rate * 2);
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_stats_closed() {
+        let port = CommPort::builder("COM1", 9600).build().unwrap();
+        assert_eq!(port.stats(), None);
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_builder_validate() {
+        let error = CommPort::builder("", 0).build().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert!(error.to_string().contains("path"), "{error}");
+
+        let error = CommPort::builder("COM1", 0).open().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert!(error.to_string().contains("baud rate"), "{error}");
+
+        assert!(CommPort::builder("COM1", 9600).validate().is_ok());
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_builder_open_missing() {
+        let result = CommPort::builder("/dev/gallivant-missing", 9600)
+            .data_bits(DataBits::Eight)
+            .open();
+        assert!(result.is_err());
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_session_timeout() {
+        let mut port = CommPort::builder("/dev/gallivant-missing", 9600)
+            .build()
+            .unwrap();
+        assert_eq!(port.session_timeout(), DEFAULT_SESSION_TIMEOUT);
+
+        // Only the session timeout is changed, not the polling period used when reading.
+        port.set_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(port.session_timeout(), Duration::from_secs(2));
+        assert_eq!(port.timeout(), Duration::from_millis(1));
+
+        let port = CommPort::builder("/dev/gallivant-missing", 9600)
+            .session_timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+        assert_eq!(port.session_timeout(), Duration::from_secs(5));
+
+        // Builders created from the port keep its polling period.
+        let builder = CommPortBuilder::from_serial_port(&port).unwrap();
+        assert_eq!(builder.timeout, Duration::from_millis(1));
+
+        // Ports wrapping an already open port use the default until set.
+        let mut port = CommPort::from(Box::new(MockTCUPort::new()) as Box<dyn SerialPort>);
+        assert_eq!(port.session_timeout(), DEFAULT_SESSION_TIMEOUT);
+
+        port.set_timeout(Duration::from_secs(3)).unwrap();
+        assert_eq!(port.session_timeout(), Duration::from_secs(3));
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_try_clone_closed() {
+        let port =
+            CommPort::from(CommPort::builder("/dev/gallivant-missing", 9600).parity(Parity::Even));
+        let clone = port.try_clone().unwrap();
+
+        assert_eq!(clone.name(), Some(String::from("/dev/gallivant-missing")));
+        assert_eq!(clone.baud_rate().unwrap(), 9600);
+        assert_eq!(clone.parity().unwrap(), Parity::Even);
+
+        // The clone is closed so can't be written to.
+        let mut clone = clone;
+        assert!(clone.write(b"M03\r").is_err());
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_port_info_usb() {
+        let info = SerialPortInfo {
+            port_name: String::from("/dev/ttyUSB0"),
+            port_type: SerialPortType::UsbPort(serialport::UsbPortInfo {
+                vid: 0x0403,
+                pid: 0x6001,
+                serial_number: None,
+                manufacturer: Some(String::from("FTDI")),
+                product: Some(String::from("FT232R USB UART")),
+            }),
+        };
+
+        assert_eq!(
+            CommPortInfo::from(info),
+            CommPortInfo {
+                path: String::from("/dev/ttyUSB0"),
+                product: Some(String::from("FT232R USB UART")),
+                manufacturer: Some(String::from("FTDI")),
+            }
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_port_info_other() {
+        let info = SerialPortInfo {
+            port_name: String::from("COM1"),
+            port_type: SerialPortType::PciPort,
+        };
+
+        assert_eq!(
+            CommPortInfo::from(info),
+            CommPortInfo {
+                path: String::from("COM1"),
+                product: None,
+                manufacturer: None,
+            }
+        );
+    }
+}
+
+////////////////////////////////////////////////////////////////
@@ -0,0 +1,374 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serialport::SerialPort;
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Wraps a serial port, recording all the bytes sent and received through it so that they can be
+/// replayed later using a PlaybackPort.
+///
+pub struct TransactionRecorder {
+    port: Box<dyn SerialPort>,
+    records: Vec<TransactionRecord>,
+    start: Instant,
+
+    /// File to save the records to once the recorder is dropped.
+    autosave: Option<PathBuf>,
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Bytes sent or received in one go.
+///
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TransactionRecord {
+    /// Time since recording started.
+    pub time: Duration,
+    pub direction: Direction,
+    pub bytes: Vec<u8>,
+}
+
+////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+////////////////////////////////////////////////////////////////
+// construction / conversion
+////////////////////////////////////////////////////////////////
+
+impl TransactionRecorder {
+    pub fn new(port: Box<dyn SerialPort>) -> Self {
+        Self {
+            port,
+            records: Vec::new(),
+            start: Instant::now(),
+            autosave: None,
+        }
+    }
+
+    /// Save the records to a file when the recorder is dropped. Ensures that the records are saved
+    /// even if script execution ends unexpectedly.
+    ///
+    pub fn with_autosave(mut self, path: impl Into<PathBuf>) -> Self {
+        self.autosave = Some(path.into());
+        self
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// methods
+////////////////////////////////////////////////////////////////
+
+impl TransactionRecorder {
+    pub fn records(&self) -> &[TransactionRecord] {
+        &self.records
+    }
+
+    /// Save the records to a file, one record per line in the form `<milliseconds> <S|R> <hex>`.
+    ///
+    pub fn save_to_file(&self, path: &Path) -> std::io::Result<()> {
+        let mut file = std::fs::File::create(path)?;
+        for record in self.records.iter() {
+            writeln!(file, "{record}")?;
+        }
+
+        Ok(())
+    }
+
+    fn record(&mut self, direction: Direction, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+
+        // Ports are often read from one byte at a time so merge consecutive reads or writes.
+        match self.records.last_mut() {
+            Some(record) if record.direction == direction => record.bytes.extend_from_slice(bytes),
+            _ => self.records.push(TransactionRecord {
+                time: self.start.elapsed(),
+                direction,
+                bytes: bytes.to_vec(),
+            }),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl TransactionRecord {
+    /// Load records from a file previously saved by a TransactionRecorder.
+    ///
+    pub fn load_from_file(path: &Path) -> std::io::Result<Vec<Self>> {
+        let file = std::fs::File::open(path)?;
+
+        BufReader::new(file)
+            .lines()
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|line| line.and_then(|line| line.parse()))
+            .collect()
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// traits
+////////////////////////////////////////////////////////////////
+
+impl Drop for TransactionRecorder {
+    fn drop(&mut self) {
+        if let Some(path) = &self.autosave {
+            if let Err(error) = self.save_to_file(path) {
+                eprintln!("Failed to save transaction records: {error}");
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl std::fmt::Debug for TransactionRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransactionRecorder")
+            .field("records", &self.records)
+            .field("autosave", &self.autosave)
+            .finish_non_exhaustive()
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl std::fmt::Display for TransactionRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let direction = match self.direction {
+            Direction::Sent => 'S',
+            Direction::Received => 'R',
+        };
+
+        write!(f, "{} {direction} ", self.time.as_millis())?;
+        for byte in self.bytes.iter() {
+            write!(f, "{byte:02X}")?;
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl std::str::FromStr for TransactionRecord {
+    type Err = std::io::Error;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid transaction record '{line}'"),
+            )
+        };
+
+        let mut parts = line.split_whitespace();
+        let (Some(time), Some(direction), Some(bytes)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+
+        let time = Duration::from_millis(time.parse().map_err(|_| invalid())?);
+
+        let direction = match direction {
+            "S" => Direction::Sent,
+            "R" => Direction::Received,
+            _ => return Err(invalid()),
+        };
+
+        if !bytes.is_ascii() || bytes.len() % 2 != 0 {
+            return Err(invalid());
+        }
+
+        let bytes = (0..bytes.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&bytes[i..i + 2], 16).map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            time,
+            direction,
+            bytes,
+        })
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl Read for TransactionRecorder {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.port.read(buf)?;
+        self.record(Direction::Received, &buf[..count]);
+        Ok(count)
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl Write for TransactionRecorder {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let count = self.port.write(buf)?;
+        self.record(Direction::Sent, &buf[..count]);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.port.flush()
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl SerialPort for TransactionRecorder {
+    fn name(&self) -> Option<String> {
+        self.port.name()
+    }
+
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        self.port.baud_rate()
+    }
+
+    fn data_bits(&self) -> serialport::Result<serialport::DataBits> {
+        self.port.data_bits()
+    }
+
+    fn flow_control(&self) -> serialport::Result<serialport::FlowControl> {
+        self.port.flow_control()
+    }
+
+    fn parity(&self) -> serialport::Result<serialport::Parity> {
+        self.port.parity()
+    }
+
+    fn stop_bits(&self) -> serialport::Result<serialport::StopBits> {
+        self.port.stop_bits()
+    }
+
+    fn timeout(&self) -> Duration {
+        self.port.timeout()
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.port.set_baud_rate(baud_rate)
+    }
+
+    fn set_data_bits(&mut self, data_bits: serialport::DataBits) -> serialport::Result<()> {
+        self.port.set_data_bits(data_bits)
+    }
+
+    fn set_flow_control(
+        &mut self,
+        flow_control: serialport::FlowControl,
+    ) -> serialport::Result<()> {
+        self.port.set_flow_control(flow_control)
+    }
+
+    fn set_parity(&mut self, parity: serialport::Parity) -> serialport::Result<()> {
+        self.port.set_parity(parity)
+    }
+
+    fn set_stop_bits(&mut self, stop_bits: serialport::StopBits) -> serialport::Result<()> {
+        self.port.set_stop_bits(stop_bits)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.port.set_timeout(timeout)
+    }
+
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.port.write_request_to_send(level)
+    }
+
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.port.write_data_terminal_ready(level)
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        self.port.read_clear_to_send()
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        self.port.read_data_set_ready()
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        self.port.read_ring_indicator()
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        self.port.read_carrier_detect()
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        self.port.bytes_to_read()
+    }
+
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        self.port.bytes_to_write()
+    }
+
+    fn clear(&self, buffer_to_clear: serialport::ClearBuffer) -> serialport::Result<()> {
+        self.port.clear(buffer_to_clear)
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        self.port.try_clone()
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        self.port.set_break()
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.port.clear_break()
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// tests
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_record_round_trip() {
+        let record = TransactionRecord {
+            time: Duration::from_millis(120),
+            direction: Direction::Sent,
+            bytes: b"M03\r".to_vec(),
+        };
+
+        assert_eq!(record.to_string(), "120 S 4D30330D");
+        assert_eq!(
+            "120 S 4D30330D".parse::<TransactionRecord>().unwrap(),
+            record
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_invalid_record() {
+        assert!("120 X 4D30330D".parse::<TransactionRecord>().is_err());
+        assert!("120 S 4D3".parse::<TransactionRecord>().is_err());
+        assert!("S 4D30330D".parse::<TransactionRecord>().is_err());
+    }
+}
+
+////////////////////////////////////////////////////////////////
@@ -1,23 +1,30 @@
+use std::{borrow::Cow, ops::Range, path::PathBuf, sync::Arc, time::Duration};
+
 use ariadne::{Config, Label, Report, ReportKind};
 
 use crate::{
-    execution::FailedTest,
-    syntax::{self, Expr, ParsedExpr},
+    execution::{format_failure_message, FailedTest},
+    syntax::{self, Expr, ExprKind, ParsedExpr},
 };
 
 ////////////////////////////////////////////////////////////////
 // types
 ////////////////////////////////////////////////////////////////
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Error {
-    reason: ErrorReason,
+    reason: Box<ErrorReason>,
     notes: Vec<ErrorNote>,
+
+    /// Name of the script file that the error occured in, if known.
+    filename: Option<String>,
 }
 
 ////////////////////////////////////////////////////////////////
 
-#[derive(Debug)]
+/// IO errors are shared between clones since std::io::Error can't be cloned.
+///
+#[derive(Clone, Debug)]
 pub enum ErrorReason {
     SyntaxError(syntax::ErrorReason),
     TestFailure {
@@ -26,16 +33,151 @@ pub enum ErrorReason {
     },
     IOError {
         expression: ParsedExpr,
-        error: std::io::Error,
+        error: Arc<std::io::Error>,
+    },
+
+    /// A device echoed back different bytes to those sent to it.
+    EchoMismatch {
+        expression: ParsedExpr,
+        sent: Vec<u8>,
+        received: Vec<u8>,
+    },
+
+    /// A device echoed back a different number of bytes to those sent to it.
+    EchoLengthMismatch {
+        expression: ParsedExpr,
+        expected: usize,
+        received: usize,
+    },
+
+    /// A device responded with something that couldn't be parsed as a measurement.
+    MeasurementParse {
+        expression: ParsedExpr,
+        raw: Vec<u8>,
+    },
+
+    /// A script referenced a variable that isn't defined.
+    UndefinedVariable {
+        expression: ParsedExpr,
+        name: String,
+    },
+
+    /// An argument's value, only known once it was resolved during evaluation e.g. from a
+    /// variable, was outside of the range allowed.
+    ArgOutOfRange {
+        expression: ParsedExpr,
+        value: i64,
+        limits: (i64, i64),
+    },
+
+    /// A variable was given a name that scripts couldn't reference.
+    InvalidVariableName {
+        name: String,
+    },
+
+    /// The result of arithmetic on variables doesn't fit in a u32.
+    ArithmeticOverflow {
+        expression: ParsedExpr,
+    },
+
+    /// A script attempted to divide a variable by 0.
+    DivisionByZero {
+        expression: ParsedExpr,
+    },
+
+    /// The script took longer to execute than allowed by a GLOBALTIMEOUT command.
+    GlobalTimeout {
+        elapsed: Duration,
+    },
+
+    /// The number of failed tests reached the limit set by a MAXFAILURES command.
+    MaxFailuresExceeded {
+        threshold: u32,
+        actual: u32,
+    },
+
+    /// The number of commands executed reached the limit set by Interpreter::with_max_commands.
+    MaxCommandsExceeded {
+        limit: u64,
+        count: u64,
+    },
+
+    /// A script requires a newer interpreter than the one running it.
+    VersionMismatch {
+        expression: ParsedExpr,
+        required: (u8, u8),
+        running: (u8, u8),
+    },
+
+    /// A command that targets the printer is issued while the printer port isn't open i.e. before
+    /// USBOPEN or after USBCLOSE.
+    PortNotOpen {
+        command: ExprKind,
+        span: Range<usize>,
+    },
+
+    /// The printer port is opened by USBOPEN but isn't closed by a USBCLOSE before the end of the
+    /// script.
+    PortNotClosed {
+        span: Range<usize>,
+    },
+
+    /// A script contains an odd number of HPMODE commands so ends with HPMODE in the opposite state
+    /// to the one it started in.
+    OddHPModeToggle {
+        count: usize,
+    },
+
+    /// The user cancelled the script e.g. from a dialog.
+    UserCancelled,
+
+    /// An interpreter was asked to seek to a label that isn't in the script.
+    UndefinedLabel {
+        name: String,
+    },
+
+    /// A script used a macro that isn't defined.
+    UndefinedMacro {
+        expression: ParsedExpr,
+        name: String,
     },
+
+    /// A macro was used, directly or via other macros, within its own definition.
+    CircularMacro {
+        expression: ParsedExpr,
+        name: String,
+    },
+
+    /// The script couldn't be read e.g. from a file.
+    ScriptRead {
+        path: Option<PathBuf>,
+        error: Arc<std::io::Error>,
+    },
+
+    /// State saved by Interpreter::save_state couldn't be read or is invalid.
+    StateRestore {
+        path: Option<PathBuf>,
+        error: Arc<std::io::Error>,
+    },
+}
+
+////////////////////////////////////////////////////////////////
+
+/// How serious a problem is. Warnings don't prevent a script from being run.
+///
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiagnosticSeverity {
+    Warning,
+    #[default]
+    Error,
 }
 
 ////////////////////////////////////////////////////////////////
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ErrorNote {
-    Note(&'static str),
-    Help(&'static str),
+    Note(Cow<'static, str>),
+    Help(Cow<'static, str>),
 }
 
 ////////////////////////////////////////////////////////////////
@@ -43,24 +185,151 @@ pub enum ErrorNote {
 ////////////////////////////////////////////////////////////////
 
 impl Error {
-    pub fn from_io_error(expression: ParsedExpr, error: std::io::Error) -> Self {
+    fn new(reason: ErrorReason) -> Self {
         Self {
-            reason: ErrorReason::IOError { expression, error },
+            reason: Box::new(reason),
             notes: Vec::new(),
+            filename: None,
         }
     }
 
+    pub fn from_io_error(expression: ParsedExpr, error: std::io::Error) -> Self {
+        Self::new(ErrorReason::IOError {
+            expression,
+            error: Arc::new(error),
+        })
+    }
+
     pub fn from_failed_test(expression: ParsedExpr, test: FailedTest) -> Self {
-        Self {
-            reason: ErrorReason::TestFailure { expression, test },
-            notes: Vec::new(),
-        }
+        Self::new(ErrorReason::TestFailure { expression, test })
+    }
+
+    pub fn from_echo_mismatch(expression: ParsedExpr, sent: Vec<u8>, received: Vec<u8>) -> Self {
+        Self::new(ErrorReason::EchoMismatch {
+            expression,
+            sent,
+            received,
+        })
+    }
+
+    pub fn from_echo_length_mismatch(
+        expression: ParsedExpr,
+        expected: usize,
+        received: usize,
+    ) -> Self {
+        Self::new(ErrorReason::EchoLengthMismatch {
+            expression,
+            expected,
+            received,
+        })
+    }
+
+    pub fn from_measurement_parse(expression: ParsedExpr, raw: Vec<u8>) -> Self {
+        Self::new(ErrorReason::MeasurementParse { expression, raw })
+    }
+
+    pub fn from_undefined_variable(expression: ParsedExpr, name: String) -> Self {
+        Self::new(ErrorReason::UndefinedVariable { expression, name })
+    }
+
+    pub fn from_arg_out_of_range(expression: ParsedExpr, value: i64, limits: (i64, i64)) -> Self {
+        Self::new(ErrorReason::ArgOutOfRange {
+            expression,
+            value,
+            limits,
+        })
+    }
+
+    pub fn from_invalid_variable_name(name: String) -> Self {
+        Self::new(ErrorReason::InvalidVariableName { name })
+    }
+
+    pub fn from_arithmetic_overflow(expression: ParsedExpr) -> Self {
+        Self::new(ErrorReason::ArithmeticOverflow { expression })
+    }
+
+    pub fn from_division_by_zero(expression: ParsedExpr) -> Self {
+        Self::new(ErrorReason::DivisionByZero { expression })
+    }
+
+    pub fn from_global_timeout(elapsed: Duration) -> Self {
+        Self::new(ErrorReason::GlobalTimeout { elapsed })
+    }
+
+    pub fn from_max_failures(threshold: u32, actual: u32) -> Self {
+        Self::new(ErrorReason::MaxFailuresExceeded { threshold, actual })
+    }
+
+    pub fn from_max_commands(limit: u64, count: u64) -> Self {
+        Self::new(ErrorReason::MaxCommandsExceeded { limit, count })
+    }
+
+    pub fn from_version_mismatch(
+        expression: ParsedExpr,
+        required: (u8, u8),
+        running: (u8, u8),
+    ) -> Self {
+        Self::new(ErrorReason::VersionMismatch {
+            expression,
+            required,
+            running,
+        })
+    }
+
+    pub fn from_port_not_open(command: ExprKind, span: Range<usize>) -> Self {
+        Self::new(ErrorReason::PortNotOpen { command, span })
+    }
+
+    pub fn from_port_not_closed(span: Range<usize>) -> Self {
+        Self::new(ErrorReason::PortNotClosed { span })
+    }
+
+    pub fn from_odd_hpmode_toggle(count: usize) -> Self {
+        Self::new(ErrorReason::OddHPModeToggle { count })
+    }
+
+    pub fn from_user_cancelled() -> Self {
+        Self::new(ErrorReason::UserCancelled)
+    }
+
+    pub fn from_undefined_label(name: String) -> Self {
+        Self::new(ErrorReason::UndefinedLabel { name })
+    }
+
+    pub fn from_undefined_macro(expression: ParsedExpr, name: String) -> Self {
+        Self::new(ErrorReason::UndefinedMacro { expression, name })
+    }
+
+    pub fn from_circular_macro(expression: ParsedExpr, name: String) -> Self {
+        Self::new(ErrorReason::CircularMacro { expression, name })
+    }
+
+    pub fn from_script_read(path: Option<PathBuf>, error: std::io::Error) -> Self {
+        Self::new(ErrorReason::ScriptRead {
+            path,
+            error: Arc::new(error),
+        })
+    }
+
+    pub fn from_state_restore(path: Option<PathBuf>, error: std::io::Error) -> Self {
+        Self::new(ErrorReason::StateRestore {
+            path,
+            error: Arc::new(error),
+        })
     }
 
     pub fn with_note(mut self, note: ErrorNote) -> Self {
         self.notes.push(note);
         self
     }
+
+    /// Record the name of the script file that the error occured in so that it's included in the
+    /// error's report.
+    ///
+    pub fn with_filename(mut self, path: &str) -> Self {
+        self.filename = Some(path.to_owned());
+        self
+    }
 }
 
 ////////////////////////////////////////////////////////////////
@@ -68,8 +337,9 @@ impl Error {
 impl From<syntax::Error> for Error {
     fn from(error: syntax::Error) -> Self {
         Self {
-            reason: ErrorReason::SyntaxError(error.reason().to_owned()),
+            reason: Box::new(ErrorReason::SyntaxError(error.reason().to_owned())),
             notes: error.notes().to_owned(),
+            filename: None,
         }
     }
 }
@@ -86,12 +356,47 @@ impl From<Error> for Report<'_> {
 
 impl From<&Error> for Report<'_> {
     fn from(error: &Error) -> Self {
-        let mut report = Report::build(ReportKind::Error, (), 0)
+        error.build_report(error.reason.message())
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl Error {
+    /// Create a report for the error. The report's message is prefixed with the script's filename,
+    /// if known, and the line and column the error occured at so that the location is available
+    /// even where the report's source snippet isn't rendered. The filename isn't used as the
+    /// report's source id so that reports can still be printed with a plain `Source`.
+    ///
+    /// # Arguments
+    /// * `source` - Script that the error was generated from.
+    ///
+    pub fn to_report(&self, source: &str) -> Report<'_> {
+        let message = self.reason.message();
+        let message = match self.reason.span() {
+            Some(span) => syntax::with_location(&message, span, source),
+            None => message,
+        };
+        let message = match &self.filename {
+            Some(filename) => format!("{filename}: {message}"),
+            None => message,
+        };
+
+        self.build_report(message)
+    }
+
+    fn build_report<'a>(&self, message: String) -> Report<'a> {
+        let kind = match self.reason.severity() {
+            DiagnosticSeverity::Warning => ReportKind::Warning,
+            DiagnosticSeverity::Error => ReportKind::Error,
+        };
+
+        let mut report = Report::build(kind, (), 0)
             .with_config(Config::default().with_cross_gap(true))
-            .with_message(error.reason.message())
-            .with_labels(error.reason.labels());
+            .with_message(message)
+            .with_labels(self.reason.labels());
 
-        for note in error.notes.iter() {
+        for note in self.notes.iter() {
             report = match note {
                 ErrorNote::Note(msg) => report.with_note(msg),
                 ErrorNote::Help(msg) => report.with_help(msg),
@@ -105,11 +410,130 @@ impl From<&Error> for Report<'_> {
 ////////////////////////////////////////////////////////////////
 
 impl ErrorReason {
+    /// Return how serious the problem is. Only problems found when analysing a script may be
+    /// warnings.
+    ///
+    pub fn severity(&self) -> DiagnosticSeverity {
+        match self {
+            ErrorReason::PortNotClosed { .. } | ErrorReason::OddHPModeToggle { .. } => {
+                DiagnosticSeverity::Warning
+            }
+            _ => DiagnosticSeverity::Error,
+        }
+    }
+
+    /// Return the area of the script that the error occured in, if known.
+    ///
+    pub fn span(&self) -> Option<&Range<usize>> {
+        match self {
+            ErrorReason::SyntaxError(reason) => reason.span(),
+            ErrorReason::TestFailure { expression, .. } => Some(expression.span()),
+            ErrorReason::IOError { expression, .. } => Some(expression.span()),
+            ErrorReason::EchoMismatch { expression, .. } => Some(expression.span()),
+            ErrorReason::EchoLengthMismatch { expression, .. } => Some(expression.span()),
+            ErrorReason::MeasurementParse { expression, .. } => Some(expression.span()),
+            ErrorReason::UndefinedVariable { expression, .. } => Some(expression.span()),
+            ErrorReason::ArgOutOfRange { expression, .. } => Some(expression.span()),
+            ErrorReason::InvalidVariableName { .. } => None,
+            ErrorReason::ArithmeticOverflow { expression } => Some(expression.span()),
+            ErrorReason::DivisionByZero { expression } => Some(expression.span()),
+            ErrorReason::GlobalTimeout { .. } => None,
+            ErrorReason::MaxFailuresExceeded { .. } => None,
+            ErrorReason::MaxCommandsExceeded { .. } => None,
+            ErrorReason::VersionMismatch { expression, .. } => Some(expression.span()),
+            ErrorReason::PortNotOpen { span, .. } => Some(span),
+            ErrorReason::PortNotClosed { span } => Some(span),
+            ErrorReason::OddHPModeToggle { .. } => None,
+            ErrorReason::UserCancelled => None,
+            ErrorReason::UndefinedLabel { .. } => None,
+            ErrorReason::UndefinedMacro { expression, .. } => Some(expression.span()),
+            ErrorReason::CircularMacro { expression, .. } => Some(expression.span()),
+            ErrorReason::ScriptRead { .. } => None,
+            ErrorReason::StateRestore { .. } => None,
+        }
+    }
+
     pub fn message(&self) -> String {
         match self {
             ErrorReason::SyntaxError(reason) => format!("Syntax error - {}", reason.message()),
             ErrorReason::TestFailure { test, .. } => format!("Test failed - {}", test.message),
             ErrorReason::IOError { error, .. } => format!("IO error - {}", error),
+            ErrorReason::EchoMismatch { sent, received, .. } => format!(
+                "Echo mismatch - sent '{}' but received '{}'",
+                sent.escape_ascii(),
+                received.escape_ascii()
+            ),
+            ErrorReason::EchoLengthMismatch {
+                expected, received, ..
+            } => format!("Echo length mismatch - sent {expected} bytes but {received} were echoed"),
+            ErrorReason::MeasurementParse { raw, .. } => format!(
+                "Measurement parse error - received '{}'",
+                raw.escape_ascii()
+            ),
+            ErrorReason::UndefinedVariable { name, .. } => {
+                format!("Undefined variable - '{name}'")
+            }
+            ErrorReason::ArgOutOfRange {
+                value,
+                limits: (min, max),
+                ..
+            } => format!(
+                "Argument out of range - value {value} must be between {min} and {max}"
+            ),
+            ErrorReason::InvalidVariableName { name } => format!(
+                "Invalid variable name - '{name}' must not be empty or contain whitespace"
+            ),
+            ErrorReason::ArithmeticOverflow { .. } => {
+                String::from("Arithmetic overflow - result must be between 0 and 4294967295")
+            }
+            ErrorReason::DivisionByZero { .. } => String::from("Division by zero"),
+            ErrorReason::GlobalTimeout { elapsed } => format!(
+                "Global timeout - script stopped after running for {}ms",
+                elapsed.as_millis()
+            ),
+            ErrorReason::MaxFailuresExceeded { threshold, actual } => format!(
+                "Maximum failures exceeded - script stopped after {actual} tests failed, limit was {threshold}"
+            ),
+            ErrorReason::MaxCommandsExceeded { limit, count } => format!(
+                "Maximum commands exceeded - script stopped after executing {count} commands, limit was {limit}"
+            ),
+            ErrorReason::VersionMismatch {
+                required: (required_major, required_minor),
+                running: (running_major, running_minor),
+                ..
+            } => format!(
+                "Version mismatch - script requires interpreter version {required_major}.{required_minor} but this is version {running_major}.{running_minor}"
+            ),
+            ErrorReason::PortNotOpen { command, .. } => format!(
+                "Port not open - {} is issued before USBOPEN or after USBCLOSE",
+                command.name()
+            ),
+            ErrorReason::PortNotClosed { .. } => {
+                String::from("Port not closed - USBOPEN isn't followed by a USBCLOSE")
+            }
+            ErrorReason::OddHPModeToggle { count } => format!(
+                "Odd HPMODE toggle - script contains {count} HPMODE commands so ends in the opposite mode to the one it starts in"
+            ),
+            ErrorReason::UserCancelled => String::from("Script cancelled by user"),
+            ErrorReason::UndefinedLabel { name } => format!("Undefined label - '{name}'"),
+            ErrorReason::UndefinedMacro { name, .. } => format!("Undefined macro - '{name}'"),
+            ErrorReason::CircularMacro { name, .. } => {
+                format!("Circular macro - '{name}' is used within its own definition")
+            }
+            ErrorReason::ScriptRead {
+                path: Some(path),
+                error,
+            } => format!("Failed to read script '{}' - {error}", path.display()),
+            ErrorReason::ScriptRead { path: None, error } => {
+                format!("Failed to read script - {error}")
+            }
+            ErrorReason::StateRestore {
+                path: Some(path),
+                error,
+            } => format!("Failed to restore state from '{}' - {error}", path.display()),
+            ErrorReason::StateRestore { path: None, error } => {
+                format!("Failed to restore state - {error}")
+            }
         }
     }
 
@@ -118,18 +542,38 @@ impl ErrorReason {
             ErrorReason::SyntaxError(reason) => reason.labels(),
 
             ErrorReason::TestFailure { expression, test } => {
+                // Create a label highlighting the failing command.
+                let message =
+                    format_failure_message(&test.message, test.measurement, &test.expected);
+                let mut labels = vec![Label::new(expression.span().clone())
+                    .with_message(message)
+                    .with_order(2)];
+
+                if let (Some(mask), Expr::MaskTest { expected, .. }) =
+                    (test.mask, expression.expression())
+                {
+                    labels.push(Label::new(expected.span().clone()).with_message(format!(
+                        "Expected ${:X} after applying mask ${mask:X} but measured ${:X}",
+                        test.expected.start(),
+                        test.measurement
+                    )));
+                    return labels;
+                }
+
                 let range_expr = match expression.expression() {
                     Expr::TCUTest { min, max, .. } => Some((min, max)),
+                    Expr::WarnTest { min, max, .. } => Some((min, max)),
+                    Expr::Assert { min, max, .. } => Some((min, max)),
                     Expr::PrinterTest { min, max, .. } => Some((min, max)),
                     Expr::USBPrinterTest { min, max, .. } => Some((min, max)),
+                    Expr::USBTest { min, max, .. } => Some((min, max)),
+                    Expr::TCUExact { expected, .. } => Some((expected, expected)),
+                    Expr::USBExact { expected, .. } => Some((expected, expected)),
                     _ => None,
                 };
 
-                // Create a label highlighting the failing command.
-                let mut labels = Vec::new();
-
                 // Create a label highlighting the bound that the measured value violated.
-                if test.measurement > *test.expected.end() {
+                if i64::from(test.measurement) > *test.expected.end() {
                     let span = range_expr
                         .map(|(_, max)| max.span())
                         .unwrap_or(expression.span());
@@ -145,7 +589,7 @@ impl ErrorReason {
                     );
                 }
 
-                if test.measurement < *test.expected.start() {
+                if i64::from(test.measurement) < *test.expected.start() {
                     let span = range_expr
                         .map(|(min, _)| min.span())
                         .unwrap_or(expression.span());
@@ -164,6 +608,84 @@ impl ErrorReason {
                 vec![Label::new(expression.span().clone())
                     .with_message("When executing this command")]
             }
+
+            ErrorReason::EchoMismatch { expression, .. } => {
+                vec![Label::new(expression.span().clone())
+                    .with_message("Device echoed this command incorrectly")]
+            }
+
+            ErrorReason::EchoLengthMismatch { expression, .. } => {
+                vec![Label::new(expression.span().clone())
+                    .with_message("Device echoed this command incompletely")]
+            }
+
+            ErrorReason::MeasurementParse { expression, raw } => {
+                vec![Label::new(expression.span().clone()).with_message(format!(
+                    "Device responded with '{}' which isn't a valid measurement",
+                    raw.escape_ascii()
+                ))]
+            }
+
+            ErrorReason::UndefinedVariable { expression, name } => {
+                vec![Label::new(expression.span().clone()).with_message(format!(
+                    "Variable '{name}' is referenced here but not defined"
+                ))]
+            }
+
+            ErrorReason::ArgOutOfRange {
+                expression, value, ..
+            } => {
+                vec![Label::new(expression.span().clone())
+                    .with_message(format!("Argument has value {value}"))]
+            }
+
+            ErrorReason::InvalidVariableName { .. } => Vec::new(),
+
+            ErrorReason::ArithmeticOverflow { expression } => {
+                vec![Label::new(expression.span().clone())
+                    .with_message("Result of this calculation is out of range")]
+            }
+
+            ErrorReason::DivisionByZero { expression } => {
+                vec![Label::new(expression.span().clone())
+                    .with_message("Divisor of this calculation is 0")]
+            }
+
+            ErrorReason::GlobalTimeout { .. } => Vec::new(),
+            ErrorReason::MaxFailuresExceeded { .. } => Vec::new(),
+            ErrorReason::MaxCommandsExceeded { .. } => Vec::new(),
+
+            ErrorReason::VersionMismatch { expression, .. } => {
+                vec![Label::new(expression.span().clone())
+                    .with_message("Required version is set here")]
+            }
+
+            ErrorReason::PortNotOpen { span, .. } => {
+                vec![Label::new(span.clone())
+                    .with_message("This command requires the printer port to be open")]
+            }
+
+            ErrorReason::PortNotClosed { span } => {
+                vec![Label::new(span.clone()).with_message("Printer port is opened here")]
+            }
+
+            ErrorReason::OddHPModeToggle { .. } => Vec::new(),
+            ErrorReason::UserCancelled => Vec::new(),
+            ErrorReason::UndefinedLabel { .. } => Vec::new(),
+
+            ErrorReason::UndefinedMacro { expression, name } => {
+                vec![Label::new(expression.span().clone())
+                    .with_message(format!("Macro '{name}' is used here but not defined"))]
+            }
+
+            ErrorReason::CircularMacro { expression, name } => {
+                vec![Label::new(expression.span().clone()).with_message(format!(
+                    "Macro '{name}' is used here while it's already being expanded"
+                ))]
+            }
+
+            ErrorReason::ScriptRead { .. } => Vec::new(),
+            ErrorReason::StateRestore { .. } => Vec::new(),
         }
     }
 }
@@ -180,6 +702,10 @@ impl Error {
     pub fn notes(&self) -> &[ErrorNote] {
         &self.notes
     }
+
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
 }
 
 ////////////////////////////////////////////////////////////////
@@ -196,13 +722,34 @@ impl std::fmt::Display for Error {
 
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match &self.reason {
+        match self.reason.as_ref() {
             ErrorReason::SyntaxError(_) => None,
             ErrorReason::TestFailure { .. } => None,
             ErrorReason::IOError {
                 expression: _,
                 error,
-            } => Some(error),
+            } => Some(error.as_ref()),
+            ErrorReason::EchoMismatch { .. } => None,
+            ErrorReason::EchoLengthMismatch { .. } => None,
+            ErrorReason::MeasurementParse { .. } => None,
+            ErrorReason::UndefinedVariable { .. } => None,
+            ErrorReason::ArgOutOfRange { .. } => None,
+            ErrorReason::InvalidVariableName { .. } => None,
+            ErrorReason::ArithmeticOverflow { .. } => None,
+            ErrorReason::DivisionByZero { .. } => None,
+            ErrorReason::GlobalTimeout { .. } => None,
+            ErrorReason::MaxFailuresExceeded { .. } => None,
+            ErrorReason::MaxCommandsExceeded { .. } => None,
+            ErrorReason::VersionMismatch { .. } => None,
+            ErrorReason::PortNotOpen { .. } => None,
+            ErrorReason::PortNotClosed { .. } => None,
+            ErrorReason::OddHPModeToggle { .. } => None,
+            ErrorReason::UserCancelled => None,
+            ErrorReason::UndefinedLabel { .. } => None,
+            ErrorReason::UndefinedMacro { .. } => None,
+            ErrorReason::CircularMacro { .. } => None,
+            ErrorReason::ScriptRead { error, .. } => Some(error.as_ref()),
+            ErrorReason::StateRestore { error, .. } => Some(error.as_ref()),
         }
     }
 }
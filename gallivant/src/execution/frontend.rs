@@ -9,16 +9,40 @@ use super::transaction::Transaction;
 /// Requests for actions a frontend needs to perform during script execution.
 ///
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FrontendRequest {
     None,
     Wait(Duration),
 
+    /// Position of the next command within the script. Only requested if the interpreter has
+    /// progress reports enabled.
+    Progress {
+        current: usize,
+        total: usize,
+    },
+
     GuiPrint(String),
-    GuiDialogue { kind: Dialog, message: String },
+
+    /// Named progress marker reached by the script, from a CHECKPOINT or a test group boundary.
+    Checkpoint(String),
+
+    GuiDialogue {
+        kind: Dialog,
+        message: String,
+    },
+
+    /// Named value reported by the script for monitoring tools.
+    Notification {
+        key: String,
+        value: u32,
+    },
 
     TCUTransact(Transaction),
     TCUFlush,
 
+    /// Change the baud rate of the TCU's port.
+    SetBaud(u32),
+
     // Requests for direct communication with the printer i.e. not via the TCU.
     PrinterOpen,
     PrinterClose,
@@ -30,7 +54,10 @@ pub enum FrontendRequest {
 /// Types of dialog a frontend may need to create during script execution.
 ///
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Dialog {
+    /// Dialog that should display a message without waiting for the user, allowing the script to
+    /// continue immediately.
     Notification,
 
     /// Dialog that should display a message and allow the user to either continue or stop the test.
@@ -38,3 +65,47 @@ pub enum Dialog {
 }
 
 ////////////////////////////////////////////////////////////////
+// ...
+////////////////////////////////////////////////////////////////
+
+impl std::fmt::Display for FrontendRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrontendRequest::None => write!(f, "None"),
+            FrontendRequest::Wait(time) => write!(f, "Wait {}ms", time.as_millis()),
+            FrontendRequest::Progress { current, total } => {
+                write!(f, "Progress {current}/{total}")
+            }
+
+            FrontendRequest::GuiPrint(message) => write!(f, "Print \"{message}\""),
+            FrontendRequest::Checkpoint(name) => write!(f, "Checkpoint \"{name}\""),
+            FrontendRequest::GuiDialogue { kind, message } => {
+                write!(f, "{kind} dialog \"{message}\"")
+            }
+            FrontendRequest::Notification { key, value } => write!(f, "Notify {key} = {value}"),
+
+            FrontendRequest::TCUTransact(transaction) => write!(f, "TCU transact {transaction}"),
+            FrontendRequest::TCUFlush => write!(f, "TCU flush"),
+            FrontendRequest::SetBaud(baud) => write!(f, "TCU set baud {baud}"),
+
+            FrontendRequest::PrinterOpen => write!(f, "Printer open"),
+            FrontendRequest::PrinterClose => write!(f, "Printer close"),
+            FrontendRequest::PrinterTransact(transaction) => {
+                write!(f, "Printer transact {transaction}")
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl std::fmt::Display for Dialog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Dialog::Notification => write!(f, "Notification"),
+            Dialog::ManualInput => write!(f, "Manual input"),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
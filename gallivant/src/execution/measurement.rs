@@ -13,11 +13,39 @@ pub struct Measurement(u32);
 
 /// A test to be performed on a measurement taken by a device.
 ///
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MeasurementTest {
-    pub expected: RangeInclusive<u32>,
+    /// Range the measurement must be within. Signed so that bounds may be negative.
+    pub expected: RangeInclusive<i64>,
     pub retries: u32,
     pub failure_message: String,
+
+    /// Mask applied to the measurement before it's compared against the expected range.
+    pub mask: Option<u32>,
+
+    /// Format the device returns the measurement in.
+    pub format: MeasurementFormat,
+
+    /// Maximum standard deviation expected across the measurements taken while retrying. Exceeding
+    /// it suggests noise or an intermittent connection.
+    pub max_std_dev: Option<f32>,
+
+    /// Measurements taken so far, after applying any mask.
+    pub samples: Vec<u32>,
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Formats that devices may return measurements in. Most firmware returns hex ASCII but some
+/// variants return decimal ASCII.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MeasurementFormat {
+    #[default]
+    Hex,
+    Decimal,
 }
 
 ////////////////////////////////////////////////////////////////
@@ -25,10 +53,13 @@ pub struct MeasurementTest {
 /// A test to be performed on a measurement taken by a device.
 ///
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FailedTest {
+    /// Measured value. If the test had a mask, this is the value after the mask was applied.
     pub measurement: u32,
-    pub expected: RangeInclusive<u32>,
+    pub expected: RangeInclusive<i64>,
     pub message: String,
+    pub mask: Option<u32>,
 }
 
 ////////////////////////////////////////////////////////////////
@@ -39,7 +70,7 @@ pub enum Error {
     TestFailedRetryable(MeasurementTest),
 
     /// Parsing of a measurement failed.
-    ParseError(Box<dyn std::error::Error>),
+    Parse(Box<dyn std::error::Error>),
 }
 
 ////////////////////////////////////////////////////////////////
@@ -48,7 +79,7 @@ pub enum Error {
 
 impl From<std::str::Utf8Error> for Error {
     fn from(error: std::str::Utf8Error) -> Self {
-        Self::ParseError(Box::new(error))
+        Self::Parse(Box::new(error))
     }
 }
 
@@ -56,7 +87,7 @@ impl From<std::str::Utf8Error> for Error {
 
 impl From<std::num::ParseIntError> for Error {
     fn from(error: std::num::ParseIntError) -> Self {
-        Self::ParseError(Box::new(error))
+        Self::Parse(Box::new(error))
     }
 }
 
@@ -66,13 +97,28 @@ impl TryFrom<&[u8]> for Measurement {
     type Error = Error;
 
     fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Measurement::try_from((bytes, MeasurementFormat::Hex))
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl TryFrom<(&[u8], MeasurementFormat)> for Measurement {
+    type Error = Error;
+
+    fn try_from((bytes, format): (&[u8], MeasurementFormat)) -> Result<Self, Self::Error> {
         let measurement = std::str::from_utf8(bytes)?;
         let measurement = measurement
             .chars()
             .take_while(|&c| c != '\r')
             .collect::<String>();
 
-        let measurement = u32::from_str_radix(&measurement, 16)?;
+        let radix = match format {
+            MeasurementFormat::Hex => 16,
+            MeasurementFormat::Decimal => 10,
+        };
+
+        let measurement = u32::from_str_radix(&measurement, radix)?;
         Ok(Measurement(measurement))
     }
 }
@@ -86,6 +132,7 @@ impl FailedTest {
             measurement,
             expected: test.expected,
             message: test.failure_message,
+            mask: test.mask,
         }
     }
 }
@@ -102,10 +149,14 @@ impl MeasurementTest {
     /// * `measurement` - Measurement to test.
     ///
     /// # Returns
-    /// Result where the Ok value indicates the test was successfull.
+    /// Result where the Ok value indicates the test was successfull and holds the measurement
+    /// after applying any mask.
     ///
-    pub fn test(mut self, Measurement(measurement): Measurement) -> Result<(), Error> {
-        let test_success = self.expected.contains(&measurement);
+    pub fn test(mut self, Measurement(measurement): Measurement) -> Result<u32, Error> {
+        let measurement = self.mask.map_or(measurement, |mask| measurement & mask);
+        self.samples.push(measurement);
+
+        let test_success = self.expected.contains(&i64::from(measurement));
 
         if !test_success {
             return if self.retries > 0 {
@@ -119,7 +170,47 @@ impl MeasurementTest {
             };
         }
 
-        Ok(())
+        Ok(measurement)
+    }
+
+    /// Return the standard deviation of the measurements taken so far, or None if there aren't
+    /// enough to calculate it.
+    ///
+    pub fn std_dev(&self) -> Option<f32> {
+        if self.samples.len() < 2 {
+            return None;
+        }
+
+        let count = self.samples.len() as f32;
+        let mean = self.samples.iter().map(|&s| s as f32).sum::<f32>() / count;
+        let variance = self
+            .samples
+            .iter()
+            .map(|&s| (s as f32 - mean).powi(2))
+            .sum::<f32>()
+            / count;
+
+        Some(variance.sqrt())
+    }
+
+    /// Return a warning if the measurements taken so far vary by more than expected.
+    ///
+    pub fn outlier_warning(&self) -> Option<String> {
+        let max = self.max_std_dev?;
+        let std_dev = self.std_dev().filter(|std_dev| *std_dev > max)?;
+
+        let samples = self
+            .samples
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        Some(format!(
+            "{} - measurements vary more than expected (standard deviation {std_dev:.1} exceeds \
+             {max:.1}): {samples}",
+            self.failure_message
+        ))
     }
 }
 
@@ -127,6 +218,30 @@ impl MeasurementTest {
 // ...
 ////////////////////////////////////////////////////////////////
 
+/// Substitute the details of a failed test into its failure message. The placeholders `{value}`,
+/// `{min}` and `{max}` are replaced with the measured value and the expected range's bounds.
+///
+/// # Arguments
+/// * `template` - Failure message, possibly containing placeholders.
+/// * `measurement` - Measured value.
+/// * `expected` - Range the measurement was expected to be within.
+///
+/// # Returns
+/// The failure message with any placeholders replaced.
+///
+pub fn format_failure_message(
+    template: &str,
+    measurement: u32,
+    expected: &RangeInclusive<i64>,
+) -> String {
+    template
+        .replace("{value}", &measurement.to_string())
+        .replace("{min}", &expected.start().to_string())
+        .replace("{max}", &expected.end().to_string())
+}
+
+////////////////////////////////////////////////////////////////
+
 impl std::fmt::Display for Measurement {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -148,7 +263,7 @@ impl std::fmt::Display for Error {
             Error::TestFailedRetryable(test) => {
                 write!(f, "Test failed, retries remaining: {}", test.retries)
             }
-            Error::ParseError(error) => write!(f, "{error}"),
+            Error::Parse(error) => write!(f, "{error}"),
         }
     }
 }
@@ -160,13 +275,32 @@ impl std::error::Error for Error {
         match self {
             Error::TestFailed(_) => None,
             Error::TestFailedRetryable(_) => None,
-            Error::ParseError(error) => Some(error.as_ref()),
+            Error::Parse(error) => Some(error.as_ref()),
         }
     }
 }
 
 ////////////////////////////////////////////////////////////////
-/// tests
+// comparison
+////////////////////////////////////////////////////////////////
+
+impl std::cmp::PartialEq for MeasurementTest {
+    fn eq(&self, other: &Self) -> bool {
+        // Compare the maximum standard deviation bitwise so that a NaN still equals itself.
+        self.expected == other.expected
+            && self.retries == other.retries
+            && self.failure_message == other.failure_message
+            && self.mask == other.mask
+            && self.format == other.format
+            && self.max_std_dev.map(f32::to_bits) == other.max_std_dev.map(f32::to_bits)
+            && self.samples == other.samples
+    }
+}
+
+impl Eq for MeasurementTest {}
+
+////////////////////////////////////////////////////////////////
+// tests
 ////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -184,16 +318,71 @@ mod tests {
 
     ////////////////////////////////////////////////////////////////
 
+    #[test]
+    fn test_measurement_formats() {
+        let hex = Measurement::try_from((&b"1000\r"[..], MeasurementFormat::Hex)).unwrap();
+        assert_eq!(hex, Measurement(0x1000));
+
+        let dec = Measurement::try_from((&b"1000\r"[..], MeasurementFormat::Decimal)).unwrap();
+        assert_eq!(dec, Measurement(1000));
+
+        assert!(Measurement::try_from((&b"AA1\r"[..], MeasurementFormat::Decimal)).is_err());
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_samples_std_dev() {
+        let mut test = MeasurementTest {
+            expected: 0..=20,
+            retries: 2,
+            failure_message: "test failed".to_owned(),
+            mask: None,
+            format: MeasurementFormat::Hex,
+            max_std_dev: Some(1.0),
+            samples: Vec::new(),
+        };
+
+        for measurement in [30, 32] {
+            test = match test.test(Measurement(measurement)) {
+                Err(Error::TestFailedRetryable(test)) => test,
+                result => panic!("Expected retry. Got: {result:?}"),
+            };
+        }
+
+        assert_eq!(test.samples, [30, 32]);
+        assert_eq!(test.std_dev(), Some(1.0));
+        assert_eq!(test.outlier_warning(), None);
+
+        test.max_std_dev = Some(0.5);
+        assert_eq!(
+            test.outlier_warning().as_deref(),
+            Some(
+                "test failed - measurements vary more than expected (standard deviation 1.0 \
+                 exceeds 0.5): 30, 32"
+            )
+        );
+
+        test.max_std_dev = Some(f32::NAN);
+        assert_eq!(test, test.clone());
+    }
+
+    ////////////////////////////////////////////////////////////////
+
     #[test]
     fn test_success() {
         let test = MeasurementTest {
             expected: 0..=20,
             retries: 0,
             failure_message: "test failed".to_owned(),
+            mask: None,
+            format: MeasurementFormat::Hex,
+            max_std_dev: None,
+            samples: Vec::new(),
         };
 
         let measurement = Measurement::try_from(&b"000A\r"[..]).unwrap();
-        assert!(matches!(test.test(measurement), Ok(())))
+        assert!(matches!(test.test(measurement), Ok(0x0A)))
     }
 
     ////////////////////////////////////////////////////////////////
@@ -204,6 +393,10 @@ mod tests {
             expected: 0..=20,
             retries: 1,
             failure_message: "test failed".to_owned(),
+            mask: None,
+            format: MeasurementFormat::Hex,
+            max_std_dev: None,
+            samples: Vec::new(),
         };
 
         let measurement = Measurement::try_from(&b"00F0\r"[..]).unwrap();
@@ -211,7 +404,7 @@ mod tests {
 
         if let Err(Error::TestFailedRetryable(test)) = result {
             let measurement = Measurement::try_from(&b"0010\r"[..]).unwrap();
-            assert!(matches!(test.test(measurement), Ok(())))
+            assert!(matches!(test.test(measurement), Ok(0x10)))
         } else {
             panic!("Expected test to fail but be retryable. Got: {result:?}");
         }
@@ -225,11 +418,90 @@ mod tests {
             expected: 0..=20,
             retries: 0,
             failure_message: "test failed".to_owned(),
+            mask: None,
+            format: MeasurementFormat::Hex,
+            max_std_dev: None,
+            samples: Vec::new(),
         };
 
         let measurement = Measurement::try_from(&b"00F0\r"[..]).unwrap();
         assert!(matches!(test.test(measurement), Err(Error::TestFailed(_))));
     }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_negative_bounds() {
+        let test = |expected| MeasurementTest {
+            expected,
+            retries: 0,
+            failure_message: "test failed".to_owned(),
+            mask: None,
+            format: MeasurementFormat::Hex,
+            max_std_dev: None,
+            samples: Vec::new(),
+        };
+
+        let measurement = Measurement::try_from(&b"0000\r"[..]).unwrap();
+        assert!(matches!(test(-10..=10).test(measurement), Ok(0)));
+        assert!(matches!(
+            test(-10..=-1).test(measurement),
+            Err(Error::TestFailed(_))
+        ));
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_mask_success() {
+        let test = MeasurementTest {
+            expected: 0x0A..=0x0A,
+            retries: 0,
+            failure_message: "test failed".to_owned(),
+            mask: Some(0x0F),
+            format: MeasurementFormat::Hex,
+            max_std_dev: None,
+            samples: Vec::new(),
+        };
+
+        let measurement = Measurement::try_from(&b"5A\r"[..]).unwrap();
+        assert!(matches!(test.test(measurement), Ok(0x0A)))
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_mask_failure() {
+        let test = MeasurementTest {
+            expected: 0x0A..=0x0A,
+            retries: 0,
+            failure_message: "test failed".to_owned(),
+            mask: Some(0x0F),
+            format: MeasurementFormat::Hex,
+            max_std_dev: None,
+            samples: Vec::new(),
+        };
+
+        let measurement = Measurement::try_from(&b"0B\r"[..]).unwrap();
+        if let Err(Error::TestFailed(test)) = test.test(measurement) {
+            assert_eq!(test.measurement, 0x0B);
+            assert_eq!(test.mask, Some(0x0F));
+        } else {
+            panic!("Expected test to fail");
+        }
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_format_failure_message() {
+        let message =
+            format_failure_message("Measured {value}, expected {min} to {max}", 5, &(10..=20));
+        assert_eq!(message, "Measured 5, expected 10 to 20");
+
+        let message = format_failure_message("Battery voltage too low", 5, &(10..=20));
+        assert_eq!(message, "Battery voltage too low");
+    }
 }
 
 ////////////////////////////////////////////////////////////////
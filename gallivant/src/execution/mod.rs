@@ -7,7 +7,10 @@ mod transaction;
 ////////////////////////////////////////////////////////////////
 
 pub use frontend::{Dialog, FrontendRequest};
-pub use measurement::{FailedTest, Measurement, MeasurementTest};
-pub use transaction::{Device, Transaction, TransactionStatus};
+pub(crate) use measurement::format_failure_message;
+pub use measurement::{FailedTest, MeasurementFormat, MeasurementTest};
+pub use transaction::{
+    Device, PendingTransaction, Transaction, TransactionPhase, TransactionStatus,
+};
 
 ////////////////////////////////////////////////////////////////
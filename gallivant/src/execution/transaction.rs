@@ -1,8 +1,11 @@
-use std::io::{self, Read, Write};
+use std::io::{Read, Write};
 
 use crate::{error::Error, syntax::ParsedExpr};
 
-use super::measurement::{self, Measurement, MeasurementTest};
+use super::{
+    frontend::FrontendRequest,
+    measurement::{self, Measurement, MeasurementTest},
+};
 
 ////////////////////////////////////////////////////////////////
 // types
@@ -10,22 +13,73 @@ use super::measurement::{self, Measurement, MeasurementTest};
 
 /// Describes a two way transaction between a frontend and a device.
 ///
+/// A transaction is carried out in two phases:
+/// 1. Write - The transaction's bytes are sent to the device using [`Transaction::write`], giving a
+///    [`PendingTransaction`].
+/// 2. Read - The device's response is read using [`PendingTransaction::read`]. If the response is
+///    incomplete, [`TransactionStatus::Ongoing`] is returned and reading should continue. If a
+///    test on the response failed but can be retried, the ongoing transaction returns to the write
+///    phase. [`Transaction::into_phase`] gives the phase an ongoing transaction is in.
+///
+/// Frontends that don't need to interleave other work between the phases can instead repeatedly
+/// call [`Transaction::process`] until [`TransactionStatus::Success`] is returned.
+///
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Transaction {
     expression: ParsedExpr,
     txbytes: Vec<u8>,
     txcomplete: bool,
+
+    /// Number of bytes written so far by the current attempt at the transaction.
+    bytes_sent: usize,
+
     device: Device,
+
+    /// Whether the device echoes back the bytes sent to it before any measurement.
+    echo_expected: bool,
+
     response: Vec<u8>,
     test: Option<MeasurementTest>,
+
+    /// Whether the frontend has been warned that the test's measurements vary more than expected.
+    /// The warning is only given once per transaction rather than on every retry.
+    outlier_warned: bool,
+}
+
+////////////////////////////////////////////////////////////////
+
+/// A transaction whose bytes have been written, waiting for the device's response to be read.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingTransaction(Transaction);
+
+////////////////////////////////////////////////////////////////
+
+/// Phase that a transaction is in i.e. what needs to be done to progress it.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransactionPhase {
+    /// Bytes need to be written using [`Transaction::write`].
+    Write(Transaction),
+
+    /// A response needs to be read using [`PendingTransaction::read`].
+    Read(PendingTransaction),
 }
 
 ////////////////////////////////////////////////////////////////
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum TransactionStatus {
-    Success,
+    /// The transaction completed. Holds the measurement that passed the transaction's test, after
+    /// applying any mask, if it performed one.
+    Success(Option<u32>),
+
     Ongoing(Transaction),
+
+    /// The transaction is ongoing but something unexpected was seen that the frontend should be
+    /// made aware of by handling the request.
+    Warning(Transaction, Box<FrontendRequest>),
 }
 
 ////////////////////////////////////////////////////////////////
@@ -33,7 +87,8 @@ pub enum TransactionStatus {
 /// Device that a frontend may need to communcate with during script execution.
 ///
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Device {
     TCU,
     Printer,
@@ -53,9 +108,25 @@ impl Transaction {
             expression,
             txbytes,
             txcomplete: false,
+            bytes_sent: 0,
             device: Device::TCU,
+            echo_expected: true,
             response: Vec::new(),
             test,
+            outlier_warned: false,
+        }
+    }
+
+    /// Create a transaction with the TCU for a command that the TCU doesn't echo back.
+    ///
+    pub fn with_tcu_no_echo(
+        expression: ParsedExpr,
+        txbytes: Vec<u8>,
+        test: Option<MeasurementTest>,
+    ) -> Self {
+        Self {
+            echo_expected: false,
+            ..Self::with_tcu(expression, txbytes, test)
         }
     }
 
@@ -68,10 +139,27 @@ impl Transaction {
             expression,
             txbytes,
             txcomplete: false,
+            bytes_sent: 0,
             device: Device::Printer,
+            echo_expected: false,
             response: Vec::new(),
             test,
+            outlier_warned: false,
+        }
+    }
+
+    /// Warn if the measurements taken while retrying the transaction's test vary by more than a
+    /// given standard deviation. Does nothing if the transaction doesn't perform a test or the
+    /// standard deviation isn't finite.
+    ///
+    pub fn with_max_std_dev(mut self, max_std_dev: f32) -> Self {
+        if !max_std_dev.is_finite() {
+            return self;
+        }
+        if let Some(test) = self.test.as_mut() {
+            test.max_std_dev = Some(max_std_dev);
         }
+        self
     }
 }
 
@@ -84,87 +172,288 @@ impl Transaction {
         &self.txbytes
     }
 
-    pub fn process<T: Read + Write>(mut self, port: &mut T) -> Result<TransactionStatus, Error> {
-        let into_io_error = |error| Error::from_io_error(self.expression.clone(), error);
+    /// Return the bytes received from the device in response to the most recent attempt at the
+    /// transaction, including any echo.
+    ///
+    pub fn bytes_received(&self) -> &[u8] {
+        &self.response
+    }
+
+    /// Return the device the transaction is carried out with.
+    ///
+    pub fn device(&self) -> Device {
+        self.device
+    }
 
-        // Send bytes if needed.
-        if !self.txcomplete {
-            port.write_all(&self.txbytes).map_err(into_io_error)?;
-            self.txcomplete = true;
+    /// Return the test to be performed on the measurement taken by the transaction, if any.
+    ///
+    pub fn test(&self) -> Option<&MeasurementTest> {
+        self.test.as_ref()
+    }
 
-            return if self.device == Device::Printer && self.test.is_none() {
-                Ok(TransactionStatus::Success)
-            } else {
-                Ok(TransactionStatus::Ongoing(self))
-            };
+    /// Return the phase an ongoing transaction is in.
+    ///
+    pub fn into_phase(self) -> TransactionPhase {
+        if self.txcomplete {
+            TransactionPhase::Read(PendingTransaction(self))
+        } else {
+            TransactionPhase::Write(self)
         }
+    }
 
-        let response = {
-            let mut buffer = [0; 256];
-            let count = port.read(&mut buffer).map_err(into_io_error)?;
-            buffer[0..count].to_owned()
-        };
+    /// Write the transaction's bytes to a port, moving it into the read phase.
+    ///
+    /// # Arguments
+    /// * `port` - Port connected to the transaction's device.
+    ///
+    /// # Returns
+    /// The transaction, now waiting on a response.
+    ///
+    pub fn write<T: Write>(mut self, port: &mut T) -> Result<PendingTransaction, Error> {
+        while !self.write_remaining(port)? {}
 
-        self.response.extend_from_slice(&response);
-        self.evaluate_response()
+        // Any previous response was to an earlier attempt at the transaction.
+        self.response.clear();
+        self.txcomplete = true;
+        self.bytes_sent = 0;
+        Ok(PendingTransaction(self))
     }
 
-    fn evaluate_response(mut self) -> Result<TransactionStatus, Error> {
-        // Find the number of expected \r characters.
-        let echo_expected = self.device == Device::TCU;
-        let expected_endings = if self.test.is_some() && echo_expected {
-            2
-        } else if self.test.is_some() || echo_expected {
-            1
+    /// Write as many of the transaction's remaining bytes to a port as it will accept. If the port
+    /// times out after accepting some of them, the rest are left to be written by the next call.
+    ///
+    /// # Returns
+    /// True once every byte has been written.
+    ///
+    fn write_remaining<T: Write>(&mut self, port: &mut T) -> Result<bool, Error> {
+        let start = self.bytes_sent;
+
+        while self.bytes_sent < self.txbytes.len() {
+            match port.write(&self.txbytes[self.bytes_sent..]) {
+                Ok(0) => {
+                    let error = std::io::Error::from(std::io::ErrorKind::WriteZero);
+                    return Err(Error::from_io_error(self.expression.clone(), error));
+                }
+                Ok(count) => self.bytes_sent += count,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(error)
+                    if self.bytes_sent > start
+                        && matches!(
+                            error.kind(),
+                            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+                        ) =>
+                {
+                    return Ok(false)
+                }
+                Err(error) => return Err(Error::from_io_error(self.expression.clone(), error)),
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Carry out the next phase of the transaction. Convenience for frontends that don't need to
+    /// separate writing from reading. Should be called repeatedly until the transaction succeeds
+    /// or fails. If the port only accepts some of the transaction's bytes before timing out, the
+    /// transaction is returned as ongoing and the rest are written by the next call.
+    ///
+    /// # Arguments
+    /// * `port` - Port connected to the transaction's device.
+    ///
+    pub fn process<T: Read + Write>(mut self, port: &mut T) -> Result<TransactionStatus, Error> {
+        if self.txcomplete {
+            return PendingTransaction(self).read(port);
+        }
+
+        if !self.write_remaining(port)? {
+            return Ok(TransactionStatus::Ongoing(self));
+        }
+
+        let PendingTransaction(transaction) = self.write(port)?;
+        if transaction.expects_response() {
+            Ok(TransactionStatus::Ongoing(transaction))
         } else {
-            0
+            Ok(TransactionStatus::Success(None))
+        }
+    }
+
+    /// Return true if the device is expected to respond to the transaction. Printers only respond
+    /// when a measurement is requested whereas the TCU echoes unless the transaction was created
+    /// with no echo.
+    ///
+    pub fn expects_response(&self) -> bool {
+        self.echo_expected || self.test.is_some()
+    }
+
+    fn evaluate_response(mut self) -> Result<TransactionStatus, Error> {
+        // Find the number of expected \r characters. The payload of a raw transaction may itself
+        // contain \r bytes so the echo ends at the \r matching the last one sent.
+        let echo_endings = match self.echo_expected {
+            true => self.txbytes.iter().filter(|&&b| b == b'\r').count(),
+            false => 0,
         };
+        let expected_endings = echo_endings + usize::from(self.test.is_some());
 
         // No response expected.
         if expected_endings == 0 {
-            return Ok(TransactionStatus::Success);
+            return Ok(TransactionStatus::Success(None));
         }
 
-        let parts: Vec<&[u8]> = self.response.split_inclusive(|&b| b == b'\r').collect();
+        let endings: Vec<usize> = (self.response.iter().enumerate())
+            .filter(|(_, &b)| b == b'\r')
+            .map(|(i, _)| i + 1)
+            .collect();
 
         // Incomplete response.
-        if parts.len() < expected_endings {
+        if endings.len() < expected_endings {
             return Ok(TransactionStatus::Ongoing(self));
         }
 
-        let (echo, measurement) = if echo_expected {
-            (parts.get(0), parts.get(1))
-        } else {
-            (None, parts.get(0))
-        };
+        let echo_end = echo_endings.checked_sub(1).map_or(0, |i| endings[i]);
+        let echo = self.echo_expected.then(|| &self.response[..echo_end]);
+        let measurement =
+            (self.test.is_some()).then(|| &self.response[echo_end..endings[expected_endings - 1]]);
 
-        // Validate the echo.
-        if echo.is_some_and(|echo| *echo != self.txbytes) {
-            todo!("Command echo incorrect");
+        // Validate the echo. A device may truncate its echo e.g. if its buffer overflows.
+        if let Some(echo) = echo.filter(|echo| echo.len() != self.txbytes.len()) {
+            let expected = self.txbytes.len();
+            let received = echo.len();
+            return Err(Error::from_echo_length_mismatch(
+                self.expression,
+                expected,
+                received,
+            ));
+        }
+
+        if let Some(echo) = echo.filter(|echo| *echo != self.txbytes) {
+            let received = echo.to_vec();
+            return Err(Error::from_echo_mismatch(
+                self.expression,
+                self.txbytes,
+                received,
+            ));
         }
 
         // Test the measurement.
         if let Some(test) = self.test {
-            let measurement = *measurement.unwrap(); // Already checked that the measurement exists.
-            let measurement = Measurement::try_from(measurement)
-                .unwrap_or_else(|_| todo!("Handle measurement parsing failure"));
+            let measurement = measurement.unwrap(); // Already checked that the measurement exists.
+            let Ok(measurement) = Measurement::try_from((measurement, test.format)) else {
+                let raw = measurement.to_vec();
+                return Err(Error::from_measurement_parse(self.expression, raw));
+            };
 
             match test.test(measurement) {
-                Ok(_) => (),
+                Ok(measurement) => return Ok(TransactionStatus::Success(Some(measurement))),
                 Err(measurement::Error::TestFailedRetryable(test)) => {
+                    let warning = test.outlier_warning().filter(|_| !self.outlier_warned);
+                    self.outlier_warned |= warning.is_some();
                     self.test = Some(test);
                     self.txcomplete = false;
-                    return Ok(TransactionStatus::Ongoing(self));
+
+                    return Ok(match warning {
+                        Some(message) => TransactionStatus::Warning(
+                            self,
+                            Box::new(FrontendRequest::GuiPrint(message)),
+                        ),
+                        None => TransactionStatus::Ongoing(self),
+                    });
                 }
                 Err(measurement::Error::TestFailed(test)) => {
                     return Err(Error::from_failed_test(self.expression, test))
                 }
-                _ => todo!(),
+                Err(measurement::Error::Parse(_)) => {
+                    unreachable!("Testing a measurement doesn't involve parsing")
+                }
             }
         }
 
         // Success.
-        Ok(TransactionStatus::Success)
+        Ok(TransactionStatus::Success(None))
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl PendingTransaction {
+    /// Read the device's response to the transaction from a port and evaluate it.
+    ///
+    /// # Arguments
+    /// * `port` - Port connected to the transaction's device.
+    ///
+    /// # Returns
+    /// Success once a complete, valid response is received. Otherwise the ongoing transaction,
+    /// either still waiting on the rest of the response or, if a test is to be retried, waiting
+    /// to be written again.
+    ///
+    pub fn read<T: Read>(self, port: &mut T) -> Result<TransactionStatus, Error> {
+        let PendingTransaction(mut transaction) = self;
+        if !transaction.expects_response() {
+            return Ok(TransactionStatus::Success(None));
+        }
+
+        let response = {
+            let mut buffer = [0; 256];
+            let count = port
+                .read(&mut buffer)
+                .map_err(|error| Error::from_io_error(transaction.expression.clone(), error))?;
+            buffer[0..count].to_owned()
+        };
+
+        transaction.response.extend_from_slice(&response);
+        transaction.evaluate_response()
+    }
+
+    /// Return the transaction that's waiting on a response.
+    ///
+    pub fn transaction(&self) -> &Transaction {
+        &self.0
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// comparison
+////////////////////////////////////////////////////////////////
+
+impl Eq for Transaction {}
+
+impl std::hash::Hash for Transaction {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // Only a subset of the fields compared by PartialEq, which is enough to tell most
+        // transactions apart.
+        self.txbytes.hash(state);
+        self.device.hash(state);
+        self.txcomplete.hash(state);
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// ...
+////////////////////////////////////////////////////////////////
+
+impl std::fmt::Display for Transaction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}'", self.txbytes.escape_ascii())?;
+
+        match &self.test {
+            Some(test @ MeasurementTest { mask: None, .. }) => write!(
+                f,
+                " expecting {} to {}",
+                test.expected.start(),
+                test.expected.end()
+            )?,
+            Some(
+                test @ MeasurementTest {
+                    mask: Some(mask), ..
+                },
+            ) => write!(
+                f,
+                " expecting ${:X} with mask ${mask:X}",
+                test.expected.start()
+            )?,
+            None => (),
+        }
+
+        Ok(())
     }
 }
 
@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use crate::{error::Error, execution::FrontendRequest, syntax::ParsedExpr};
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Callbacks made by an interpreter during script execution. Allows frontends to observe the
+/// progress of a script without having to be involved in handling it's requests.
+///
+/// All methods have empty default implementations so implementors only need to provide the ones
+/// they're interested in.
+///
+pub trait ScriptHooks {
+    /// Called after an expression has been evaluated but before the resulting request is handed to
+    /// the frontend.
+    ///
+    /// # Arguments
+    /// * `expression` - Expression that was evaluated.
+    /// * `request` - Request the expression evaluated to.
+    ///
+    fn before_request(&mut self, _expression: &ParsedExpr, _request: &FrontendRequest) {}
+
+    /// Called once the frontend reports that it has finished handling a request.
+    ///
+    /// # Arguments
+    /// * `expression` - Expression that the handled request was evaluated from.
+    /// * `result` - Outcome of handling the request.
+    ///
+    fn after_request(&mut self, _expression: &ParsedExpr, _result: Result<(), &Error>) {}
+
+    /// Called once the frontend reports that it has finished handling a wait request, before
+    /// after_request. The elapsed time may exceed the requested time e.g. due to OS scheduling.
+    ///
+    /// # Arguments
+    /// * `expression` - Expression that the wait request was evaluated from.
+    /// * `requested` - Time the script asked to wait for.
+    /// * `elapsed` - Time between the request being handed to the frontend and it being
+    ///   completed.
+    ///
+    fn after_wait(&mut self, _expression: &ParsedExpr, _requested: Duration, _elapsed: Duration) {}
+
+    /// Called when the frontend reports the measurement that passed a request's test, before
+    /// after_request. Measurements that failed are instead given by the error passed to
+    /// after_request.
+    ///
+    /// # Arguments
+    /// * `expression` - Expression that the test's request was evaluated from.
+    /// * `measurement` - Measurement that passed the test, after applying any mask.
+    ///
+    fn after_measurement(&mut self, _expression: &ParsedExpr, _measurement: u32) {}
+
+    /// Called after before_request for each request evaluated while tracing is enabled by
+    /// TRACE ON. The TRACE ON and TRACE OFF commands themselves aren't traced.
+    ///
+    /// # Arguments
+    /// * `expression` - Expression that was evaluated.
+    /// * `request` - Request the expression evaluated to.
+    ///
+    fn trace_request(&mut self, _expression: &ParsedExpr, _request: &FrontendRequest) {}
+}
+
+////////////////////////////////////////////////////////////////
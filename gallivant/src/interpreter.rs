@@ -1,7 +1,22 @@
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::HashMap,
+    io::{Read, Write},
+    path::Path,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
 use super::{
-    error::Error,
-    execution::FrontendRequest,
-    syntax::{evaluate, parse_from_str, EvalState, ParsedExpr},
+    error::{DiagnosticSeverity, Error, ErrorNote, ErrorReason},
+    execution::{format_failure_message, FrontendRequest},
+    hooks::ScriptHooks,
+    syntax::{
+        evaluate, line_col, parse_from_str_with_comment_char, CommentChar, EvalState, Expr,
+        ExprKind, ParsedExpr,
+    },
+    transaction_log::TransactionLog,
 };
 
 ////////////////////////////////////////////////////////////////
@@ -10,26 +25,380 @@ use super::{
 
 /// Interpreter for test scripts.
 ///
-#[derive(Default, Clone, Debug, PartialEq)]
+/// Clones share the hooks added by with_hooks. Hooks, errors and the transaction log are ignored
+/// when comparing interpreters.
+///
+#[derive(Clone, Debug, Default)]
 pub struct Interpreter {
+    /// Normalised text of the script, which the spans of expressions and errors refer to.
+    script: String,
+
     ast: Vec<ParsedExpr>,
     index: usize,
     state: EvalState,
+
+    /// Index of the most recent CHECKPOINT, TESTGROUP or ENDTESTGROUP reached and the state
+    /// immediately before it was evaluated, from which the script may be resumed.
+    checkpoint: Option<(usize, EvalState)>,
+    hooks: Hooks,
+
+    /// Index of each LABEL within the script, by name.
+    labels: HashMap<String, usize>,
+
+    /// Index of the expression that produced the most recent request. None if the request didn't
+    /// come from an expression e.g. a warning.
+    current: Option<usize>,
+
+    /// Warning produced by a failed WARNTEST that's yet to be passed to the frontend.
+    pending_warning: Option<String>,
+
+    /// Duration of the most recent WAIT request and when it was handed to the frontend, if it's yet
+    /// to be completed.
+    pending_wait: Option<(Duration, Instant)>,
+
+    /// Whether to make a progress request before each expression is evaluated.
+    progress_reports: bool,
+
+    /// Whether a progress request has been made for the expression at the current index.
+    progress_reported: bool,
+
+    /// Variables defined before the script is run.
+    params: HashMap<String, u32>,
+
+    /// Which errors the script continues after.
+    error_policy: ErrorPolicy,
+
+    /// Errors that the script continued after.
+    errors: Vec<Error>,
+
+    /// Problems found when analysing the script that don't prevent it from being run.
+    script_warnings: Vec<Error>,
+
+    /// Record of the requests made so far, if enabled.
+    transaction_log: Option<TransactionLog>,
+
+    /// Maximum number of bytes a single USBPRINT may send, if limited.
+    usb_print_limit: Option<u32>,
+
+    /// Maximum number of commands that may be executed, counting each iteration of a loop.
+    max_commands: u64,
+
+    /// Name of the file the script was read from, if any.
+    filename: Option<String>,
 }
 
+////////////////////////////////////////////////////////////////
+
+/// Hooks added to an interpreter, in the order they were added. Shared so that interpreters can
+/// still be cloned when their hooks hold resources such as open files.
+///
+#[derive(Clone, Default)]
+struct Hooks(Vec<Rc<RefCell<dyn ScriptHooks>>>);
+
+////////////////////////////////////////////////////////////////
+
+/// Determines which errors cause script execution to stop. Errors the script continues after are
+/// accumulated by the interpreter instead of being returned to the frontend.
+///
+/// A global timeout, reaching the limit set by MAXFAILURES or the user cancelling the script always
+/// stops it.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ErrorPolicy {
+    /// Stop on the first error.
+    #[default]
+    AbortOnFirst,
+
+    /// Continue after IO errors e.g. a failed write to a device. Any other error stops the script.
+    ContinueOnIO,
+
+    /// Continue after any error, including failed tests.
+    ContinueOnAll,
+}
+
+////////////////////////////////////////////////////////////////
+
+/// States the printer port may be in at a point in a script, depending on the path taken to reach
+/// it.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct PortState {
+    closed: bool,
+    open: bool,
+
+    /// Index of the first USBOPEN that may have left the port open.
+    opened_by: Option<usize>,
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Version of the interpreter as (major, minor), taken from the crate's version. Scripts may
+/// require a minimum version using SCRIPTVERSION.
+///
+pub const INTERPRETER_VERSION: (u8, u8) = (
+    parse_version(env!("CARGO_PKG_VERSION_MAJOR")),
+    parse_version(env!("CARGO_PKG_VERSION_MINOR")),
+);
+
+////////////////////////////////////////////////////////////////
+
+/// First line of state saved by Interpreter::save_state, identifying the format.
+///
+const STATE_HEADER: &str = "GALLIVANT STATE 2";
+
 ////////////////////////////////////////////////////////////////
 // construction / conversion
 ////////////////////////////////////////////////////////////////
 
 impl Interpreter {
+    /// Create an interpreter from a script. Fails if the script can't be parsed or requires a newer
+    /// interpreter version than INTERPRETER_VERSION.
+    ///
     pub fn try_from_str(script: &str) -> Result<Self, Vec<Error>> {
+        Self::try_from_str_with_comment_char(script, CommentChar::default())
+    }
+
+    /// Create an interpreter from a script, only treating text following the given characters as
+    /// script comments.
+    ///
+    pub fn try_from_str_with_comment_char(
+        script: &str,
+        comment_char: CommentChar,
+    ) -> Result<Self, Vec<Error>> {
+        let script = normalize_script(script);
+        let ast = parse_from_str_with_comment_char(&script, comment_char)
+            .map_err(|error| error.into_iter().map(Error::from).collect::<Vec<Error>>())?;
+        let ast = expand_macros(ast)?;
+
+        let version_errors: Vec<Error> = ast.iter().filter_map(check_version).collect();
+        if !version_errors.is_empty() {
+            return Err(version_errors);
+        }
+
+        let (script_warnings, analysis_errors): (Vec<Error>, Vec<Error>) =
+            validate_printer_lifecycle(&ast)
+                .into_iter()
+                .chain(validate_hpmode_parity(&ast))
+                .partition(|error| error.reason().severity() == DiagnosticSeverity::Warning);
+
+        if !analysis_errors.is_empty() {
+            return Err(analysis_errors);
+        }
+
         Ok(Self {
-            ast: parse_from_str(script)
-                .map_err(|error| error.into_iter().map(Error::from).collect::<Vec<Error>>())?,
+            script: script.into_owned(),
+            labels: label_index(&ast),
+            ast,
             index: 0,
             state: EvalState::new(),
+            checkpoint: None,
+            hooks: Hooks::default(),
+            current: None,
+            pending_warning: None,
+            pending_wait: None,
+            progress_reports: false,
+            progress_reported: false,
+            params: HashMap::new(),
+            error_policy: ErrorPolicy::default(),
+            errors: Vec::new(),
+            script_warnings,
+            transaction_log: None,
+            usb_print_limit: None,
+            max_commands: u64::MAX,
+            filename: None,
         })
     }
+
+    /// Create an interpreter from a script read from a reader e.g. an open file. The whole script
+    /// is read before it's parsed.
+    ///
+    pub fn try_from_reader<R: Read>(mut reader: R) -> Result<Self, Vec<Error>> {
+        let mut script = String::new();
+        reader
+            .read_to_string(&mut script)
+            .map_err(|error| vec![Error::from_script_read(None, error)])?;
+
+        Self::try_from_str(&script)
+    }
+
+    /// Create an interpreter from a script file. Any errors parsing the script are noted with the
+    /// file's path and the line and column they occured at. The script's warnings and errors
+    /// occuring while it's executed record the file's path as their filename.
+    ///
+    pub fn try_from_path(path: &Path) -> Result<Self, Vec<Error>> {
+        let script = std::fs::read_to_string(path)
+            .map_err(|error| vec![Error::from_script_read(Some(path.to_owned()), error)])?;
+        let script = normalize_script(&script);
+
+        Self::try_from_str(&script)
+            .map_err(|errors| {
+                errors
+                    .into_iter()
+                    .map(|error| {
+                        let location = match error.reason().span() {
+                            Some(span) => {
+                                let (line, column) = line_col(span, &script);
+                                format!("{}:{line}:{column}", path.display())
+                            }
+                            None => path.display().to_string(),
+                        };
+
+                        error.with_note(ErrorNote::Note(format!("In {location}").into()))
+                    })
+                    .collect()
+            })
+            .map(|interpreter| interpreter.with_filename(path.display().to_string()))
+    }
+
+    /// Create an interpreter from state previously saved by save_state e.g. to resume a script that
+    /// was interrupted. Requests that were in progress when the state was saved can't be resumed
+    /// so execution resumes from the most recent CHECKPOINT, TESTGROUP or ENDTESTGROUP reached
+    /// before the state was saved, with the state the script had at that point. Scripts that hadn't
+    /// reached one are resumed from the beginning.
+    ///
+    /// # Arguments
+    /// * `reader` - Reader to read the saved state from.
+    ///
+    pub fn restore_state(reader: &mut impl Read) -> Result<Self, Vec<Error>> {
+        let invalid = |message: &str| {
+            let error = std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_owned());
+            vec![Error::from_state_restore(None, error)]
+        };
+
+        let mut saved = String::new();
+        reader
+            .read_to_string(&mut saved)
+            .map_err(|error| vec![Error::from_state_restore(None, error)])?;
+
+        let Some((header, script)) = saved.split_once("\nscript\n") else {
+            return Err(invalid("Saved state doesn't contain a script"));
+        };
+
+        let mut lines = header.lines();
+        if lines.next() != Some(STATE_HEADER) {
+            return Err(invalid("Unrecognised saved state format"));
+        }
+
+        let mut index = 0;
+        let mut state = EvalState::new();
+        for line in lines {
+            match line.split_once(' ') {
+                Some(("index", value)) => {
+                    index = value.parse().map_err(|_| invalid("Invalid saved index"))?;
+                }
+                Some((key, value)) => state
+                    .restore_line(key, value)
+                    .map_err(|message| invalid(&message))?,
+                None => return Err(invalid("Unrecognised line in saved state")),
+            }
+        }
+
+        // The script was written by save_state so any problem parsing it means the state is invalid.
+        let mut interpreter = Self::try_from_str(script).map_err(|errors| {
+            let messages: Vec<String> = errors.iter().map(|e| e.reason().message()).collect();
+            invalid(&format!("Invalid saved script - {}", messages.join(", ")))
+        })?;
+
+        if index > interpreter.ast.len() {
+            return Err(invalid("Saved index is beyond the end of the script"));
+        }
+
+        interpreter.index = index;
+        interpreter.state = state;
+        Ok(interpreter)
+    }
+
+    /// Define variables that the script can reference before it's run e.g. parameters passed in by
+    /// the user. Replaces any previously defined parameters.
+    ///
+    /// # Returns
+    /// An error if any of the parameter names aren't valid variable names.
+    ///
+    pub fn with_params(mut self, params: HashMap<String, u32>) -> Result<Self, Error> {
+        self.params = HashMap::new();
+        let mut state = self.initial_state();
+        for (name, &value) in params.iter() {
+            state.set_variable(name, value)?;
+        }
+
+        self.params = params;
+        self.state = state;
+        Ok(self)
+    }
+
+    /// Limit the number of bytes a single USBPRINT may send e.g. to the size of the printer's
+    /// receive buffer. Unlimited by default.
+    ///
+    pub fn with_usb_print_limit(mut self, limit: u32) -> Self {
+        self.usb_print_limit = Some(limit);
+        self.state.usb_print_limit = Some(limit);
+        self
+    }
+
+    /// Limit the number of commands that may be executed, counting each iteration of a loop, so
+    /// that runaway loops are stopped. Script comments aren't counted. Unlimited by default.
+    ///
+    pub fn with_max_commands(mut self, limit: u64) -> Self {
+        self.max_commands = limit;
+        self
+    }
+
+    /// Remove script comments so that they don't produce any requests. Useful to reduce noise in
+    /// the request stream when debugging. Comments are kept by default.
+    ///
+    pub fn with_strip_comments(mut self, strip: bool) -> Self {
+        if strip {
+            self.ast
+                .retain(|expr| !matches!(expr.expression(), Expr::ScriptComment(_)));
+            self.labels = label_index(&self.ast);
+        }
+        self
+    }
+
+    /// Make a FrontendRequest::Progress before each expression is evaluated so that frontends can
+    /// display how far through the script execution is. Disabled by default since it doubles the
+    /// number of requests.
+    ///
+    pub fn with_progress_reports(mut self, enable: bool) -> Self {
+        self.progress_reports = enable;
+        self
+    }
+
+    /// Set which errors the script continues after.
+    ///
+    pub fn with_error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
+
+    /// Add hooks to be called as the script is executed. May be called multiple times to add
+    /// multiple sets of hooks. Hooks are called in the order they were added.
+    ///
+    pub fn with_hooks(mut self, hooks: impl ScriptHooks + 'static) -> Self {
+        self.hooks.0.push(Rc::new(RefCell::new(hooks)));
+        self
+    }
+
+    /// Record each request made by the script, other than those of script comments, so that they
+    /// remain available once the script has finished executing.
+    ///
+    pub fn with_transaction_log(mut self) -> Self {
+        self.transaction_log = Some(TransactionLog::default());
+        self
+    }
+
+    /// Set the name of the file the script was read from, which is recorded by the script's
+    /// warnings and by any errors reported to complete.
+    ///
+    pub fn with_filename(mut self, filename: impl Into<String>) -> Self {
+        let filename = filename.into();
+        self.script_warnings = std::mem::take(&mut self.script_warnings)
+            .into_iter()
+            .map(|warning| warning.with_filename(&filename))
+            .collect();
+        self.filename = Some(filename);
+        self
+    }
 }
 
 ////////////////////////////////////////////////////////////////
@@ -40,9 +409,129 @@ impl Iterator for Interpreter {
     type Item = Result<FrontendRequest, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        // Warnings are reported before the script continues.
+        if let Some(message) = self.pending_warning.take() {
+            let request = FrontendRequest::GuiPrint(format!("[WARNING] {message}"));
+            if let Some(expr) = self.current.and_then(|i| self.ast.get(i)) {
+                for hooks in self.hooks.0.iter() {
+                    hooks.borrow_mut().before_request(expr, &request);
+                }
+            }
+
+            self.current = None;
+            return Some(Ok(request));
+        }
+
+        if self.progress_reports && !self.progress_reported && self.index < self.ast.len() {
+            self.progress_reported = true;
+            self.current = None;
+            return Some(Ok(FrontendRequest::Progress {
+                current: self.index,
+                total: self.ast.len(),
+            }));
+        }
+
         if let Some(expr) = self.ast.get(self.index) {
+            self.current = Some(self.index);
             self.index += 1;
-            Some(evaluate(expr, &mut self.state))
+            self.progress_reported = false;
+            self.pending_wait = None;
+
+            // The expression isn't evaluated if the script has run out of time.
+            if let Some((start, timeout)) = self.state.global_timeout {
+                let elapsed = start.elapsed();
+                if elapsed >= timeout {
+                    return Some(Err(Error::from_global_timeout(elapsed)));
+                }
+            }
+
+            // Nor is it evaluated if too many tests have failed.
+            if let Some(threshold) = self.state.max_failures {
+                let failures = self.state.failures;
+                if failures > 0 && failures >= threshold {
+                    return Some(Err(Error::from_max_failures(threshold, failures)));
+                }
+            }
+
+            if matches!(
+                expr.expression(),
+                Expr::Checkpoint(_) | Expr::TestGroup(_) | Expr::EndTestGroup
+            ) {
+                self.checkpoint = Some((self.index - 1, self.state.clone()));
+            }
+
+            // Nor if too many commands have been executed.
+            if !matches!(expr.expression(), Expr::ScriptComment(_)) {
+                if self.state.commands >= self.max_commands {
+                    return Some(Err(Error::from_max_commands(
+                        self.max_commands,
+                        self.state.commands,
+                    )));
+                }
+
+                self.state.commands += 1;
+            }
+
+            let result = evaluate(expr, &mut self.state);
+
+            // Skip to the end of a conditional block if its condition isn't met.
+            let condition_met = match expr.expression() {
+                Expr::IfTestPass => self.state.last_test_passed == Some(true),
+                Expr::IfTestFail => self.state.last_test_passed == Some(false),
+                _ => true,
+            };
+
+            if !condition_met {
+                self.index = matching_endif(&self.ast, self.index - 1);
+            }
+
+            // Loops with no iterations remaining are skipped over or exited. Loops whose count
+            // couldn't be evaluated are skipped entirely.
+            match expr.expression() {
+                Expr::Repeat(_) if result.is_err() => {
+                    self.index = matching_endrepeat(&self.ast, self.index - 1) + 1;
+                }
+                Expr::Repeat(_) if self.state.loops.last() == Some(&0) => {
+                    self.state.loops.pop();
+                    self.index = matching_endrepeat(&self.ast, self.index - 1) + 1;
+                }
+                Expr::EndRepeat => match self.state.loops.last_mut() {
+                    Some(remaining) if *remaining > 1 => {
+                        *remaining -= 1;
+                        self.index = matching_repeat(&self.ast, self.index - 1) + 1;
+                    }
+                    _ => {
+                        self.state.loops.pop();
+                    }
+                },
+                _ => (),
+            }
+
+            if let Ok(request) = &result {
+                for hooks in self.hooks.0.iter() {
+                    hooks.borrow_mut().before_request(expr, request);
+                }
+
+                if self.state.trace() && !matches!(expr.expression(), Expr::TraceOn) {
+                    for hooks in self.hooks.0.iter() {
+                        hooks.borrow_mut().trace_request(expr, request);
+                    }
+                }
+
+                if let Some(log) = &mut self.transaction_log {
+                    if !matches!(expr.expression(), Expr::ScriptComment(_)) {
+                        log.push(expr, request);
+                    }
+                }
+
+                // Timed from when the request is handed over so that hooks see how long the
+                // frontend actually waited for.
+                if let FrontendRequest::Wait(duration) = request {
+                    self.pending_wait = Some((*duration, Instant::now()));
+                }
+            }
+
+            Some(result)
         } else {
             None
         }
@@ -54,10 +543,741 @@ impl Iterator for Interpreter {
 ////////////////////////////////////////////////////////////////
 
 impl Interpreter {
+    /// Return the state that the script starts executing with.
+    ///
+    fn initial_state(&self) -> EvalState {
+        let mut state = EvalState::with_variables(self.params.clone());
+        state.usb_print_limit = self.usb_print_limit;
+        state
+    }
+
+    /// Return whether HPMODE is active at the current point in the script. HPMODE is toggled each
+    /// time an HPMODE command is evaluated.
+    ///
+    pub fn hpmode(&self) -> bool {
+        self.state.hpmode()
+    }
+
+    /// Return when the time limit set by GLOBALTIMEOUT started and its length, if one is set.
+    /// Frontends waiting on a request should stop once it's exceeded.
+    ///
+    pub fn global_timeout(&self) -> Option<(Instant, Duration)> {
+        self.state.global_timeout
+    }
+
+    /// Return the name of the test group that the script is currently within, if any.
+    ///
+    pub fn test_group(&self) -> Option<&str> {
+        self.state.test_group.as_deref()
+    }
+
+    /// Return the failure messages of every WARNTEST that has failed so far.
+    ///
+    pub fn warnings(&self) -> &[String] {
+        &self.state.warnings
+    }
+
+    /// Return the normalised text of the script, which the spans of expressions and errors refer
+    /// to. For a restored interpreter this is the script saved with its state.
+    ///
+    pub fn script(&self) -> &str {
+        &self.script
+    }
+
+    /// Return the expression that produced the most recent request, if any.
+    ///
+    pub fn current_expression(&self) -> Option<&ParsedExpr> {
+        self.current.and_then(|i| self.ast.get(i))
+    }
+
+    /// Return the errors that the script continued after, in the order they occured.
+    ///
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    /// Return the problems found when the script was analysed that don't prevent it from being
+    /// run e.g. a USBOPEN without a following USBCLOSE. Each has a severity of warning.
+    ///
+    pub fn script_warnings(&self) -> &[Error] {
+        &self.script_warnings
+    }
+
+    /// Return the record of requests made so far, if enabled by with_transaction_log.
+    ///
+    pub fn transaction_log(&self) -> Option<&TransactionLog> {
+        self.transaction_log.as_ref()
+    }
+
+    /// Save the position of the most recent CHECKPOINT, TESTGROUP or ENDTESTGROUP reached, the
+    /// complete state of the script at that point and the script itself so that execution can later
+    /// be resumed from there by restore_state.
+    ///
+    /// # Arguments
+    /// * `writer` - Writer to write the state to e.g. a file.
+    ///
+    pub fn save_state(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let (index, state) = match &self.checkpoint {
+            Some((index, state)) => (*index, Cow::Borrowed(state)),
+            None => (0, Cow::Owned(self.initial_state())),
+        };
+
+        writeln!(writer, "{STATE_HEADER}")?;
+        writeln!(writer, "index {index}")?;
+        state.save(writer)?;
+
+        // Each expression is written on its own line so that the script parses back into the same
+        // expressions in the same positions.
+        writeln!(writer, "script")?;
+        for expr in self.ast.iter() {
+            writeln!(writer, "{expr}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Move the interpreter to a LABEL without executing any of the commands in between e.g. to
+    /// start a script from a particular phase. The next request is produced by the command
+    /// following the label.
+    ///
+    /// State such as HPMODE and variables isn't reset so it reflects whatever commands have been
+    /// executed so far, not those that were skipped. Any loops being executed are exited.
+    ///
+    /// # Arguments
+    /// * `label` - Name of the label.
+    ///
+    /// # Returns
+    /// An error if the script doesn't contain the label.
+    ///
+    pub fn seek_to_label(&mut self, label: &str) -> Result<(), Error> {
+        let Some(index) = self.labels.get(label) else {
+            return Err(Error::from_undefined_label(label.to_owned()));
+        };
+
+        self.index = index + 1;
+        self.current = None;
+        self.pending_warning = None;
+        self.pending_wait = None;
+        self.progress_reported = false;
+        self.state.loops.clear();
+        Ok(())
+    }
+
     /// Restart the interpreter from the beginning of the script.
     pub fn restart(&mut self) {
         self.index = 0;
-        self.state = EvalState::new();
+        self.current = None;
+        self.pending_warning = None;
+        self.pending_wait = None;
+        self.progress_reported = false;
+        self.errors.clear();
+        self.state = self.initial_state();
+        self.checkpoint = None;
+    }
+
+    /// Report the measurement that passed the most recent request's test, as given by
+    /// [`crate::TransactionStatus::Success`]. Frontends should call this before complete so that hooks
+    /// can record the measurement.
+    ///
+    /// # Arguments
+    /// * `measurement` - Measurement that passed the test.
+    ///
+    pub fn report_measurement(&mut self, measurement: u32) {
+        if let Some(expr) = self.current.and_then(|i| self.ast.get(i)) {
+            for hooks in self.hooks.0.iter() {
+                hooks.borrow_mut().after_measurement(expr, measurement);
+            }
+        }
+    }
+
+    /// Report the outcome of handling the most recent request back to the interpreter. Frontends
+    /// should call this once they've finished handling each request. Errors returned by next
+    /// should also be reported here so that they're handled the same as errors from the frontend.
+    ///
+    /// # Arguments
+    /// * `result` - Outcome of handling the request.
+    ///
+    /// # Returns
+    /// The outcome, passed through so that frontends can continue to handle any error. Errors are
+    /// given a note recording the last checkpoint reached and the script's filename. A failed
+    /// test that's immediately followed by a conditional is handled by the script so Ok is
+    /// returned instead. A failed WARNTEST only produces a warning so Ok is also returned. Errors
+    /// that the error policy allows the script to continue after are accumulated and Ok is
+    /// returned.
+    ///
+    pub fn complete(&mut self, result: Result<(), Error>) -> Result<(), Error> {
+        let result =
+            result.map_err(|error| with_context(error, &self.state, self.filename.as_deref()));
+
+        if let Some(expr) = self.current.and_then(|i| self.ast.get(i)) {
+            if let Some((requested, start)) = self.pending_wait.take() {
+                let elapsed = start.elapsed();
+                for hooks in self.hooks.0.iter() {
+                    hooks.borrow_mut().after_wait(expr, requested, elapsed);
+                }
+            }
+
+            for hooks in self.hooks.0.iter() {
+                hooks
+                    .borrow_mut()
+                    .after_request(expr, result.as_ref().map(|_| ()));
+            }
+
+            if is_test(expr.expression()) {
+                match &result {
+                    Ok(()) => self.state.last_test_passed = Some(true),
+                    Err(error) if matches!(error.reason(), ErrorReason::TestFailure { .. }) => {
+                        self.state.last_test_passed = Some(false);
+
+                        if !matches!(expr.expression(), Expr::WarnTest { .. }) {
+                            self.state.failures += 1;
+                        }
+                    }
+                    Err(_) => (),
+                }
+            }
+
+            if let (Expr::WarnTest { .. }, Err(error)) = (expr.expression(), &result) {
+                if let ErrorReason::TestFailure { test, .. } = error.reason() {
+                    let message =
+                        format_failure_message(&test.message, test.measurement, &test.expected);
+                    self.state.warnings.push(message.clone());
+                    self.pending_warning = Some(message);
+                    return Ok(());
+                }
+            }
+        }
+
+        let next_expr = self.ast[self.index..]
+            .iter()
+            .find(|expr| !matches!(expr.expression(), Expr::ScriptComment(_)));
+
+        match (result, next_expr.map(ParsedExpr::expression)) {
+            (Err(error), Some(Expr::IfTestPass | Expr::IfTestFail))
+                if matches!(error.reason(), ErrorReason::TestFailure { .. }) =>
+            {
+                Ok(())
+            }
+            (Err(error), _) if self.error_policy.continues_after(&error) => {
+                self.errors.push(error);
+                Ok(())
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Execute the entire script from the beginning without affecting the interpreter's position.
+    /// Requests aren't handled so any that would normally require a frontend e.g. transactions,
+    /// should be ignored by the caller. Conditionals and loops are followed as if every request
+    /// succeeded i.e. every test passed.
+    ///
+    /// # Returns
+    /// The result of each expression executed, in the order they're executed.
+    ///
+    pub fn dry_run(&self) -> Vec<Result<FrontendRequest, Error>> {
+        let mut interpreter = Self {
+            script: self.script.clone(),
+            ast: self.ast.clone(),
+            state: self.initial_state(),
+            labels: self.labels.clone(),
+            params: self.params.clone(),
+            usb_print_limit: self.usb_print_limit,
+            max_commands: self.max_commands,
+            ..Default::default()
+        };
+
+        let mut results = Vec::new();
+        while let Some(result) = interpreter.next() {
+            if result.is_ok() {
+                // Completing a successful request can't fail.
+                let _ = interpreter.complete(Ok(()));
+            }
+
+            results.push(result);
+        }
+
+        results
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl ErrorPolicy {
+    /// Return true if the script should continue executing after an error.
+    ///
+    fn continues_after(&self, error: &Error) -> bool {
+        match (self, error.reason()) {
+            (_, ErrorReason::GlobalTimeout { .. }) => false,
+            (_, ErrorReason::MaxFailuresExceeded { .. }) => false,
+            (_, ErrorReason::MaxCommandsExceeded { .. }) => false,
+            (_, ErrorReason::UserCancelled) => false,
+            (ErrorPolicy::AbortOnFirst, _) => false,
+            (ErrorPolicy::ContinueOnIO, reason) => matches!(reason, ErrorReason::IOError { .. }),
+            (ErrorPolicy::ContinueOnAll, _) => true,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Prepare a script for parsing by removing any byte order mark and converting Windows line endings
+/// to '\n'. Scripts are normalised when an interpreter is created so the spans of any errors refer
+/// to the normalised script rather than the original.
+///
+/// # Arguments
+/// * `script` - Script to normalise.
+///
+/// # Returns
+/// The normalised script, borrowed if it didn't need changing.
+///
+pub fn normalize_script(script: &str) -> Cow<'_, str> {
+    let script = strip_bom(script);
+    if script.contains("\r\n") {
+        Cow::Owned(script.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(script)
+    }
+}
+
+/// Remove the UTF-8 byte order mark that some editors add to the start of files. It isn't
+/// whitespace so would otherwise be parsed as an unrecognised command.
+///
+fn strip_bom(script: &str) -> &str {
+    script.strip_prefix('\u{FEFF}').unwrap_or(script)
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Return true if an expression performs a test on a measurement.
+///
+fn is_test(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::TCUTest { .. }
+            | Expr::WarnTest { .. }
+            | Expr::MaskTest { .. }
+            | Expr::Assert { .. }
+            | Expr::PrinterTest { .. }
+            | Expr::USBPrinterTest { .. }
+            | Expr::USBTest { .. }
+            | Expr::TCUExact { .. }
+            | Expr::USBExact { .. }
+    )
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Check that commands targeting the printer are only issued while the printer port is open i.e.
+/// after a USBOPEN and before the following USBCLOSE. Every path through the script's conditionals
+/// and loops is followed, and since a script may be started from any LABEL, the port may be in
+/// either state after one.
+///
+/// # Returns
+/// A PortNotOpen error for each command issued while the port is closed, whichever path is taken to
+/// it, and a PortNotClosed warning if any path leaves the port open at the end of the script.
+///
+fn validate_printer_lifecycle(ast: &[ParsedExpr]) -> Vec<Error> {
+    // States the port may be in before each expression, and at the end of the script.
+    let mut states: Vec<Option<PortState>> = vec![None; ast.len() + 1];
+    states[0] = Some(PortState {
+        closed: true,
+        ..Default::default()
+    });
+
+    let mut pending = vec![0];
+    while let Some(index) = pending.pop() {
+        let (Some(expr), Some(state)) = (ast.get(index), states[index]) else {
+            continue;
+        };
+
+        let state = match expr.expression() {
+            Expr::USBOpen => PortState {
+                open: true,
+                opened_by: Some(index),
+                ..Default::default()
+            },
+            Expr::USBClose => PortState {
+                closed: true,
+                ..Default::default()
+            },
+            Expr::Label(_) => PortState {
+                closed: true,
+                open: true,
+                ..state
+            },
+            _ => state,
+        };
+
+        let branch = match expr.expression() {
+            Expr::IfTestPass | Expr::IfTestFail => Some(matching_endif(ast, index)),
+            Expr::Repeat(_) => Some(matching_endrepeat(ast, index) + 1),
+            Expr::EndRepeat => Some(matching_repeat(ast, index) + 1),
+            _ => None,
+        };
+
+        for next in std::iter::once(index + 1).chain(branch) {
+            let joined = match states[next] {
+                Some(other) => PortState {
+                    closed: state.closed || other.closed,
+                    open: state.open || other.open,
+                    opened_by: match (state.opened_by, other.opened_by) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    },
+                },
+                None => state,
+            };
+
+            if states[next] != Some(joined) {
+                states[next] = Some(joined);
+                pending.push(next);
+            }
+        }
+    }
+
+    let mut errors = Vec::new();
+    for (expr, state) in ast.iter().zip(&states) {
+        match expr.expression() {
+            Expr::USBPrint(_)
+            | Expr::USBPrinterTest { .. }
+            | Expr::USBTest { .. }
+            | Expr::USBExact { .. }
+            | Expr::USBSetTimeFormat(_)
+            | Expr::USBSetTime
+            | Expr::USBSetOption { .. }
+            | Expr::USBPrinterSet(_)
+                if state.is_some_and(|state| !state.open) =>
+            {
+                errors.push(Error::from_port_not_open(
+                    expr.expression_kind(),
+                    expr.span().clone(),
+                ))
+            }
+            _ => (),
+        }
+    }
+
+    let opened_by = states[ast.len()].and_then(|state| state.opened_by);
+    errors.extend(opened_by.map(|index| Error::from_port_not_closed(ast[index].span().clone())));
+    errors
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Check that a script contains an even number of HPMODE commands. As HPMODE toggles the mode,
+/// an odd number leaves the script in the opposite mode to the one it started in.
+///
+/// # Returns
+/// An OddHPModeToggle warning if the number of HPMODE commands is odd.
+///
+fn validate_hpmode_parity(ast: &[ParsedExpr]) -> Vec<Error> {
+    let count = ast
+        .iter()
+        .filter(|expr| expr.expression_kind() == ExprKind::HPMode)
+        .count();
+
+    if count % 2 == 1 {
+        vec![Error::from_odd_hpmode_toggle(count)]
+    } else {
+        Vec::new()
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Check that the interpreter is at least the version required by a SCRIPTVERSION command.
+///
+/// # Returns
+/// An error if the expression is a SCRIPTVERSION requiring a newer interpreter.
+///
+fn check_version(expr: &ParsedExpr) -> Option<Error> {
+    let Expr::ScriptVersion { major, minor } = expr.expression() else {
+        return None;
+    };
+
+    // Arguments are validated to be byte literals when parsed.
+    let (Expr::UInt(major), Expr::UInt(minor)) = (major.expression(), minor.expression()) else {
+        unreachable!("Invalid SCRIPTVERSION args {major:?} {minor:?}")
+    };
+
+    let required = (*major as u8, *minor as u8);
+    if required > INTERPRETER_VERSION {
+        Some(Error::from_version_mismatch(
+            expr.clone(),
+            required,
+            INTERPRETER_VERSION,
+        ))
+    } else {
+        None
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Parse a component of the crate's version at compile time.
+///
+const fn parse_version(component: &str) -> u8 {
+    let bytes = component.as_bytes();
+    let mut value = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        value = value * 10 + (bytes[i] - b'0');
+        i += 1;
+    }
+
+    value
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Splice the body of each macro into the script wherever it's used by a USEMACRO, removing the
+/// DEFINE blocks the macros are defined in. Macros may be used before they're defined and within
+/// other macros. If a macro is defined more than once, the first definition is used.
+///
+/// # Returns
+/// The expanded script or an error for each USEMACRO of a macro that isn't defined or that's used
+/// within its own definition.
+///
+fn expand_macros(ast: Vec<ParsedExpr>) -> Result<Vec<ParsedExpr>, Vec<Error>> {
+    let mut macros = HashMap::new();
+    let mut script = Vec::new();
+    let mut definition: Option<(String, Vec<ParsedExpr>)> = None;
+
+    // Definitions are validated when parsed so every DEFINE is closed and none are nested.
+    for expr in ast {
+        match expr.expression() {
+            Expr::Define(arg) => {
+                definition = Some((macro_name(arg), Vec::new()));
+                continue;
+            }
+            Expr::EndDefine => {
+                if let Some((name, body)) = definition.take() {
+                    macros.entry(name).or_insert(body);
+                }
+                continue;
+            }
+            _ => (),
+        }
+
+        match definition.as_mut() {
+            Some((_, body)) => body.push(expr),
+            None => script.push(expr),
+        }
+    }
+
+    let mut expanded = Vec::new();
+    let mut errors = Vec::new();
+    expand_macro_uses(
+        &script,
+        &macros,
+        &mut Vec::new(),
+        &mut expanded,
+        &mut errors,
+    );
+
+    if errors.is_empty() {
+        Ok(expanded)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Append expressions to an expanded script, replacing each USEMACRO with the body of the macro.
+///
+/// # Arguments
+/// * `ast` - Expressions to expand.
+/// * `macros` - Body of each macro, by name.
+/// * `active` - Names of the macros currently being expanded, used to detect circular macros.
+/// * `expanded` - Expanded script to append to.
+/// * `errors` - Errors to append to for any macros that can't be expanded.
+///
+fn expand_macro_uses(
+    ast: &[ParsedExpr],
+    macros: &HashMap<String, Vec<ParsedExpr>>,
+    active: &mut Vec<String>,
+    expanded: &mut Vec<ParsedExpr>,
+    errors: &mut Vec<Error>,
+) {
+    for expr in ast {
+        let Expr::UseMacro(arg) = expr.expression() else {
+            expanded.push(expr.clone());
+            continue;
+        };
+
+        let name = macro_name(arg);
+        if active.contains(&name) {
+            errors.push(Error::from_circular_macro(expr.clone(), name));
+            continue;
+        }
+
+        let Some(body) = macros.get(&name) else {
+            errors.push(Error::from_undefined_macro(expr.clone(), name));
+            continue;
+        };
+
+        active.push(name);
+        expand_macro_uses(body, macros, active, expanded, errors);
+        active.pop();
+    }
+}
+
+/// Return the name given as the argument of a DEFINE or USEMACRO.
+///
+fn macro_name(arg: &ParsedExpr) -> String {
+    // Arguments are validated to be strings when parsed.
+    let Expr::String(name) = arg.expression() else {
+        unreachable!("Invalid macro name {arg:?}")
+    };
+
+    name.to_owned()
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Find the index of each LABEL within a script. Scripts are validated when parsed so labels are
+/// unique, except where a macro containing one is expanded more than once. The first definition is
+/// used in that case.
+///
+fn label_index(ast: &[ParsedExpr]) -> HashMap<String, usize> {
+    let mut labels = HashMap::new();
+    for (i, expr) in ast.iter().enumerate() {
+        if let Expr::Label(arg) = expr.expression() {
+            if let Expr::String(name) = arg.expression() {
+                labels.entry(name.to_owned()).or_insert(i);
+            }
+        }
+    }
+
+    labels
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Add a note to an error recording the last checkpoint reached, if any, so that it's clear how far
+/// through the script execution got. The script's filename is also recorded, if known.
+///
+fn with_context(error: Error, state: &EvalState, filename: Option<&str>) -> Error {
+    let error = match filename {
+        Some(filename) => error.with_filename(filename),
+        None => error,
+    };
+
+    match &state.last_checkpoint {
+        Some(name) => error.with_note(ErrorNote::Note(format!("Last checkpoint: {name}").into())),
+        None => error,
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Find the ENDIF closing a conditional. Scripts are validated when parsed so every conditional is
+/// guaranteed to have one.
+///
+/// # Arguments
+/// * `ast` - Script the conditional is part of.
+/// * `index` - Index of the conditional within the script.
+///
+/// # Returns
+/// The index of the matching ENDIF.
+///
+fn matching_endif(ast: &[ParsedExpr], index: usize) -> usize {
+    let mut depth = 0;
+    for (i, expr) in ast.iter().enumerate().skip(index + 1) {
+        match expr.expression() {
+            Expr::IfTestPass | Expr::IfTestFail => depth += 1,
+            Expr::EndIf if depth == 0 => return i,
+            Expr::EndIf => depth -= 1,
+            _ => (),
+        }
+    }
+
+    unreachable!("Conditional without a matching ENDIF")
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Find the ENDREPEAT closing a loop. Scripts are validated when parsed so every loop is guaranteed
+/// to have one.
+///
+/// # Arguments
+/// * `ast` - Script the loop is part of.
+/// * `index` - Index of the REPEAT within the script.
+///
+/// # Returns
+/// The index of the matching ENDREPEAT.
+///
+fn matching_endrepeat(ast: &[ParsedExpr], index: usize) -> usize {
+    let mut depth = 0;
+    for (i, expr) in ast.iter().enumerate().skip(index + 1) {
+        match expr.expression() {
+            Expr::Repeat(_) => depth += 1,
+            Expr::EndRepeat if depth == 0 => return i,
+            Expr::EndRepeat => depth -= 1,
+            _ => (),
+        }
+    }
+
+    unreachable!("REPEAT without a matching ENDREPEAT")
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Find the REPEAT starting a loop.
+///
+/// # Arguments
+/// * `ast` - Script the loop is part of.
+/// * `index` - Index of the ENDREPEAT within the script.
+///
+/// # Returns
+/// The index of the matching REPEAT.
+///
+fn matching_repeat(ast: &[ParsedExpr], index: usize) -> usize {
+    let mut depth = 0;
+    for (i, expr) in ast.iter().enumerate().take(index).rev() {
+        match expr.expression() {
+            Expr::EndRepeat => depth += 1,
+            Expr::Repeat(_) if depth == 0 => return i,
+            Expr::Repeat(_) => depth -= 1,
+            _ => (),
+        }
+    }
+
+    unreachable!("ENDREPEAT without a matching REPEAT")
+}
+
+////////////////////////////////////////////////////////////////
+// ...
+////////////////////////////////////////////////////////////////
+
+impl std::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Hooks({})", self.0.len())
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// comparison
+////////////////////////////////////////////////////////////////
+
+impl std::cmp::PartialEq for Interpreter {
+    fn eq(&self, other: &Self) -> bool {
+        // Only compare the script and the state of its execution. Errors can't be compared while
+        // the transaction log and any pending wait hold timestamps.
+        self.script == other.script
+            && self.ast == other.ast
+            && self.index == other.index
+            && self.state == other.state
+            && self.checkpoint == other.checkpoint
+            && self.labels == other.labels
+            && self.current == other.current
+            && self.pending_warning == other.pending_warning
+            && self.progress_reports == other.progress_reports
+            && self.progress_reported == other.progress_reported
+            && self.params == other.params
+            && self.error_policy == other.error_policy
+            && self.usb_print_limit == other.usb_print_limit
+            && self.max_commands == other.max_commands
+            && self.filename == other.filename
     }
 }
 
@@ -1,16 +1,31 @@
 mod error;
 mod execution;
+mod hooks;
 mod interpreter;
 mod syntax;
+mod transaction_log;
+
+#[cfg(feature = "serde")]
+pub mod serde_support;
 
 ////////////////////////////////////////////////////////////////
 // exports
 ////////////////////////////////////////////////////////////////
 
 pub use crate::{
-    error::Error,
-    execution::{Device, Dialog, FrontendRequest, Transaction, TransactionStatus},
-    interpreter::Interpreter,
+    error::{DiagnosticSeverity, Error, ErrorNote, ErrorReason},
+    execution::{
+        Device, Dialog, FailedTest, FrontendRequest, MeasurementFormat, MeasurementTest,
+        PendingTransaction, Transaction, TransactionPhase, TransactionStatus,
+    },
+    hooks::ScriptHooks,
+    interpreter::{normalize_script, ErrorPolicy, Interpreter, INTERPRETER_VERSION},
+    syntax::{
+        evaluate, format_script, highlight, line_col, parse_script as parse_from_str, tokenize,
+        Color, ColorScheme, CommentChar, EvalState, Expr, ExprKind, ParsedExpr, TimeZone, Token,
+        KEYWORDS,
+    },
+    transaction_log::{TransactionLog, TransactionRecord},
 };
 
 ////////////////////////////////////////////////////////////////
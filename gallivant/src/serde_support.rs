@@ -0,0 +1,33 @@
+//! Serialization of requests and transactions using serde, enabled by the `serde` feature e.g. to
+//! persist or replay the requests made by a script.
+
+pub use crate::{
+    execution::{
+        Device, Dialog, FailedTest, FrontendRequest, MeasurementFormat, MeasurementTest,
+        Transaction,
+    },
+    syntax::{Expr, ParsedExpr, TimeZone},
+};
+
+////////////////////////////////////////////////////////////////
+
+/// Serializes the span of a parsed expression as a `[start, end]` pair.
+///
+pub(crate) mod span {
+    use std::ops::Range;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(span: &Range<usize>, serializer: S) -> Result<S::Ok, S::Error> {
+        [span.start, span.end].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Range<usize>, D::Error> {
+        let [start, end] = <[usize; 2]>::deserialize(deserializer)?;
+        Ok(start..end)
+    }
+}
+
+////////////////////////////////////////////////////////////////
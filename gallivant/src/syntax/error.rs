@@ -34,8 +34,71 @@ pub enum ErrorReason {
     /// An argument value beyond limits.
     ArgValue {
         span: Span,
-        value: u32,
-        limits: (u32, u32),
+        value: i64,
+        limits: (i64, i64),
+    },
+
+    /// An ENDIF was found without a preceding IFTESTPASS or IFTESTFAIL.
+    UnmatchedEndIf {
+        span: Span,
+    },
+
+    /// An IFTESTPASS or IFTESTFAIL wasn't closed by an ENDIF.
+    UnclosedConditional {
+        span: Span,
+    },
+
+    /// An ENDREPEAT was found without a preceding REPEAT.
+    UnmatchedEndRepeat {
+        span: Span,
+    },
+
+    /// A REPEAT wasn't closed by an ENDREPEAT.
+    UnclosedRepeat {
+        span: Span,
+    },
+
+    /// A TESTGROUP was found within another test group.
+    NestedTestGroup {
+        span: Span,
+    },
+
+    /// An ENDTESTGROUP was found without a preceding TESTGROUP.
+    UnmatchedEndTestGroup {
+        span: Span,
+    },
+
+    /// A TESTGROUP wasn't closed by an ENDTESTGROUP.
+    UnclosedTestGroup {
+        span: Span,
+    },
+
+    /// A DEFINE was found within another macro definition.
+    NestedDefine {
+        span: Span,
+    },
+
+    /// An ENDDEFINE was found without a preceding DEFINE.
+    UnmatchedEndDefine {
+        span: Span,
+    },
+
+    /// A DEFINE wasn't closed by an ENDDEFINE.
+    UnclosedDefine {
+        span: Span,
+    },
+
+    /// A LABEL was given the same name as an earlier LABEL.
+    DuplicateLabel {
+        span: Span,
+        first: Span,
+    },
+
+    /// The first value of a range was greater than the last.
+    InvalidRange {
+        span: Span,
+        first: u32,
+        last: u32,
     },
 }
 
@@ -89,6 +152,43 @@ impl Error {
         }
     }
 
+    /// Create a new error resulting from arguments being separated by whitespace instead of a
+    /// comma.
+    ///
+    /// # Arguments
+    /// * `span` - Area in the input that the error occured i.e. the argument following the missing
+    ///   comma.
+    /// * `found` - Kind of argument found in place of the comma.
+    ///
+    pub fn missing_comma(span: Span, found: ExprKind) -> Self {
+        Self {
+            reason: ErrorReason::ArgType {
+                span,
+                expected: vec![","],
+                found: found.name(),
+            },
+            notes: Vec::new(),
+        }
+    }
+
+    /// Create a new error resulting from a hex literal containing characters that aren't hex
+    /// digits.
+    ///
+    /// # Arguments
+    /// * `span` - Area in the input that the error occured i.e. the whole literal, including the
+    ///   leading '$'.
+    ///
+    pub fn invalid_hex(span: Span) -> Self {
+        Self {
+            reason: ErrorReason::ArgType {
+                span,
+                expected: vec![ExprKind::UInt.name()],
+                found: "Invalid Hex Integer",
+            },
+            notes: Vec::new(),
+        }
+    }
+
     /// Create a new error resulting from an arguments value being outside of limits.
     ///
     /// # Arguments
@@ -96,7 +196,7 @@ impl Error {
     /// * `value` - Found argument value.
     /// * `limits` - Minumum and maximum value allowed for the argument.
     ///
-    pub fn argument_value_size(span: Span, value: u32, limits: (u32, u32)) -> Self {
+    pub fn argument_value_size(span: Span, value: i64, limits: (i64, i64)) -> Self {
         debug_assert!(limits.0 <= limits.1);
 
         Self {
@@ -108,6 +208,103 @@ impl Error {
             notes: Vec::new(),
         }
     }
+
+    pub fn unmatched_endif(span: Span) -> Self {
+        Self {
+            reason: ErrorReason::UnmatchedEndIf { span },
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn unclosed_conditional(span: Span) -> Self {
+        Self {
+            reason: ErrorReason::UnclosedConditional { span },
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn unmatched_end_repeat(span: Span) -> Self {
+        Self {
+            reason: ErrorReason::UnmatchedEndRepeat { span },
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn unclosed_repeat(span: Span) -> Self {
+        Self {
+            reason: ErrorReason::UnclosedRepeat { span },
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn nested_test_group(span: Span) -> Self {
+        Self {
+            reason: ErrorReason::NestedTestGroup { span },
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn unmatched_end_test_group(span: Span) -> Self {
+        Self {
+            reason: ErrorReason::UnmatchedEndTestGroup { span },
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn unclosed_test_group(span: Span) -> Self {
+        Self {
+            reason: ErrorReason::UnclosedTestGroup { span },
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn nested_define(span: Span) -> Self {
+        Self {
+            reason: ErrorReason::NestedDefine { span },
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn unmatched_end_define(span: Span) -> Self {
+        Self {
+            reason: ErrorReason::UnmatchedEndDefine { span },
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn unclosed_define(span: Span) -> Self {
+        Self {
+            reason: ErrorReason::UnclosedDefine { span },
+            notes: Vec::new(),
+        }
+    }
+
+    /// Create a new error resulting from a LABEL with the same name as an earlier LABEL.
+    ///
+    /// # Arguments
+    /// * `span` - Area in the input that the error occured i.e. the duplicate LABEL.
+    /// * `first` - Area in the input of the earlier LABEL.
+    ///
+    pub fn duplicate_label(span: Span, first: Span) -> Self {
+        Self {
+            reason: ErrorReason::DuplicateLabel { span, first },
+            notes: Vec::new(),
+        }
+    }
+
+    /// Create a new error resulting from a range whose first value is greater than its last.
+    ///
+    /// # Arguments
+    /// * `span` - Area in the input that the error occured i.e. the whole range.
+    /// * `first` - First value of the range.
+    /// * `last` - Last value of the range.
+    ///
+    pub fn invalid_range(span: Span, first: u32, last: u32) -> Self {
+        Self {
+            reason: ErrorReason::InvalidRange { span, first, last },
+            notes: Vec::new(),
+        }
+    }
 }
 
 ////////////////////////////////////////////////////////////////
@@ -142,6 +339,30 @@ impl std::fmt::Display for Error {
 ////////////////////////////////////////////////////////////////
 
 impl ErrorReason {
+    /// Return the area of the input that the error occured in, if known.
+    ///
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            ErrorReason::Unexpected { span, .. } => Some(span),
+            ErrorReason::Unclosed => None,
+            ErrorReason::UnrecognisedCommand { span } => Some(span),
+            ErrorReason::ArgType { span, .. } => Some(span),
+            ErrorReason::ArgValue { span, .. } => Some(span),
+            ErrorReason::UnmatchedEndIf { span } => Some(span),
+            ErrorReason::UnclosedConditional { span } => Some(span),
+            ErrorReason::UnmatchedEndRepeat { span } => Some(span),
+            ErrorReason::UnclosedRepeat { span } => Some(span),
+            ErrorReason::NestedTestGroup { span } => Some(span),
+            ErrorReason::UnmatchedEndTestGroup { span } => Some(span),
+            ErrorReason::UnclosedTestGroup { span } => Some(span),
+            ErrorReason::NestedDefine { span } => Some(span),
+            ErrorReason::UnmatchedEndDefine { span } => Some(span),
+            ErrorReason::UnclosedDefine { span } => Some(span),
+            ErrorReason::DuplicateLabel { span, .. } => Some(span),
+            ErrorReason::InvalidRange { span, .. } => Some(span),
+        }
+    }
+
     pub fn message(&self) -> &'static str {
         match self {
             ErrorReason::Unexpected { .. } => "Unexpected token",
@@ -149,6 +370,20 @@ impl ErrorReason {
             ErrorReason::UnrecognisedCommand { .. } => "Unrecognised command found",
             ErrorReason::ArgType { .. } => "Invalid argument type",
             ErrorReason::ArgValue { .. } => "Argument value exceeds limits",
+            ErrorReason::UnmatchedEndIf { .. } => "ENDIF without a matching conditional",
+            ErrorReason::UnclosedConditional { .. } => "Conditional without a matching ENDIF",
+            ErrorReason::UnmatchedEndRepeat { .. } => "ENDREPEAT without a matching REPEAT",
+            ErrorReason::UnclosedRepeat { .. } => "REPEAT without a matching ENDREPEAT",
+            ErrorReason::NestedTestGroup { .. } => "Test groups cannot be nested",
+            ErrorReason::UnmatchedEndTestGroup { .. } => {
+                "ENDTESTGROUP without a matching TESTGROUP"
+            }
+            ErrorReason::UnclosedTestGroup { .. } => "TESTGROUP without a matching ENDTESTGROUP",
+            ErrorReason::NestedDefine { .. } => "Macro definitions cannot be nested",
+            ErrorReason::UnmatchedEndDefine { .. } => "ENDDEFINE without a matching DEFINE",
+            ErrorReason::UnclosedDefine { .. } => "DEFINE without a matching ENDDEFINE",
+            ErrorReason::DuplicateLabel { .. } => "Label is already defined",
+            ErrorReason::InvalidRange { .. } => "Range is empty",
         }
     }
 
@@ -240,6 +475,83 @@ impl ErrorReason {
                         .with_priority(9),
                 ]
             }
+
+            ErrorReason::UnmatchedEndIf { span } => {
+                vec![Label::new(span.clone())
+                    .with_message("No IFTESTPASS or IFTESTFAIL precedes this ENDIF")
+                    .with_priority(10)]
+            }
+
+            ErrorReason::UnclosedConditional { span } => {
+                vec![Label::new(span.clone())
+                    .with_message("No ENDIF follows this conditional")
+                    .with_priority(10)]
+            }
+
+            ErrorReason::UnmatchedEndRepeat { span } => {
+                vec![Label::new(span.clone())
+                    .with_message("No REPEAT precedes this ENDREPEAT")
+                    .with_priority(10)]
+            }
+
+            ErrorReason::UnclosedRepeat { span } => {
+                vec![Label::new(span.clone())
+                    .with_message("No ENDREPEAT follows this REPEAT")
+                    .with_priority(10)]
+            }
+
+            ErrorReason::NestedTestGroup { span } => {
+                vec![Label::new(span.clone())
+                    .with_message("This TESTGROUP is within another test group")
+                    .with_priority(10)]
+            }
+
+            ErrorReason::UnmatchedEndTestGroup { span } => {
+                vec![Label::new(span.clone())
+                    .with_message("No TESTGROUP precedes this ENDTESTGROUP")
+                    .with_priority(10)]
+            }
+
+            ErrorReason::UnclosedTestGroup { span } => {
+                vec![Label::new(span.clone())
+                    .with_message("No ENDTESTGROUP follows this TESTGROUP")
+                    .with_priority(10)]
+            }
+
+            ErrorReason::NestedDefine { span } => {
+                vec![Label::new(span.clone())
+                    .with_message("This DEFINE is within another macro definition")
+                    .with_priority(10)]
+            }
+
+            ErrorReason::UnmatchedEndDefine { span } => {
+                vec![Label::new(span.clone())
+                    .with_message("No DEFINE precedes this ENDDEFINE")
+                    .with_priority(10)]
+            }
+
+            ErrorReason::UnclosedDefine { span } => {
+                vec![Label::new(span.clone())
+                    .with_message("No ENDDEFINE follows this DEFINE")
+                    .with_priority(10)]
+            }
+
+            ErrorReason::DuplicateLabel { span, first } => {
+                vec![
+                    Label::new(span.clone())
+                        .with_message("This LABEL has the same name as an earlier one")
+                        .with_priority(10),
+                    Label::new(first.clone())
+                        .with_message("First defined here")
+                        .with_priority(9),
+                ]
+            }
+
+            ErrorReason::InvalidRange { span, first, last } => {
+                vec![Label::new(span.clone())
+                    .with_message(format!("Range starts at {first} but ends at {last}"))
+                    .with_priority(10)]
+            }
         }
     }
 }
@@ -247,9 +559,21 @@ impl ErrorReason {
 ////////////////////////////////////////////////////////////////
 
 impl Error {
-    pub fn to_report(&self) -> Report {
+    /// Create a report for the error. The report's message is prefixed with the line and column
+    /// the error occured at so that the location is available even where the report's source
+    /// snippet isn't rendered.
+    ///
+    /// # Arguments
+    /// * `source` - Script that the error was generated from.
+    ///
+    pub fn to_report(&self, source: &str) -> Report<'_> {
+        let message = match self.reason.span() {
+            Some(span) => with_location(self.reason.message(), span, source),
+            None => self.reason.message().to_owned(),
+        };
+
         let mut report = Report::build(ReportKind::Error, (), 0)
-            .with_message(self.reason.message())
+            .with_message(message)
             .with_labels(self.reason.labels());
 
         for note in self.notes.iter() {
@@ -265,6 +589,38 @@ impl Error {
 
 ////////////////////////////////////////////////////////////////
 
+/// Find the line and column that a span starts at within the source it was taken from.
+///
+/// # Arguments
+/// * `span` - Area in the source. Spans count characters, not bytes.
+/// * `source` - Source the span was taken from.
+///
+/// # Returns
+/// The line and column numbers, both starting from 1.
+///
+pub fn line_col(span: &Span, source: &str) -> (usize, usize) {
+    let preceding = source.chars().take(span.start);
+
+    preceding.fold((1, 1), |(line, column), c| {
+        if c == '\n' {
+            (line + 1, 1)
+        } else {
+            (line, column + 1)
+        }
+    })
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Prefix a message with the line and column that a span starts at.
+///
+pub(crate) fn with_location(message: &str, span: &Span, source: &str) -> String {
+    let (line, column) = line_col(span, source);
+    format!("line {line}, column {column}: {message}")
+}
+
+////////////////////////////////////////////////////////////////
+
 impl std::error::Error for Error {}
 
 ////////////////////////////////////////////////////////////////
@@ -289,7 +645,7 @@ impl chumsky::error::Error<char> for Error {
     }
 
     fn with_label(mut self, label: Self::Label) -> Self {
-        self.notes.push(ErrorNote::Note(label));
+        self.notes.push(ErrorNote::Note(label.into()));
         self
     }
 
@@ -299,3 +655,45 @@ impl chumsky::error::Error<char> for Error {
 }
 
 ////////////////////////////////////////////////////////////////
+// tests
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_line_col_first_line() {
+        assert_eq!(line_col(&(0..1), "TCUCLOSE 256"), (1, 1));
+        assert_eq!(line_col(&(9..12), "TCUCLOSE 256"), (1, 10));
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_line_col_later_line() {
+        let source = "HPMODE\nFLUSH\n  TCUCLOSE 256";
+        assert_eq!(line_col(&(15..16), source), (3, 3));
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_report_message_location() {
+        let source = "HPMODE\nTCUCLOSE 256";
+        let error = Error::argument_value_size(16..19, 256, (0, 255));
+
+        let mut buffer = Vec::new();
+        error
+            .to_report(source)
+            .write_for_stdout(ariadne::Source::from(source), &mut buffer)
+            .unwrap();
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert!(output.contains("line 2, column 10: Argument value exceeds limits"));
+    }
+}
+
+////////////////////////////////////////////////////////////////
@@ -1,19 +1,27 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use chrono::{Datelike, Local, Timelike};
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, Timelike, Utc};
 
 use crate::{
     error::Error,
-    execution::{Dialog, FrontendRequest, MeasurementTest, Transaction},
+    execution::{Dialog, FailedTest, FrontendRequest, MeasurementTest, Transaction},
+    syntax,
 };
 
 use super::{
     expression::{Expr, ParsedExpr},
-    state::EvalState,
+    state::{EvalState, TimeZone},
 };
 
 ////////////////////////////////////////////////////////////////
 
+/// Maximum number of bytes a single PRINT may send. Each is sent as 2 hex characters and the total
+/// number of characters is sent as a single byte.
+///
+const PRINT_LIMIT: usize = 127;
+
+////////////////////////////////////////////////////////////////
+
 /// Format a byte into a hex representation using ascii characters. Return those characters as
 /// bytes.
 ///
@@ -23,39 +31,309 @@ fn tcu_format_byte(byte: u8) -> Vec<u8> {
 
 ////////////////////////////////////////////////////////////////
 
+/// Convert the current time into the time zone that the script has selected.
+///
+/// # Arguments
+/// * `timezone` - Time zone selected by the script.
+/// * `now` - Current time.
+///
+fn current_time(timezone: TimeZone, now: DateTime<Utc>) -> NaiveDateTime {
+    match timezone {
+        TimeZone::Local => now.with_timezone(&Local).naive_local(),
+        TimeZone::Utc => now.naive_utc(),
+    }
+}
+
+/// Format a date and time in the form printers expect when setting their clock i.e.
+/// hh:mm:ss,DD/MM/YY.
+///
+fn format_datetime(datetime: NaiveDateTime) -> String {
+    format!(
+        "{:02}:{:02}:{:02},{:02}/{:02}/{:02}",
+        datetime.hour(),
+        datetime.minute(),
+        datetime.second(),
+        datetime.day(),
+        datetime.month(),
+        datetime.year().rem_euclid(100)
+    )
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Return the value of a measurement test bound, which may be negative.
+///
+fn measurement_bound(expr: &Expr) -> Option<i64> {
+    match expr {
+        Expr::UInt(uint) => Some(i64::from(*uint)),
+        Expr::SInt(sint) => Some(i64::from(*sint)),
+        _ => None,
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Resolve the value of an unsigned integer argument, which may either be a literal or a reference
+/// to a variable.
+///
+/// # Arguments
+/// * `arg` - Argument to resolve.
+/// * `state` - State containing the variables.
+///
+/// # Returns
+/// The argument's value or an error if it references a variable that isn't defined.
+///
+fn resolve_uint(arg: &ParsedExpr, state: &EvalState) -> Result<u32, Error> {
+    match arg.expression() {
+        Expr::UInt(uint) => Ok(*uint),
+        Expr::Variable(name) => state
+            .variables
+            .get(name)
+            .copied()
+            .ok_or_else(|| Error::from_undefined_variable(arg.clone(), name.to_owned())),
+        _ => panic!("Invalid uint arg {arg:?}"),
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Resolve the value of a byte argument, which may either be a literal or a reference to a
+/// variable. Literals are checked to be within range when parsing but variables can only be checked
+/// here.
+///
+/// # Arguments
+/// * `arg` - Argument to resolve.
+/// * `state` - State containing the variables.
+///
+/// # Returns
+/// The argument's value or an error if it references a variable that isn't defined or whose value
+/// is greater than 255.
+///
+fn resolve_byte(arg: &ParsedExpr, state: &EvalState) -> Result<u8, Error> {
+    let value = resolve_uint(arg, state)?;
+    u8::try_from(value)
+        .map_err(|_| Error::from_arg_out_of_range(arg.to_owned(), value.into(), (0, 255)))
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Resolve the value of a channel argument, which may be a literal, a channel named by
+/// DEFINECHANNEL or a reference to a variable. Channel names take precedence over variables of the
+/// same name.
+///
+/// # Arguments
+/// * `arg` - Argument to resolve.
+/// * `state` - State containing the channel names and variables.
+///
+/// # Returns
+/// The channel or an error if it can't be resolved as a byte.
+///
+fn resolve_channel(arg: &ParsedExpr, state: &EvalState) -> Result<u8, Error> {
+    if let Expr::Variable(name) = arg.expression() {
+        if let Some(channel) = state.channels.get(name) {
+            return Ok(*channel);
+        }
+    }
+
+    resolve_byte(arg, state)
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Resolve the option argument of a SETOPTION or USBSETOPTION and check that it's within the range
+/// set by SETOPTION_RANGE. Any byte is allowed if no range has been set.
+///
+/// # Arguments
+/// * `arg` - Argument to resolve.
+/// * `state` - State containing the variables and option range.
+///
+/// # Returns
+/// The argument's value or an error if it can't be resolved as a byte or is outside of the range.
+///
+fn resolve_option(arg: &ParsedExpr, state: &EvalState) -> Result<u8, Error> {
+    let option = resolve_byte(arg, state)?;
+
+    match &state.option_range {
+        Some(range) if !range.contains(&option) => Err(Error::from_arg_out_of_range(
+            arg.to_owned(),
+            option.into(),
+            ((*range.start()).into(), (*range.end()).into()),
+        )),
+        _ => Ok(option),
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Apply an arithmetic operation to two sources and store the result in a variable.
+///
+/// # Arguments
+/// * `expr` - Arithmetic expression being evaluated. Used to locate any error.
+/// * `dest` - Variable to store the result in.
+/// * `sources` - Arguments to apply the operation to.
+/// * `state` - State containing the variables.
+/// * `operation` - Operation to apply. Returns None if the result would overflow.
+///
+fn arithmetic(
+    expr: &ParsedExpr,
+    dest: &ParsedExpr,
+    sources: (u32, u32),
+    state: &mut EvalState,
+    operation: fn(u32, u32) -> Option<u32>,
+) -> Result<FrontendRequest, Error> {
+    let Expr::Variable(name) = dest.expression() else {
+        panic!("Invalid arithmetic destination {dest:?}")
+    };
+
+    let result = operation(sources.0, sources.1)
+        .ok_or_else(|| Error::from_arithmetic_overflow(expr.clone()))?;
+
+    state.set_variable(name, result)?;
+    Ok(FrontendRequest::None)
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Replace any references to variables within a string, in the form `${NAME}`, with the variable's
+/// value. `$$` is replaced with a single `$`. Any other `$` is left as is so that strings such as
+/// "$FF" are unchanged.
+///
+/// # Arguments
+/// * `string` - String to interpolate.
+/// * `expr` - Expression the string belongs to. Used to locate any error.
+/// * `state` - State containing the variables.
+///
+/// # Returns
+/// The interpolated string or an error if a referenced variable isn't defined.
+///
+fn interpolate(string: &str, expr: &ParsedExpr, state: &EvalState) -> Result<String, Error> {
+    let mut output = String::with_capacity(string.len());
+    let mut remaining = string;
+
+    while let Some(position) = remaining.find('$') {
+        output.push_str(&remaining[..position]);
+        let after = &remaining[position + 1..];
+
+        if let Some(after) = after.strip_prefix('$') {
+            output.push('$');
+            remaining = after;
+            continue;
+        }
+
+        let reference = after
+            .strip_prefix('{')
+            .and_then(|after| after.split_once('}'))
+            .filter(|(name, _)| is_variable_name(name));
+
+        match reference {
+            Some((name, after)) => {
+                let Some(value) = state.variables.get(name) else {
+                    return Err(Error::from_undefined_variable(
+                        expr.clone(),
+                        name.to_owned(),
+                    ));
+                };
+
+                output.push_str(&value.to_string());
+                remaining = after;
+            }
+            None => {
+                output.push('$');
+                remaining = after;
+            }
+        }
+    }
+
+    output.push_str(remaining);
+    Ok(output)
+}
+
+/// Return true if a name could be a variable referenced by a script i.e. an identifier.
+///
+fn is_variable_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Create a transaction with the TCU, expecting an echo unless disabled by SETNOECHO ON.
+///
+fn tcu_transaction(
+    state: &EvalState,
+    expression: ParsedExpr,
+    bytes: Vec<u8>,
+    test: Option<MeasurementTest>,
+) -> Transaction {
+    if state.echo_expected() {
+        Transaction::with_tcu(expression, bytes, test)
+    } else {
+        Transaction::with_tcu_no_echo(expression, bytes, test)
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
 pub fn evaluate(expr: &ParsedExpr, state: &mut EvalState) -> Result<FrontendRequest, Error> {
     match expr.expression() {
         Expr::String(_) => panic!("Orphaned String"),
         Expr::UInt(_) => panic!("Orphaned UInt"),
+        Expr::SInt(_) => panic!("Orphaned SInt"),
+        Expr::Variable(_) => panic!("Orphaned Variable"),
 
         Expr::ScriptComment(_) => Ok(FrontendRequest::None),
 
+        Expr::SetMeasurementFormat(format) => {
+            state.measurement_format = *format;
+            Ok(FrontendRequest::None)
+        }
+
+        Expr::SetTimeZone(timezone) => {
+            state.timezone = *timezone;
+            Ok(FrontendRequest::None)
+        }
+
         Expr::HPMode => {
             state.hpmode = !state.hpmode;
             Ok(FrontendRequest::None)
         }
-        Expr::Comment(arg) => {
-            if let Expr::String(str) = arg.expression() {
-                return Ok(FrontendRequest::GuiPrint(str.to_owned()));
+
+        Expr::Echo(arg) => match arg.expression() {
+            Expr::String(str) => Ok(FrontendRequest::GuiPrint(interpolate(str, arg, state)?)),
+            Expr::Variable(name) => {
+                let value = resolve_uint(arg, state)?;
+                Ok(FrontendRequest::GuiPrint(format!(
+                    "{name} = {value} (${value:04X})"
+                )))
+            }
+            _ => panic!("Invalid ECHO arg {arg:?}"),
+        },
+
+        Expr::Comment(args) => {
+            let mut message = String::new();
+            for arg in args {
+                match arg.expression() {
+                    Expr::String(str) => message.push_str(&interpolate(str, arg, state)?),
+                    Expr::UInt(uint) => message.push_str(&uint.to_string()),
+                    Expr::SInt(sint) => message.push_str(&sint.to_string()),
+                    _ => panic!("Invalid COMMENT arg {arg:?}"),
+                }
             }
 
-            panic!("Invalid COMMENT arg {:?}", arg);
+            Ok(FrontendRequest::GuiPrint(message))
         }
 
         Expr::Wait(arg) => {
-            if let Expr::UInt(milliseconds) = arg.expression() {
-                return Ok(FrontendRequest::Wait(Duration::from_millis(
-                    (*milliseconds).into(),
-                )));
-            }
-
-            panic!("Invalid WAIT arg {:?}", arg);
+            let milliseconds = resolve_uint(arg, state)?;
+            Ok(FrontendRequest::Wait(Duration::from_millis(
+                milliseconds.into(),
+            )))
         }
 
         Expr::OpenDialog(arg) => {
             if let Expr::String(message) = arg.expression() {
                 let kind = Dialog::Notification;
-                let message = message.to_owned();
+                let message = interpolate(message, arg, state)?;
                 return Ok(FrontendRequest::GuiDialogue { kind, message });
             }
 
@@ -65,83 +343,106 @@ pub fn evaluate(expr: &ParsedExpr, state: &mut EvalState) -> Result<FrontendRequ
         Expr::WaitDialog(arg) => {
             if let Expr::String(message) = arg.expression() {
                 let kind = Dialog::ManualInput;
-                let message = message.to_owned();
+                let message = interpolate(message, arg, state)?;
                 return Ok(FrontendRequest::GuiDialogue { kind, message });
             }
 
             panic!("Invalid WAITDIALOG arg {:?}", arg);
         }
 
+        Expr::Notify { key, value } => {
+            let Expr::String(name) = key.expression() else {
+                panic!("Invalid NOTIFY key {key:?}");
+            };
+
+            Ok(FrontendRequest::Notification {
+                key: interpolate(name, key, state)?,
+                value: resolve_uint(value, state)?,
+            })
+        }
+
         Expr::Flush => Ok(FrontendRequest::TCUFlush),
+        Expr::SetBaud(arg) => Ok(FrontendRequest::SetBaud(resolve_uint(arg, state)?)),
         Expr::Protocol => Ok(FrontendRequest::None),
 
         Expr::Print(args) => {
             let mut arg_bytes = Vec::new();
             for arg in args {
                 if let Expr::String(str) = arg.expression() {
-                    arg_bytes.extend_from_slice(str.as_bytes());
+                    arg_bytes.extend_from_slice(interpolate(str, arg, state)?.as_bytes());
                 } else if let Expr::UInt(uint) = arg.expression() {
                     debug_assert!(*uint <= 255);
                     arg_bytes.push(*uint as u8);
+                } else if let Expr::Variable(_) = arg.expression() {
+                    arg_bytes.push(resolve_byte(arg, state)?);
                 } else {
                     panic!("Invalid PRINT arg {arg:?}")
                 }
             }
 
-            // Each byte needs to be transformed into an ascii hex representation.
-            let arg_bytes: Vec<u8> = arg_bytes.into_iter().flat_map(tcu_format_byte).collect();
-
-            if arg_bytes.len() > 255 {
-                todo!(
-                    "Print command is limited to 255 args due to requirement to transmit the number
-                    of args as a byte. Need to return an error here."
-                );
+            // The number of bytes is transmitted as a single byte once each byte has been
+            // transformed into a 2 character ascii hex representation.
+            if arg_bytes.len() > PRINT_LIMIT {
+                let size = arg_bytes.len() as i64;
+                return Err(syntax::Error::argument_value_size(
+                    expr.span().clone(),
+                    size,
+                    (0, PRINT_LIMIT as i64),
+                )
+                .into());
             }
 
+            let arg_bytes: Vec<u8> = arg_bytes.into_iter().flat_map(tcu_format_byte).collect();
+
             let mut bytes = vec![b'P'];
             bytes.extend_from_slice(&tcu_format_byte(arg_bytes.len() as u8));
             bytes.extend_from_slice(&arg_bytes);
             bytes.push(b'\r');
 
-            Ok(FrontendRequest::TCUTransact(Transaction::with_tcu(
+            Ok(FrontendRequest::TCUTransact(tcu_transaction(
+                state,
                 expr.to_owned(),
                 bytes,
                 None,
             )))
         }
 
-        Expr::SetTimeFormat(arg) => {
-            if let Expr::UInt(uint) = arg.expression() {
-                let mut bytes = if state.hpmode {
-                    Vec::from("P051B007466".as_bytes())
-                } else {
-                    Vec::from("P051B7466".as_bytes())
-                };
+        Expr::PrintRaw(args) => {
+            let mut bytes = args
+                .iter()
+                .map(|arg| resolve_byte(arg, state))
+                .collect::<Result<Vec<u8>, Error>>()?;
+            bytes.push(b'\r');
 
-                bytes.extend_from_slice(&tcu_format_byte(*uint as u8));
-                bytes.push(b'\r');
+            Ok(FrontendRequest::TCUTransact(tcu_transaction(
+                state,
+                expr.to_owned(),
+                bytes,
+                None,
+            )))
+        }
 
-                return Ok(FrontendRequest::TCUTransact(Transaction::with_tcu(
-                    expr.to_owned(),
-                    bytes,
-                    None,
-                )));
-            }
+        Expr::SetTimeFormat(arg) => {
+            let format = resolve_byte(arg, state)?;
+            let mut bytes = if state.hpmode {
+                Vec::from("P051B007466".as_bytes())
+            } else {
+                Vec::from("P051B7466".as_bytes())
+            };
+
+            bytes.extend_from_slice(&tcu_format_byte(format));
+            bytes.push(b'\r');
 
-            panic!()
+            Ok(FrontendRequest::TCUTransact(tcu_transaction(
+                state,
+                expr.to_owned(),
+                bytes,
+                None,
+            )))
         }
 
         Expr::SetTime => {
-            let datetime = Local::now();
-            let datetime = format!(
-                "{:02}:{:02}:{:02},{:02}/{:02}/{:02}",
-                datetime.hour(),
-                datetime.minute(),
-                datetime.second(),
-                datetime.day(),
-                datetime.month(),
-                (datetime.year() - 1900) % 100
-            );
+            let datetime = format_datetime(current_time(state.timezone, Utc::now()));
 
             let datetime = datetime.into_bytes().into_iter().flat_map(tcu_format_byte);
 
@@ -154,7 +455,8 @@ pub fn evaluate(expr: &ParsedExpr, state: &mut EvalState) -> Result<FrontendRequ
             bytes.extend(datetime);
             bytes.push(b'\r');
 
-            Ok(FrontendRequest::TCUTransact(Transaction::with_tcu(
+            Ok(FrontendRequest::TCUTransact(tcu_transaction(
+                state,
                 expr.to_owned(),
                 bytes,
                 None,
@@ -162,110 +464,259 @@ pub fn evaluate(expr: &ParsedExpr, state: &mut EvalState) -> Result<FrontendRequ
         }
 
         Expr::SetOption { option, setting } => {
-            if let (Expr::UInt(option), Expr::UInt(setting)) =
-                (option.expression(), setting.expression())
-            {
-                debug_assert!(*option <= 255);
-                debug_assert!(*setting <= 255);
+            let option = resolve_option(option, state)?;
+            let setting = resolve_byte(setting, state)?;
 
-                let bytes = if state.hpmode {
-                    format!("P061B00004F{:02X}{:02X}\r", option, setting).into_bytes()
-                } else {
-                    format!("P061B004F{:02X}{:02X}\r", option, setting).into_bytes()
-                };
+            let bytes = if state.hpmode {
+                format!("P061B00004F{:02X}{:02X}\r", option, setting).into_bytes()
+            } else {
+                format!("P061B004F{:02X}{:02X}\r", option, setting).into_bytes()
+            };
 
-                return Ok(FrontendRequest::TCUTransact(Transaction::with_tcu(
-                    expr.to_owned(),
-                    bytes,
-                    None,
-                )));
+            Ok(FrontendRequest::TCUTransact(tcu_transaction(
+                state,
+                expr.to_owned(),
+                bytes,
+                None,
+            )))
+        }
+
+        Expr::SetOptionRange { min, max } => {
+            let (min, max) = (resolve_byte(min, state)?, resolve_byte(max, state)?);
+            if min > max {
+                let error =
+                    syntax::Error::invalid_range(expr.span().clone(), min.into(), max.into());
+                return Err(error.into());
             }
 
-            panic!("Invalid SETOPTION args {option:?}, {setting:?}")
+            state.option_range = Some(min..=max);
+            Ok(FrontendRequest::None)
         }
 
         Expr::TCUClose(arg) => {
-            if let Expr::UInt(relay) = arg.expression() {
-                debug_assert!(*relay <= 255);
-                return Ok(FrontendRequest::TCUTransact(Transaction::with_tcu(
-                    expr.to_owned(),
-                    format!("C{:02X}\r", relay).into_bytes(),
-                    None,
-                )));
-            }
-
-            panic!("Invalid TCUCLOSE arg {arg:?}")
+            let relay = resolve_byte(arg, state)?;
+            Ok(FrontendRequest::TCUTransact(tcu_transaction(
+                state,
+                expr.to_owned(),
+                format!("C{:02X}\r", relay).into_bytes(),
+                None,
+            )))
         }
 
         Expr::TCUOpen(arg) => {
-            if let Expr::UInt(relay) = arg.expression() {
-                debug_assert!(*relay <= 255);
-                return Ok(FrontendRequest::TCUTransact(Transaction::with_tcu(
-                    expr.to_owned(),
-                    format!("O{:02X}\r", relay).into_bytes(),
-                    None,
-                )));
-            }
-
-            panic!("Invalid TCUOPEN arg {arg:?}")
+            let relay = resolve_byte(arg, state)?;
+            Ok(FrontendRequest::TCUTransact(tcu_transaction(
+                state,
+                expr.to_owned(),
+                format!("O{:02X}\r", relay).into_bytes(),
+                None,
+            )))
         }
 
+        // Whether a failed test produces a warning is handled by the interpreter.
         Expr::TCUTest {
             channel,
             min,
             max,
             retries,
             message,
+        }
+        | Expr::WarnTest {
+            channel,
+            min,
+            max,
+            retries,
+            message,
         } => {
+            let channel = resolve_channel(channel, state)?;
+            let retries = resolve_uint(retries, state)?;
             let args = (
-                channel.expression(),
-                min.expression(),
-                max.expression(),
-                retries.expression(),
+                measurement_bound(min.expression()),
+                measurement_bound(max.expression()),
                 message.expression(),
             );
-            if let (
-                Expr::UInt(channel),
-                Expr::UInt(min),
-                Expr::UInt(max),
-                Expr::UInt(retries),
-                Expr::String(message),
-            ) = args
-            {
-                debug_assert!(*channel <= 255);
-
-                return Ok(FrontendRequest::TCUTransact(Transaction::with_tcu(
+            if let (Some(min), Some(max), Expr::String(message)) = args {
+                return Ok(FrontendRequest::TCUTransact(tcu_transaction(
+                    state,
                     expr.clone(),
                     format!("M{channel:02X}\r").into_bytes(),
                     Some(MeasurementTest {
-                        expected: *min..=*max,
-                        retries: *retries,
-                        failure_message: message.to_owned(),
+                        expected: min..=max,
+                        retries,
+                        failure_message: interpolate(message, expr, state)?,
+                        mask: None,
+                        format: state.measurement_format,
+                        max_std_dev: None,
+                        samples: Vec::new(),
                     }),
                 )));
             }
 
-            panic!("Invalid TCUTEST args {channel:?}, {min:?}, {max:?}, {retries:?}, {message:?}")
+            panic!("Invalid TCUTEST args {min:?}, {max:?}, {message:?}")
         }
 
-        Expr::PrinterSet(arg) => {
-            if let Expr::UInt(channel) = arg.expression() {
-                debug_assert!(*channel <= 255);
+        Expr::MaskTest {
+            channel,
+            mask,
+            expected,
+            retries,
+            message,
+        } => {
+            let channel = resolve_channel(channel, state)?;
+            let mask = resolve_uint(mask, state)?;
+            let expected = resolve_uint(expected, state)?;
+            let retries = resolve_uint(retries, state)?;
+            if let Expr::String(message) = message.expression() {
+                return Ok(FrontendRequest::TCUTransact(tcu_transaction(
+                    state,
+                    expr.clone(),
+                    format!("M{channel:02X}\r").into_bytes(),
+                    Some(MeasurementTest {
+                        expected: i64::from(expected)..=i64::from(expected),
+                        retries,
+                        failure_message: interpolate(message, expr, state)?,
+                        mask: Some(mask),
+                        format: state.measurement_format,
+                        max_std_dev: None,
+                        samples: Vec::new(),
+                    }),
+                )));
+            }
 
-                let bytes = if state.hpmode {
-                    format!("P051B000053{:02X}\r", channel).into_bytes()
-                } else {
-                    format!("P051B0053{:02X}\r", channel).into_bytes()
-                };
+            panic!("Invalid MASKTEST arg {message:?}")
+        }
 
-                return Ok(FrontendRequest::TCUTransact(Transaction::with_tcu(
-                    expr.to_owned(),
-                    bytes,
-                    None,
+        Expr::TCUExact {
+            channel,
+            expected,
+            retries,
+            message,
+        } => {
+            let channel = resolve_channel(channel, state)?;
+            let expected = resolve_uint(expected, state)?;
+            let retries = resolve_uint(retries, state)?;
+            if let Expr::String(message) = message.expression() {
+                return Ok(FrontendRequest::TCUTransact(tcu_transaction(
+                    state,
+                    expr.clone(),
+                    format!("M{channel:02X}\r").into_bytes(),
+                    Some(MeasurementTest {
+                        expected: i64::from(expected)..=i64::from(expected),
+                        retries,
+                        failure_message: interpolate(message, expr, state)?,
+                        mask: None,
+                        format: state.measurement_format,
+                        max_std_dev: None,
+                        samples: Vec::new(),
+                    }),
                 )));
             }
 
-            panic!("Invalid PRINTERSET arg {arg:?}")
+            panic!("Invalid TCUEXACT arg {message:?}")
+        }
+
+        Expr::Assert {
+            var,
+            min,
+            max,
+            message,
+        } => {
+            let args = (
+                var.expression(),
+                measurement_bound(min.expression()),
+                measurement_bound(max.expression()),
+                message.expression(),
+            );
+            if let (Expr::Variable(name), Some(min), Some(max), Expr::String(message)) = args {
+                let Some(value) = state.variables.get(name).copied() else {
+                    return Err(Error::from_undefined_variable(
+                        expr.clone(),
+                        name.to_owned(),
+                    ));
+                };
+
+                if (min..=max).contains(&i64::from(value)) {
+                    return Ok(FrontendRequest::None);
+                }
+
+                return Err(Error::from_failed_test(
+                    expr.clone(),
+                    FailedTest {
+                        measurement: value,
+                        expected: min..=max,
+                        message: interpolate(message, expr, state)?,
+                        mask: None,
+                    },
+                ));
+            }
+
+            panic!("Invalid ASSERT args {var:?}, {min:?}, {max:?}, {message:?}")
+        }
+
+        Expr::AddVar { dest, src1, src2 } => {
+            let sources = (resolve_uint(src1, state)?, resolve_uint(src2, state)?);
+            arithmetic(expr, dest, sources, state, u32::checked_add)
+        }
+
+        Expr::SubVar { dest, src1, src2 } => {
+            let sources = (resolve_uint(src1, state)?, resolve_uint(src2, state)?);
+            arithmetic(expr, dest, sources, state, u32::checked_sub)
+        }
+
+        Expr::MulVar { dest, src1, src2 } => {
+            let sources = (resolve_uint(src1, state)?, resolve_uint(src2, state)?);
+            arithmetic(expr, dest, sources, state, u32::checked_mul)
+        }
+
+        Expr::DivVar { dest, src1, src2 } => {
+            let sources = (resolve_uint(src1, state)?, resolve_uint(src2, state)?);
+            if sources.1 == 0 {
+                return Err(Error::from_division_by_zero(expr.clone()));
+            }
+
+            arithmetic(expr, dest, sources, state, u32::checked_div)
+        }
+
+        // Values that could be relay addresses are printed in decimal, anything larger in hex.
+        Expr::DumpVars => {
+            let mut variables: Vec<(&String, &u32)> = state.variables.iter().collect();
+            variables.sort();
+
+            let variables: Vec<String> = variables
+                .into_iter()
+                .map(|(name, value)| match value {
+                    0..=255 => format!("{name}={value}"),
+                    _ => format!("{name}=${value:X}"),
+                })
+                .collect();
+
+            Ok(FrontendRequest::GuiPrint(variables.join(", ")))
+        }
+
+        Expr::DefineChannel { name, channel } => {
+            let Expr::String(name) = name.expression() else {
+                panic!("Invalid DEFINECHANNEL name {name:?}");
+            };
+
+            let channel = resolve_byte(channel, state)?;
+            state.channels.insert(name.to_owned(), channel);
+            Ok(FrontendRequest::None)
+        }
+
+        Expr::PrinterSet(arg) => {
+            let channel = resolve_channel(arg, state)?;
+            let bytes = if state.hpmode {
+                format!("P051B000053{:02X}\r", channel).into_bytes()
+            } else {
+                format!("P051B0053{:02X}\r", channel).into_bytes()
+            };
+
+            Ok(FrontendRequest::TCUTransact(tcu_transaction(
+                state,
+                expr.to_owned(),
+                bytes,
+                None,
+            )))
         }
 
         Expr::PrinterTest {
@@ -275,44 +726,38 @@ pub fn evaluate(expr: &ParsedExpr, state: &mut EvalState) -> Result<FrontendRequ
             retries,
             message,
         } => {
+            let channel = resolve_channel(channel, state)?;
+            let retries = resolve_uint(retries, state)?;
             let args = (
-                channel.expression(),
-                min.expression(),
-                max.expression(),
-                retries.expression(),
+                measurement_bound(min.expression()),
+                measurement_bound(max.expression()),
                 message.expression(),
             );
 
-            if let (
-                Expr::UInt(channel),
-                Expr::UInt(min),
-                Expr::UInt(max),
-                Expr::UInt(retries),
-                Expr::String(message),
-            ) = args
-            {
-                debug_assert!(*channel <= 255);
-
+            if let (Some(min), Some(max), Expr::String(message)) = args {
                 let bytes = if state.hpmode {
                     format!("W051B00004D{channel:02X}\r").into_bytes()
                 } else {
                     format!("W051B004D{channel:02X}\r").into_bytes()
                 };
 
-                return Ok(FrontendRequest::TCUTransact(Transaction::with_tcu(
+                return Ok(FrontendRequest::TCUTransact(tcu_transaction(
+                    state,
                     expr.clone(),
                     bytes,
                     Some(MeasurementTest {
-                        expected: *min..=*max,
-                        retries: *retries,
-                        failure_message: message.to_owned(),
+                        expected: min..=max,
+                        retries,
+                        failure_message: interpolate(message, expr, state)?,
+                        mask: None,
+                        format: state.measurement_format,
+                        max_std_dev: None,
+                        samples: Vec::new(),
                     }),
                 )));
             }
 
-            panic!(
-                "Invalid PRINTERTEST args {channel:?}, {min:?}, {max:?}, {retries:?}, {message:?}"
-            )
+            panic!("Invalid PRINTERTEST args {min:?}, {max:?}, {message:?}")
         }
 
         Expr::IssueTest(_) => Ok(FrontendRequest::None),
@@ -325,15 +770,30 @@ pub fn evaluate(expr: &ParsedExpr, state: &mut EvalState) -> Result<FrontendRequ
             let mut bytes = Vec::new();
             for arg in args {
                 if let Expr::String(str) = arg.expression() {
-                    bytes.extend_from_slice(str.as_bytes());
+                    bytes.extend_from_slice(interpolate(str, arg, state)?.as_bytes());
                 } else if let Expr::UInt(uint) = arg.expression() {
                     debug_assert!(*uint <= 255);
                     bytes.push(*uint as u8);
+                } else if let Expr::Variable(_) = arg.expression() {
+                    bytes.push(resolve_byte(arg, state)?);
                 } else {
                     panic!("Invalid USBPRINT arg {arg:?}")
                 }
             }
 
+            if let Some(limit) = state.usb_print_limit {
+                let size = bytes.len() as u32;
+                if size > limit {
+                    let limits = (0, limit.into());
+                    let error = syntax::Error::argument_value_size(
+                        expr.span().clone(),
+                        size.into(),
+                        limits,
+                    );
+                    return Err(error.into());
+                }
+            }
+
             Ok(FrontendRequest::PrinterTransact(Transaction::with_printer(
                 expr.clone(),
                 bytes,
@@ -342,34 +802,22 @@ pub fn evaluate(expr: &ParsedExpr, state: &mut EvalState) -> Result<FrontendRequ
         }
 
         Expr::USBSetTimeFormat(arg) => {
-            if let Expr::UInt(uint) = arg.expression() {
-                let bytes = if state.hpmode {
-                    vec![0x1B, 0x00, b't', b'f', *uint as u8]
-                } else {
-                    vec![0x1B, b't', b'f', *uint as u8]
-                };
-
-                return Ok(FrontendRequest::PrinterTransact(Transaction::with_printer(
-                    expr.clone(),
-                    bytes,
-                    None,
-                )));
-            }
+            let format = resolve_byte(arg, state)?;
+            let bytes = if state.hpmode {
+                vec![0x1B, 0x00, b't', b'f', format]
+            } else {
+                vec![0x1B, b't', b'f', format]
+            };
 
-            panic!()
+            Ok(FrontendRequest::PrinterTransact(Transaction::with_printer(
+                expr.clone(),
+                bytes,
+                None,
+            )))
         }
 
         Expr::USBSetTime => {
-            let datetime = Local::now();
-            let datetime = format!(
-                "{:02}:{:02}:{:02},{:02}/{:02}/{:02}",
-                datetime.hour(),
-                datetime.minute(),
-                datetime.second(),
-                datetime.day(),
-                datetime.month(),
-                (datetime.year() - 1900) % 100
-            );
+            let datetime = format_datetime(current_time(state.timezone, Utc::now()));
 
             let mut bytes = if state.hpmode {
                 vec![0x1B, 0x00, b't', b's']
@@ -387,94 +835,351 @@ pub fn evaluate(expr: &ParsedExpr, state: &mut EvalState) -> Result<FrontendRequ
         }
 
         Expr::USBSetOption { option, setting } => {
-            if let (Expr::UInt(option), Expr::UInt(setting)) =
-                (option.expression(), setting.expression())
-            {
-                debug_assert!(*option <= 255);
-                debug_assert!(*setting <= 255);
-
-                let bytes = if state.hpmode {
-                    vec![0x1B, 0x00, 0x00, b'O', *option as u8, *setting as u8]
-                } else {
-                    vec![0x1B, 0x00, b'O', *option as u8, *setting as u8]
-                };
+            let option = resolve_option(option, state)?;
+            let setting = resolve_byte(setting, state)?;
 
-                return Ok(FrontendRequest::PrinterTransact(Transaction::with_printer(
-                    expr.clone(),
-                    bytes,
-                    None,
-                )));
-            }
+            let bytes = if state.hpmode {
+                vec![0x1B, 0x00, 0x00, b'O', option, setting]
+            } else {
+                vec![0x1B, 0x00, b'O', option, setting]
+            };
 
-            panic!("Invalid USBSETOPTION args {option:?}, {setting:?}")
+            Ok(FrontendRequest::PrinterTransact(Transaction::with_printer(
+                expr.clone(),
+                bytes,
+                None,
+            )))
         }
 
         Expr::USBPrinterSet(arg) => {
-            if let Expr::UInt(channel) = arg.expression() {
-                debug_assert!(*channel <= 255);
+            let channel = resolve_channel(arg, state)?;
+            let bytes = if state.hpmode {
+                vec![0x1B, 0x00, 0x00, b'S', channel]
+            } else {
+                vec![0x1B, 0x00, b'S', channel]
+            };
 
+            Ok(FrontendRequest::PrinterTransact(Transaction::with_printer(
+                expr.clone(),
+                bytes,
+                None,
+            )))
+        }
+
+        Expr::USBPrinterTest {
+            channel,
+            min,
+            max,
+            retries,
+            message,
+        }
+        | Expr::USBTest {
+            channel,
+            min,
+            max,
+            retries,
+            message,
+        } => {
+            let channel = resolve_channel(channel, state)?;
+            let retries = resolve_uint(retries, state)?;
+            let args = (
+                measurement_bound(min.expression()),
+                measurement_bound(max.expression()),
+                message.expression(),
+            );
+
+            if let (Some(min), Some(max), Expr::String(message)) = args {
                 let bytes = if state.hpmode {
-                    vec![0x1B, 0x00, 0x00, b'S', *channel as u8]
+                    vec![0x1B, 0x00, 0x00, b'M', channel]
                 } else {
-                    vec![0x1B, 0x00, b'S', *channel as u8]
+                    vec![0x1B, 0x00, b'M', channel]
                 };
 
                 return Ok(FrontendRequest::PrinterTransact(Transaction::with_printer(
                     expr.clone(),
                     bytes,
-                    None,
+                    Some(MeasurementTest {
+                        expected: min..=max,
+                        retries,
+                        failure_message: interpolate(message, expr, state)?,
+                        mask: None,
+                        format: state.measurement_format,
+                        max_std_dev: None,
+                        samples: Vec::new(),
+                    }),
                 )));
             }
 
-            panic!("Invalid USBPRINTERSET arg {arg:?}")
+            panic!("Invalid USBPRINTERTEST args {min:?}, {max:?}, {message:?}")
         }
 
-        Expr::USBPrinterTest {
+        Expr::USBExact {
             channel,
-            min,
-            max,
+            expected,
             retries,
             message,
         } => {
-            let args = (
-                channel.expression(),
-                min.expression(),
-                max.expression(),
-                retries.expression(),
-                message.expression(),
-            );
-
-            if let (
-                Expr::UInt(channel),
-                Expr::UInt(min),
-                Expr::UInt(max),
-                Expr::UInt(retries),
-                Expr::String(message),
-            ) = args
-            {
-                debug_assert!(*channel <= 255);
-
+            let channel = resolve_channel(channel, state)?;
+            let expected = resolve_uint(expected, state)?;
+            let retries = resolve_uint(retries, state)?;
+            if let Expr::String(message) = message.expression() {
                 let bytes = if state.hpmode {
-                    vec![0x1B, 0x00, 0x00, b'M', *channel as u8]
+                    vec![0x1B, 0x00, 0x00, b'M', channel]
                 } else {
-                    vec![0x1B, 0x00, b'M', *channel as u8]
+                    vec![0x1B, 0x00, b'M', channel]
                 };
 
                 return Ok(FrontendRequest::PrinterTransact(Transaction::with_printer(
                     expr.clone(),
                     bytes,
                     Some(MeasurementTest {
-                        expected: *min..=*max,
-                        retries: *retries,
-                        failure_message: message.to_owned(),
+                        expected: i64::from(expected)..=i64::from(expected),
+                        retries,
+                        failure_message: interpolate(message, expr, state)?,
+                        mask: None,
+                        format: state.measurement_format,
+                        max_std_dev: None,
+                        samples: Vec::new(),
                     }),
                 )));
             }
 
-            panic!(
-                "Invalid USBPRINTERTEST args {channel:?}, {min:?}, {max:?}, {retries:?}, {message:?}"
-            )
+            panic!("Invalid USBEXACT arg {message:?}")
         }
+
+        // Skipping over conditional blocks is handled by the interpreter.
+        Expr::IfTestPass | Expr::IfTestFail | Expr::EndIf => Ok(FrontendRequest::None),
+
+        // Looping is handled by the interpreter. Only the number of iterations is recorded here.
+        Expr::Repeat(arg) => {
+            let count = resolve_uint(arg, state)?;
+            state.loops.push(count);
+            Ok(FrontendRequest::None)
+        }
+        Expr::EndRepeat => Ok(FrontendRequest::None),
+
+        Expr::Checkpoint(arg) => {
+            if let Expr::String(name) = arg.expression() {
+                let name = interpolate(name, arg, state)?;
+                state.last_checkpoint = Some(name.clone());
+                return Ok(FrontendRequest::Checkpoint(name));
+            }
+
+            panic!("Invalid CHECKPOINT arg {arg:?}")
+        }
+
+        Expr::Label(_) => Ok(FrontendRequest::None),
+
+        // The version is checked when the script is loaded.
+        Expr::ScriptVersion { .. } => Ok(FrontendRequest::None),
+
+        // Macros are expanded when the script is loaded.
+        Expr::Define(_) | Expr::EndDefine | Expr::UseMacro(_) => Ok(FrontendRequest::None),
+
+        // Each test group boundary is also a checkpoint.
+        Expr::TestGroup(arg) => {
+            if let Expr::String(name) = arg.expression() {
+                let name = interpolate(name, arg, state)?;
+                state.test_group = Some(name.clone());
+                state.last_checkpoint = Some(name.clone());
+                return Ok(FrontendRequest::Checkpoint(name));
+            }
+
+            panic!("Invalid TESTGROUP arg {arg:?}")
+        }
+
+        Expr::EndTestGroup => {
+            let name = state.test_group.take().unwrap_or_default();
+            let name = format!("End of {name}");
+            state.last_checkpoint = Some(name.clone());
+            Ok(FrontendRequest::Checkpoint(name))
+        }
+
+        Expr::GlobalTimeout(arg) => {
+            let milliseconds = resolve_uint(arg, state)?;
+            let timeout = Duration::from_millis(milliseconds.into());
+            state.global_timeout = Some((Instant::now(), timeout));
+            Ok(FrontendRequest::None)
+        }
+
+        Expr::MaxFailures(arg) => {
+            state.max_failures = Some(resolve_uint(arg, state)?);
+            Ok(FrontendRequest::None)
+        }
+
+        Expr::TraceOn => {
+            state.trace = true;
+            Ok(FrontendRequest::None)
+        }
+
+        Expr::SetNoEchoOn => {
+            state.no_echo = true;
+            Ok(FrontendRequest::None)
+        }
+
+        Expr::SetNoEchoOff => {
+            state.no_echo = false;
+            Ok(FrontendRequest::None)
+        }
+
+        Expr::TraceOff => {
+            state.trace = false;
+            Ok(FrontendRequest::None)
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// tests
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::error::ErrorReason;
+
+    ////////////////////////////////////////////////////////////////
+
+    fn datetime(year: i32, month: u32, day: u32, time: (u32, u32, u32)) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .and_then(|date| date.and_hms_opt(time.0, time.1, time.2))
+            .unwrap()
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_format_datetime() {
+        let formatted = format_datetime(datetime(2023, 7, 4, (9, 5, 30)));
+        assert_eq!(formatted, "09:05:30,04/07/23");
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_format_datetime_century_boundaries() {
+        let formatted = format_datetime(datetime(1999, 12, 31, (23, 59, 59)));
+        assert_eq!(formatted, "23:59:59,31/12/99");
+
+        let formatted = format_datetime(datetime(2000, 1, 1, (0, 0, 0)));
+        assert_eq!(formatted, "00:00:00,01/01/00");
+
+        let formatted = format_datetime(datetime(2100, 3, 1, (12, 0, 0)));
+        assert_eq!(formatted, "12:00:00,01/03/00");
+
+        let formatted = format_datetime(datetime(1899, 6, 15, (12, 0, 0)));
+        assert_eq!(formatted, "12:00:00,15/06/99");
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_current_time() {
+        let now = datetime(2023, 7, 4, (9, 5, 30)).and_utc();
+
+        assert_eq!(current_time(TimeZone::Utc, now), now.naive_utc());
+        assert_eq!(
+            current_time(TimeZone::Local, now),
+            now.with_timezone(&Local).naive_local()
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_option_range() {
+        let set_option = |option| -> ParsedExpr {
+            Expr::SetOption {
+                option: Expr::UInt(option).into(),
+                setting: Expr::UInt(0).into(),
+            }
+            .into()
+        };
+
+        // Any byte is allowed until a range is set.
+        let mut state = EvalState::new();
+        assert!(evaluate(&set_option(255), &mut state).is_ok());
+
+        let range = Expr::SetOptionRange {
+            min: Expr::UInt(2).into(),
+            max: Expr::UInt(8).into(),
+        };
+        evaluate(&range.into(), &mut state).unwrap();
+
+        assert!(evaluate(&set_option(2), &mut state).is_ok());
+        assert!(evaluate(&set_option(8), &mut state).is_ok());
+
+        let error = evaluate(&set_option(9), &mut state).unwrap_err();
+        assert!(matches!(
+            error.reason(),
+            ErrorReason::ArgOutOfRange {
+                value: 9,
+                limits: (2, 8),
+                ..
+            }
+        ));
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_invalid_option_range() {
+        let range = Expr::SetOptionRange {
+            min: Expr::UInt(8).into(),
+            max: Expr::UInt(2).into(),
+        };
+
+        let error = evaluate(&range.into(), &mut EvalState::new()).unwrap_err();
+        assert!(matches!(
+            error.reason(),
+            ErrorReason::SyntaxError(syntax::ErrorReason::InvalidRange {
+                first: 8,
+                last: 2,
+                ..
+            })
+        ));
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_trace() {
+        let mut state = EvalState::new();
+        assert!(!state.trace());
+
+        evaluate(&Expr::TraceOn.into(), &mut state).unwrap();
+        assert!(state.trace());
+
+        evaluate(&Expr::TraceOff.into(), &mut state).unwrap();
+        assert!(!state.trace());
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_set_time_utc() {
+        let mut state = EvalState::new();
+        evaluate(&Expr::SetTimeZone(TimeZone::Utc).into(), &mut state).unwrap();
+        assert_eq!(state.timezone, TimeZone::Utc);
+
+        let before = format_datetime(Utc::now().naive_utc());
+        let Ok(FrontendRequest::PrinterTransact(transaction)) =
+            evaluate(&Expr::USBSetTime.into(), &mut state)
+        else {
+            panic!("Expected printer transaction");
+        };
+        let after = format_datetime(Utc::now().naive_utc());
+
+        // The time may have changed while evaluating.
+        let (command, time) = transaction.bytes().split_at(3);
+        assert_eq!(command, b"\x1Bts");
+        assert!(
+            time == before.as_bytes() || time == after.as_bytes(),
+            "{}",
+            time.escape_ascii()
+        );
     }
 }
 
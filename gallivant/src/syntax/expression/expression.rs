@@ -1,5 +1,7 @@
 use std::{borrow::Borrow, ops::Range};
 
+use crate::{execution::MeasurementFormat, syntax::TimeZone};
+
 use super::kind::ExprKind;
 
 ////////////////////////////////////////////////////////////////
@@ -7,29 +9,66 @@ use super::kind::ExprKind;
 ////////////////////////////////////////////////////////////////
 
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
     String(String),
     UInt(u32),
+    SInt(i32),
+
+    /// Name of a variable defined before the script is run.
+    Variable(String),
 
     ScriptComment(String),
 
     HPMode,
-    Comment(Box<ParsedExpr>),
+
+    /// Print a message built from the concatenation of each argument.
+    Comment(Vec<ParsedExpr>),
+
+    /// Print a string, or the name and value of a variable.
+    Echo(Box<ParsedExpr>),
+
     Wait(Box<ParsedExpr>),
     OpenDialog(Box<ParsedExpr>),
     WaitDialog(Box<ParsedExpr>),
+
+    /// Report a named value to the frontend e.g. for monitoring tools.
+    Notify {
+        key: Box<ParsedExpr>,
+        value: Box<ParsedExpr>,
+    },
+
     Flush,
+
+    /// Change the baud rate of the TCU's port.
+    SetBaud(Box<ParsedExpr>),
+
     Protocol,
+    SetMeasurementFormat(MeasurementFormat),
     Print(Vec<ParsedExpr>),
+
+    /// Send bytes to the TCU exactly as given, followed by a carriage return.
+    PrintRaw(Vec<ParsedExpr>),
+
     SetTimeFormat(Box<ParsedExpr>),
 
     /// This requires getting the current time from the OS and sending it to the printer via the
     /// TCU. Need to consider that the time must be acquired just before the command is sent.
     SetTime,
+
+    /// Select the time zone used by SetTime and USBSetTime.
+    SetTimeZone(TimeZone),
     SetOption {
         option: Box<ParsedExpr>,
         setting: Box<ParsedExpr>,
     },
+
+    /// Limit the options that SetOption and USBSetOption may set.
+    SetOptionRange {
+        min: Box<ParsedExpr>,
+        max: Box<ParsedExpr>,
+    },
+
     TCUClose(Box<ParsedExpr>),
     TCUOpen(Box<ParsedExpr>),
     TCUTest {
@@ -39,6 +78,70 @@ pub enum Expr {
         retries: Box<ParsedExpr>,
         message: Box<ParsedExpr>,
     },
+    /// Same as TCUTest except that failing only produces a warning.
+    WarnTest {
+        channel: Box<ParsedExpr>,
+        min: Box<ParsedExpr>,
+        max: Box<ParsedExpr>,
+        retries: Box<ParsedExpr>,
+        message: Box<ParsedExpr>,
+    },
+    MaskTest {
+        channel: Box<ParsedExpr>,
+        mask: Box<ParsedExpr>,
+        expected: Box<ParsedExpr>,
+        retries: Box<ParsedExpr>,
+        message: Box<ParsedExpr>,
+    },
+    /// Same as TCUTest except that the measurement must equal a single expected value.
+    TCUExact {
+        channel: Box<ParsedExpr>,
+        expected: Box<ParsedExpr>,
+        retries: Box<ParsedExpr>,
+        message: Box<ParsedExpr>,
+    },
+
+    /// Test the value of a variable without communicating with any device.
+    Assert {
+        var: Box<ParsedExpr>,
+        min: Box<ParsedExpr>,
+        max: Box<ParsedExpr>,
+        message: Box<ParsedExpr>,
+    },
+
+    /// Arithmetic on variables. The result of applying the operation to the sources is stored in
+    /// the destination variable.
+    AddVar {
+        dest: Box<ParsedExpr>,
+        src1: Box<ParsedExpr>,
+        src2: Box<ParsedExpr>,
+    },
+    SubVar {
+        dest: Box<ParsedExpr>,
+        src1: Box<ParsedExpr>,
+        src2: Box<ParsedExpr>,
+    },
+    MulVar {
+        dest: Box<ParsedExpr>,
+        src1: Box<ParsedExpr>,
+        src2: Box<ParsedExpr>,
+    },
+    DivVar {
+        dest: Box<ParsedExpr>,
+        src1: Box<ParsedExpr>,
+        src2: Box<ParsedExpr>,
+    },
+
+    /// Print the name and value of every variable.
+    DumpVars,
+
+    /// Name a channel so that the channel arguments of later commands may refer to it by name
+    /// rather than number.
+    DefineChannel {
+        name: Box<ParsedExpr>,
+        channel: Box<ParsedExpr>,
+    },
+
     PrinterSet(Box<ParsedExpr>),
     PrinterTest {
         channel: Box<ParsedExpr>,
@@ -71,13 +174,78 @@ pub enum Expr {
         retries: Box<ParsedExpr>,
         message: Box<ParsedExpr>,
     },
+
+    /// Alias for USBPrinterTest with the same arguments as TCUTest.
+    USBTest {
+        channel: Box<ParsedExpr>,
+        min: Box<ParsedExpr>,
+        max: Box<ParsedExpr>,
+        retries: Box<ParsedExpr>,
+        message: Box<ParsedExpr>,
+    },
+    /// Same as USBTest except that the measurement must equal a single expected value.
+    USBExact {
+        channel: Box<ParsedExpr>,
+        expected: Box<ParsedExpr>,
+        retries: Box<ParsedExpr>,
+        message: Box<ParsedExpr>,
+    },
+
+    /// Conditionally execute the following commands, up to the matching EndIf, depending on the
+    /// outcome of the most recent test.
+    IfTestPass,
+    IfTestFail,
+    EndIf,
+
+    /// Execute the following commands, up to the matching EndRepeat, a number of times.
+    Repeat(Box<ParsedExpr>),
+    EndRepeat,
+
+    /// Named marker recording how far through the script execution has progressed.
+    Checkpoint(Box<ParsedExpr>),
+
+    /// Named position in the script that the interpreter can be made to seek to.
+    Label(Box<ParsedExpr>),
+
+    /// Start of a named group of related tests, ended by an EndTestGroup.
+    TestGroup(Box<ParsedExpr>),
+    EndTestGroup,
+
+    /// Limit on how long, in milliseconds, the rest of the script may take to execute.
+    GlobalTimeout(Box<ParsedExpr>),
+
+    /// Number of failed tests after which the script stops executing.
+    MaxFailures(Box<ParsedExpr>),
+
+    /// Minimum interpreter version that the script requires.
+    ScriptVersion {
+        major: Box<ParsedExpr>,
+        minor: Box<ParsedExpr>,
+    },
+
+    /// Start or stop tracing the requests of the following commands.
+    TraceOn,
+    TraceOff,
+
+    /// Start of a named macro whose commands, up to the matching EndDefine, are spliced into the
+    /// script wherever it's used by a UseMacro. Macros are expanded when a script is loaded.
+    Define(Box<ParsedExpr>),
+    EndDefine,
+    UseMacro(Box<ParsedExpr>),
+
+    /// Stop or start expecting the TCU to echo the following commands.
+    SetNoEchoOn,
+    SetNoEchoOff,
 }
 
 ////////////////////////////////////////////////////////////////
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ParsedExpr {
     expr: Expr,
+
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::span"))]
     span: Range<usize>,
 }
 
@@ -126,21 +294,40 @@ impl<T: Borrow<Expr>> From<T> for ExprKind {
         match expr.borrow() {
             Expr::String(_) => ExprKind::String,
             Expr::UInt(_) => ExprKind::UInt,
+            Expr::SInt(_) => ExprKind::SInt,
+            Expr::Variable(_) => ExprKind::Variable,
             Expr::ScriptComment(_) => ExprKind::ScriptComment,
             Expr::HPMode => ExprKind::HPMode,
             Expr::Comment(_) => ExprKind::Comment,
+            Expr::Echo(_) => ExprKind::Echo,
             Expr::Wait(_) => ExprKind::Wait,
             Expr::OpenDialog(_) => ExprKind::OpenDialog,
             Expr::WaitDialog(_) => ExprKind::WaitDialog,
+            Expr::Notify { .. } => ExprKind::Notify,
             Expr::Flush => ExprKind::Flush,
+            Expr::SetBaud(_) => ExprKind::SetBaud,
             Expr::Protocol => ExprKind::Protocol,
+            Expr::SetMeasurementFormat(_) => ExprKind::SetMeasurementFormat,
             Expr::Print(_) => ExprKind::Print,
+            Expr::PrintRaw(_) => ExprKind::PrintRaw,
             Expr::SetTimeFormat(_) => ExprKind::SetTimeFormat,
             Expr::SetTime => ExprKind::SetTime,
+            Expr::SetTimeZone(_) => ExprKind::SetTimeZone,
             Expr::SetOption { .. } => ExprKind::SetOption,
+            Expr::SetOptionRange { .. } => ExprKind::SetOptionRange,
             Expr::TCUClose(_) => ExprKind::TCUClose,
             Expr::TCUOpen(_) => ExprKind::TCUOpen,
             Expr::TCUTest { .. } => ExprKind::TCUTest,
+            Expr::WarnTest { .. } => ExprKind::WarnTest,
+            Expr::MaskTest { .. } => ExprKind::MaskTest,
+            Expr::TCUExact { .. } => ExprKind::TCUExact,
+            Expr::Assert { .. } => ExprKind::Assert,
+            Expr::AddVar { .. } => ExprKind::AddVar,
+            Expr::SubVar { .. } => ExprKind::SubVar,
+            Expr::MulVar { .. } => ExprKind::MulVar,
+            Expr::DivVar { .. } => ExprKind::DivVar,
+            Expr::DumpVars => ExprKind::DumpVars,
+            Expr::DefineChannel { .. } => ExprKind::DefineChannel,
             Expr::PrinterSet(_) => ExprKind::PrinterSet,
             Expr::PrinterTest { .. } => ExprKind::PrinterTest,
             Expr::IssueTest(_) => ExprKind::IssueTest,
@@ -153,6 +340,27 @@ impl<T: Borrow<Expr>> From<T> for ExprKind {
             Expr::USBSetOption { .. } => ExprKind::USBSetOption,
             Expr::USBPrinterSet(_) => ExprKind::USBPrinterSet,
             Expr::USBPrinterTest { .. } => ExprKind::USBPrinterTest,
+            Expr::USBTest { .. } => ExprKind::USBTest,
+            Expr::USBExact { .. } => ExprKind::USBExact,
+            Expr::IfTestPass => ExprKind::IfTestPass,
+            Expr::IfTestFail => ExprKind::IfTestFail,
+            Expr::EndIf => ExprKind::EndIf,
+            Expr::Repeat(_) => ExprKind::Repeat,
+            Expr::EndRepeat => ExprKind::EndRepeat,
+            Expr::Checkpoint(_) => ExprKind::Checkpoint,
+            Expr::Label(_) => ExprKind::Label,
+            Expr::TestGroup(_) => ExprKind::TestGroup,
+            Expr::EndTestGroup => ExprKind::EndTestGroup,
+            Expr::GlobalTimeout(_) => ExprKind::GlobalTimeout,
+            Expr::MaxFailures(_) => ExprKind::MaxFailures,
+            Expr::ScriptVersion { .. } => ExprKind::ScriptVersion,
+            Expr::TraceOn => ExprKind::TraceOn,
+            Expr::TraceOff => ExprKind::TraceOff,
+            Expr::Define(_) => ExprKind::Define,
+            Expr::EndDefine => ExprKind::EndDefine,
+            Expr::UseMacro(_) => ExprKind::UseMacro,
+            Expr::SetNoEchoOn => ExprKind::SetNoEchoOn,
+            Expr::SetNoEchoOff => ExprKind::SetNoEchoOff,
         }
     }
 }
@@ -209,3 +417,249 @@ impl std::cmp::PartialEq for ParsedExpr {
 }
 
 ////////////////////////////////////////////////////////////////
+// formatting
+////////////////////////////////////////////////////////////////
+
+impl Expr {
+    /// Return the keyword and arguments of a command, in the order they're written in a script.
+    /// Returns None for values, script comments and commands whose arguments aren't expressions.
+    ///
+    fn command(&self) -> Option<(&'static str, Vec<&ParsedExpr>)> {
+        let command: (&'static str, Vec<&ParsedExpr>) = match self {
+            Expr::String(_)
+            | Expr::UInt(_)
+            | Expr::SInt(_)
+            | Expr::Variable(_)
+            | Expr::ScriptComment(_)
+            | Expr::SetMeasurementFormat(_)
+            | Expr::SetTimeZone(_)
+            | Expr::TraceOn
+            | Expr::TraceOff
+            | Expr::SetNoEchoOn
+            | Expr::SetNoEchoOff => return None,
+
+            Expr::HPMode => ("HPMODE", Vec::new()),
+            Expr::Comment(args) => ("COMMENT", args.iter().collect()),
+            Expr::Echo(arg) => ("ECHO", vec![arg.as_ref()]),
+            Expr::Wait(arg) => ("WAIT", vec![arg.as_ref()]),
+            Expr::OpenDialog(arg) => ("OPENDIALOG", vec![arg.as_ref()]),
+            Expr::WaitDialog(arg) => ("WAITDIALOG", vec![arg.as_ref()]),
+            Expr::Notify { key, value } => ("NOTIFY", vec![key, value]),
+            Expr::Flush => ("FLUSH", Vec::new()),
+            Expr::SetBaud(arg) => ("SETBAUD", vec![arg.as_ref()]),
+            Expr::Protocol => ("PROTOCOL", Vec::new()),
+            Expr::Print(args) => ("PRINT", args.iter().collect()),
+            Expr::PrintRaw(args) => ("PRINTRAW", args.iter().collect()),
+            Expr::SetTimeFormat(arg) => ("SETTIMEFORMAT", vec![arg.as_ref()]),
+            Expr::SetTime => ("SETTIME", Vec::new()),
+            Expr::SetOption { option, setting } => ("SETOPTION", vec![option, setting]),
+            Expr::SetOptionRange { min, max } => ("SETOPTION_RANGE", vec![min, max]),
+            Expr::TCUClose(arg) => ("TCUCLOSE", vec![arg.as_ref()]),
+            Expr::TCUOpen(arg) => ("TCUOPEN", vec![arg.as_ref()]),
+            Expr::TCUTest {
+                channel,
+                min,
+                max,
+                retries,
+                message,
+            } => ("TCUTEST", vec![channel, min, max, retries, message]),
+            Expr::WarnTest {
+                channel,
+                min,
+                max,
+                retries,
+                message,
+            } => ("WARNTEST", vec![channel, min, max, retries, message]),
+            Expr::MaskTest {
+                channel,
+                mask,
+                expected,
+                retries,
+                message,
+            } => ("MASKTEST", vec![channel, mask, expected, retries, message]),
+            Expr::TCUExact {
+                channel,
+                expected,
+                retries,
+                message,
+            } => ("TCUEXACT", vec![channel, expected, retries, message]),
+            Expr::Assert {
+                var,
+                min,
+                max,
+                message,
+            } => ("ASSERT", vec![var, min, max, message]),
+            Expr::AddVar { dest, src1, src2 } => ("ADDVAR", vec![dest, src1, src2]),
+            Expr::SubVar { dest, src1, src2 } => ("SUBVAR", vec![dest, src1, src2]),
+            Expr::MulVar { dest, src1, src2 } => ("MULVAR", vec![dest, src1, src2]),
+            Expr::DivVar { dest, src1, src2 } => ("DIVVAR", vec![dest, src1, src2]),
+            Expr::DumpVars => ("DUMPVARS", Vec::new()),
+            Expr::DefineChannel { name, channel } => ("DEFINECHANNEL", vec![name, channel]),
+            Expr::PrinterSet(arg) => ("PRINTERSET", vec![arg.as_ref()]),
+            Expr::PrinterTest {
+                channel,
+                min,
+                max,
+                retries,
+                message,
+            } => ("PRINTERTEST", vec![channel, min, max, retries, message]),
+            Expr::IssueTest(arg) => ("ISSUETEST", vec![arg.as_ref()]),
+            Expr::TestResult { min, max, message } => ("TESTRESULT", vec![min, max, message]),
+            Expr::USBOpen => ("USBOPEN", Vec::new()),
+            Expr::USBClose => ("USBCLOSE", Vec::new()),
+            Expr::USBPrint(args) => ("USBPRINT", args.iter().collect()),
+            Expr::USBSetTimeFormat(arg) => ("USBSETTIMEFORMAT", vec![arg.as_ref()]),
+            Expr::USBSetTime => ("USBSETTIME", Vec::new()),
+            Expr::USBSetOption { option, setting } => ("USBSETOPTION", vec![option, setting]),
+            Expr::USBPrinterSet(arg) => ("USBPRINTERSET", vec![arg.as_ref()]),
+            Expr::USBPrinterTest {
+                channel,
+                min,
+                max,
+                retries,
+                message,
+            } => ("USBPRINTERTEST", vec![channel, min, max, retries, message]),
+            Expr::USBTest {
+                channel,
+                min,
+                max,
+                retries,
+                message,
+            } => ("USBTEST", vec![channel, min, max, retries, message]),
+            Expr::USBExact {
+                channel,
+                expected,
+                retries,
+                message,
+            } => ("USBEXACT", vec![channel, expected, retries, message]),
+            Expr::IfTestPass => ("IFTESTPASS", Vec::new()),
+            Expr::IfTestFail => ("IFTESTFAIL", Vec::new()),
+            Expr::EndIf => ("ENDIF", Vec::new()),
+            Expr::Repeat(arg) => ("REPEAT", vec![arg.as_ref()]),
+            Expr::EndRepeat => ("ENDREPEAT", Vec::new()),
+            Expr::Checkpoint(arg) => ("CHECKPOINT", vec![arg.as_ref()]),
+            Expr::Label(arg) => ("LABEL", vec![arg.as_ref()]),
+            Expr::TestGroup(arg) => ("TESTGROUP", vec![arg.as_ref()]),
+            Expr::EndTestGroup => ("ENDTESTGROUP", Vec::new()),
+            Expr::GlobalTimeout(arg) => ("GLOBALTIMEOUT", vec![arg.as_ref()]),
+            Expr::MaxFailures(arg) => ("MAXFAILURES", vec![arg.as_ref()]),
+            Expr::ScriptVersion { major, minor } => ("SCRIPTVERSION", vec![major, minor]),
+            Expr::Define(arg) => ("DEFINE", vec![arg.as_ref()]),
+            Expr::EndDefine => ("ENDDEFINE", Vec::new()),
+            Expr::UseMacro(arg) => ("USEMACRO", vec![arg.as_ref()]),
+        };
+
+        Some(command)
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Formats an expression as it would be written in a script such that parsing the output gives
+/// the same expression. Arguments are separated by commas and UInts are written in decimal.
+///
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::String(string) if string.contains(['"', '\n', '\r']) => {
+                write!(f, "\"\"\"{string}\"\"\"")
+            }
+            Expr::String(string) => write!(f, "\"{string}\""),
+            Expr::UInt(uint) => write!(f, "{uint}"),
+            Expr::SInt(sint) => write!(f, "{sint}"),
+            Expr::Variable(name) => write!(f, "{name}"),
+            Expr::ScriptComment(comment) => write!(f, ";{comment}"),
+
+            Expr::SetMeasurementFormat(MeasurementFormat::Hex) => {
+                write!(f, "SETMEASUREMENTFORMAT HEX")
+            }
+            Expr::SetMeasurementFormat(MeasurementFormat::Decimal) => {
+                write!(f, "SETMEASUREMENTFORMAT DEC")
+            }
+            Expr::SetTimeZone(TimeZone::Utc) => write!(f, "SETTIMEZONE UTC"),
+            Expr::SetTimeZone(TimeZone::Local) => write!(f, "SETTIMEZONE LOCAL"),
+            Expr::TraceOn => write!(f, "TRACE ON"),
+            Expr::TraceOff => write!(f, "TRACE OFF"),
+            Expr::SetNoEchoOn => write!(f, "SETNOECHO ON"),
+            Expr::SetNoEchoOff => write!(f, "SETNOECHO OFF"),
+
+            command => {
+                let (keyword, args) = command.command().expect("Expression is a command");
+
+                write!(f, "{keyword}")?;
+                for (i, arg) in args.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    write!(f, "{separator}{arg}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl std::fmt::Display for ParsedExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expr)
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// tests
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use crate::syntax::parse::parse_from_str;
+
+    use super::*;
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_display() {
+        let expr = Expr::TCUClose(Box::new(Expr::UInt(6).into()));
+        assert_eq!(format!("{expr}"), "TCUCLOSE 6");
+
+        let expr = Expr::TCUTest {
+            channel: Expr::UInt(3).into(),
+            min: Expr::SInt(-5).into(),
+            max: Expr::Variable(String::from("limit")).into(),
+            retries: Expr::UInt(1).into(),
+            message: Expr::String(String::from("FAIL")).into(),
+        };
+        assert_eq!(format!("{expr}"), "TCUTEST 3, -5, limit, 1, \"FAIL\"");
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_display_reparses() {
+        let script = r#"
+HPMODE
+COMMENT "Value" 42
+SETMEASUREMENTFORMAT DEC
+SETTIMEZONE UTC
+PRINTRAW $00 $FF
+SETOPTION 6, 7
+MASKTEST 3, $F0, $A0, 1, "FAIL"
+ASSERT count, 1, 10, "FAIL"
+USBPRINT "test", 45, $D4
+OPENDIALOG """Line "1"
+Line 2"""
+REPEAT 2
+ENDREPEAT
+SCRIPTVERSION 0, 1
+; comment
+        "#;
+
+        let ast = parse_from_str(script).unwrap();
+        let formatted: Vec<String> = ast.iter().map(ParsedExpr::to_string).collect();
+
+        assert_eq!(parse_from_str(&formatted.join("\n")).unwrap(), ast);
+    }
+}
+
+////////////////////////////////////////////////////////////////
@@ -1,6 +1,10 @@
-use chumsky::{prelude::*, text::newline};
+use chumsky::prelude::*;
 
-use crate::syntax::error::{Error, ErrorNote};
+use crate::{
+    execution::MeasurementFormat,
+    syntax::error::{Error, ErrorNote},
+    syntax::{parse::CommentChar, TimeZone},
+};
 
 use super::{
     expression::{Expr, ParsedExpr},
@@ -15,23 +19,42 @@ use super::{
 pub enum ExprKind {
     String,
     UInt,
+    SInt,
+    Variable,
 
     ScriptComment,
 
     HPMode,
     Comment,
+    Echo,
     Wait,
     OpenDialog,
     WaitDialog,
+    Notify,
     Flush,
+    SetBaud,
     Protocol,
+    SetMeasurementFormat,
     Print,
+    PrintRaw,
     SetTimeFormat,
     SetTime,
+    SetTimeZone,
     SetOption,
+    SetOptionRange,
     TCUClose,
     TCUOpen,
     TCUTest,
+    WarnTest,
+    MaskTest,
+    TCUExact,
+    Assert,
+    AddVar,
+    SubVar,
+    MulVar,
+    DivVar,
+    DumpVars,
+    DefineChannel,
     PrinterSet,
     PrinterTest,
     IssueTest,
@@ -44,6 +67,27 @@ pub enum ExprKind {
     USBSetOption,
     USBPrinterSet,
     USBPrinterTest,
+    USBTest,
+    USBExact,
+    IfTestPass,
+    IfTestFail,
+    EndIf,
+    Repeat,
+    EndRepeat,
+    Checkpoint,
+    Label,
+    TestGroup,
+    EndTestGroup,
+    GlobalTimeout,
+    MaxFailures,
+    ScriptVersion,
+    TraceOn,
+    TraceOff,
+    Define,
+    EndDefine,
+    UseMacro,
+    SetNoEchoOn,
+    SetNoEchoOff,
 }
 
 ////////////////////////////////////////////////////////////////
@@ -53,23 +97,42 @@ impl ExprKind {
         match self {
             ExprKind::String => "String",
             ExprKind::UInt => "Unsigned Integer",
+            ExprKind::SInt => "Signed Integer",
+            ExprKind::Variable => "Variable",
 
             ExprKind::ScriptComment => "Script Comment",
 
             ExprKind::HPMode => "Command: 'HPMODE'",
             ExprKind::Comment => "Command: 'COMMENT'",
+            ExprKind::Echo => "Command: 'ECHO'",
             ExprKind::Wait => "Command: 'WAIT'",
             ExprKind::OpenDialog => "Command: 'OPENDIALOG'",
             ExprKind::WaitDialog => "Command: 'WAITDIALOG'",
+            ExprKind::Notify => "Command: 'NOTIFY'",
             ExprKind::Flush => "Command: 'FLUSH'",
+            ExprKind::SetBaud => "Command: 'SETBAUD'",
             ExprKind::Protocol => "Command: 'PROTOCOL'",
+            ExprKind::SetMeasurementFormat => "Command: 'SETMEASUREMENTFORMAT'",
             ExprKind::Print => "Command: 'PRINT'",
+            ExprKind::PrintRaw => "Command: 'PRINTRAW'",
             ExprKind::SetTimeFormat => "Command: 'SETTIMEFORMAT'",
             ExprKind::SetTime => "Command: 'SETTIME'",
+            ExprKind::SetTimeZone => "Command: 'SETTIMEZONE'",
             ExprKind::SetOption => "Command: 'SETOPTION'",
+            ExprKind::SetOptionRange => "Command: 'SETOPTION_RANGE'",
             ExprKind::TCUClose => "Command: 'TCUCLOSE'",
             ExprKind::TCUOpen => "Command: 'TCUOPEN'",
             ExprKind::TCUTest => "Command: 'TCUTEST'",
+            ExprKind::WarnTest => "Command: 'WARNTEST'",
+            ExprKind::MaskTest => "Command: 'MASKTEST'",
+            ExprKind::TCUExact => "Command: 'TCUEXACT'",
+            ExprKind::Assert => "Command: 'ASSERT'",
+            ExprKind::AddVar => "Command: 'ADDVAR'",
+            ExprKind::SubVar => "Command: 'SUBVAR'",
+            ExprKind::MulVar => "Command: 'MULVAR'",
+            ExprKind::DivVar => "Command: 'DIVVAR'",
+            ExprKind::DumpVars => "Command: 'DUMPVARS'",
+            ExprKind::DefineChannel => "Command: 'DEFINECHANNEL'",
             ExprKind::PrinterSet => "Command: 'PRINTERSET'",
             ExprKind::PrinterTest => "Command: 'PRINTERTEST'",
             ExprKind::IssueTest => "Command: 'ISSUETEST'",
@@ -82,6 +145,27 @@ impl ExprKind {
             ExprKind::USBSetOption => "Command: 'USBSETOPTION'",
             ExprKind::USBPrinterSet => "Command: 'USBPRINTERSET'",
             ExprKind::USBPrinterTest => "Command: 'USBPRINTERTEST'",
+            ExprKind::USBTest => "Command: 'USBTEST'",
+            ExprKind::USBExact => "Command: 'USBEXACT'",
+            ExprKind::IfTestPass => "Command: 'IFTESTPASS'",
+            ExprKind::IfTestFail => "Command: 'IFTESTFAIL'",
+            ExprKind::EndIf => "Command: 'ENDIF'",
+            ExprKind::Repeat => "Command: 'REPEAT'",
+            ExprKind::EndRepeat => "Command: 'ENDREPEAT'",
+            ExprKind::Checkpoint => "Command: 'CHECKPOINT'",
+            ExprKind::Label => "Command: 'LABEL'",
+            ExprKind::TestGroup => "Command: 'TESTGROUP'",
+            ExprKind::EndTestGroup => "Command: 'ENDTESTGROUP'",
+            ExprKind::GlobalTimeout => "Command: 'GLOBALTIMEOUT'",
+            ExprKind::MaxFailures => "Command: 'MAXFAILURES'",
+            ExprKind::ScriptVersion => "Command: 'SCRIPTVERSION'",
+            ExprKind::TraceOn => "Command: 'TRACE ON'",
+            ExprKind::TraceOff => "Command: 'TRACE OFF'",
+            ExprKind::Define => "Command: 'DEFINE'",
+            ExprKind::EndDefine => "Command: 'ENDDEFINE'",
+            ExprKind::UseMacro => "Command: 'USEMACRO'",
+            ExprKind::SetNoEchoOn => "Command: 'SETNOECHO ON'",
+            ExprKind::SetNoEchoOff => "Command: 'SETNOECHO OFF'",
         }
     }
 
@@ -90,39 +174,95 @@ impl ExprKind {
     pub fn parser(&self) -> impl Parser<char, ParsedExpr, Error = Error> + Clone {
         match self {
             ////////////////////////////////////////////////////////////////
-            ExprKind::String => filter(|c| *c != '"')
-                .repeated()
-                .delimited_by(just('"'), just('"'))
-                .map(String::from_iter)
-                .map(Expr::String)
+            // Triple quoted strings may contain newlines and single quote characters.
+            ExprKind::String => {
+                let triple_quoted = just("\"\"\"")
+                    .ignore_then(take_until(just("\"\"\"")))
+                    .map(|(s, _)| String::from_iter(s));
+
+                let quoted = filter(|c| *c != '"')
+                    .repeated()
+                    .delimited_by(just('"'), just('"'))
+                    .map(String::from_iter);
+
+                triple_quoted.or(quoted).map(Expr::String).boxed()
+            }
+
+            ////////////////////////////////////////////////////////////////
+            ExprKind::UInt => parse::uint_literal()
+                .try_map(|(_, digits, radix), span| {
+                    parse::uint_value(&digits, radix)
+                        .map(Expr::UInt)
+                        .ok_or_else(|| Error::expected_input_found(span, None, None))
+                })
                 .boxed(),
 
             ////////////////////////////////////////////////////////////////
-            ExprKind::UInt => {
-                let uint_dec = parse::uint(10).map(|s: String| Expr::UInt(s.parse().unwrap()));
-                let uint_hex = just("$")
-                    .ignore_then(parse::uint(16))
-                    .map(|s: String| Expr::UInt(u32::from_str_radix(&s, 16).unwrap()));
+            ExprKind::SInt => just('-')
+                .ignore_then(parse::uint_literal())
+                .try_map(|(_, digits, radix), span| {
+                    parse::uint_value(&digits, radix)
+                        .and_then(|value| i32::try_from(-i64::from(value)).ok())
+                        .map(Expr::SInt)
+                        .ok_or_else(|| Error::expected_input_found(span, None, None))
+                })
+                .boxed(),
 
-                choice((uint_dec, uint_hex)).boxed()
-            }
+            ////////////////////////////////////////////////////////////////
+            ExprKind::Variable => text::ident().map(Expr::Variable).boxed(),
 
             ////////////////////////////////////////////////////////////////
-            ExprKind::ScriptComment => just(';')
-                .ignore_then(take_until(choice((newline(), end())).rewind()))
-                .map(|(s, _)| String::from_iter(s))
-                .map(Expr::ScriptComment)
+            ExprKind::ScriptComment => parse::script_comment(CommentChar::default())
                 .padded_by(parse::whitespace())
                 .boxed(),
 
             ////////////////////////////////////////////////////////////////
             ExprKind::HPMode => text::keyword("HPMODE").to(Expr::HPMode).boxed(),
 
-            ExprKind::Comment => parse::command("COMMENT", [validate_string(argument())])
-                .map(|[arg]| Expr::Comment(arg))
-                .boxed(),
+            // Bare words are rejected rather than treated as variables. Variables can be included
+            // by interpolating them into a string instead.
+            ExprKind::Comment => {
+                let arg = argument().validate(|arg, span, emit| {
+                    if !matches!(
+                        arg.expression(),
+                        Expr::String(_) | Expr::UInt(_) | Expr::SInt(_)
+                    ) {
+                        emit(Error::argument_type(
+                            span,
+                            [ExprKind::String, ExprKind::UInt, ExprKind::SInt],
+                            arg.expression_kind(),
+                        ))
+                    }
+
+                    arg
+                });
+
+                parse::command_list("COMMENT", arg)
+                    .map(Expr::Comment)
+                    .boxed()
+            }
 
+            ExprKind::Echo => {
+                let arg = argument().validate(|arg, span, emit| {
+                    if !matches!(arg.expression(), Expr::String(_) | Expr::Variable(_)) {
+                        emit(Error::argument_type(
+                            span,
+                            [ExprKind::String, ExprKind::Variable],
+                            arg.expression_kind(),
+                        ))
+                    }
+
+                    arg
+                });
+
+                parse::command("ECHO", [arg])
+                    .map(|[arg]| Expr::Echo(arg))
+                    .boxed()
+            }
+
+            // DELAY is accepted as an alias for WAIT.
             ExprKind::Wait => parse::command("WAIT", [validate_uint(argument())])
+                .or(parse::command("DELAY", [validate_uint(argument())]))
                 .map(|[arg]| Expr::Wait(arg))
                 .boxed(),
 
@@ -134,25 +274,67 @@ impl ExprKind {
                 .map(|[arg]| Expr::WaitDialog(arg))
                 .boxed(),
 
+            ExprKind::Notify => parse::command(
+                "NOTIFY",
+                [validate_string(argument()), validate_uint(argument())],
+            )
+            .map(|[key, value]| Expr::Notify { key, value })
+            .boxed(),
+
             ExprKind::Flush => text::keyword("FLUSH").to(Expr::Flush).boxed(),
 
+            ExprKind::SetBaud => parse::command("SETBAUD", [validate_uint(argument())])
+                .map(|[arg]| Expr::SetBaud(arg))
+                .boxed(),
+
             ExprKind::Protocol => text::keyword("PROTOCOL").to(Expr::Protocol).boxed(),
 
+            ExprKind::SetMeasurementFormat => text::keyword("SETMEASUREMENTFORMAT")
+                .then(parse::whitespace())
+                .ignore_then(choice((
+                    text::keyword("HEX").to(MeasurementFormat::Hex),
+                    text::keyword("DEC").to(MeasurementFormat::Decimal),
+                )))
+                .map(Expr::SetMeasurementFormat)
+                .boxed(),
+
             ExprKind::Print => parse::command_variadic("PRINT", argument())
                 .map(Expr::Print)
                 .boxed(),
 
+            ExprKind::PrintRaw => parse::command_list("PRINTRAW", validate_byte(argument()))
+                .map(Expr::PrintRaw)
+                .boxed(),
+
             ExprKind::SetTimeFormat => parse::command("SETTIMEFORMAT", [validate_byte(argument())])
                 .map(|[arg]| Expr::SetTimeFormat(arg))
                 .boxed(),
 
             ExprKind::SetTime => text::keyword("SETTIME").to(Expr::SetTime).boxed(),
 
+            ExprKind::SetTimeZone => text::keyword("SETTIMEZONE")
+                .then(parse::whitespace())
+                .ignore_then(choice((
+                    text::keyword("UTC").to(TimeZone::Utc),
+                    text::keyword("LOCAL").to(TimeZone::Local),
+                )))
+                .map(Expr::SetTimeZone)
+                .boxed(),
+
             ExprKind::SetOption => parse::command(
                 "SETOPTION",
                 [validate_byte(argument()), validate_byte(argument())],
             )
             .map(|[option, setting]| Expr::SetOption { option, setting })
+            .or(missing_comma("SETOPTION", ["option", "setting"])
+                .map(|(option, setting)| Expr::SetOption { option, setting }))
+            .boxed(),
+
+            ExprKind::SetOptionRange => parse::command(
+                "SETOPTION_RANGE",
+                [validate_byte(argument()), validate_byte(argument())],
+            )
+            .map(|[min, max]| Expr::SetOptionRange { min, max })
             .boxed(),
 
             ExprKind::TCUClose => parse::command("TCUCLOSE", [validate_byte(argument())])
@@ -163,31 +345,69 @@ impl ExprKind {
                 .map(|[arg]| Expr::TCUOpen(arg))
                 .boxed(),
 
-            ExprKind::TCUTest => parse::command(
+            ExprKind::TCUTest => validate_bounds(
                 "TCUTEST",
+                parse::command(
+                    "TCUTEST",
+                    [
+                        validate_byte(argument()),
+                        validate_int(argument()),
+                        validate_int(argument()),
+                        validate_uint(argument()),
+                        validate_string(argument()),
+                    ],
+                ),
+            )
+            .map(|[channel, min, max, retries, message]| Expr::TCUTest {
+                channel,
+                min,
+                max,
+                retries,
+                message,
+            })
+            .boxed(),
+
+            ExprKind::WarnTest => validate_bounds(
+                "WARNTEST",
+                parse::command(
+                    "WARNTEST",
+                    [
+                        validate_byte(argument()),
+                        validate_int(argument()),
+                        validate_int(argument()),
+                        validate_uint(argument()),
+                        validate_string(argument()),
+                    ],
+                ),
+            )
+            .map(|[channel, min, max, retries, message]| Expr::WarnTest {
+                channel,
+                min,
+                max,
+                retries,
+                message,
+            })
+            .boxed(),
+
+            ExprKind::TCUExact => parse::command(
+                "TCUEXACT",
                 [
                     validate_byte(argument()),
                     validate_uint(argument()),
                     validate_uint(argument()),
-                    validate_uint(argument()),
                     validate_string(argument()),
                 ],
             )
-            .map(|[channel, min, max, retries, message]| Expr::TCUTest {
+            .map(|[channel, expected, retries, message]| Expr::TCUExact {
                 channel,
-                min,
-                max,
+                expected,
                 retries,
                 message,
             })
             .boxed(),
 
-            ExprKind::PrinterSet => parse::command("PRINTERSET", [validate_byte(argument())])
-                .map(|[arg]| Expr::PrinterSet(arg))
-                .boxed(),
-
-            ExprKind::PrinterTest => parse::command(
-                "PRINTERTEST",
+            ExprKind::MaskTest => parse::command(
+                "MASKTEST",
                 [
                     validate_byte(argument()),
                     validate_uint(argument()),
@@ -196,6 +416,79 @@ impl ExprKind {
                     validate_string(argument()),
                 ],
             )
+            .map(
+                |[channel, mask, expected, retries, message]| Expr::MaskTest {
+                    channel,
+                    mask,
+                    expected,
+                    retries,
+                    message,
+                },
+            )
+            .boxed(),
+
+            ExprKind::Assert => validate_bounds(
+                "ASSERT",
+                parse::command(
+                    "ASSERT",
+                    [
+                        ExprKind::Variable.parser().boxed(),
+                        validate_int(argument()),
+                        validate_int(argument()),
+                        validate_string(argument()),
+                    ],
+                ),
+            )
+            .map(|[var, min, max, message]| Expr::Assert {
+                var,
+                min,
+                max,
+                message,
+            })
+            .boxed(),
+
+            ExprKind::AddVar => arithmetic("ADDVAR")
+                .map(|[dest, src1, src2]| Expr::AddVar { dest, src1, src2 })
+                .boxed(),
+
+            ExprKind::SubVar => arithmetic("SUBVAR")
+                .map(|[dest, src1, src2]| Expr::SubVar { dest, src1, src2 })
+                .boxed(),
+
+            ExprKind::MulVar => arithmetic("MULVAR")
+                .map(|[dest, src1, src2]| Expr::MulVar { dest, src1, src2 })
+                .boxed(),
+
+            ExprKind::DivVar => arithmetic("DIVVAR")
+                .map(|[dest, src1, src2]| Expr::DivVar { dest, src1, src2 })
+                .boxed(),
+
+            ExprKind::DumpVars => text::keyword("DUMPVARS").to(Expr::DumpVars).boxed(),
+
+            ExprKind::DefineChannel => parse::command(
+                "DEFINECHANNEL",
+                [validate_string(argument()), validate_byte(argument())],
+            )
+            .map(|[name, channel]| Expr::DefineChannel { name, channel })
+            .boxed(),
+
+            ExprKind::PrinterSet => parse::command("PRINTERSET", [validate_byte(argument())])
+                .map(|[arg]| Expr::PrinterSet(arg))
+                .boxed(),
+
+            ExprKind::PrinterTest => validate_bounds(
+                "PRINTERTEST",
+                parse::command(
+                    "PRINTERTEST",
+                    [
+                        validate_byte(argument()),
+                        validate_int(argument()),
+                        validate_int(argument()),
+                        validate_uint(argument()),
+                        validate_string(argument()),
+                    ],
+                ),
+            )
             .map(|[channel, min, max, retries, message]| Expr::PrinterTest {
                 channel,
                 min,
@@ -234,15 +527,18 @@ impl ExprKind {
                 .map(|[arg]| Expr::USBPrinterSet(arg))
                 .boxed(),
 
-            ExprKind::USBPrinterTest => parse::command(
+            ExprKind::USBPrinterTest => validate_bounds(
                 "USBPRINTERTEST",
-                [
-                    validate_byte(argument()),
-                    validate_uint(argument()),
-                    validate_uint(argument()),
-                    validate_uint(argument()),
-                    validate_string(argument()),
-                ],
+                parse::command(
+                    "USBPRINTERTEST",
+                    [
+                        validate_byte(argument()),
+                        validate_int(argument()),
+                        validate_int(argument()),
+                        validate_uint(argument()),
+                        validate_string(argument()),
+                    ],
+                ),
             )
             .map(
                 |[channel, min, max, retries, message]| Expr::USBPrinterTest {
@@ -254,6 +550,121 @@ impl ExprKind {
                 },
             )
             .boxed(),
+
+            ExprKind::USBTest => validate_bounds(
+                "USBTEST",
+                parse::command(
+                    "USBTEST",
+                    [
+                        validate_byte(argument()),
+                        validate_int(argument()),
+                        validate_int(argument()),
+                        validate_uint(argument()),
+                        validate_string(argument()),
+                    ],
+                ),
+            )
+            .map(|[channel, min, max, retries, message]| Expr::USBTest {
+                channel,
+                min,
+                max,
+                retries,
+                message,
+            })
+            .boxed(),
+
+            ExprKind::USBExact => parse::command(
+                "USBEXACT",
+                [
+                    validate_byte(argument()),
+                    validate_uint(argument()),
+                    validate_uint(argument()),
+                    validate_string(argument()),
+                ],
+            )
+            .map(|[channel, expected, retries, message]| Expr::USBExact {
+                channel,
+                expected,
+                retries,
+                message,
+            })
+            .boxed(),
+
+            ExprKind::IfTestPass => text::keyword("IFTESTPASS").to(Expr::IfTestPass).boxed(),
+            ExprKind::IfTestFail => text::keyword("IFTESTFAIL").to(Expr::IfTestFail).boxed(),
+            ExprKind::EndIf => text::keyword("ENDIF").to(Expr::EndIf).boxed(),
+
+            ExprKind::Repeat => parse::command("REPEAT", [validate_uint(argument())])
+                .map(|[arg]| Expr::Repeat(arg))
+                .boxed(),
+
+            ExprKind::EndRepeat => text::keyword("ENDREPEAT").to(Expr::EndRepeat).boxed(),
+
+            ExprKind::Checkpoint => parse::command("CHECKPOINT", [validate_string(argument())])
+                .map(|[arg]| Expr::Checkpoint(arg))
+                .boxed(),
+
+            ExprKind::Label => parse::command("LABEL", [validate_string(argument())])
+                .map(|[arg]| Expr::Label(arg))
+                .boxed(),
+
+            ExprKind::TestGroup => parse::command("TESTGROUP", [validate_string(argument())])
+                .map(|[arg]| Expr::TestGroup(arg))
+                .boxed(),
+
+            ExprKind::EndTestGroup => text::keyword("ENDTESTGROUP").to(Expr::EndTestGroup).boxed(),
+
+            ExprKind::GlobalTimeout => parse::command("GLOBALTIMEOUT", [validate_uint(argument())])
+                .map(|[arg]| Expr::GlobalTimeout(arg))
+                .boxed(),
+
+            ExprKind::MaxFailures => parse::command("MAXFAILURES", [validate_uint(argument())])
+                .map(|[arg]| Expr::MaxFailures(arg))
+                .boxed(),
+
+            ExprKind::ScriptVersion => parse::command(
+                "SCRIPTVERSION",
+                [
+                    validate_literal(validate_byte(argument())),
+                    validate_literal(validate_byte(argument())),
+                ],
+            )
+            .map(|[major, minor]| Expr::ScriptVersion { major, minor })
+            .boxed(),
+
+            ExprKind::TraceOn => text::keyword("TRACE")
+                .then(parse::whitespace())
+                .then(text::keyword("ON"))
+                .to(Expr::TraceOn)
+                .boxed(),
+
+            ExprKind::TraceOff => text::keyword("TRACE")
+                .then(parse::whitespace())
+                .then(text::keyword("OFF"))
+                .to(Expr::TraceOff)
+                .boxed(),
+
+            ExprKind::Define => parse::command("DEFINE", [validate_string(argument())])
+                .map(|[arg]| Expr::Define(arg))
+                .boxed(),
+
+            ExprKind::EndDefine => text::keyword("ENDDEFINE").to(Expr::EndDefine).boxed(),
+
+            ExprKind::UseMacro => parse::command("USEMACRO", [validate_string(argument())])
+                .map(|[arg]| Expr::UseMacro(arg))
+                .boxed(),
+
+            ExprKind::SetNoEchoOn => text::keyword("SETNOECHO")
+                .then(parse::whitespace())
+                .then(text::keyword("ON"))
+                .to(Expr::SetNoEchoOn)
+                .boxed(),
+
+            ExprKind::SetNoEchoOff => text::keyword("SETNOECHO")
+                .then(parse::whitespace())
+                .then(text::keyword("OFF"))
+                .to(Expr::SetNoEchoOff)
+                .boxed(),
         }
         .map_with_span(ParsedExpr::from_kind_and_span)
     }
@@ -261,10 +672,84 @@ impl ExprKind {
 
 ////////////////////////////////////////////////////////////////
 
-/// Parser that matches any value type. i.e. a String or UInt.
+/// Parser that matches any value type or a reference to a variable. i.e. a String, UInt, SInt or
+/// Variable.
 ///
 fn argument() -> impl Parser<char, ParsedExpr, Error = Error> + Clone {
-    choice((ExprKind::String.parser(), ExprKind::UInt.parser())).padded_by(parse::whitespace())
+    choice((
+        ExprKind::String.parser(),
+        ExprKind::UInt.parser(),
+        ExprKind::SInt.parser(),
+        ExprKind::Variable.parser(),
+        invalid_hex(),
+    ))
+    .padded_by(parse::whitespace())
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Parser that matches a '$' followed by characters that aren't all hex digits e.g. $G0. Always
+/// outputs an error but still outputs a UInt so that parsing can continue.
+///
+fn invalid_hex() -> impl Parser<char, ParsedExpr, Error = Error> + Clone {
+    just('$')
+        .ignore_then(filter(|c: &char| c.is_ascii_alphanumeric() || *c == '_').repeated())
+        .validate(|_, span, emit| {
+            emit(Error::invalid_hex(span).with_note(ErrorNote::Help(
+                "Hex digits are 0-9 and A-F. Hex digits are case-insensitive (e.g., $FF or $ff)"
+                    .into(),
+            )));
+
+            Expr::UInt(0)
+        })
+        .map_with_span(ParsedExpr::from_kind_and_span)
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Parser for the arguments of an arithmetic command. i.e. a destination variable followed by two
+/// sources, each of which may be either a variable or a UInt.
+///
+fn arithmetic(cmd: &'static str) -> impl Parser<char, [Box<ParsedExpr>; 3], Error = Error> {
+    parse::command(
+        cmd,
+        [
+            ExprKind::Variable.parser().boxed(),
+            validate_uint(argument()),
+            validate_uint(argument()),
+        ],
+    )
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Parser that matches a command taking two byte arguments which haven't been separated by a comma,
+/// as was allowed by the old script format. Always outputs an error but still outputs the
+/// arguments so that parsing can continue.
+///
+/// # Arguments
+/// * `cmd` - The command.
+/// * `args` - Names of the command's arguments, used in the help showing how it should be written.
+///
+fn missing_comma(
+    cmd: &'static str,
+    args: [&'static str; 2],
+) -> impl Parser<char, (Box<ParsedExpr>, Box<ParsedExpr>), Error = Error> {
+    text::keyword(cmd)
+        .then(parse::whitespace())
+        .ignore_then(validate_byte(argument()))
+        .then(validate_byte(argument()))
+        .validate(move |(first, second), _, emit| {
+            let usage = args.map(|arg| format!("<{arg}>")).join(", ");
+            let help = format!("{cmd} requires a comma between the two arguments: {cmd} {usage}");
+
+            emit(
+                Error::missing_comma(second.span().clone(), second.expression_kind())
+                    .with_note(ErrorNote::Help(help.into())),
+            );
+
+            (Box::new(first), Box::new(second))
+        })
 }
 
 ////////////////////////////////////////////////////////////////
@@ -282,7 +767,7 @@ where
                 emit(
                 Error::argument_type(span, [ExprKind::String], arg.expression_kind())
                     .with_note(ErrorNote::Note(
-                    "If the argument was intended to be a string it should be delimited by \"\"",
+                    "If the argument was intended to be a string it should be delimited by \"\"".into(),
                 )),
             )
             }
@@ -294,8 +779,8 @@ where
 
 ////////////////////////////////////////////////////////////////
 
-/// Takes a parser and validates that the output is a Uint. If not, it outputs an error.
-/// If it isn't a string, it outputs an error.
+/// Takes a parser and validates that the output is a Uint or a Variable, whose value is resolved
+/// during evaluation. If not, it outputs an error.
 ///
 fn validate_uint<'a, 'b, P>(parser: P) -> BoxedParser<'b, char, ParsedExpr, Error>
 where
@@ -303,14 +788,17 @@ where
     'a: 'b,
 {
     parser.validate(|arg, span, emit| {
-        if !matches!(arg.expression(), Expr::UInt(_)) {
-            let mut error = Error::argument_type(span, [ExprKind::UInt], arg.expression_kind());
+        if !matches!(arg.expression(), Expr::UInt(_) | Expr::Variable(_)) {
+            let expected = [ExprKind::UInt, ExprKind::Variable];
+            let mut error = Error::argument_type(span, expected, arg.expression_kind());
 
-            if let Expr::String(string) = arg.expression() {
+            if let Expr::SInt(_) = arg.expression() {
+                error = error.with_note(ErrorNote::Help("This argument cannot be negative".into()));
+            } else if let Expr::String(string) = arg.expression() {
                 if string.chars().all(|c| c.is_numeric()) {
-                    error = error.with_note(ErrorNote::Help("If the argument was intended to be an unsigned integer, try removing the enclosing \"\""));
+                    error = error.with_note(ErrorNote::Help("If the argument was intended to be an unsigned integer, try removing the enclosing \"\"".into()));
                 } else if string.starts_with('$') && string.chars().skip(1).all(|c| c.is_ascii_hexdigit()) {
-                    error = error.with_note(ErrorNote::Help("If the argument was intended to be a hex unsigned integer, try removing the enclosing \"\""));
+                    error = error.with_note(ErrorNote::Help("If the argument was intended to be a hex unsigned integer, try removing the enclosing \"\". Hex digits are case-insensitive (e.g., $FF or $ff)".into()));
                 }
             }
             emit(error)
@@ -321,22 +809,116 @@ where
 
 ////////////////////////////////////////////////////////////////
 
-/// Takes a parser and validates that the output is a UInt < 256. If not, it outputs an error.
-/// If it isn't a string, it outputs an error.
+/// Takes a parser and validates that the output is a UInt or SInt. If not, it outputs an error.
 ///
-fn validate_byte<'a, 'b, P>(parser: P) -> BoxedParser<'b, char, ParsedExpr, Error>
+fn validate_int<'a, 'b, P>(parser: P) -> BoxedParser<'b, char, ParsedExpr, Error>
 where
     P: Parser<char, ParsedExpr, Error = Error> + 'a,
     'a: 'b,
 {
     parser
         .validate(|arg, span, emit| {
-            if let Expr::UInt(value) = arg.expression() {
-                if *value > 255 {
-                    emit(Error::argument_value_size(span, *value, (0, 255)))
+            if !matches!(arg.expression(), Expr::UInt(_) | Expr::SInt(_)) {
+                emit(Error::argument_type(
+                    span,
+                    [ExprKind::UInt, ExprKind::SInt],
+                    arg.expression_kind(),
+                ))
+            }
+
+            arg
+        })
+        .boxed()
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Takes a command parser whose second and third arguments are the minimum and maximum of a range
+/// and validates that the minimum doesn't exceed the maximum. If it does, it outputs an error at
+/// the maximum.
+///
+fn validate_bounds<const N: usize, P>(
+    cmd: &'static str,
+    parser: P,
+) -> BoxedParser<'static, char, [Box<ParsedExpr>; N], Error>
+where
+    P: Parser<char, [Box<ParsedExpr>; N], Error = Error> + 'static,
+{
+    let value = |arg: &ParsedExpr| match arg.expression() {
+        Expr::UInt(uint) => Some(i64::from(*uint)),
+        Expr::SInt(sint) => Some(i64::from(*sint)),
+        _ => None,
+    };
+
+    parser
+        .validate(move |args, _, emit| {
+            if let (Some(min), Some(max)) = (value(&args[1]), value(&args[2])) {
+                if min > max {
+                    let limits = (min, i64::from(u32::MAX));
+                    emit(
+                        Error::argument_value_size(args[2].span().clone(), max, limits)
+                            .with_note(ErrorNote::Note(format!("{cmd} min must be ≤ max").into())),
+                    );
                 }
             }
 
+            args
+        })
+        .boxed()
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Takes a parser and validates that the output isn't a Variable, for arguments whose value must be
+/// known before the script is run. If not, it outputs an error.
+///
+fn validate_literal<'a, 'b, P>(parser: P) -> BoxedParser<'b, char, ParsedExpr, Error>
+where
+    P: Parser<char, ParsedExpr, Error = Error> + 'a,
+    'a: 'b,
+{
+    parser
+        .validate(|arg, span, emit| {
+            if let Expr::Variable(_) = arg.expression() {
+                emit(
+                    Error::argument_type(span, [ExprKind::UInt], ExprKind::Variable).with_note(
+                        ErrorNote::Note("This argument is checked before the script is run".into()),
+                    ),
+                )
+            }
+
+            arg
+        })
+        .boxed()
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Takes a parser and validates that the output is a UInt < 256 or a Variable. If not, it outputs
+/// an error. Variables can only be checked to be in range once their value is resolved during
+/// evaluation.
+///
+fn validate_byte<'a, 'b, P>(parser: P) -> BoxedParser<'b, char, ParsedExpr, Error>
+where
+    P: Parser<char, ParsedExpr, Error = Error> + 'a,
+    'a: 'b,
+{
+    parser
+        .validate(|arg, span, emit| {
+            match arg.expression() {
+                Expr::UInt(value) if *value > 255 => emit(Error::argument_value_size(
+                    span,
+                    i64::from(*value),
+                    (0, 255),
+                )),
+                Expr::SInt(_) => emit(
+                    Error::argument_type(span, [ExprKind::UInt], ExprKind::SInt).with_note(
+                        ErrorNote::Help("Byte arguments must be between 0 and 255".into()),
+                    ),
+                ),
+                _ => (),
+            }
+
             arg
         })
         .boxed()
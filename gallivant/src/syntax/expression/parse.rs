@@ -1,25 +1,48 @@
-use chumsky::{combinator::Repeated, prelude::*, text::Character};
+use chumsky::{
+    combinator::Repeated,
+    prelude::*,
+    text::{newline, Character},
+};
 
-use crate::syntax::error::Error;
+use crate::syntax::{error::Error, parse::CommentChar};
 
-use super::expression::ParsedExpr;
+use super::expression::{Expr, ParsedExpr};
 
 ////////////////////////////////////////////////////////////////
 
 /// Parser that matches inline whitepsace only. i.e. Whitespace not part of a newline. This differs
-/// from chumsky's builtin whitespace parser which does match newline characters.
+/// from chumsky's builtin whitespace parser which does match newline characters. A '\\' followed
+/// by a newline continues a command onto the next line so is also matched.
 ///
 /// # Returns
 /// A parser matching inline whitespace.
 ///   
-pub fn whitespace() -> Repeated<impl Parser<char, (), Error = Error> + Copy + Clone> {
+pub fn whitespace() -> Repeated<impl Parser<char, (), Error = Error> + Copy> {
     filter(|c: &char| c.is_inline_whitespace())
         .ignored()
+        .or(just('\\').ignore_then(newline()))
         .repeated()
 }
 
 ////////////////////////////////////////////////////////////////
 
+/// Parser that matches a script comment, running to the end of the line.
+///
+/// # Arguments
+/// * `comment_char` - Characters that may begin the comment.
+///
+/// # Returns
+/// A parser matching a script comment, outputting the text following the comment character.
+///
+pub fn script_comment(comment_char: CommentChar) -> impl Parser<char, Expr, Error = Error> + Clone {
+    filter(move |c: &char| comment_char.matches(*c))
+        .ignore_then(take_until(choice((newline(), end())).rewind()))
+        .map(|(s, _)| String::from_iter(s))
+        .map(Expr::ScriptComment)
+}
+
+////////////////////////////////////////////////////////////////
+
 /// Parser that matches unsigned integers. This differs from chumsky's builtin text::int parser in
 /// that it allows leading 0's.
 ///
@@ -29,7 +52,7 @@ pub fn whitespace() -> Repeated<impl Parser<char, (), Error = Error> + Copy + Cl
 /// # Returns
 /// A parser matching unsigned integers.
 ///   
-pub fn uint(radix: u32) -> impl Parser<char, String, Error = Error> + Copy + Clone {
+pub fn uint(radix: u32) -> impl Parser<char, String, Error = Error> + Copy {
     filter(move |c: &char| c.is_digit(radix))
         .map(Some)
         .chain::<char, Vec<_>, _>(filter(move |c: &char| c.is_digit(radix)).repeated())
@@ -38,6 +61,44 @@ pub fn uint(radix: u32) -> impl Parser<char, String, Error = Error> + Copy + Clo
 
 ////////////////////////////////////////////////////////////////
 
+/// Parser that matches unsigned integer literals. These may be decimal, hexadecimal prefixed with
+/// '$' or binary prefixed with '%'. The digits of binary literals may be separated by '_'.
+///
+/// # Returns
+/// A parser outputting the literal's prefix, its digits as written and its radix. Use uint_value
+/// to get the literal's value.
+///
+pub fn uint_literal() -> impl Parser<char, (String, String, u32), Error = Error> + Clone {
+    let dec = uint(10).map(|digits| (String::new(), digits, 10));
+
+    let hex = just('$')
+        .ignore_then(uint(16))
+        .map(|digits| (String::from("$"), digits, 16));
+
+    let bin = just('%')
+        .ignore_then(
+            filter(|c: &char| c.is_digit(2))
+                .map(Some)
+                .chain::<char, Vec<_>, _>(filter(|c: &char| c.is_digit(2) || *c == '_').repeated())
+                .collect::<String>(),
+        )
+        .map(|digits| (String::from("%"), digits, 2));
+
+    choice((dec, hex, bin))
+}
+
+/// Return the value of an unsigned integer literal's digits, ignoring any '_' separators.
+///
+/// # Returns
+/// The value or None if it doesn't fit in a u32.
+///
+pub fn uint_value(digits: &str, radix: u32) -> Option<u32> {
+    let digits: String = digits.chars().filter(|c| *c != '_').collect();
+    u32::from_str_radix(&digits, radix).ok()
+}
+
+////////////////////////////////////////////////////////////////
+
 /// Takes a parser that outputs an expression and outputs a parser that outputs a comma seperated
 /// list of those expressions.  
 ///
@@ -85,7 +146,7 @@ where
 {
     text::keyword(cmd)
         .then(whitespace())
-        .ignore_then(comma_seperated_list(parsers).map(|args| args.map(|arg| Box::new(arg))))
+        .ignore_then(comma_seperated_list(parsers).map(|args| args.map(Box::new)))
         .boxed()
 }
 
@@ -114,3 +175,30 @@ where
 }
 
 ////////////////////////////////////////////////////////////////
+
+/// Parse a command and a list of at least one parameter. Unlike command_variadic, parameters may
+/// be separated by whitespace alone as well as by commas.
+///
+/// # Arguments
+///
+/// * `cmd` - The command.
+/// * `expr` - Parser for parsing the commands parameters.
+///
+/// # Returns
+/// A parser that parses the command and it's parameters but only returns it's parameters in a Vec.
+///
+pub fn command_list<'a, E>(
+    cmd: &'a str,
+    param_parser: E,
+) -> impl Parser<char, Vec<ParsedExpr>, Error = Error> + 'a
+where
+    E: Parser<char, ParsedExpr, Error = Error> + 'a,
+{
+    text::keyword(cmd).then(whitespace()).ignore_then(
+        param_parser
+            .separated_by(just(',').padded_by(whitespace()).or_not())
+            .at_least(1),
+    )
+}
+
+////////////////////////////////////////////////////////////////
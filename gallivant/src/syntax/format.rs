@@ -0,0 +1,164 @@
+use super::{
+    error::{line_col, Error},
+    expression::{Expr, ParsedExpr},
+    parse::parse_from_str,
+    tokenize::{tokenize, Token},
+};
+
+////////////////////////////////////////////////////////////////
+// ...
+////////////////////////////////////////////////////////////////
+
+/// Format a script so that every command is on its own line with a single space following the
+/// command and each comma separating its arguments. The contents of conditionals, loops and test
+/// groups are indented. Arguments are otherwise kept exactly as written e.g. hex literals remain
+/// hex.
+///
+/// Script comments following a command on the same line are kept on that line. Any other comment
+/// is placed on its own line. Runs of blank lines are reduced to a single blank line.
+///
+/// # Arguments
+/// * `script` - Script to format.
+///
+/// # Returns
+/// The formatted script or the errors encountered parsing it.
+///
+pub fn format_script(script: &str) -> Result<String, Vec<Error>> {
+    const INDENT: &str = "    ";
+
+    let ast = parse_from_str(script)?;
+    let chars: Vec<char> = script.chars().collect();
+
+    let mut output = String::new();
+    let mut depth = 0_usize;
+
+    // Line that the previous expression ended on and whether it was a command.
+    let mut previous: Option<(usize, bool)> = None;
+
+    for expr in ast.iter() {
+        let (line, _) = line_col(expr.span(), script);
+        let (end_line, _) = line_col(&(expr.span().end..expr.span().end), script);
+
+        if let Expr::ScriptComment(comment) = expr.expression() {
+            // Comments on the same line as a command stay attached to it.
+            if previous == Some((line, true)) {
+                output.push(' ');
+            } else {
+                push_line(&mut output, previous, line, &INDENT.repeat(depth));
+            }
+
+            // Keep whichever character the comment was written with.
+            let comment_char = chars[expr.span().clone()]
+                .iter()
+                .find(|c| !c.is_whitespace())
+                .unwrap_or(&';');
+
+            output.push(*comment_char);
+            output.push_str(comment.trim_end());
+            previous = Some((end_line, false));
+            continue;
+        }
+
+        if is_block_end(expr) {
+            depth = depth.saturating_sub(1);
+        }
+
+        push_line(&mut output, previous, line, &INDENT.repeat(depth));
+        output.push_str(&format_command(expr, &chars));
+        previous = Some((end_line, true));
+
+        if is_block_start(expr) {
+            depth += 1;
+        }
+    }
+
+    if !output.is_empty() {
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Start a new line in the output, keeping a single blank line if there was at least one between
+/// the previous expression and the next in the original script.
+///
+/// # Arguments
+/// * `output` - Formatted script so far.
+/// * `previous` - Line the previous expression ended on, if any.
+/// * `line` - Line the next expression starts on.
+/// * `indent` - Indentation for the new line.
+///
+fn push_line(output: &mut String, previous: Option<(usize, bool)>, line: usize, indent: &str) {
+    if let Some((previous_line, _)) = previous {
+        output.push('\n');
+
+        if line > previous_line + 1 {
+            output.push('\n');
+        }
+    }
+
+    output.push_str(indent);
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Format a single command and its arguments.
+///
+/// # Arguments
+/// * `expr` - Command to format.
+/// * `chars` - Characters of the script the command was parsed from.
+///
+fn format_command(expr: &ParsedExpr, chars: &[char]) -> String {
+    let source = String::from_iter(&chars[expr.span().clone()]);
+
+    let mut output = String::new();
+    let mut separated = false;
+
+    for token in tokenize(&source) {
+        match token {
+            Token::Whitespace(_) | Token::Newline(_) => separated = true,
+            Token::Comma(_) => {
+                output.push(',');
+                separated = true;
+            }
+            token => {
+                if separated && !output.is_empty() {
+                    output.push(' ');
+                }
+
+                output.extend(&chars[expr.span().start..][token.span().clone()]);
+                separated = false;
+            }
+        }
+    }
+
+    output
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Return true if the expression opens a block whose contents should be indented.
+///
+fn is_block_start(expr: &ParsedExpr) -> bool {
+    matches!(
+        expr.expression(),
+        Expr::IfTestPass
+            | Expr::IfTestFail
+            | Expr::Repeat(_)
+            | Expr::TestGroup(_)
+            | Expr::Define(_)
+    )
+}
+
+/// Return true if the expression closes a block whose contents should be indented.
+///
+fn is_block_end(expr: &ParsedExpr) -> bool {
+    matches!(
+        expr.expression(),
+        Expr::EndIf | Expr::EndRepeat | Expr::EndTestGroup | Expr::EndDefine
+    )
+}
+
+////////////////////////////////////////////////////////////////
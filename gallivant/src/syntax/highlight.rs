@@ -0,0 +1,162 @@
+use super::tokenize::{tokenize, Token};
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Terminal colors that can be used when highlighting scripts.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    Gray,
+
+    /// One of the 256 colors supported by most terminals.
+    Fixed(u8),
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Colors used for each kind of token when highlighting scripts. Tokens without a color i.e.
+/// whitespace and commas are left as is.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorScheme {
+    pub keyword: Color,
+    pub string: Color,
+    pub uint: Color,
+    pub comment: Color,
+
+    /// Color for anything that isn't a valid token.
+    pub unknown: Color,
+}
+
+////////////////////////////////////////////////////////////////
+// construction / conversion
+////////////////////////////////////////////////////////////////
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        Self {
+            keyword: Color::Cyan,
+            string: Color::Green,
+            uint: Color::Yellow,
+            comment: Color::Gray,
+            unknown: Color::Red,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// methods
+////////////////////////////////////////////////////////////////
+
+impl Color {
+    /// Return the ANSI escape sequence that sets the foreground to this color.
+    ///
+    pub fn escape_code(&self) -> String {
+        match self {
+            Color::Black => String::from("\x1b[30m"),
+            Color::Red => String::from("\x1b[31m"),
+            Color::Green => String::from("\x1b[32m"),
+            Color::Yellow => String::from("\x1b[33m"),
+            Color::Blue => String::from("\x1b[34m"),
+            Color::Magenta => String::from("\x1b[35m"),
+            Color::Cyan => String::from("\x1b[36m"),
+            Color::White => String::from("\x1b[37m"),
+            Color::Gray => String::from("\x1b[2;90m"),
+            Color::Fixed(color) => format!("\x1b[38;5;{color}m"),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+impl ColorScheme {
+    /// Apply ANSI color codes to a script according to this color scheme.
+    ///
+    /// # Arguments
+    /// * `script` - Script to highlight. Needn't be valid.
+    ///
+    /// # Returns
+    /// The script with each token wrapped in the escape codes for its color.
+    ///
+    pub fn highlight(&self, script: &str) -> String {
+        const RESET: &str = "\x1b[0m";
+
+        let chars: Vec<char> = script.chars().collect();
+
+        let mut output = String::new();
+        for token in tokenize(script) {
+            let text = String::from_iter(&chars[token.span().clone()]);
+
+            let color = match token {
+                Token::Keyword(..) => Some(self.keyword),
+                Token::StringLiteral(..) => Some(self.string),
+                Token::UIntLiteral(..) => Some(self.uint),
+                Token::Comment(..) => Some(self.comment),
+                Token::Unknown(..) => Some(self.unknown),
+                Token::Comma(_) | Token::Whitespace(_) | Token::Newline(_) => None,
+            };
+
+            match color {
+                Some(color) => output.push_str(&format!("{}{text}{RESET}", color.escape_code())),
+                None => output.push_str(&text),
+            }
+        }
+
+        output
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// ...
+////////////////////////////////////////////////////////////////
+
+/// Apply ANSI color codes to a script using the default color scheme.
+///
+pub fn highlight(script: &str) -> String {
+    ColorScheme::default().highlight(script)
+}
+
+////////////////////////////////////////////////////////////////
+// tests
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_highlight() {
+        let output = highlight("WAIT 10, \"x\" ;c\n?");
+
+        assert_eq!(
+            output,
+            "\x1b[36mWAIT\x1b[0m \x1b[33m10\x1b[0m, \x1b[32m\"x\"\x1b[0m \x1b[2;90m;c\x1b[0m\n\x1b[31m?\x1b[0m"
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_highlight_custom_scheme() {
+        let scheme = ColorScheme {
+            keyword: Color::Fixed(200),
+            ..Default::default()
+        };
+
+        assert_eq!(scheme.highlight("FLUSH"), "\x1b[38;5;200mFLUSH\x1b[0m");
+    }
+}
+
+////////////////////////////////////////////////////////////////
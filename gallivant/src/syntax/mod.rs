@@ -1,17 +1,24 @@
 mod error;
 mod evaluate;
 mod expression;
+mod format;
+mod highlight;
 mod parse;
 mod state;
+mod tokenize;
 
 ////////////////////////////////////////////////////////////////
 // exports
 ////////////////////////////////////////////////////////////////
 
-pub use error::{Error, ErrorReason};
+pub(crate) use error::with_location;
+pub use error::{line_col, Error, ErrorReason};
 pub use evaluate::evaluate;
-pub use expression::{Expr, ParsedExpr};
-pub use parse::parse_from_str;
-pub use state::EvalState;
+pub use expression::{Expr, ExprKind, ParsedExpr};
+pub use format::format_script;
+pub use highlight::{highlight, Color, ColorScheme};
+pub use parse::{parse_from_str_with_comment_char, parse_script, CommentChar};
+pub use state::{EvalState, TimeZone};
+pub use tokenize::{tokenize, Token, KEYWORDS};
 
 ////////////////////////////////////////////////////////////////
@@ -1,38 +1,120 @@
+use std::collections::HashMap;
+
 use chumsky::prelude::*;
 
 use super::{
     error::{Error, ErrorReason},
-    expression::{parse, ExprKind, ParsedExpr},
+    expression::{parse, Expr, ExprKind, ParsedExpr},
 };
 
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Characters that may begin a script comment.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CommentChar {
+    Semicolon,
+    Hash,
+
+    /// Comments may begin with either ';' or '#'.
+    #[default]
+    Both,
+}
+
+////////////////////////////////////////////////////////////////
+// methods
+////////////////////////////////////////////////////////////////
+
+impl CommentChar {
+    /// Return true if a character begins a script comment.
+    ///
+    pub fn matches(&self, c: char) -> bool {
+        match self {
+            CommentChar::Semicolon => c == ';',
+            CommentChar::Hash => c == '#',
+            CommentChar::Both => c == ';' || c == '#',
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// ...
 ////////////////////////////////////////////////////////////////
 
 pub fn parse_from_str(script: &str) -> Result<Vec<ParsedExpr>, Vec<Error>> {
-    parser().parse(script)
+    parse_from_str_with_comment_char(script, CommentChar::default())
+}
+
+/// Parse a script, only treating text following the given characters as script comments. A '\\'
+/// at the very end of a line continues the command onto the next line.
+///
+pub fn parse_from_str_with_comment_char(
+    script: &str,
+    comment_char: CommentChar,
+) -> Result<Vec<ParsedExpr>, Vec<Error>> {
+    let ast = parser(comment_char).parse(script)?;
+    validate_conditionals(&ast)?;
+    validate_loops(&ast)?;
+    validate_test_groups(&ast)?;
+    validate_macro_definitions(&ast)?;
+    validate_labels(&ast)?;
+    Ok(ast)
+}
+
+/// Parse a script into expressions without creating an interpreter. Each expression may then be
+/// evaluated in order using `evaluate`, with a single `EvalState` shared between them, to produce
+/// the requests the script makes.
+///
+/// Unlike `Interpreter`, this doesn't expand macros, check SCRIPTVERSION or analyse the script for
+/// problems, and control flow such as REPEAT and IFTESTPASS is left to the caller.
+///
+/// # Stability
+/// Part of the crate's stable API. Its signature won't change without a major version bump.
+///
+/// # Arguments
+/// * `script` - Script to parse.
+///
+/// # Returns
+/// The script's expressions in order, or the errors encountered parsing it.
+///
+pub fn parse_script(script: &str) -> Result<Vec<ParsedExpr>, Vec<crate::error::Error>> {
+    parse_from_str(script).map_err(|errors| errors.into_iter().map(Into::into).collect())
 }
 
 ////////////////////////////////////////////////////////////////
 
-fn parser() -> impl Parser<char, Vec<ParsedExpr>, Error = Error> {
+fn parser(comment_char: CommentChar) -> impl Parser<char, Vec<ParsedExpr>, Error = Error> {
     let command = choice((
         ExprKind::HPMode.parser(),
         ExprKind::Comment.parser(),
+        ExprKind::Echo.parser(),
         ExprKind::Wait.parser(),
         ExprKind::OpenDialog.parser(),
         ExprKind::WaitDialog.parser(),
+        ExprKind::Notify.parser(),
         ExprKind::Flush.parser(),
+        ExprKind::SetBaud.parser(),
         ExprKind::Protocol.parser(),
+        ExprKind::SetMeasurementFormat.parser(),
         ExprKind::Print.parser(),
+        ExprKind::PrintRaw.parser(),
         ExprKind::SetTimeFormat.parser(),
         ExprKind::SetTime.parser(),
+        ExprKind::SetTimeZone.parser(),
         ExprKind::SetOption.parser(),
+        ExprKind::SetOptionRange.parser(),
         ExprKind::TCUClose.parser(),
         ExprKind::TCUOpen.parser(),
-        ExprKind::TCUTest.parser(),
         ExprKind::PrinterSet.parser(),
-        ExprKind::PrinterTest.parser(),
         // ExprKind::IssueTest.parser(),
         // ExprKind::TestResult.parser(),
+    ))
+    .padded_by(parse::whitespace());
+
+    // Commands communicating directly with the printer rather than via the TCU.
+    let usb = choice((
         ExprKind::USBOpen.parser(),
         ExprKind::USBClose.parser(),
         ExprKind::USBPrint.parser(),
@@ -40,37 +122,332 @@ fn parser() -> impl Parser<char, Vec<ParsedExpr>, Error = Error> {
         ExprKind::USBSetTime.parser(),
         ExprKind::USBSetOption.parser(),
         ExprKind::USBPrinterSet.parser(),
+    ))
+    .padded_by(parse::whitespace());
+
+    // Commands testing measurements or values. Kept separate from the other commands since
+    // choice() only accepts a limited number of parsers.
+    let test = choice((
+        ExprKind::TCUTest.parser(),
+        ExprKind::WarnTest.parser(),
+        ExprKind::MaskTest.parser(),
+        ExprKind::TCUExact.parser(),
+        ExprKind::Assert.parser(),
+        ExprKind::PrinterTest.parser(),
         ExprKind::USBPrinterTest.parser(),
+        ExprKind::USBTest.parser(),
+        ExprKind::USBExact.parser(),
+    ))
+    .padded_by(parse::whitespace());
+
+    // Commands performing arithmetic on or printing variables, or naming channels.
+    let arithmetic = choice((
+        ExprKind::AddVar.parser(),
+        ExprKind::SubVar.parser(),
+        ExprKind::MulVar.parser(),
+        ExprKind::DivVar.parser(),
+        ExprKind::DumpVars.parser(),
+        ExprKind::DefineChannel.parser(),
+    ))
+    .padded_by(parse::whitespace());
+
+    // Commands controlling the flow of the script itself.
+    let control = choice((
+        ExprKind::IfTestPass.parser(),
+        ExprKind::IfTestFail.parser(),
+        ExprKind::EndIf.parser(),
+        ExprKind::Repeat.parser(),
+        ExprKind::EndRepeat.parser(),
+        ExprKind::Checkpoint.parser(),
+        ExprKind::Label.parser(),
+        ExprKind::TestGroup.parser(),
+        ExprKind::EndTestGroup.parser(),
+        ExprKind::GlobalTimeout.parser(),
+        ExprKind::MaxFailures.parser(),
+        ExprKind::ScriptVersion.parser(),
+        ExprKind::TraceOn.parser(),
+        ExprKind::TraceOff.parser(),
+        ExprKind::SetNoEchoOn.parser(),
+        ExprKind::SetNoEchoOff.parser(),
+        ExprKind::Define.parser(),
+        ExprKind::EndDefine.parser(),
+        ExprKind::UseMacro.parser(),
+    ))
+    .padded_by(parse::whitespace());
+
+    // Commands operating on a range of relays. These expand to a command for each relay so are
+    // tried before the single relay forms.
+    let range = choice((
+        relay_range("TCUCLOSE", Expr::TCUClose),
+        relay_range("TCUOPEN", Expr::TCUOpen),
     ))
     .padded_by(parse::whitespace());
 
     ////////////////
 
-    choice((
+    let single = choice((
         command,
+        usb,
+        test,
+        arithmetic,
+        control,
         ExprKind::UInt.parser(),
         ExprKind::String.parser(),
-        ExprKind::ScriptComment.parser(),
+        parse::script_comment(comment_char)
+            .padded_by(parse::whitespace())
+            .map_with_span(ParsedExpr::from_kind_and_span),
     ))
-    .separated_by(text::newline().repeated())
-    .padded()
-    .then_ignore(end())
-    .map_err(|error| {
-        if let ErrorReason::Unexpected { span, .. } = error.reason() {
-            return Error::unrecognised_command(span.clone());
+    .map(|expr| vec![expr]);
+
+    choice((range, single))
+        .separated_by(text::newline().repeated())
+        .flatten()
+        .padded()
+        .then_ignore(end())
+        .map_err(|error| {
+            if let ErrorReason::Unexpected { span, .. } = error.reason() {
+                return Error::unrecognised_command(span.clone());
+            }
+
+            error
+        })
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Parser for a command taking a range of relays e.g. TCUCLOSE 1...8. The range is expanded into
+/// a separate command for each relay, each spanning the whole of the range command.
+///
+/// # Arguments
+/// * `cmd` - The command.
+/// * `expr` - Constructor for the expression of a single relay command.
+///
+/// # Returns
+/// A parser outputting an expression for each relay in the range, in order.
+///
+fn relay_range(
+    cmd: &'static str,
+    expr: fn(Box<ParsedExpr>) -> Expr,
+) -> impl Parser<char, Vec<ParsedExpr>, Error = Error> + Clone {
+    let relay = ExprKind::UInt
+        .parser()
+        .padded_by(parse::whitespace())
+        .validate(|relay, span, emit| {
+            let Expr::UInt(value) = relay.expression() else {
+                unreachable!("UInt parser only outputs UInt expressions");
+            };
+
+            if *value > 255 {
+                emit(Error::argument_value_size(
+                    span,
+                    i64::from(*value),
+                    (0, 255),
+                ));
+            }
+
+            *value
+        });
+
+    text::keyword(cmd)
+        .then(parse::whitespace())
+        .ignore_then(relay.clone())
+        .then_ignore(just("..."))
+        .then(relay)
+        .validate(move |(first, last), span: std::ops::Range<usize>, emit| {
+            if first > last {
+                emit(Error::invalid_range(span.clone(), first, last));
+            }
+
+            (first..=last.min(255))
+                .map(|relay| {
+                    let relay = ParsedExpr::from_kind_and_span(Expr::UInt(relay), span.clone());
+                    ParsedExpr::from_kind_and_span(expr(Box::new(relay)), span.clone())
+                })
+                .collect()
+        })
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Check that every conditional in a script is closed by a matching ENDIF and that every ENDIF
+/// closes a conditional. Conditionals within a macro definition must be closed within it.
+///
+fn validate_conditionals(ast: &[ParsedExpr]) -> Result<(), Vec<Error>> {
+    let mut errors = Vec::new();
+    let mut open = Vec::new();
+
+    // Number of conditionals opened before the current macro definition, which can't be closed
+    // within it.
+    let mut outer = 0;
+
+    for expr in ast {
+        match expr.expression() {
+            Expr::IfTestPass | Expr::IfTestFail => open.push(expr.span()),
+            Expr::EndIf if open.len() > outer => _ = open.pop(),
+            Expr::EndIf => errors.push(Error::unmatched_endif(expr.span().clone())),
+            Expr::Define(_) => outer = open.len(),
+            Expr::EndDefine => {
+                let unclosed = open.drain(outer..);
+                errors.extend(unclosed.map(|span| Error::unclosed_conditional(span.clone())));
+                outer = 0;
+            }
+            _ => (),
         }
+    }
+
+    errors.extend(
+        open.into_iter()
+            .map(|span| Error::unclosed_conditional(span.clone())),
+    );
 
-        error
-    })
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Check that every REPEAT in a script is closed by a matching ENDREPEAT and that every ENDREPEAT
+/// closes a REPEAT. Loops within a macro definition must be closed within it.
+///
+fn validate_loops(ast: &[ParsedExpr]) -> Result<(), Vec<Error>> {
+    let mut errors = Vec::new();
+    let mut open = Vec::new();
+
+    // Number of loops opened before the current macro definition, which can't be closed within it.
+    let mut outer = 0;
+
+    for expr in ast {
+        match expr.expression() {
+            Expr::Repeat(_) => open.push(expr.span()),
+            Expr::EndRepeat if open.len() > outer => _ = open.pop(),
+            Expr::EndRepeat => errors.push(Error::unmatched_end_repeat(expr.span().clone())),
+            Expr::Define(_) => outer = open.len(),
+            Expr::EndDefine => {
+                let unclosed = open.drain(outer..);
+                errors.extend(unclosed.map(|span| Error::unclosed_repeat(span.clone())));
+                outer = 0;
+            }
+            _ => (),
+        }
+    }
+
+    errors.extend(
+        open.into_iter()
+            .map(|span| Error::unclosed_repeat(span.clone())),
+    );
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
 }
 
 ////////////////////////////////////////////////////////////////
-/// tests
+
+/// Check that every test group in a script is closed by an ENDTESTGROUP, that every ENDTESTGROUP
+/// closes a test group and that test groups aren't nested.
+///
+fn validate_test_groups(ast: &[ParsedExpr]) -> Result<(), Vec<Error>> {
+    let mut errors = Vec::new();
+    let mut open = None;
+
+    for expr in ast {
+        match expr.expression() {
+            Expr::TestGroup(_) if open.is_some() => {
+                errors.push(Error::nested_test_group(expr.span().clone()))
+            }
+            Expr::TestGroup(_) => open = Some(expr.span()),
+            Expr::EndTestGroup => open.take().map_or_else(
+                || errors.push(Error::unmatched_end_test_group(expr.span().clone())),
+                |_| (),
+            ),
+            _ => (),
+        }
+    }
+
+    errors.extend(open.map(|span| Error::unclosed_test_group(span.clone())));
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Check that every macro definition in a script is closed by an ENDDEFINE, that every ENDDEFINE
+/// closes a definition and that definitions aren't nested.
+///
+fn validate_macro_definitions(ast: &[ParsedExpr]) -> Result<(), Vec<Error>> {
+    let mut errors = Vec::new();
+    let mut open = None;
+
+    for expr in ast {
+        match expr.expression() {
+            Expr::Define(_) if open.is_some() => {
+                errors.push(Error::nested_define(expr.span().clone()))
+            }
+            Expr::Define(_) => open = Some(expr.span()),
+            Expr::EndDefine => open.take().map_or_else(
+                || errors.push(Error::unmatched_end_define(expr.span().clone())),
+                |_| (),
+            ),
+            _ => (),
+        }
+    }
+
+    errors.extend(open.map(|span| Error::unclosed_define(span.clone())));
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Check that no two LABELs in a script have the same name, so that it's clear which one the
+/// script is started from.
+///
+fn validate_labels(ast: &[ParsedExpr]) -> Result<(), Vec<Error>> {
+    let mut errors = Vec::new();
+    let mut labels: HashMap<&str, std::ops::Range<usize>> = HashMap::new();
+
+    for expr in ast {
+        let Expr::Label(arg) = expr.expression() else {
+            continue;
+        };
+
+        let Expr::String(name) = arg.expression() else {
+            unreachable!("Invalid LABEL arg {arg:?}");
+        };
+
+        match labels.get(name.as_str()) {
+            Some(first) => errors.push(Error::duplicate_label(expr.span().clone(), first.clone())),
+            None => _ = labels.insert(name, expr.span().clone()),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// tests
 ////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
 mod tests {
-    use crate::syntax::Expr;
+    use crate::{error::ErrorNote, execution::MeasurementFormat, syntax::TimeZone};
 
     use super::*;
 
@@ -80,19 +457,38 @@ mod tests {
     fn test_parse_commands() {
         let script = r#"
 HPMODE
-COMMENT "Test"
+COMMENT "Test" 42, "!"
+ECHO "Echo"
+ECHO COUNT
 WAIT 1234
 OPENDIALOG "Hello"
 WAITDIALOG "PLEASE WAIT"
+NOTIFY "relay_1", 1
 FLUSH
+SETBAUD 115200
 PROTOCOL
+SETMEASUREMENTFORMAT DEC
 PRINT "print me"
+PRINTRAW 0, 255, $AB
+PRINTRAW 1 2
 SETTIMEFORMAT $A6
 SETTIME
+SETTIMEZONE UTC
 SETOPTION 4, 6
+SETOPTION_RANGE 1, 20
 TCUCLOSE 4
 TCUOPEN $F
 TCUTEST 5, 12000, 56000, 0, "error"
+WARNTEST 5, 12000, 56000, 2, "warning"
+MASKTEST 5, $0F, $0A, 1, "error"
+TCUEXACT 8, $29, 0, "wrong version"
+ASSERT VOLTAGE, 1000, 2000, "error"
+ADDVAR TOTAL, COUNT, 1
+SUBVAR TOTAL, 10, COUNT
+MULVAR TOTAL, COUNT, $10
+DIVVAR TOTAL, COUNT, STEP
+DUMPVARS
+DEFINECHANNEL "battery_voltage", 4
 PRINTERSET 1
 PRINTERTEST 4,133, 987,5,"error message"
 USBOPEN
@@ -103,26 +499,73 @@ USBSETTIME
 USBSETOPTION 5, 9
 USBPRINTERSET 6
 USBPRINTERTEST 4, 133, 987, 5, "error message"
+USBTEST 4, 133, 987, 5, "error message"
+USBEXACT 4, 133, 5, "error message"
+CHECKPOINT "phase 1"
+LABEL "start"
+TESTGROUP "Power supply tests"
+ENDTESTGROUP
+GLOBALTIMEOUT 60000
+MAXFAILURES 10
+SCRIPTVERSION 0, 1
+TRACE ON
+TRACE OFF
+SETNOECHO ON
+SETNOECHO OFF
+DEFINE "setup"
+USEMACRO "setup"
+ENDDEFINE
+REPEAT 3
+REPEAT COUNT
+ENDREPEAT
+ENDREPEAT
         "#;
 
         assert_eq!(
             parse_from_str(script).unwrap(),
             [
                 Expr::HPMode.into(),
-                Expr::Comment(Expr::String("Test".to_owned()).into()).into(),
+                Expr::Comment(vec![
+                    Expr::String("Test".to_owned()).into(),
+                    Expr::UInt(42).into(),
+                    Expr::String("!".to_owned()).into(),
+                ])
+                .into(),
+                Expr::Echo(Expr::String("Echo".to_owned()).into()).into(),
+                Expr::Echo(Expr::Variable("COUNT".to_owned()).into()).into(),
                 Expr::Wait(Expr::UInt(1234).into()).into(),
                 Expr::OpenDialog(Expr::String("Hello".to_owned()).into()).into(),
                 Expr::WaitDialog(Expr::String("PLEASE WAIT".to_owned()).into()).into(),
+                Expr::Notify {
+                    key: Expr::String("relay_1".to_owned()).into(),
+                    value: Expr::UInt(1).into(),
+                }
+                .into(),
                 Expr::Flush.into(),
+                Expr::SetBaud(Expr::UInt(115200).into()).into(),
                 Expr::Protocol.into(),
+                Expr::SetMeasurementFormat(MeasurementFormat::Decimal).into(),
                 Expr::Print(vec![Expr::String("print me".to_owned()).into()]).into(),
+                Expr::PrintRaw(vec![
+                    Expr::UInt(0).into(),
+                    Expr::UInt(255).into(),
+                    Expr::UInt(0xAB).into(),
+                ])
+                .into(),
+                Expr::PrintRaw(vec![Expr::UInt(1).into(), Expr::UInt(2).into()]).into(),
                 Expr::SetTimeFormat(Expr::UInt(0xA6).into()).into(),
                 Expr::SetTime.into(),
+                Expr::SetTimeZone(TimeZone::Utc).into(),
                 Expr::SetOption {
                     option: Expr::UInt(4).into(),
                     setting: Expr::UInt(6).into(),
                 }
                 .into(),
+                Expr::SetOptionRange {
+                    min: Expr::UInt(1).into(),
+                    max: Expr::UInt(20).into(),
+                }
+                .into(),
                 Expr::TCUClose(Expr::UInt(4).into()).into(),
                 Expr::TCUOpen(Expr::UInt(0xF).into()).into(),
                 Expr::TCUTest {
@@ -133,6 +576,66 @@ USBPRINTERTEST 4, 133, 987, 5, "error message"
                     message: Expr::String("error".to_owned()).into(),
                 }
                 .into(),
+                Expr::WarnTest {
+                    channel: Expr::UInt(5).into(),
+                    min: Expr::UInt(12000).into(),
+                    max: Expr::UInt(56000).into(),
+                    retries: Expr::UInt(2).into(),
+                    message: Expr::String("warning".to_owned()).into(),
+                }
+                .into(),
+                Expr::MaskTest {
+                    channel: Expr::UInt(5).into(),
+                    mask: Expr::UInt(0x0F).into(),
+                    expected: Expr::UInt(0x0A).into(),
+                    retries: Expr::UInt(1).into(),
+                    message: Expr::String("error".to_owned()).into(),
+                }
+                .into(),
+                Expr::TCUExact {
+                    channel: Expr::UInt(8).into(),
+                    expected: Expr::UInt(0x29).into(),
+                    retries: Expr::UInt(0).into(),
+                    message: Expr::String("wrong version".to_owned()).into(),
+                }
+                .into(),
+                Expr::Assert {
+                    var: Expr::Variable("VOLTAGE".to_owned()).into(),
+                    min: Expr::UInt(1000).into(),
+                    max: Expr::UInt(2000).into(),
+                    message: Expr::String("error".to_owned()).into(),
+                }
+                .into(),
+                Expr::AddVar {
+                    dest: Expr::Variable("TOTAL".to_owned()).into(),
+                    src1: Expr::Variable("COUNT".to_owned()).into(),
+                    src2: Expr::UInt(1).into(),
+                }
+                .into(),
+                Expr::SubVar {
+                    dest: Expr::Variable("TOTAL".to_owned()).into(),
+                    src1: Expr::UInt(10).into(),
+                    src2: Expr::Variable("COUNT".to_owned()).into(),
+                }
+                .into(),
+                Expr::MulVar {
+                    dest: Expr::Variable("TOTAL".to_owned()).into(),
+                    src1: Expr::Variable("COUNT".to_owned()).into(),
+                    src2: Expr::UInt(0x10).into(),
+                }
+                .into(),
+                Expr::DivVar {
+                    dest: Expr::Variable("TOTAL".to_owned()).into(),
+                    src1: Expr::Variable("COUNT".to_owned()).into(),
+                    src2: Expr::Variable("STEP".to_owned()).into(),
+                }
+                .into(),
+                Expr::DumpVars.into(),
+                Expr::DefineChannel {
+                    name: Expr::String("battery_voltage".to_owned()).into(),
+                    channel: Expr::UInt(4).into(),
+                }
+                .into(),
                 Expr::PrinterSet(Expr::UInt(1).into()).into(),
                 Expr::PrinterTest {
                     channel: Expr::UInt(4).into(),
@@ -164,6 +667,43 @@ USBPRINTERTEST 4, 133, 987, 5, "error message"
                     message: Expr::String("error message".to_owned()).into(),
                 }
                 .into(),
+                Expr::USBTest {
+                    channel: Expr::UInt(4).into(),
+                    min: Expr::UInt(133).into(),
+                    max: Expr::UInt(987).into(),
+                    retries: Expr::UInt(5).into(),
+                    message: Expr::String("error message".to_owned()).into(),
+                }
+                .into(),
+                Expr::USBExact {
+                    channel: Expr::UInt(4).into(),
+                    expected: Expr::UInt(133).into(),
+                    retries: Expr::UInt(5).into(),
+                    message: Expr::String("error message".to_owned()).into(),
+                }
+                .into(),
+                Expr::Checkpoint(Expr::String("phase 1".to_owned()).into()).into(),
+                Expr::Label(Expr::String("start".to_owned()).into()).into(),
+                Expr::TestGroup(Expr::String("Power supply tests".to_owned()).into()).into(),
+                Expr::EndTestGroup.into(),
+                Expr::GlobalTimeout(Expr::UInt(60000).into()).into(),
+                Expr::MaxFailures(Expr::UInt(10).into()).into(),
+                Expr::ScriptVersion {
+                    major: Expr::UInt(0).into(),
+                    minor: Expr::UInt(1).into(),
+                }
+                .into(),
+                Expr::TraceOn.into(),
+                Expr::TraceOff.into(),
+                Expr::SetNoEchoOn.into(),
+                Expr::SetNoEchoOff.into(),
+                Expr::Define(Expr::String("setup".to_owned()).into()).into(),
+                Expr::UseMacro(Expr::String("setup".to_owned()).into()).into(),
+                Expr::EndDefine.into(),
+                Expr::Repeat(Expr::UInt(3).into()).into(),
+                Expr::Repeat(Expr::Variable("COUNT".to_owned()).into()).into(),
+                Expr::EndRepeat.into(),
+                Expr::EndRepeat.into(),
             ]
         );
     }
@@ -175,7 +715,7 @@ USBPRINTERTEST 4, 133, 987, 5, "error message"
         let script = r#"COMMENT "Comment 1234""#;
         assert_eq!(
             parse_from_str(script).unwrap(),
-            [Expr::Comment(Expr::String("Comment 1234".to_owned()).into()).into()]
+            [Expr::Comment(vec![Expr::String("Comment 1234".to_owned()).into()]).into()]
         );
     }
 
@@ -203,6 +743,94 @@ USBPRINTERTEST 4, 133, 987, 5, "error message"
 
     ////////////////////////////////////////////////////////////////
 
+    #[test]
+    fn test_hex_arg_case_insensitive() {
+        for script in [r#"TCUOPEN $ff"#, r#"TCUOPEN $fF"#, r#"TCUOPEN $FF"#] {
+            assert_eq!(
+                parse_from_str(script).unwrap(),
+                [Expr::TCUOpen(Expr::UInt(0xFF).into()).into()],
+                "{script}"
+            );
+        }
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_invalid_hex_arg() {
+        let script = r#"TCUOPEN $G0"#;
+
+        let errors = parser(CommentChar::default()).parse(script).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors.first().unwrap().reason(),
+            ErrorReason::ArgType { .. }
+        ));
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_bin_arg() {
+        let script = r#"TCUOPEN %11111111"#;
+        assert_eq!(
+            parse_from_str(script).unwrap(),
+            [Expr::TCUOpen(Expr::UInt(255).into()).into()]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_bin_arg_separators() {
+        let script = r#"TCUOPEN %0000_1111"#;
+        assert_eq!(
+            parse_from_str(script).unwrap(),
+            [Expr::TCUOpen(Expr::UInt(15).into()).into()]
+        );
+
+        let script = r#"TCUOPEN %00001111"#;
+        assert_eq!(
+            parse_from_str(script).unwrap(),
+            [Expr::TCUOpen(Expr::UInt(15).into()).into()]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_invalid_bin_byte_arg() {
+        let script = r#"TCUOPEN %1_0000_0001"#;
+
+        let errors = parser(CommentChar::default()).parse(script).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors.first().unwrap().reason(),
+            ErrorReason::ArgValue { value: 257, .. }
+        ));
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_hex_arg_starting_with_b() {
+        let script = r#"TCUOPEN $b2"#;
+        assert_eq!(
+            parse_from_str(script).unwrap(),
+            [Expr::TCUOpen(Expr::UInt(0xB2).into()).into()]
+        );
+
+        let script = r#"TCUOPEN $b0"#;
+        assert_eq!(
+            parse_from_str(script).unwrap(),
+            [Expr::TCUOpen(Expr::UInt(0xB0).into()).into()]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
     #[test]
     fn test_dec_arg_0() {
         let script = r#"TCUOPEN 0"#;
@@ -214,11 +842,88 @@ USBPRINTERTEST 4, 133, 987, 5, "error message"
 
     ////////////////////////////////////////////////////////////////
 
+    #[test]
+    fn test_negative_dec_arg() {
+        let script = r#"TCUTEST 5, -100, 56000, 0, "error""#;
+        assert_eq!(
+            parse_from_str(script).unwrap(),
+            [Expr::TCUTest {
+                channel: Expr::UInt(5).into(),
+                min: Expr::SInt(-100).into(),
+                max: Expr::UInt(56000).into(),
+                retries: Expr::UInt(0).into(),
+                message: Expr::String("error".to_owned()).into(),
+            }
+            .into()]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_negative_hex_arg() {
+        let script = r#"TCUTEST 5, -$FF, -$0A, 0, "error""#;
+        assert_eq!(
+            parse_from_str(script).unwrap(),
+            [Expr::TCUTest {
+                channel: Expr::UInt(5).into(),
+                min: Expr::SInt(-0xFF).into(),
+                max: Expr::SInt(-0x0A).into(),
+                retries: Expr::UInt(0).into(),
+                message: Expr::String("error".to_owned()).into(),
+            }
+            .into()]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_negative_arg_out_of_range() {
+        let script = r#"TCUTEST 5, -2147483648, 0, 0, "error""#;
+        assert!(parse_from_str(script).is_ok());
+
+        let script = r#"TCUTEST 5, -2147483649, 0, 0, "error""#;
+        assert!(parse_from_str(script).is_err());
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_invalid_negative_byte_arg() {
+        let script = r#"TCUCLOSE -4"#;
+
+        let errors = parser(CommentChar::default()).parse(script).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors.first().unwrap().reason(),
+            ErrorReason::ArgType { .. }
+        ));
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_invalid_negative_uint_arg() {
+        let script = r#"WAIT -100"#;
+
+        let errors = parser(CommentChar::default()).parse(script).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors.first().unwrap().reason(),
+            ErrorReason::ArgType { .. }
+        ));
+    }
+
+    ////////////////////////////////////////////////////////////////
+
     #[test]
     fn test_invalid_string_type_arg() {
-        let script = r#"COMMENT 1234"#;
+        let script = r#"OPENDIALOG 1234"#;
 
-        let errors = parser().parse(script).unwrap_err();
+        let errors = parser(CommentChar::default()).parse(script).unwrap_err();
 
         assert_eq!(errors.len(), 1);
         assert!(matches!(
@@ -229,11 +934,26 @@ USBPRINTERTEST 4, 133, 987, 5, "error message"
 
     ////////////////////////////////////////////////////////////////
 
+    #[test]
+    fn test_comment_bare_word() {
+        let script = r#"COMMENT "Value" value"#;
+
+        let errors = parser(CommentChar::default()).parse(script).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors.first().unwrap().reason(),
+            ErrorReason::ArgType { span, .. } if *span == (16..21)
+        ));
+    }
+
+    ////////////////////////////////////////////////////////////////
+
     #[test]
     fn test_invalid_uint_type_arg() {
         let script = r#"WAIT "$F54A""#;
 
-        let errors = parser().parse(script).unwrap_err();
+        let errors = parser(CommentChar::default()).parse(script).unwrap_err();
 
         assert_eq!(errors.len(), 1);
         assert!(matches!(
@@ -248,7 +968,7 @@ USBPRINTERTEST 4, 133, 987, 5, "error message"
     fn test_invalid_uint_value_arg() {
         let script = r#"TCUCLOSE 256"#;
 
-        let errors = parser().parse(script).unwrap_err();
+        let errors = parser(CommentChar::default()).parse(script).unwrap_err();
 
         assert_eq!(errors.len(), 1);
         assert!(matches!(
@@ -259,6 +979,20 @@ USBPRINTERTEST 4, 133, 987, 5, "error message"
 
     ////////////////////////////////////////////////////////////////
 
+    #[test]
+    fn test_triple_quoted_string() {
+        let script = "OPENDIALOG \"\"\"Line \"1\"\nLine 2\"\"\"\nCOMMENT \"\"\"\"\"\"";
+        assert_eq!(
+            parse_from_str(script).unwrap(),
+            [
+                Expr::OpenDialog(Expr::String("Line \"1\"\nLine 2".to_owned()).into()).into(),
+                Expr::Comment(vec![Expr::String(String::new()).into()]).into(),
+            ]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
     #[test]
     fn test_comment_own_line() {
         let script = r#";Test comment"#;
@@ -311,6 +1045,30 @@ PRINT "test" ; Comment
 
     ////////////////////////////////////////////////////////////////
 
+    #[test]
+    fn test_comment_char() {
+        let script = "# Hash\n; Semicolon";
+        let comments = [
+            Expr::ScriptComment(" Hash".to_owned()).into(),
+            Expr::ScriptComment(" Semicolon".to_owned()).into(),
+        ];
+
+        assert_eq!(parse_from_str(script).unwrap(), comments);
+        assert_eq!(
+            parse_from_str_with_comment_char(script, CommentChar::Both).unwrap(),
+            comments
+        );
+
+        assert!(parse_from_str_with_comment_char(script, CommentChar::Semicolon).is_err());
+        assert!(parse_from_str_with_comment_char(script, CommentChar::Hash).is_err());
+        assert_eq!(
+            parse_from_str_with_comment_char("# Hash", CommentChar::Hash).unwrap(),
+            [Expr::ScriptComment(" Hash".to_owned()).into()]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
     #[test]
     fn test_commented_out_command() {
         let script = r#"; PRINT "test""#;
@@ -320,6 +1078,275 @@ PRINT "test" ; Comment
             [Expr::ScriptComment(" PRINT \"test\"".to_owned()).into(),]
         );
     }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_conditionals() {
+        let script = r#"
+IFTESTPASS
+    IFTESTFAIL
+        FLUSH
+    ENDIF
+ENDIF
+        "#;
+
+        assert_eq!(
+            parse_from_str(script).unwrap(),
+            [
+                Expr::IfTestPass.into(),
+                Expr::IfTestFail.into(),
+                Expr::Flush.into(),
+                Expr::EndIf.into(),
+                Expr::EndIf.into(),
+            ]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_setoption_missing_comma() {
+        let script = "SETOPTION 4 6";
+
+        let errors = parse_from_str(script).unwrap_err();
+
+        assert_eq!(
+            errors,
+            [Error::missing_comma(12..13, ExprKind::UInt).with_note(ErrorNote::Help(
+                "SETOPTION requires a comma between the two arguments: SETOPTION <option>, <setting>"
+                    .into()
+            ))]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_unmatched_endif() {
+        let script = "IFTESTFAIL\nENDIF\nENDIF";
+
+        let errors = parse_from_str(script).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors.first().unwrap().reason(),
+            &ErrorReason::UnmatchedEndIf { span: 17..22 }
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_unclosed_conditional() {
+        let script = "IFTESTPASS\nIFTESTFAIL\nENDIF";
+
+        let errors = parse_from_str(script).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors.first().unwrap().reason(),
+            &ErrorReason::UnclosedConditional { span: 0..10 }
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_unclosed_repeat() {
+        let script = "REPEAT 2\nENDREPEAT\nENDREPEAT\nREPEAT 2";
+
+        let errors = parse_from_str(script).unwrap_err();
+
+        assert_eq!(
+            errors,
+            [
+                Error::unmatched_end_repeat(19..28),
+                Error::unclosed_repeat(29..37),
+            ]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_nested_test_group() {
+        let script = "TESTGROUP \"a\"\nTESTGROUP \"b\"\nENDTESTGROUP";
+
+        let errors = parse_from_str(script).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors.first().unwrap().reason(),
+            &ErrorReason::NestedTestGroup { span: 14..27 }
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_unclosed_test_group() {
+        let script = "ENDTESTGROUP\nTESTGROUP \"a\"";
+
+        let errors = parse_from_str(script).unwrap_err();
+
+        assert_eq!(
+            errors,
+            [
+                Error::unmatched_end_test_group(0..12),
+                Error::unclosed_test_group(13..26),
+            ]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_unclosed_define() {
+        let script = "ENDDEFINE\nDEFINE \"a\"\nDEFINE \"b\"";
+
+        let errors = parse_from_str(script).unwrap_err();
+
+        assert_eq!(
+            errors,
+            [
+                Error::unmatched_end_define(0..9),
+                Error::nested_define(21..31),
+                Error::unclosed_define(10..20),
+            ]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_block_crossing_define() {
+        let script = "REPEAT 0\nDEFINE \"a\"\nENDREPEAT\nENDDEFINE";
+        assert_eq!(
+            parse_from_str(script).unwrap_err(),
+            [
+                Error::unmatched_end_repeat(20..29),
+                Error::unclosed_repeat(0..8)
+            ]
+        );
+
+        let script = "DEFINE \"a\"\nIFTESTPASS\nENDDEFINE\nENDIF";
+        assert_eq!(
+            parse_from_str(script).unwrap_err(),
+            [
+                Error::unclosed_conditional(11..21),
+                Error::unmatched_endif(32..37)
+            ]
+        );
+
+        let script = "DEFINE \"a\"\nREPEAT 2\nWAIT 1\nENDREPEAT\nENDDEFINE\nREPEAT 1\nENDREPEAT";
+        assert!(parse_from_str(script).is_ok());
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_duplicate_label() {
+        let script = "LABEL \"a\"\nLABEL \"b\"\nLABEL \"a\"\nLABEL \"a\"";
+
+        let errors = parse_from_str(script).unwrap_err();
+
+        assert_eq!(
+            errors,
+            [
+                Error::duplicate_label(20..29, 0..9),
+                Error::duplicate_label(30..39, 0..9),
+            ]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_relay_range() {
+        let script = "TCUCLOSE 3...5\nTCUOPEN $FE...$FF";
+
+        let relay = |relay| ParsedExpr::from_kind_and_span(Expr::UInt(relay), 0..14);
+        let close = |relay| ParsedExpr::from_kind_and_span(Expr::TCUClose(Box::new(relay)), 0..14);
+
+        let relay_open = |relay| ParsedExpr::from_kind_and_span(Expr::UInt(relay), 15..32);
+        let open = |relay| ParsedExpr::from_kind_and_span(Expr::TCUOpen(Box::new(relay)), 15..32);
+
+        assert_eq!(
+            parse_from_str(script).unwrap(),
+            [
+                close(relay(3)),
+                close(relay(4)),
+                close(relay(5)),
+                open(relay_open(0xFE)),
+                open(relay_open(0xFF)),
+            ]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_invalid_relay_range() {
+        let errors = parse_from_str("TCUCLOSE 5...3").unwrap_err();
+        assert_eq!(errors, [Error::invalid_range(0..14, 5, 3)]);
+
+        let errors = parse_from_str("TCUOPEN 250...256").unwrap_err();
+        assert_eq!(errors, [Error::argument_value_size(14..17, 256, (0, 255))]);
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_line_continuation() {
+        let single = r#"PRINT "first line", $0A, "second line", $0A"#;
+        let continued = "PRINT \"first line\", $0A, \\\n      \"second line\", $0A";
+        assert_eq!(
+            parse_from_str(continued).unwrap(),
+            parse_from_str(single).unwrap()
+        );
+
+        let continued = "PRINT \"first line\", $0A, \\\r\n      \"second line\", $0A\r\nTCUCLOSE 6";
+        let ast = parse_from_str(continued).unwrap();
+        assert_eq!(ast[0], parse_from_str(single).unwrap()[0]);
+        assert_eq!(ast[1], Expr::TCUClose(Expr::UInt(6).into()).into());
+
+        // Spans still refer to the original script.
+        assert_eq!(ast[1].span(), &(54..64));
+
+        // Comments and strings ending in a '\\' don't continue onto the next line.
+        let ast = parse_from_str("HPMODE ; note \\\nTCUCLOSE 6").unwrap();
+        assert_eq!(ast.len(), 3);
+        assert_eq!(ast[1], Expr::ScriptComment(String::from(" note \\")).into());
+        assert_eq!(ast[2], Expr::TCUClose(Expr::UInt(6).into()).into());
+
+        let ast = parse_from_str("COMMENT \"\"\"a \\\nb\"\"\"").unwrap();
+        assert_eq!(
+            ast[0],
+            Expr::Comment(vec![Expr::String(String::from("a \\\nb")).into()]).into()
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_min_exceeds_max() {
+        let errors = parse_from_str(r#"TCUTEST 3, 9000, 1000, 0, "x""#).unwrap_err();
+        let note = ErrorNote::Note("TCUTEST min must be ≤ max".into());
+        assert_eq!(
+            errors,
+            [Error::argument_value_size(17..21, 1000, (9000, u32::MAX.into())).with_note(note)]
+        );
+
+        let errors = parse_from_str(r#"ASSERT VOLTAGE, 10, -10, "x""#).unwrap_err();
+        let note = ErrorNote::Note("ASSERT min must be ≤ max".into());
+        assert_eq!(
+            errors,
+            [Error::argument_value_size(20..23, -10, (10, u32::MAX.into())).with_note(note)]
+        );
+
+        assert!(parse_from_str(r#"TCUTEST 3, 1000, 1000, 0, "x""#).is_ok());
+    }
 }
 
 ////////////////////////////////////////////////////////////////
@@ -1,12 +1,85 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
+
+use crate::{error::Error, execution::MeasurementFormat};
+
 ////////////////////////////////////////////////////////////////
 // types
 ////////////////////////////////////////////////////////////////
 
+/// Time zone that the current time is given in when setting a device's clock.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimeZone {
+    #[default]
+    Local,
+    Utc,
+}
+
+////////////////////////////////////////////////////////////////
+
 /// Contains any state that needs to persist through script evaluation.
 ///
-#[derive(Default, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
 pub struct EvalState {
-    pub(super) hpmode: bool,
+    pub(crate) hpmode: bool,
+
+    /// Format that measurements are returned in by devices.
+    pub(super) measurement_format: MeasurementFormat,
+
+    /// Time zone used when setting a device's clock.
+    pub(super) timezone: TimeZone,
+
+    /// Outcome of the most recently completed test, if any test has completed yet.
+    pub(crate) last_test_passed: Option<bool>,
+
+    /// Iterations remaining for each loop currently being executed, innermost last.
+    pub(crate) loops: Vec<u32>,
+
+    /// Name of the most recently reached checkpoint.
+    pub(crate) last_checkpoint: Option<String>,
+
+    /// Failure messages of the WARNTESTs that have failed so far.
+    pub(crate) warnings: Vec<String>,
+
+    /// Name of the test group currently being executed, if any.
+    pub(crate) test_group: Option<String>,
+
+    /// Variables that can be referenced by the script.
+    pub(crate) variables: HashMap<String, u32>,
+
+    /// Channels named by DEFINECHANNEL.
+    pub(super) channels: HashMap<String, u8>,
+
+    /// Time at which a global timeout was set and how long after that the script may continue
+    /// executing.
+    pub(crate) global_timeout: Option<(Instant, Duration)>,
+
+    /// Number of tests that have failed so far, not including WARNTESTs.
+    pub(crate) failures: u32,
+
+    /// Number of failed tests after which the script stops executing, if limited.
+    pub(crate) max_failures: Option<u32>,
+
+    /// Whether the requests of commands are currently being traced.
+    pub(super) trace: bool,
+
+    /// Options that SETOPTION and USBSETOPTION may set, if limited by SETOPTION_RANGE.
+    pub(super) option_range: Option<RangeInclusive<u8>>,
+
+    /// Maximum number of bytes a single USBPRINT may send, if limited.
+    pub(crate) usb_print_limit: Option<u32>,
+
+    /// Whether the TCU has been set not to echo commands by SETNOECHO ON.
+    pub(super) no_echo: bool,
+
+    /// Number of commands executed so far, counting each iteration of a loop.
+    pub(crate) commands: u64,
 }
 
 ////////////////////////////////////////////////////////////////
@@ -17,6 +90,302 @@ impl EvalState {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Create a new state with variables already defined.
+    ///
+    pub fn with_variables(variables: HashMap<String, u32>) -> Self {
+        Self {
+            variables,
+            ..Self::default()
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// methods
+////////////////////////////////////////////////////////////////
+
+impl EvalState {
+    /// Return whether HPMODE is currently active.
+    ///
+    pub fn hpmode(&self) -> bool {
+        self.hpmode
+    }
+
+    /// Return whether tracing has been enabled by TRACE ON.
+    ///
+    pub fn trace(&self) -> bool {
+        self.trace
+    }
+
+    /// Return whether the TCU is expected to echo commands i.e. SETNOECHO ON isn't in effect.
+    ///
+    pub fn echo_expected(&self) -> bool {
+        !self.no_echo
+    }
+
+    /// Return the value of a variable, if it's defined.
+    ///
+    pub fn variable(&self, name: &str) -> Option<u32> {
+        self.variables.get(name).copied()
+    }
+
+    /// Define a variable or change the value of an existing one.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the variable. Must not be empty or contain whitespace.
+    /// * `value` - Value to give the variable.
+    ///
+    /// # Returns
+    /// An error if the name is invalid, in which case the variable isn't set.
+    ///
+    pub fn set_variable(&mut self, name: &str, value: u32) -> Result<(), Error> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(Error::from_invalid_variable_name(name.to_owned()));
+        }
+
+        self.variables.insert(name.to_owned(), value);
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// saving / restoring
+////////////////////////////////////////////////////////////////
+
+impl EvalState {
+    /// Write the state as lines of the form `key value`, which may be read back one at a time by
+    /// restore_line. Strings are written quoted and escaped. A global timeout is saved as the time
+    /// remaining when the state is written.
+    ///
+    pub(crate) fn save(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let format = match self.measurement_format {
+            MeasurementFormat::Hex => "hex",
+            MeasurementFormat::Decimal => "decimal",
+        };
+
+        let timezone = match self.timezone {
+            TimeZone::Local => "local",
+            TimeZone::Utc => "utc",
+        };
+
+        writeln!(writer, "hpmode {}", self.hpmode)?;
+        writeln!(writer, "measurement_format {format}")?;
+        writeln!(writer, "timezone {timezone}")?;
+        writeln!(writer, "trace {}", self.trace)?;
+        writeln!(writer, "no_echo {}", self.no_echo)?;
+        writeln!(writer, "failures {}", self.failures)?;
+        writeln!(writer, "commands {}", self.commands)?;
+
+        if let Some(passed) = self.last_test_passed {
+            writeln!(writer, "last_test_passed {passed}")?;
+        }
+
+        for remaining in self.loops.iter() {
+            writeln!(writer, "loop {remaining}")?;
+        }
+
+        if let Some(checkpoint) = &self.last_checkpoint {
+            writeln!(writer, "last_checkpoint {checkpoint:?}")?;
+        }
+
+        for warning in self.warnings.iter() {
+            writeln!(writer, "warning {warning:?}")?;
+        }
+
+        if let Some(group) = &self.test_group {
+            writeln!(writer, "test_group {group:?}")?;
+        }
+
+        let mut variables: Vec<(&String, &u32)> = self.variables.iter().collect();
+        variables.sort();
+        for (name, value) in variables {
+            writeln!(writer, "variable {name} {value}")?;
+        }
+
+        let mut channels: Vec<(&String, &u8)> = self.channels.iter().collect();
+        channels.sort();
+        for (name, channel) in channels {
+            writeln!(writer, "channel {name} {channel}")?;
+        }
+
+        if let Some((start, timeout)) = self.global_timeout {
+            let remaining = timeout.saturating_sub(start.elapsed());
+            writeln!(writer, "global_timeout {}", remaining.as_millis())?;
+        }
+
+        if let Some(threshold) = self.max_failures {
+            writeln!(writer, "max_failures {threshold}")?;
+        }
+
+        if let Some(range) = &self.option_range {
+            writeln!(writer, "option_range {} {}", range.start(), range.end())?;
+        }
+
+        if let Some(limit) = self.usb_print_limit {
+            writeln!(writer, "usb_print_limit {limit}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Restore a single field of the state from a line written by save.
+    ///
+    /// # Arguments
+    /// * `key` - Name of the field.
+    /// * `value` - Value of the field, as it was written.
+    ///
+    /// # Returns
+    /// A description of the problem if the key isn't recognised or the value is invalid.
+    ///
+    pub(crate) fn restore_line(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("Invalid saved {key} '{value}'");
+
+        let fields: Vec<&str> = value.split_whitespace().collect();
+        match (key, &fields[..]) {
+            ("hpmode", [value]) => self.hpmode = value.parse().map_err(|_| invalid())?,
+            ("measurement_format", ["hex"]) => self.measurement_format = MeasurementFormat::Hex,
+            ("measurement_format", ["decimal"]) => {
+                self.measurement_format = MeasurementFormat::Decimal
+            }
+            ("timezone", ["local"]) => self.timezone = TimeZone::Local,
+            ("timezone", ["utc"]) => self.timezone = TimeZone::Utc,
+            ("trace", [value]) => self.trace = value.parse().map_err(|_| invalid())?,
+            ("no_echo", [value]) => self.no_echo = value.parse().map_err(|_| invalid())?,
+            ("failures", [value]) => self.failures = value.parse().map_err(|_| invalid())?,
+            ("commands", [value]) => self.commands = value.parse().map_err(|_| invalid())?,
+            ("last_test_passed", [value]) => {
+                self.last_test_passed = Some(value.parse().map_err(|_| invalid())?)
+            }
+            ("loop", [value]) => self.loops.push(value.parse().map_err(|_| invalid())?),
+            ("last_checkpoint", _) => {
+                self.last_checkpoint = Some(unquote(value).ok_or_else(invalid)?)
+            }
+            ("warning", _) => self.warnings.push(unquote(value).ok_or_else(invalid)?),
+            ("test_group", _) => self.test_group = Some(unquote(value).ok_or_else(invalid)?),
+            ("variable", [name, value]) => {
+                let value = value.parse().map_err(|_| invalid())?;
+                (self.set_variable(name, value)).map_err(|error| error.reason().message())?;
+            }
+            ("channel", [name, channel]) => {
+                let channel = channel.parse().map_err(|_| invalid())?;
+                self.channels.insert((*name).to_owned(), channel);
+            }
+            ("global_timeout", [value]) => {
+                let remaining = value.parse().map_err(|_| invalid())?;
+                self.global_timeout = Some((Instant::now(), Duration::from_millis(remaining)));
+            }
+            ("max_failures", [value]) => {
+                self.max_failures = Some(value.parse().map_err(|_| invalid())?)
+            }
+            ("option_range", [start, end]) => {
+                let start = start.parse().map_err(|_| invalid())?;
+                let end = end.parse().map_err(|_| invalid())?;
+                self.option_range = Some(start..=end);
+            }
+            ("usb_print_limit", [value]) => {
+                self.usb_print_limit = Some(value.parse().map_err(|_| invalid())?)
+            }
+            _ => return Err(format!("Unrecognised line in saved state '{key} {value}'")),
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// ...
+////////////////////////////////////////////////////////////////
+
+/// Reverse the quoting and escaping of a string written using its Debug representation.
+///
+/// # Returns
+/// The original string or None if the string isn't quoted or contains an invalid escape.
+///
+fn unquote(quoted: &str) -> Option<String> {
+    let mut chars = quoted.strip_prefix('"')?.strip_suffix('"')?.chars();
+    let mut string = String::with_capacity(quoted.len());
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            string.push(c);
+            continue;
+        }
+
+        let escaped = match chars.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            'u' => {
+                let code: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let code = u32::from_str_radix(code.strip_prefix('{')?, 16).ok()?;
+                char::from_u32(code)?
+            }
+            _ => return None,
+        };
+
+        string.push(escaped);
+    }
+
+    Some(string)
+}
+
+////////////////////////////////////////////////////////////////
+// tests
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_save_and_restore() {
+        let mut state = EvalState::with_variables(HashMap::from([(String::from("COUNT"), 3)]));
+        state.hpmode = true;
+        state.measurement_format = MeasurementFormat::Decimal;
+        state.timezone = TimeZone::Utc;
+        state.last_test_passed = Some(false);
+        state.loops = vec![4, 2];
+        state.last_checkpoint = Some(String::from("phase \"2\"\n\u{1b}"));
+        state.warnings = vec![String::from("low voltage"), String::from("tab\there")];
+        state.test_group = Some(String::from("battery"));
+        state.channels = HashMap::from([(String::from("battery_voltage"), 4)]);
+        state.failures = 2;
+        state.max_failures = Some(5);
+        state.trace = true;
+        state.option_range = Some(2..=9);
+        state.usb_print_limit = Some(64);
+        state.no_echo = true;
+        state.commands = 42;
+
+        let mut saved = Vec::new();
+        state.save(&mut saved).unwrap();
+
+        let mut restored = EvalState::new();
+        for line in String::from_utf8(saved).unwrap().lines() {
+            let (key, value) = line.split_once(' ').unwrap();
+            restored.restore_line(key, value).unwrap();
+        }
+
+        assert_eq!(restored, state);
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_restore_invalid() {
+        let mut state = EvalState::new();
+        assert!(state.restore_line("loop", "-1").is_err());
+        assert!(state.restore_line("timezone", "mars").is_err());
+        assert!(state.restore_line("warning", "unquoted").is_err());
+        assert!(state.restore_line("colour", "red").is_err());
+    }
 }
 
 ////////////////////////////////////////////////////////////////
@@ -0,0 +1,382 @@
+use chumsky::{prelude::*, text::Character};
+
+use super::{
+    error::Error,
+    expression::parse::{uint_literal, uint_value},
+};
+
+type Span = std::ops::Range<usize>;
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Every keyword recognised in scripts, including those that are only valid as a command's argument
+/// e.g. HEX.
+///
+pub const KEYWORDS: [&str; 67] = [
+    "HPMODE",
+    "COMMENT",
+    "ECHO",
+    "WAIT",
+    "DELAY",
+    "OPENDIALOG",
+    "WAITDIALOG",
+    "NOTIFY",
+    "FLUSH",
+    "SETBAUD",
+    "PROTOCOL",
+    "SETMEASUREMENTFORMAT",
+    "PRINT",
+    "PRINTRAW",
+    "SETTIMEFORMAT",
+    "SETTIME",
+    "SETTIMEZONE",
+    "SETOPTION",
+    "SETOPTION_RANGE",
+    "SETNOECHO",
+    "TCUCLOSE",
+    "TCUOPEN",
+    "TCUTEST",
+    "WARNTEST",
+    "MASKTEST",
+    "TCUEXACT",
+    "ASSERT",
+    "ADDVAR",
+    "SUBVAR",
+    "MULVAR",
+    "DIVVAR",
+    "DUMPVARS",
+    "DEFINECHANNEL",
+    "PRINTERSET",
+    "PRINTERTEST",
+    "USBOPEN",
+    "USBCLOSE",
+    "USBPRINT",
+    "USBSETTIMEFORMAT",
+    "USBSETTIME",
+    "USBSETOPTION",
+    "USBPRINTERSET",
+    "USBPRINTERTEST",
+    "USBTEST",
+    "USBEXACT",
+    "IFTESTPASS",
+    "IFTESTFAIL",
+    "ENDIF",
+    "REPEAT",
+    "ENDREPEAT",
+    "CHECKPOINT",
+    "LABEL",
+    "TESTGROUP",
+    "ENDTESTGROUP",
+    "GLOBALTIMEOUT",
+    "MAXFAILURES",
+    "SCRIPTVERSION",
+    "TRACE",
+    "DEFINE",
+    "ENDDEFINE",
+    "USEMACRO",
+    "HEX",
+    "DEC",
+    "UTC",
+    "LOCAL",
+    "ON",
+    "OFF",
+];
+
+////////////////////////////////////////////////////////////////
+
+/// A lexical token in a script, along with the area of the script it was found in. Spans count
+/// characters, not bytes, the same as those of parsed expressions.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Token {
+    Keyword(&'static str, Span),
+    StringLiteral(String, Span),
+    UIntLiteral(u32, Span),
+
+    /// A script comment, not including the leading ';' or '#'.
+    Comment(String, Span),
+
+    Comma(Span),
+
+    /// One or more inline whitespace characters.
+    Whitespace(Span),
+    Newline(Span),
+
+    /// A character that doesn't begin any valid token.
+    Unknown(char, Span),
+}
+
+////////////////////////////////////////////////////////////////
+// methods
+////////////////////////////////////////////////////////////////
+
+impl Token {
+    pub fn span(&self) -> &Span {
+        match self {
+            Token::Keyword(_, span) => span,
+            Token::StringLiteral(_, span) => span,
+            Token::UIntLiteral(_, span) => span,
+            Token::Comment(_, span) => span,
+            Token::Comma(span) => span,
+            Token::Whitespace(span) => span,
+            Token::Newline(span) => span,
+            Token::Unknown(_, span) => span,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+// ...
+////////////////////////////////////////////////////////////////
+
+/// Split a script into tokens without parsing it. Unlike parsing, this never fails. Anything that
+/// isn't a valid token is output as Token::Unknown one character at a time.
+///
+/// # Arguments
+/// * `script` - Script to tokenize.
+///
+/// # Returns
+/// An iterator over the script's tokens, in order.
+///
+pub fn tokenize(script: &str) -> impl Iterator<Item = Token> {
+    tokenizer()
+        .parse(script)
+        .expect("Tokenizer accepts any input")
+        .into_iter()
+}
+
+////////////////////////////////////////////////////////////////
+
+fn tokenizer() -> impl Parser<char, Vec<Token>, Error = Error> {
+    // Words are consumed whole so that keywords aren't matched part way through other words.
+    let word = text::ident().map_with_span(|word: String, span: Span| {
+        match KEYWORDS.iter().find(|keyword| **keyword == word) {
+            Some(keyword) => vec![Token::Keyword(keyword, span)],
+            None => unknown(&word, span.start),
+        }
+    });
+
+    let triple_quoted = just("\"\"\"")
+        .ignore_then(take_until(just("\"\"\"")))
+        .map(|(string, _)| String::from_iter(string))
+        .map_with_span(|string, span| vec![Token::StringLiteral(string, span)]);
+
+    let string = filter(|c| *c != '"' && !is_newline(c))
+        .repeated()
+        .delimited_by(just('"'), just('"'))
+        .collect::<String>()
+        .map_with_span(|string, span| vec![Token::StringLiteral(string, span)]);
+
+    let comment = one_of(";#")
+        .ignore_then(filter(|c| !is_newline(c)).repeated())
+        .collect::<String>()
+        .map_with_span(|comment, span| vec![Token::Comment(comment, span)]);
+
+    let comma = just(',').map_with_span(|_, span| vec![Token::Comma(span)]);
+
+    // A '\\' followed by a newline continues a command onto the next line, the same as when parsing.
+    let whitespace = filter(|c: &char| c.is_inline_whitespace())
+        .ignored()
+        .or(just('\\').ignore_then(text::newline()))
+        .repeated()
+        .at_least(1)
+        .map_with_span(|_, span| vec![Token::Whitespace(span)]);
+
+    let newline = text::newline().map_with_span(|_, span| vec![Token::Newline(span)]);
+
+    let other = any().map_with_span(|c, span| vec![Token::Unknown(c, span)]);
+
+    choice((
+        word,
+        uint(),
+        triple_quoted,
+        string,
+        comment,
+        comma,
+        whitespace,
+        newline,
+        other,
+    ))
+    .repeated()
+    .flatten()
+    .then_ignore(end())
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Tokenizer for unsigned integer literals. Literals that don't fit in a u32 are output as
+/// Token::Unknown one character at a time.
+///
+fn uint() -> impl Parser<char, Vec<Token>, Error = Error> {
+    uint_literal().map_with_span(|(prefix, digits, radix), span: Span| {
+        match uint_value(&digits, radix) {
+            Some(value) => vec![Token::UIntLiteral(value, span)],
+            None => unknown(&format!("{prefix}{digits}"), span.start),
+        }
+    })
+}
+
+////////////////////////////////////////////////////////////////
+
+fn is_newline(c: &char) -> bool {
+    *c == '\n' || *c == '\r'
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Output a string as unknown tokens, one for each character.
+///
+fn unknown(string: &str, start: usize) -> Vec<Token> {
+    string
+        .chars()
+        .enumerate()
+        .map(|(i, c)| Token::Unknown(c, start + i..start + i + 1))
+        .collect()
+}
+
+////////////////////////////////////////////////////////////////
+// tests
+////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::syntax::{error::ErrorReason, parse::parse_from_str};
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_tokenize_keywords() {
+        // Keywords that are only valid as an argument, along with a command they're valid for.
+        let arguments = HashMap::from([
+            ("HEX", "SETMEASUREMENTFORMAT"),
+            ("DEC", "SETMEASUREMENTFORMAT"),
+            ("UTC", "SETTIMEZONE"),
+            ("LOCAL", "SETTIMEZONE"),
+            ("ON", "TRACE"),
+            ("OFF", "SETNOECHO"),
+        ]);
+
+        for keyword in KEYWORDS {
+            let tokens: Vec<Token> = tokenize(keyword).collect();
+            let span = 0..keyword.chars().count();
+            assert_eq!(tokens, [Token::Keyword(keyword, span)], "{keyword}");
+
+            // Every keyword must also be recognised by the parser, which reports an unrecognised
+            // command from its start. Commands are given an argument that may not be valid for
+            // them, in which case the error is reported after the keyword.
+            let script = match arguments.get(keyword) {
+                Some(command) => format!("{command} {keyword}"),
+                None => format!("{keyword} 0"),
+            };
+            assert!(!unrecognised(&script), "{keyword}");
+        }
+
+        // Words that the parser doesn't recognise aren't keywords.
+        for word in ["ISSUETEST", "TESTRESULT", "NOTACOMMAND"] {
+            assert!(!KEYWORDS.contains(&word), "{word}");
+            assert!(unrecognised(&format!("{word} 0")), "{word}");
+        }
+    }
+
+    /// Return true if parsing the script fails because its first command isn't recognised.
+    ///
+    fn unrecognised(script: &str) -> bool {
+        parse_from_str(script).is_err_and(|errors| {
+            errors.iter().any(|error| {
+                matches!(error.reason(), ErrorReason::UnrecognisedCommand { span } if span.start == 0)
+            })
+        })
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_tokenize_command() {
+        let tokens: Vec<Token> = tokenize("TCUTEST 5, $FF, \"error\" ; check\r\n").collect();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Keyword("TCUTEST", 0..7),
+                Token::Whitespace(7..8),
+                Token::UIntLiteral(5, 8..9),
+                Token::Comma(9..10),
+                Token::Whitespace(10..11),
+                Token::UIntLiteral(255, 11..14),
+                Token::Comma(14..15),
+                Token::Whitespace(15..16),
+                Token::StringLiteral(String::from("error"), 16..23),
+                Token::Whitespace(23..24),
+                Token::Comment(String::from(" check"), 24..31),
+                Token::Newline(31..33),
+            ]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_tokenize_literals() {
+        let tokens: Vec<Token> = tokenize("%1_01 $b12 0042").collect();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::UIntLiteral(5, 0..5),
+                Token::Whitespace(5..6),
+                Token::UIntLiteral(0xB12, 6..10),
+                Token::Whitespace(10..11),
+                Token::UIntLiteral(42, 11..15),
+            ]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_tokenize_triple_quoted() {
+        let tokens: Vec<Token> = tokenize("COMMENT \"\"\"a\n\"b\"\"\"").collect();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Keyword("COMMENT", 0..7),
+                Token::Whitespace(7..8),
+                Token::StringLiteral(String::from("a\n\"b"), 8..18),
+            ]
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////
+
+    #[test]
+    fn test_tokenize_unknown() {
+        let tokens: Vec<Token> = tokenize("HPMOD -1 \"abc").collect();
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Unknown('H', 0..1),
+                Token::Unknown('P', 1..2),
+                Token::Unknown('M', 2..3),
+                Token::Unknown('O', 3..4),
+                Token::Unknown('D', 4..5),
+                Token::Whitespace(5..6),
+                Token::Unknown('-', 6..7),
+                Token::UIntLiteral(1, 7..8),
+                Token::Whitespace(8..9),
+                Token::Unknown('"', 9..10),
+                Token::Unknown('a', 10..11),
+                Token::Unknown('b', 11..12),
+                Token::Unknown('c', 12..13),
+            ]
+        );
+    }
+}
+
+////////////////////////////////////////////////////////////////
@@ -0,0 +1,102 @@
+use std::time::Instant;
+
+use crate::{execution::FrontendRequest, syntax::ParsedExpr};
+
+////////////////////////////////////////////////////////////////
+// types
+////////////////////////////////////////////////////////////////
+
+/// Record of every request made by an interpreter, in the order they were made. Remains available
+/// once the script has finished executing.
+///
+#[derive(Clone, Debug, Default)]
+pub struct TransactionLog {
+    records: Vec<TransactionRecord>,
+}
+
+////////////////////////////////////////////////////////////////
+
+/// A single request made by an interpreter.
+///
+#[derive(Clone, Debug)]
+pub struct TransactionRecord {
+    /// Expression that the request was evaluated from.
+    pub expression: ParsedExpr,
+
+    pub request: FrontendRequest,
+
+    /// When the request was handed to the frontend.
+    pub timestamp: Instant,
+}
+
+////////////////////////////////////////////////////////////////
+
+const HEADER: &str = "elapsed_ms,command,request";
+
+////////////////////////////////////////////////////////////////
+// methods
+////////////////////////////////////////////////////////////////
+
+impl TransactionLog {
+    pub(crate) fn push(&mut self, expression: &ParsedExpr, request: &FrontendRequest) {
+        self.records.push(TransactionRecord {
+            expression: expression.clone(),
+            request: request.clone(),
+            timestamp: Instant::now(),
+        });
+    }
+
+    pub fn records(&self) -> &[TransactionRecord] {
+        &self.records
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Format the log as CSV, with a header row followed by a row for each request. Times are given
+    /// in milliseconds since the first request.
+    ///
+    pub fn to_csv_string(&self) -> String {
+        let start = self.records.first().map(|record| record.timestamp);
+
+        let mut csv = format!("{HEADER}\n");
+        for record in self.records.iter() {
+            let elapsed = start.map_or(0, |start| {
+                record
+                    .timestamp
+                    .saturating_duration_since(start)
+                    .as_millis()
+            });
+
+            let row = [
+                elapsed.to_string(),
+                csv_field(&record.expression.to_string()),
+                csv_field(&format!("{:?}", record.request)),
+            ];
+
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+
+        csv
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+/// Quote a CSV field if it contains characters that would otherwise break the row.
+///
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+////////////////////////////////////////////////////////////////
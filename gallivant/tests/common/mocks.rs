@@ -1,6 +1,6 @@
 use std::{
     collections::VecDeque,
-    io::{Read, Write},
+    io::{ErrorKind, Read, Write},
 };
 
 ////////////////////////////////////////////////////////////////
@@ -9,6 +9,16 @@ use std::{
 pub struct PortMock {
     pub rxdata: VecDeque<u8>,
     pub txdata: VecDeque<u8>,
+
+    /// Error returned by every write, if any.
+    pub write_error: Option<ErrorKind>,
+
+    /// Number of bytes of each write to echo back, if any. Echoes cut short are terminated by a
+    /// carriage return, the same as a complete echo.
+    pub echo_limit: Option<usize>,
+
+    /// Number of bytes that may be written before writes time out, if limited.
+    pub write_capacity: Option<usize>,
 }
 
 ////////////////////////////////////////////////////////////////
@@ -18,8 +28,18 @@ impl PortMock {
         Self {
             rxdata: VecDeque::new(),
             txdata: VecDeque::new(),
+            write_error: None,
+            echo_limit: None,
+            write_capacity: None,
         }
     }
+
+    /// Echo back only the first N bytes of each write.
+    ///
+    #[allow(dead_code)] // Not every test uses it.
+    pub fn truncate_echo_at(&mut self, n: usize) {
+        self.echo_limit = Some(n);
+    }
 }
 
 ////////////////////////////////////////////////////////////////
@@ -44,7 +64,29 @@ impl Read for PortMock {
 
 impl Write for PortMock {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(kind) = self.write_error {
+            return Err(kind.into());
+        }
+
+        let buf = match self.write_capacity.as_mut() {
+            Some(0) => return Err(std::io::ErrorKind::TimedOut.into()),
+            Some(capacity) => {
+                let count = buf.len().min(*capacity);
+                *capacity -= count;
+                &buf[..count]
+            }
+            None => buf,
+        };
+
         self.txdata.extend(buf);
+
+        if let Some(limit) = self.echo_limit {
+            self.rxdata.extend(buf.iter().take(limit));
+            if limit < buf.len() {
+                self.rxdata.push_back(b'\r');
+            }
+        }
+
         Ok(buf.len())
     }
 
@@ -4,10 +4,13 @@ pub mod mocks;
 
 ////////////////////////////////////////////////////////////////
 
+/// Interpret a script, returning every request made except progress reports.
+///
 pub fn interpret_script(script: &str) -> Vec<FrontendRequest> {
     Interpreter::try_from_str(script)
         .unwrap()
         .map(|r| r.unwrap())
+        .filter(|request| !matches!(request, FrontendRequest::Progress { .. }))
         .collect()
 }
 
@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use gallivant::format_script;
+
+////////////////////////////////////////////////////////////////
+
+/// Format a script from the format directory and compare it against the expected output. The
+/// expected output must also be left unchanged by formatting.
+///
+fn assert_golden(name: &str) {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/format");
+    let script = std::fs::read_to_string(dir.join(format!("{name}.script"))).unwrap();
+    let expected = std::fs::read_to_string(dir.join(format!("{name}.expected"))).unwrap();
+
+    assert_eq!(format_script(&script).unwrap(), expected);
+    assert_eq!(format_script(&expected).unwrap(), expected);
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_format_inline_comments() {
+    assert_golden("inline_comments");
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_format_comment_lines() {
+    assert_golden("comment_lines");
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_format_no_comments() {
+    assert_golden("no_comments");
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_format_line_continuation() {
+    assert_golden("line_continuation");
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_format_hash_comments() {
+    assert_eq!(
+        format_script("# Setup\nWAIT  500 # settle\n;Done").unwrap(),
+        "# Setup\nWAIT 500 # settle\n;Done\n"
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_format_invalid_script() {
+    assert!(format_script("NOTACOMMAND").is_err());
+}
+
+////////////////////////////////////////////////////////////////
@@ -34,7 +34,7 @@ SETTIMEFORMAT 5
         port.rxdata.extend(&port.txdata);
         assert!(matches!(
             transaction.process(&mut port),
-            Ok(TransactionStatus::Success)
+            Ok(TransactionStatus::Success(None))
         ));
     }
 }
@@ -68,7 +68,7 @@ SETOPTION 6, 8
         port.rxdata.extend(&port.txdata);
         assert!(matches!(
             transaction.process(&mut port),
-            Ok(TransactionStatus::Success)
+            Ok(TransactionStatus::Success(None))
         ));
     }
 }
@@ -102,7 +102,7 @@ PRINTERSET 2
         port.rxdata.extend(port.txdata.iter());
         assert_eq!(
             transaction.process(&mut port).unwrap(),
-            TransactionStatus::Success
+            TransactionStatus::Success(None)
         );
     }
 }
@@ -142,7 +142,7 @@ PRINTERTEST 3, 1000, 12000, 1, "FAIL"
             port.rxdata.extend("AA1\r".as_bytes());
             assert!(matches!(
                 tr.process(&mut port),
-                Ok(TransactionStatus::Success)
+                Ok(TransactionStatus::Success(Some(0xAA1)))
             ))
         }
     }
@@ -153,6 +153,7 @@ PRINTERTEST 3, 1000, 12000, 1, "FAIL"
 #[test]
 fn test_hpmode_usbsettimeformat() {
     let script = r#"
+USBOPEN
 HPMODE
 USBSETTIMEFORMAT 6
     "#;
@@ -160,14 +161,18 @@ USBSETTIMEFORMAT 6
     let requests = interpret_script(script);
     assert!(matches!(
         requests[..],
-        [Request::None, Request::PrinterTransact(_)]
+        [
+            Request::PrinterOpen,
+            Request::None,
+            Request::PrinterTransact(_)
+        ]
     ));
 
-    if let Request::PrinterTransact(transaction) = requests[1].clone() {
+    if let Request::PrinterTransact(transaction) = requests[2].clone() {
         let mut port = PortMock::new();
         assert_eq!(
             transaction.process(&mut port).unwrap(),
-            TransactionStatus::Success
+            TransactionStatus::Success(None)
         );
 
         assert_eq!(port.txdata, vec![0x1B, 0x00, b't', b'f', 6])
@@ -179,6 +184,7 @@ USBSETTIMEFORMAT 6
 #[test]
 fn test_hpmode_usbsetoption() {
     let script = r#"
+USBOPEN
 HPMODE
 USBSETOPTION 6, 7
     "#;
@@ -186,14 +192,18 @@ USBSETOPTION 6, 7
     let requests = interpret_script(script);
     assert!(matches!(
         requests[..],
-        [Request::None, Request::PrinterTransact(_)]
+        [
+            Request::PrinterOpen,
+            Request::None,
+            Request::PrinterTransact(_)
+        ]
     ));
 
-    if let Request::PrinterTransact(transaction) = requests[1].clone() {
+    if let Request::PrinterTransact(transaction) = requests[2].clone() {
         let mut port = PortMock::new();
         assert_eq!(
             transaction.process(&mut port).unwrap(),
-            TransactionStatus::Success
+            TransactionStatus::Success(None)
         );
 
         assert_eq!(port.txdata, vec![0x1B, 0x00, 0x00, b'O', 6, 7])
@@ -205,6 +215,7 @@ USBSETOPTION 6, 7
 #[test]
 fn test_hpmode_usbprinterset() {
     let script = r#"
+USBOPEN
 HPMODE
 USBPRINTERSET 2
     "#;
@@ -212,14 +223,18 @@ USBPRINTERSET 2
     let requests = interpret_script(script);
     assert!(matches!(
         requests[..],
-        [Request::None, Request::PrinterTransact(_)]
+        [
+            Request::PrinterOpen,
+            Request::None,
+            Request::PrinterTransact(_)
+        ]
     ));
 
-    if let Request::PrinterTransact(transaction) = requests[1].clone() {
+    if let Request::PrinterTransact(transaction) = requests[2].clone() {
         let mut port = PortMock::new();
         assert_eq!(
             transaction.process(&mut port).unwrap(),
-            TransactionStatus::Success
+            TransactionStatus::Success(None)
         );
 
         assert_eq!(port.txdata, vec![0x1B, 0x00, 0x00, b'S', 2])
@@ -231,6 +246,7 @@ USBPRINTERSET 2
 #[test]
 fn test_hpmode_usbprintertest() {
     let script = r#"
+USBOPEN
 HPMODE
 USBPRINTERTEST 3, 1000, 12000, 1, "FAIL"
     "#;
@@ -238,10 +254,14 @@ USBPRINTERTEST 3, 1000, 12000, 1, "FAIL"
     let requests = interpret_script(script);
     assert!(matches!(
         requests[..],
-        [Request::None, Request::PrinterTransact(_)]
+        [
+            Request::PrinterOpen,
+            Request::None,
+            Request::PrinterTransact(_)
+        ]
     ));
 
-    if let Request::PrinterTransact(mut transaction) = requests[1].clone() {
+    if let Request::PrinterTransact(mut transaction) = requests[2].clone() {
         let mut port = PortMock::new();
 
         if let Ok(TransactionStatus::Ongoing(tr)) = transaction.process(&mut port) {
@@ -255,7 +275,7 @@ USBPRINTERTEST 3, 1000, 12000, 1, "FAIL"
         port.rxdata.extend("AA1\r".as_bytes());
         assert!(matches!(
             transaction.process(&mut port),
-            Ok(TransactionStatus::Success)
+            Ok(TransactionStatus::Success(Some(0xAA1)))
         ));
     }
 }
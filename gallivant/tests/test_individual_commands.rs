@@ -1,6 +1,7 @@
 use std::time::Duration;
 
 use gallivant::{Dialog, FrontendRequest, TransactionStatus};
+use gallivant_serial::MockPrinterPort;
 
 type Request = FrontendRequest;
 
@@ -24,6 +25,35 @@ fn test_comment() {
         interpret_script(script),
         [Request::GuiPrint(String::from("This is a comment 1234"))]
     );
+
+    let script = r#"COMMENT "Value" 42"#;
+    assert_eq!(
+        interpret_script(script),
+        [Request::GuiPrint(String::from("Value42"))]
+    );
+
+    let script = r#"COMMENT "Relay ", $01, " open""#;
+    assert_eq!(
+        interpret_script(script),
+        [Request::GuiPrint(String::from("Relay 1 open"))]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_echo() {
+    let script = r#"ECHO "literal""#;
+    assert_eq!(
+        interpret_script(script),
+        [Request::GuiPrint(String::from("literal"))]
+    );
+
+    let script = "ADDVAR DELAYTIME, 500, 0\nECHO DELAYTIME";
+    assert_eq!(
+        interpret_script(script)[1],
+        Request::GuiPrint(String::from("DELAYTIME = 500 ($01F4)"))
+    );
 }
 
 ////////////////////////////////////////////////////////////////
@@ -39,6 +69,49 @@ fn test_wait() {
 
 ////////////////////////////////////////////////////////////////
 
+#[test]
+fn test_dumpvars() {
+    let script = "ADDVAR VOLTAGE, 4000, 96\nADDVAR CHANNEL, 3, 0\nADDVAR RELAY, 255, 0\nDUMPVARS";
+    assert_eq!(
+        interpret_script(script)[3],
+        Request::GuiPrint(String::from("CHANNEL=3, RELAY=255, VOLTAGE=$1000"))
+    );
+
+    let script = r#"DUMPVARS"#;
+    assert_eq!(interpret_script(script), [Request::GuiPrint(String::new())]);
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_definechannel() {
+    let script = r#"
+DEFINECHANNEL "battery_voltage", 4
+TCUTEST battery_voltage, 4000, 5000, 0, "bad voltage"
+PRINTERSET battery_voltage
+    "#;
+    let requests = interpret_script(script);
+    assert!(matches!(
+        requests[..],
+        [
+            Request::None,
+            Request::TCUTransact(_),
+            Request::TCUTransact(_)
+        ]
+    ));
+
+    let mut port = PortMock::new();
+    for request in requests[1..].iter() {
+        if let Request::TCUTransact(transaction) = request.clone() {
+            let _ = transaction.process(&mut port);
+        }
+    }
+
+    assert_eq!(port.txdata, b"M04\rP051B005304\r");
+}
+
+////////////////////////////////////////////////////////////////
+
 #[test]
 fn test_opendialog() {
     let script = r#"OPENDIALOG "Open a dialog""#;
@@ -75,6 +148,14 @@ fn test_flush() {
 
 ////////////////////////////////////////////////////////////////
 
+#[test]
+fn test_setbaud() {
+    let script = r#"SETBAUD 115200"#;
+    assert_eq!(interpret_script(script), [Request::SetBaud(115200)]);
+}
+
+////////////////////////////////////////////////////////////////
+
 #[test]
 fn test_protocol() {
     let script = r#"PROTOCOL"#;
@@ -103,7 +184,7 @@ fn test_print() {
         port.rxdata.extend(&port.txdata);
         assert!(matches!(
             transaction.process(&mut port),
-            Ok(TransactionStatus::Success)
+            Ok(TransactionStatus::Success(None))
         ));
     }
 }
@@ -130,7 +211,7 @@ fn test_settimeformat() {
         port.rxdata.extend(&port.txdata);
         assert!(matches!(
             transaction.process(&mut port),
-            Ok(TransactionStatus::Success)
+            Ok(TransactionStatus::Success(None))
         ));
     }
 }
@@ -157,7 +238,7 @@ fn test_setoption() {
         port.rxdata.extend(&port.txdata);
         assert!(matches!(
             transaction.process(&mut port),
-            Ok(TransactionStatus::Success)
+            Ok(TransactionStatus::Success(None))
         ));
     }
 }
@@ -184,7 +265,7 @@ fn test_tcuclose() {
         port.rxdata.extend(&port.txdata);
         assert!(matches!(
             transaction.process(&mut port),
-            Ok(TransactionStatus::Success)
+            Ok(TransactionStatus::Success(None))
         ));
     }
 }
@@ -211,7 +292,7 @@ fn test_tcuopen() {
         port.rxdata.extend(&port.txdata);
         assert!(matches!(
             transaction.process(&mut port),
-            Ok(TransactionStatus::Success)
+            Ok(TransactionStatus::Success(None))
         ));
     }
 }
@@ -244,7 +325,7 @@ fn test_tcutest() {
             port.rxdata.extend("AA1\r".as_bytes());
             assert!(matches!(
                 tr.process(&mut port),
-                Ok(TransactionStatus::Success)
+                Ok(TransactionStatus::Success(Some(0xAA1)))
             ))
         }
     }
@@ -252,6 +333,37 @@ fn test_tcutest() {
 
 ////////////////////////////////////////////////////////////////
 
+#[test]
+fn test_setmeasurementformat() {
+    let script = r#"
+SETMEASUREMENTFORMAT DEC
+TCUTEST 3, 990, 1010, 0, "FAIL"
+    "#;
+    let requests = interpret_script(script);
+    assert!(matches!(
+        requests[..],
+        [Request::None, Request::TCUTransact(_)]
+    ));
+
+    if let Request::TCUTransact(transaction) = requests[1].clone() {
+        let mut port = PortMock::new();
+
+        let Ok(TransactionStatus::Ongoing(transaction)) = transaction.process(&mut port) else {
+            panic!()
+        };
+
+        // Echo followed by a decimal measurement.
+        port.rxdata.extend(&port.txdata);
+        port.rxdata.extend(b"1000\r");
+        assert!(matches!(
+            transaction.process(&mut port),
+            Ok(TransactionStatus::Success(Some(1000)))
+        ));
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
 #[test]
 fn test_printerset() {
     let script = r#"PRINTERSET 2"#;
@@ -272,7 +384,7 @@ fn test_printerset() {
         port.rxdata.extend(&port.txdata);
         assert!(matches!(
             transaction.process(&mut port),
-            Ok(TransactionStatus::Success)
+            Ok(TransactionStatus::Success(None))
         ));
     }
 }
@@ -305,7 +417,7 @@ fn test_printertest() {
             port.rxdata.extend("AA1\r".as_bytes());
             assert!(matches!(
                 tr.process(&mut port),
-                Ok(TransactionStatus::Success)
+                Ok(TransactionStatus::Success(Some(0xAA1)))
             ))
         }
     }
@@ -331,18 +443,21 @@ fn test_usbclose() {
 
 #[test]
 fn test_usbprint() {
-    let script = r#"USBPRINT "test", 45, $D4"#;
+    let script = "USBOPEN\nUSBPRINT \"test\", 45, $D4";
     let requests = interpret_script(script);
-    assert!(matches!(requests[..], [Request::PrinterTransact(_)]));
+    assert!(matches!(
+        requests[..],
+        [Request::PrinterOpen, Request::PrinterTransact(_)]
+    ));
 
     let mut expected = "test".as_bytes().to_owned();
     expected.extend_from_slice(&[45, 0xD4]);
 
-    if let Request::PrinterTransact(transaction) = requests[0].clone() {
+    if let Request::PrinterTransact(transaction) = requests[1].clone() {
         let mut port = PortMock::new();
         assert_eq!(
             transaction.process(&mut port).unwrap(),
-            TransactionStatus::Success
+            TransactionStatus::Success(None)
         );
 
         assert_eq!(port.txdata, expected)
@@ -351,17 +466,40 @@ fn test_usbprint() {
 
 ////////////////////////////////////////////////////////////////
 
+#[test]
+fn test_usbprint_printed() {
+    let script = "USBOPEN\nUSBPRINT \"Hello\"\nUSBSETOPTION 33, 2\nUSBPRINT \", world\"\nUSBCLOSE";
+    let mut port = MockPrinterPort::new();
+
+    for request in interpret_script(script) {
+        if let Request::PrinterTransact(transaction) = request {
+            assert_eq!(
+                transaction.process(&mut port).unwrap(),
+                TransactionStatus::Success(None)
+            );
+        }
+    }
+
+    assert_eq!(port.printed(), b"Hello, world");
+    assert_eq!(port.option(33), Some(2));
+}
+
+////////////////////////////////////////////////////////////////
+
 #[test]
 fn test_usbsettimeformat() {
-    let script = r#"USBSETTIMEFORMAT 6"#;
+    let script = "USBOPEN\nUSBSETTIMEFORMAT 6";
     let requests = interpret_script(script);
-    assert!(matches!(requests[..], [Request::PrinterTransact(_)]));
+    assert!(matches!(
+        requests[..],
+        [Request::PrinterOpen, Request::PrinterTransact(_)]
+    ));
 
-    if let Request::PrinterTransact(transaction) = requests[0].clone() {
+    if let Request::PrinterTransact(transaction) = requests[1].clone() {
         let mut port = PortMock::new();
         assert_eq!(
             transaction.process(&mut port).unwrap(),
-            TransactionStatus::Success
+            TransactionStatus::Success(None)
         );
 
         assert_eq!(port.txdata, vec![0x1B, b't', b'f', 6])
@@ -372,15 +510,18 @@ fn test_usbsettimeformat() {
 
 #[test]
 fn test_usbsetoption() {
-    let script = r#"USBSETOPTION 6, 7"#;
+    let script = "USBOPEN\nUSBSETOPTION 6, 7";
     let requests = interpret_script(script);
-    assert!(matches!(requests[..], [Request::PrinterTransact(_)]));
+    assert!(matches!(
+        requests[..],
+        [Request::PrinterOpen, Request::PrinterTransact(_)]
+    ));
 
-    if let Request::PrinterTransact(transaction) = requests[0].clone() {
+    if let Request::PrinterTransact(transaction) = requests[1].clone() {
         let mut port = PortMock::new();
         assert_eq!(
             transaction.process(&mut port).unwrap(),
-            TransactionStatus::Success
+            TransactionStatus::Success(None)
         );
 
         assert_eq!(port.txdata, vec![0x1B, 0x00, b'O', 6, 7])
@@ -391,15 +532,18 @@ fn test_usbsetoption() {
 
 #[test]
 fn test_usbprinterset() {
-    let script = r#"USBPRINTERSET 2"#;
+    let script = "USBOPEN\nUSBPRINTERSET 2";
     let requests = interpret_script(script);
-    assert!(matches!(requests[..], [Request::PrinterTransact(_)]));
+    assert!(matches!(
+        requests[..],
+        [Request::PrinterOpen, Request::PrinterTransact(_)]
+    ));
 
-    if let Request::PrinterTransact(transaction) = requests[0].clone() {
+    if let Request::PrinterTransact(transaction) = requests[1].clone() {
         let mut port = PortMock::new();
         assert_eq!(
             transaction.process(&mut port).unwrap(),
-            TransactionStatus::Success
+            TransactionStatus::Success(None)
         );
 
         assert_eq!(port.txdata, vec![0x1B, 0x00, b'S', 2])
@@ -410,27 +554,61 @@ fn test_usbprinterset() {
 
 #[test]
 fn test_usbprintertest() {
-    let script = r#"USBPRINTERTEST 3, 1000, 12000, 1, "FAIL""#;
+    let script = "USBOPEN\nUSBPRINTERTEST 3, 1000, 12000, 1, \"FAIL\"";
     let requests = interpret_script(script);
-    assert!(matches!(requests[..], [Request::PrinterTransact(_)]));
+    assert!(matches!(
+        requests[..],
+        [Request::PrinterOpen, Request::PrinterTransact(_)]
+    ));
+
+    let Request::PrinterTransact(mut transaction) = requests[1].clone() else {
+        panic!("Expected printer transaction but found {:?}", requests[1]);
+    };
+
+    let mut port = MockPrinterPort::builder().channel(3, 0xAA1).build();
+    loop {
+        match transaction.process(&mut port) {
+            Ok(TransactionStatus::Ongoing(ongoing)) => transaction = ongoing,
+            Ok(TransactionStatus::Success(Some(0xAA1))) => break,
+            result => panic!("Expected the test to pass. Got: {result:?}"),
+        }
+    }
+}
 
-    if let Request::TCUTransact(mut transaction) = requests[0].clone() {
-        let mut port = PortMock::new();
+////////////////////////////////////////////////////////////////
 
-        if let Ok(TransactionStatus::Ongoing(tr)) = transaction.process(&mut port) {
-            assert_eq!(port.txdata, vec![0x1B, 0x00, b'M', 3]);
-            transaction = tr;
-        } else {
-            panic!()
+#[test]
+fn test_usbprintertest_failure() {
+    let script = "USBOPEN\nUSBPRINTERTEST 3, 1000, 12000, 0, \"FAIL\"";
+    let requests = interpret_script(script);
+    let Request::PrinterTransact(mut transaction) = requests[1].clone() else {
+        panic!("Expected printer transaction but found {:?}", requests[1]);
+    };
+
+    let mut port = MockPrinterPort::builder().channel(3, 500).build();
+    let error = loop {
+        match transaction.process(&mut port) {
+            Ok(TransactionStatus::Ongoing(ongoing)) => transaction = ongoing,
+            Ok(status) => panic!("Expected the test to fail. Got: {status:?}"),
+            Err(error) => break error,
         }
+    };
 
-        // Measurement.
-        port.rxdata.extend("AA1\r".as_bytes());
-        assert!(matches!(
-            transaction.process(&mut port),
-            Ok(TransactionStatus::Success)
-        ));
-    }
+    assert!(matches!(
+        error.reason(),
+        gallivant::ErrorReason::TestFailure { test, .. } if test.measurement == 500
+    ));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_checkpoint() {
+    let script = r#"CHECKPOINT "phase 1 complete""#;
+    assert_eq!(
+        interpret_script(script),
+        [Request::Checkpoint(String::from("phase 1 complete"))]
+    );
 }
 
 ////////////////////////////////////////////////////////////////
@@ -0,0 +1,1772 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+
+use gallivant::{
+    DiagnosticSeverity, Dialog, Error, ErrorNote, ErrorPolicy, ErrorReason, Expr, ExprKind,
+    FailedTest, FrontendRequest, Interpreter, ParsedExpr, ScriptHooks, INTERPRETER_VERSION,
+};
+
+type Request = FrontendRequest;
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_dry_run() {
+    let script = r#"
+COMMENT "Start"
+TCUCLOSE 6
+WAIT 100
+    "#;
+
+    let interpreter = Interpreter::try_from_str(script).unwrap();
+    let requests: Vec<Request> = interpreter
+        .dry_run()
+        .into_iter()
+        .map(|r| r.unwrap())
+        .collect();
+
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[0], Request::GuiPrint(String::from("Start")));
+    assert!(matches!(requests[1], Request::TCUTransact(_)));
+    assert_eq!(requests[2], Request::Wait(Duration::from_millis(100)));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_notify() {
+    let script = r#"
+NOTIFY "relay_1", 1
+NOTIFY "voltage", VOLTAGE
+    "#;
+
+    let interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_params(HashMap::from([(String::from("VOLTAGE"), 1200)]))
+        .unwrap();
+
+    let requests: Vec<Request> = interpreter
+        .dry_run()
+        .into_iter()
+        .map(|r| r.unwrap())
+        .collect();
+
+    assert_eq!(
+        requests,
+        [
+            Request::Notification {
+                key: String::from("relay_1"),
+                value: 1,
+            },
+            Request::Notification {
+                key: String::from("voltage"),
+                value: 1200,
+            },
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_multi_line_dialog() {
+    let script = r#"
+OPENDIALOG """Connect the printer.
+Then press continue."""
+    "#;
+
+    let mut interpreter = Interpreter::try_from_str(script).unwrap();
+
+    assert_eq!(
+        interpreter.next().unwrap().unwrap(),
+        Request::GuiDialogue {
+            kind: Dialog::Notification,
+            message: String::from("Connect the printer.\nThen press continue."),
+        }
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_print_limit() {
+    let print = |size: usize| format!("PRINT \"{}\"", "a".repeat(size));
+
+    let interpreter = Interpreter::try_from_str(&print(127)).unwrap();
+    assert!(interpreter.dry_run()[0].is_ok());
+
+    let interpreter = Interpreter::try_from_str(&print(128)).unwrap();
+    let error = interpreter.dry_run().remove(0).unwrap_err();
+    assert!(matches!(error.reason(), ErrorReason::SyntaxError(_)));
+    assert_eq!(
+        error.reason().message(),
+        "Syntax error - Argument value exceeds limits"
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_usb_print_limit() {
+    let script = "USBOPEN\nUSBPRINT \"abcd\"\nUSBPRINT \"abcde\"\nUSBCLOSE";
+
+    // Unlimited by default.
+    let interpreter = Interpreter::try_from_str(script).unwrap();
+    assert!(interpreter.dry_run().iter().all(Result::is_ok));
+
+    let interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_usb_print_limit(4);
+
+    let results = interpreter.dry_run();
+    assert!(results[1].is_ok());
+
+    let error = results[2].as_ref().unwrap_err();
+    assert!(matches!(error.reason(), ErrorReason::SyntaxError(_)));
+    assert_eq!(error.reason().span(), Some(&(24..40)));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_dry_run_doesnt_advance() {
+    let script = r#"
+HPMODE
+WAIT 100
+    "#;
+
+    let mut interpreter = Interpreter::try_from_str(script).unwrap();
+    assert_eq!(interpreter.dry_run().len(), 2);
+    assert_eq!(interpreter.next().unwrap().unwrap(), Request::None);
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_dry_run_control_flow() {
+    let script = r#"
+REPEAT 2
+WAIT 100
+ENDREPEAT
+TCUTEST 1, 0, 10, 0, "fail"
+IFTESTFAIL
+COMMENT "Failed"
+ENDIF
+IFTESTPASS
+COMMENT "Passed"
+ENDIF
+    "#;
+
+    // Tests are treated as having passed.
+    let interpreter = Interpreter::try_from_str(script).unwrap();
+    let requests: Vec<Request> = interpreter
+        .dry_run()
+        .into_iter()
+        .map(|r| r.unwrap())
+        .filter(|r| !matches!(r, Request::None | Request::TCUTransact(_)))
+        .collect();
+
+    assert_eq!(
+        requests,
+        [
+            Request::Wait(Duration::from_millis(100)),
+            Request::Wait(Duration::from_millis(100)),
+            Request::GuiPrint(String::from("Passed")),
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_delay() {
+    let script = "WAIT 100\nDELAY 100";
+
+    let interpreter = Interpreter::try_from_str(script).unwrap();
+    let requests: Vec<Request> = interpreter
+        .dry_run()
+        .into_iter()
+        .map(|r| r.unwrap())
+        .collect();
+
+    assert_eq!(
+        requests,
+        [
+            Request::Wait(Duration::from_millis(100)),
+            Request::Wait(Duration::from_millis(100)),
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_hpmode_toggles() {
+    let script = r#"
+HPMODE
+WAIT 100
+HPMODE
+HPMODE
+    "#;
+
+    let mut interpreter = Interpreter::try_from_str(script).unwrap();
+    assert!(!interpreter.hpmode());
+
+    let states: Vec<bool> = std::iter::from_fn(|| {
+        interpreter.next()?.unwrap();
+        Some(interpreter.hpmode())
+    })
+    .collect();
+
+    assert_eq!(states, [true, true, false, true]);
+
+    interpreter.restart();
+    assert!(!interpreter.hpmode());
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_request_display() {
+    let script = r#"
+COMMENT "Start"
+TCUCLOSE 6
+TCUTEST 3, 1000, 12000, 1, "FAIL"
+WAIT 100
+    "#;
+
+    let requests: Vec<String> = Interpreter::try_from_str(script)
+        .unwrap()
+        .dry_run()
+        .into_iter()
+        .map(|r| r.unwrap().to_string())
+        .collect();
+
+    assert_eq!(
+        requests,
+        [
+            r#"Print "Start""#,
+            r#"TCU transact 'C06\r'"#,
+            r#"TCU transact 'M03\r' expecting 1000 to 12000"#,
+            "Wait 100ms",
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Default)]
+struct RecordingHooks {
+    events: Rc<RefCell<Vec<String>>>,
+}
+
+impl ScriptHooks for RecordingHooks {
+    fn before_request(&mut self, _: &ParsedExpr, request: &FrontendRequest) {
+        self.events.borrow_mut().push(format!("before {request}"));
+    }
+
+    fn after_request(&mut self, _: &ParsedExpr, result: Result<(), &Error>) {
+        self.events
+            .borrow_mut()
+            .push(format!("after {}", result.is_ok()));
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_hooks() {
+    let script = r#"
+WAIT 100
+TCUCLOSE 6
+    "#;
+
+    let hooks = RecordingHooks::default();
+    let mut interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_hooks(hooks.clone());
+
+    interpreter.next().unwrap().unwrap();
+    interpreter.complete(Ok(())).unwrap();
+    interpreter.next().unwrap().unwrap();
+
+    let error = Error::from_io_error(
+        ParsedExpr::from_kind_default(Expr::TCUClose(Box::new(ParsedExpr::from_uint_default(6)))),
+        std::io::Error::other("Port closed"),
+    );
+    assert!(interpreter.complete(Err(error)).is_err());
+
+    assert_eq!(
+        *hooks.events.borrow(),
+        [
+            "before Wait 100ms",
+            "after true",
+            r#"before TCU transact 'C06\r'"#,
+            "after false",
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_clone_shares_hooks() {
+    let script = r#"
+WAIT 100
+TCUCLOSE 6
+    "#;
+
+    let hooks = RecordingHooks::default();
+    let mut interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_hooks(hooks.clone());
+
+    interpreter.next().unwrap().unwrap();
+    let mut clone = interpreter.clone();
+    assert_eq!(clone, interpreter);
+
+    // The clone continues independently but still calls the same hooks.
+    clone.complete(Ok(())).unwrap();
+    clone.next().unwrap().unwrap();
+    assert_ne!(clone, interpreter);
+
+    interpreter.complete(Ok(())).unwrap();
+    assert_eq!(
+        *hooks.events.borrow(),
+        [
+            "before Wait 100ms",
+            "after true",
+            r#"before TCU transact 'C06\r'"#,
+            "after true",
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Default)]
+struct WaitHooks {
+    waits: Rc<RefCell<Vec<(Duration, Duration)>>>,
+}
+
+impl ScriptHooks for WaitHooks {
+    fn after_wait(&mut self, _: &ParsedExpr, requested: Duration, elapsed: Duration) {
+        self.waits.borrow_mut().push((requested, elapsed));
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_wait_hooks() {
+    let script = "WAIT 5\nTCUCLOSE 6";
+
+    let hooks = WaitHooks::default();
+    let mut interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_hooks(hooks.clone());
+
+    assert_eq!(
+        interpreter.next().unwrap().unwrap(),
+        Request::Wait(Duration::from_millis(5))
+    );
+
+    // Stand in for a frontend whose sleep overran.
+    std::thread::sleep(Duration::from_millis(20));
+    interpreter.complete(Ok(())).unwrap();
+
+    // Only waits are reported.
+    interpreter.next().unwrap().unwrap();
+    interpreter.complete(Ok(())).unwrap();
+
+    let waits = hooks.waits.borrow();
+    let [(requested, elapsed)] = waits[..] else {
+        panic!("Expected a single wait but found {waits:?}");
+    };
+    assert_eq!(requested, Duration::from_millis(5));
+    assert!(elapsed >= Duration::from_millis(20), "{elapsed:?}");
+}
+
+////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Default)]
+struct TraceHooks {
+    traced: Rc<RefCell<Vec<Request>>>,
+}
+
+impl ScriptHooks for TraceHooks {
+    fn trace_request(&mut self, _: &ParsedExpr, request: &Request) {
+        self.traced.borrow_mut().push(request.clone());
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_trace_hooks() {
+    let script = "WAIT 1\nTRACE ON\nWAIT 2\nCOMMENT \"traced\"\nTRACE OFF\nWAIT 3";
+
+    let hooks = TraceHooks::default();
+    let mut interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_hooks(hooks.clone());
+
+    while let Some(request) = interpreter.next() {
+        request.unwrap();
+        interpreter.complete(Ok(())).unwrap();
+    }
+
+    assert_eq!(
+        *hooks.traced.borrow(),
+        [
+            Request::Wait(Duration::from_millis(2)),
+            Request::GuiPrint(String::from("traced")),
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_transaction_log() {
+    let script = r#"
+; Setup
+COMMENT "Start"
+TCUCLOSE 6 ; Relay
+WAIT 1
+HPMODE
+HPMODE
+    "#;
+
+    let mut interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_transaction_log();
+
+    while let Some(request) = interpreter.next() {
+        request.unwrap();
+        interpreter.complete(Ok(())).unwrap();
+    }
+
+    let log = interpreter.transaction_log().unwrap();
+    assert_eq!(log.len(), 5);
+    assert_eq!(
+        log.records()[0].request,
+        Request::GuiPrint(String::from("Start"))
+    );
+
+    let csv = log.to_csv_string();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("elapsed_ms,command,request"));
+    assert_eq!(
+        lines.next(),
+        Some(r#"0,"COMMENT ""Start""","GuiPrint(""Start"")""#)
+    );
+    assert_eq!(lines.count(), 4);
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_transaction_log_disabled() {
+    let interpreter = Interpreter::try_from_str("WAIT 1").unwrap();
+    assert!(interpreter.transaction_log().is_none());
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_save_and_restore_state() {
+    let script = r#"
+HPMODE
+ADDVAR COUNT, COUNT, 1
+CHECKPOINT "phase 2"
+COMMENT "count ${COUNT}" ; trailing comment
+WAIT 1
+HPMODE
+    "#;
+
+    let mut interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_params(HashMap::from([(String::from("COUNT"), 1)]))
+        .unwrap();
+
+    for _ in 0..4 {
+        interpreter.next().unwrap().unwrap();
+        interpreter.complete(Ok(())).unwrap();
+    }
+
+    let mut saved = Vec::new();
+    interpreter.save_state(&mut saved).unwrap();
+
+    // Resumes from the checkpoint preceding the saved position.
+    let mut interpreter = Interpreter::restore_state(&mut saved.as_slice()).unwrap();
+    assert!(interpreter.hpmode());
+    assert_eq!(
+        interpreter.next().unwrap().unwrap(),
+        Request::Checkpoint(String::from("phase 2"))
+    );
+    assert_eq!(
+        interpreter.next().unwrap().unwrap(),
+        Request::GuiPrint(String::from("count 2"))
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_restore_invalid_state() {
+    let errors = Interpreter::restore_state(&mut "index 3\nscript\nHPMODE".as_bytes()).unwrap_err();
+    assert!(matches!(
+        errors[..],
+        [ref error] if matches!(error.reason(), ErrorReason::StateRestore { .. })
+    ));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_restore_state_within_loop() {
+    let script = r#"
+REPEAT 3
+    CHECKPOINT "iteration"
+    ADDVAR COUNT, COUNT, 1
+    COMMENT "count ${COUNT}"
+ENDREPEAT
+COMMENT "done"
+    "#;
+
+    let mut interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_params(HashMap::from([(String::from("COUNT"), 0)]))
+        .unwrap();
+
+    // Run the first iteration and the checkpoint of the second.
+    let mut checkpoints = 0;
+    while checkpoints < 2 {
+        if let Request::Checkpoint(_) = interpreter.next().unwrap().unwrap() {
+            checkpoints += 1;
+        }
+        interpreter.complete(Ok(())).unwrap();
+    }
+
+    let mut saved = Vec::new();
+    interpreter.save_state(&mut saved).unwrap();
+
+    // Only the remaining iterations are run, continuing from the saved variables.
+    let mut interpreter = Interpreter::restore_state(&mut saved.as_slice()).unwrap();
+    let mut requests = Vec::new();
+    while let Some(request) = interpreter.next() {
+        match request.unwrap() {
+            Request::None => (),
+            request => requests.push(request),
+        }
+        interpreter.complete(Ok(())).unwrap();
+    }
+
+    let print = |message: &str| Request::GuiPrint(String::from(message));
+    let checkpoint = Request::Checkpoint(String::from("iteration"));
+    assert_eq!(
+        requests,
+        [
+            checkpoint.clone(),
+            print("count 2"),
+            checkpoint,
+            print("count 3"),
+            print("done")
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_conditional_on_failed_test() {
+    let script = r#"
+TCUTEST 1, 0, 10, 0, "FAIL"
+IFTESTPASS
+    COMMENT "Passed"
+ENDIF
+IFTESTFAIL
+    COMMENT "Failed"
+ENDIF
+    "#;
+
+    let mut interpreter = Interpreter::try_from_str(script).unwrap();
+    let Request::TCUTransact(_) = interpreter.next().unwrap().unwrap() else {
+        panic!("Expected TCU transaction");
+    };
+
+    let test = FailedTest {
+        measurement: 20,
+        expected: 0..=10,
+        message: String::from("FAIL"),
+        mask: None,
+    };
+    let error = Error::from_failed_test(ParsedExpr::from_kind_default(Expr::Flush), test);
+
+    // The failure is handled by the script so shouldn't be passed on.
+    assert!(interpreter.complete(Err(error)).is_ok());
+
+    let requests: Vec<Request> = interpreter.map(|r| r.unwrap()).collect();
+    assert_eq!(
+        requests,
+        [
+            Request::None,
+            Request::None,
+            Request::None,
+            Request::GuiPrint(String::from("Failed")),
+            Request::None,
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_checkpoint_note() {
+    let script = r#"
+CHECKPOINT "phase 1 complete"
+TCUCLOSE 6
+    "#;
+
+    let mut interpreter = Interpreter::try_from_str(script).unwrap();
+    interpreter.next().unwrap().unwrap();
+    interpreter.complete(Ok(())).unwrap();
+    interpreter.next().unwrap().unwrap();
+
+    let error = Error::from_io_error(
+        ParsedExpr::from_kind_default(Expr::TCUClose(Box::new(ParsedExpr::from_uint_default(6)))),
+        std::io::Error::other("Port closed"),
+    );
+    let error = interpreter.complete(Err(error)).unwrap_err();
+
+    assert_eq!(
+        error.notes(),
+        [ErrorNote::Note("Last checkpoint: phase 1 complete".into())]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_checkpoint_note_evaluation_error() {
+    let script = r#"
+CHECKPOINT "phase 1 complete"
+ASSERT VOLTAGE, 0, 10, "FAIL"
+    "#;
+
+    let mut interpreter = Interpreter::try_from_str(script).unwrap();
+    interpreter.next().unwrap().unwrap();
+    interpreter.complete(Ok(())).unwrap();
+
+    // Errors from evaluation are given the note once they're reported back.
+    let error = interpreter.next().unwrap().unwrap_err();
+    let error = interpreter.complete(Err(error)).unwrap_err();
+
+    assert_eq!(
+        error.notes(),
+        [ErrorNote::Note("Last checkpoint: phase 1 complete".into())]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_param_interpolation() {
+    let script = r#"
+COMMENT "Battery ${BATTERY} of ${COUNT}"
+COMMENT "Costs $5"
+COMMENT "Costs $$${COUNT}"
+    "#;
+
+    let params = HashMap::from([(String::from("BATTERY"), 2), (String::from("COUNT"), 4)]);
+    let requests: Vec<Request> = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_params(params)
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+
+    assert_eq!(
+        requests,
+        [
+            Request::GuiPrint(String::from("Battery 2 of 4")),
+            Request::GuiPrint(String::from("Costs $5")),
+            Request::GuiPrint(String::from("Costs $4")),
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_dollar_strings_unchanged() {
+    let script = r#"
+COMMENT "reg $A0"
+PRINT "$FF"
+OPENDIALOG "Costs $COUNT, ${ or ${1}"
+    "#;
+
+    let params = HashMap::from([(String::from("COUNT"), 4)]);
+    let requests: Vec<Request> = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_params(params)
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+
+    assert_eq!(requests[0], Request::GuiPrint(String::from("reg $A0")));
+    let Request::TCUTransact(transaction) = &requests[1] else {
+        panic!("Expected TCU transaction but found {:?}", requests[1]);
+    };
+    assert_eq!(transaction.bytes(), b"P06244646\r");
+    assert!(
+        matches!(&requests[2], Request::GuiDialogue { message, .. } if message == "Costs $COUNT, ${ or ${1}"),
+        "{:?}",
+        requests[2]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_param_undefined() {
+    let script = r#"COMMENT "Battery ${BATTERY}""#;
+
+    let error = Interpreter::try_from_str(script)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap_err();
+
+    assert!(matches!(
+        error.reason(),
+        ErrorReason::UndefinedVariable { name, .. } if name == "BATTERY"
+    ));
+    assert_eq!(error.reason().span(), Some(&(8..28)));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_param_as_argument() {
+    let script = "WAIT DELAY\nTCUCLOSE RELAY";
+
+    let params = HashMap::from([(String::from("DELAY"), 250), (String::from("RELAY"), 10)]);
+    let requests: Vec<Request> = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_params(params)
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+
+    assert_eq!(requests[0], Request::Wait(Duration::from_millis(250)));
+
+    let Request::TCUTransact(transaction) = &requests[1] else {
+        panic!("Expected TCU transaction but found {:?}", requests[1]);
+    };
+    assert_eq!(transaction.bytes(), b"C0A\r");
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_print_raw() {
+    let script = "PRINTRAW 0 255 $AB";
+
+    let request = Interpreter::try_from_str(script)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+
+    let Request::TCUTransact(transaction) = &request else {
+        panic!("Expected TCU transaction but found {request:?}");
+    };
+    assert_eq!(transaction.bytes(), b"\x00\xFF\xAB\r");
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_param_argument_undefined() {
+    let script = "WAIT DELAY";
+
+    let error = Interpreter::try_from_str(script)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap_err();
+
+    assert!(matches!(
+        error.reason(),
+        ErrorReason::UndefinedVariable { name, .. } if name == "DELAY"
+    ));
+    assert_eq!(error.reason().span(), Some(&(5..10)));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_param_argument_out_of_range() {
+    let script = "TCUOPEN RELAY";
+
+    let params = HashMap::from([(String::from("RELAY"), 256)]);
+    let error = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_params(params)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap_err();
+
+    assert!(matches!(
+        error.reason(),
+        ErrorReason::ArgOutOfRange {
+            value: 256,
+            limits: (0, 255),
+            ..
+        }
+    ));
+    assert_eq!(error.reason().span(), Some(&(8..13)));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_print_too_long() {
+    // Each character is transmitted as 2 hex digits.
+    let script = format!("PRINT \"{}\"", "A".repeat(130));
+
+    let error = Interpreter::try_from_str(&script)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap_err();
+
+    assert_eq!(
+        error.reason().message(),
+        "Syntax error - Argument value exceeds limits"
+    );
+    assert_eq!(error.reason().span(), Some(&(0..script.len())));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_assert_pass() {
+    let script = r#"ASSERT VOLTAGE, 1000, 2000, "Voltage out of range""#;
+
+    let params = HashMap::from([(String::from("VOLTAGE"), 1500)]);
+    let mut interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_params(params)
+        .unwrap();
+
+    assert_eq!(interpreter.next().unwrap().unwrap(), Request::None);
+    assert!(interpreter.next().is_none());
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_params_invalid_name() {
+    let params = HashMap::from([
+        (String::from("VOLTAGE"), 1500),
+        (String::from("MY VOLTAGE"), 1),
+    ]);
+    let error = Interpreter::try_from_str("ECHO VOLTAGE")
+        .unwrap()
+        .with_params(params)
+        .unwrap_err();
+
+    let ErrorReason::InvalidVariableName { name } = error.reason() else {
+        panic!("Expected invalid variable name but found {error:?}");
+    };
+    assert_eq!(name, "MY VOLTAGE");
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_assert_fail() {
+    let script = r#"ASSERT VOLTAGE, 1000, 2000, "Voltage ${VOLTAGE} out of range""#;
+
+    let params = HashMap::from([(String::from("VOLTAGE"), 2500)]);
+    let error = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_params(params)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap_err();
+
+    let ErrorReason::TestFailure { test, .. } = error.reason() else {
+        panic!("Expected test failure but found {error:?}");
+    };
+
+    assert_eq!(
+        *test,
+        FailedTest {
+            measurement: 2500,
+            expected: 1000..=2000,
+            message: String::from("Voltage 2500 out of range"),
+            mask: None,
+        }
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_strip_comments() {
+    let script = r#"
+; Setup
+COMMENT "Start" ; inline
+; TCUCLOSE 1
+WAIT 10
+"#;
+
+    let requests: Vec<Request> = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_strip_comments(true)
+        .map(|r| r.unwrap())
+        .collect();
+
+    assert_eq!(
+        requests,
+        [
+            Request::GuiPrint(String::from("Start")),
+            Request::Wait(Duration::from_millis(10)),
+        ]
+    );
+
+    let count = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_strip_comments(false)
+        .count();
+    assert_eq!(count, 5);
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_seek_to_label() {
+    let script = r#"
+HPMODE
+COMMENT "Setup"
+LABEL "phase 2"
+COMMENT "Phase 2"
+"#;
+
+    let mut interpreter = Interpreter::try_from_str(script).unwrap();
+    assert_eq!(interpreter.next().unwrap().unwrap(), Request::None);
+    assert!(interpreter.hpmode());
+
+    interpreter.seek_to_label("phase 2").unwrap();
+    assert!(interpreter.hpmode());
+
+    let requests: Vec<Request> = interpreter.map(|r| r.unwrap()).collect();
+    assert_eq!(requests, [Request::GuiPrint(String::from("Phase 2"))]);
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_seek_to_undefined_label() {
+    let mut interpreter = Interpreter::try_from_str("LABEL \"start\"").unwrap();
+
+    let error = interpreter.seek_to_label("end").unwrap_err();
+    let ErrorReason::UndefinedLabel { name } = error.reason() else {
+        panic!("Expected undefined label but found {error:?}");
+    };
+    assert_eq!(name, "end");
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_macros() {
+    let script = r#"
+USEMACRO "measure"
+DEFINE "wait"
+WAIT 100
+ENDDEFINE
+DEFINE "measure"
+COMMENT "Measuring"
+USEMACRO "wait"
+ENDDEFINE
+COMMENT "Done"
+"#;
+
+    let requests: Vec<Request> = Interpreter::try_from_str(script)
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+
+    assert_eq!(
+        requests,
+        [
+            Request::GuiPrint(String::from("Measuring")),
+            Request::Wait(Duration::from_millis(100)),
+            Request::GuiPrint(String::from("Done")),
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_undefined_macro() {
+    let script = "DEFINE \"a\"\nWAIT 100\nENDDEFINE\nUSEMACRO \"b\"";
+
+    let errors = Interpreter::try_from_str(script).unwrap_err();
+    assert_eq!(errors.len(), 1);
+
+    let ErrorReason::UndefinedMacro { name, .. } = errors[0].reason() else {
+        panic!("Expected undefined macro but found {:?}", errors[0]);
+    };
+    assert_eq!(name, "b");
+    assert_eq!(errors[0].reason().span(), Some(&(30..42)));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_repeat_crossing_define() {
+    // Used to parse then panic once the macro had been expanded.
+    let script = "REPEAT 0\nDEFINE \"a\"\nENDREPEAT\nENDDEFINE";
+
+    let errors = Interpreter::try_from_str(script).unwrap_err();
+    assert_eq!(errors.len(), 2, "{errors:?}");
+    assert!(errors
+        .iter()
+        .all(|error| matches!(error.reason(), ErrorReason::SyntaxError(_))));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_circular_macro() {
+    let script = r#"
+DEFINE "a"
+USEMACRO "b"
+ENDDEFINE
+DEFINE "b"
+USEMACRO "a"
+ENDDEFINE
+USEMACRO "a"
+"#;
+
+    let errors = Interpreter::try_from_str(script).unwrap_err();
+    assert_eq!(errors.len(), 1);
+
+    let ErrorReason::CircularMacro { name, .. } = errors[0].reason() else {
+        panic!("Expected circular macro but found {:?}", errors[0]);
+    };
+    assert_eq!(name, "a");
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_bom_and_crlf() {
+    let clean = "COMMENT \"Start\"\nOPENDIALOG \"\"\"Line 1\nLine 2\"\"\"\nTCUCLOSE 6\n";
+    let windows =
+        "\u{FEFF}COMMENT \"Start\"\r\nOPENDIALOG \"\"\"Line 1\r\nLine 2\"\"\"\r\nTCUCLOSE 6\r\n";
+
+    let requests = |script| -> Vec<Request> {
+        Interpreter::try_from_str(script)
+            .unwrap()
+            .dry_run()
+            .into_iter()
+            .map(|r| r.unwrap())
+            .collect()
+    };
+
+    assert_eq!(requests(windows), requests(clean));
+
+    // Spans refer to the normalised script.
+    let errors = Interpreter::try_from_str("\u{FEFF}WAIT 1\r\nNOTACOMMAND").unwrap_err();
+    assert_eq!(errors[0].reason().span(), Some(&(7..18)));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_max_commands() {
+    let script = r#"
+REPEAT 10
+COMMENT "1"
+COMMENT "2"
+COMMENT "3"
+COMMENT "4"
+COMMENT "5"
+ENDREPEAT
+"#;
+
+    let mut interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_max_commands(4);
+
+    for _ in 0..4 {
+        assert!(interpreter.next().unwrap().is_ok());
+    }
+
+    let error = loop {
+        match interpreter.next().unwrap() {
+            Ok(Request::None) => continue,
+            Ok(request) => panic!("Expected max commands exceeded but found {request:?}"),
+            Err(error) => break error,
+        }
+    };
+
+    let ErrorReason::MaxCommandsExceeded { limit, count } = error.reason() else {
+        panic!("Expected max commands exceeded but found {error:?}");
+    };
+    assert_eq!((*limit, *count), (4, 4));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_script_version() {
+    let (major, minor) = INTERPRETER_VERSION;
+    let script = format!("SCRIPTVERSION {major}, {minor}\nCOMMENT \"Supported\"");
+
+    let requests: Vec<Request> = Interpreter::try_from_str(&script)
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+
+    assert_eq!(
+        requests,
+        [Request::None, Request::GuiPrint(String::from("Supported"))]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_parse_error_report_line() {
+    let script = "HPMODE\nWAIT 100\nTCUCLOSE 256";
+
+    let errors = Interpreter::try_from_str(script).unwrap_err();
+    let [error] = &errors[..] else {
+        panic!("Expected a single error but found {errors:?}");
+    };
+
+    let mut buffer = Vec::new();
+    error
+        .to_report(script)
+        .write_for_stdout(ariadne::Source::from(script), &mut buffer)
+        .unwrap();
+
+    let output = String::from_utf8(buffer).unwrap();
+    assert!(output.contains("line 3, column 10: "), "{output}");
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_script_version_mismatch() {
+    let (major, minor) = INTERPRETER_VERSION;
+    let script = format!("COMMENT \"Start\"\nSCRIPTVERSION {major}, {}", minor + 1);
+
+    let errors = Interpreter::try_from_str(&script).unwrap_err();
+
+    let [error] = &errors[..] else {
+        panic!("Expected a single error but found {errors:?}");
+    };
+    let ErrorReason::VersionMismatch {
+        required, running, ..
+    } = error.reason()
+    else {
+        panic!("Expected version mismatch but found {error:?}");
+    };
+    assert_eq!(*required, (major, minor + 1));
+    assert_eq!(*running, INTERPRETER_VERSION);
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_printer_lifecycle_port_not_open() {
+    let script = "USBPRINT \"test\"\nUSBOPEN\nUSBCLOSE\nUSBPRINTERSET 2";
+
+    let errors = Interpreter::try_from_str(script).unwrap_err();
+
+    let reasons: Vec<_> = errors
+        .iter()
+        .map(|error| match error.reason() {
+            ErrorReason::PortNotOpen { command, span } => (*command, span.clone()),
+            _ => panic!("Expected port not open but found {error:?}"),
+        })
+        .collect();
+
+    assert_eq!(
+        reasons,
+        [
+            (ExprKind::USBPrint, 0..15),
+            (ExprKind::USBPrinterSet, 33..48)
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_printer_lifecycle() {
+    let script = "USBOPEN\nUSBPRINT \"test\"\nUSBCLOSE";
+
+    let interpreter = Interpreter::try_from_str(script).unwrap();
+    assert!(interpreter.script_warnings().is_empty());
+
+    // Leaving the port open is only a warning.
+    let script = "USBOPEN\nUSBPRINT \"test\"";
+
+    let interpreter = Interpreter::try_from_str(script).unwrap();
+    let [warning] = interpreter.script_warnings() else {
+        panic!("Expected a single warning");
+    };
+    assert!(matches!(
+        warning.reason(),
+        ErrorReason::PortNotClosed { span } if *span == (0..7)
+    ));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_printer_lifecycle_control_flow() {
+    // The port is only closed if the test fails.
+    let script = r#"TCUTEST 1, 0, 10, 0, "fail"
+USBOPEN
+IFTESTFAIL
+USBCLOSE
+ENDIF
+USBPRINT "test"
+USBCLOSE"#;
+
+    let interpreter = Interpreter::try_from_str(script).unwrap();
+    assert!(interpreter.script_warnings().is_empty());
+
+    // The port is open for every iteration of the loop.
+    let script = "USBOPEN\nREPEAT 2\nUSBPRINT \"test\"\nENDREPEAT\nUSBCLOSE";
+
+    let interpreter = Interpreter::try_from_str(script).unwrap();
+    assert!(interpreter.script_warnings().is_empty());
+
+    // The port is closed however many times the loop is run.
+    let script = "REPEAT 2\nUSBOPEN\nUSBCLOSE\nENDREPEAT\nUSBPRINT \"test\"";
+
+    let errors = Interpreter::try_from_str(script).unwrap_err();
+    let [error] = errors.as_slice() else {
+        panic!("Expected a single error but found {errors:?}");
+    };
+    assert!(matches!(
+        error.reason(),
+        ErrorReason::PortNotOpen { span, .. } if *span == (36..51)
+    ));
+
+    // The script may be started from the label with the port already open.
+    let script = "USBOPEN\nUSBCLOSE\nLABEL \"print\"\nUSBPRINT \"test\"\nUSBCLOSE";
+
+    let interpreter = Interpreter::try_from_str(script).unwrap();
+    assert!(interpreter.script_warnings().is_empty());
+
+    // The port is left open if the test passes.
+    let script = r#"TCUTEST 1, 0, 10, 0, "fail"
+USBOPEN
+IFTESTFAIL
+USBCLOSE
+ENDIF"#;
+
+    let interpreter = Interpreter::try_from_str(script).unwrap();
+    let [warning] = interpreter.script_warnings() else {
+        panic!("Expected a single warning");
+    };
+    assert!(matches!(
+        warning.reason(),
+        ErrorReason::PortNotClosed { span } if *span == (28..35)
+    ));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_hpmode_parity() {
+    let interpreter = Interpreter::try_from_str("HPMODE\nCOMMENT \"Start\"").unwrap();
+
+    let [warning] = interpreter.script_warnings() else {
+        panic!("Expected a single warning");
+    };
+    assert!(matches!(
+        warning.reason(),
+        ErrorReason::OddHPModeToggle { count: 1 }
+    ));
+    assert_eq!(warning.reason().severity(), DiagnosticSeverity::Warning);
+
+    let interpreter = Interpreter::try_from_str("HPMODE\nCOMMENT \"Start\"\nHPMODE").unwrap();
+    assert!(interpreter.script_warnings().is_empty());
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_user_cancelled_stops_script() {
+    let script = "WAITDIALOG \"Connect the printer\"\nCOMMENT \"Connected\"";
+
+    let mut interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_error_policy(ErrorPolicy::ContinueOnAll);
+
+    interpreter.next().unwrap().unwrap();
+    let error = interpreter
+        .complete(Err(Error::from_user_cancelled()))
+        .unwrap_err();
+
+    assert!(matches!(error.reason(), ErrorReason::UserCancelled));
+    assert!(interpreter.errors().is_empty());
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_progress_reports() {
+    let script = "HPMODE\nCOMMENT \"Done\"";
+
+    let requests: Vec<Request> = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_progress_reports(true)
+        .map(|r| r.unwrap())
+        .collect();
+
+    assert_eq!(
+        requests,
+        [
+            Request::Progress {
+                current: 0,
+                total: 2
+            },
+            Request::None,
+            Request::Progress {
+                current: 1,
+                total: 2
+            },
+            Request::GuiPrint(String::from("Done")),
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_global_timeout() {
+    let script = "GLOBALTIMEOUT 1\nCOMMENT \"Still running\"";
+
+    let mut interpreter = Interpreter::try_from_str(script).unwrap();
+    assert!(interpreter.global_timeout().is_none());
+    assert_eq!(interpreter.next().unwrap().unwrap(), Request::None);
+    assert!(matches!(
+        interpreter.global_timeout(),
+        Some((_, timeout)) if timeout == Duration::from_millis(1)
+    ));
+
+    std::thread::sleep(Duration::from_millis(2));
+
+    let error = interpreter.next().unwrap().unwrap_err();
+    let ErrorReason::GlobalTimeout { elapsed } = error.reason() else {
+        panic!("Expected global timeout but found {error:?}");
+    };
+    assert!(*elapsed >= Duration::from_millis(1));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_global_timeout_not_exceeded() {
+    let script = "GLOBALTIMEOUT 60000\nCOMMENT \"Still running\"";
+
+    let requests: Vec<Request> = Interpreter::try_from_str(script)
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+
+    assert_eq!(
+        requests,
+        [
+            Request::None,
+            Request::GuiPrint(String::from("Still running"))
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_max_failures() {
+    let script = format!(
+        "MAXFAILURES 2\n{}",
+        "TCUTEST 1, 0, 10, 0, \"FAIL\"\n".repeat(10)
+    );
+
+    let mut interpreter = Interpreter::try_from_str(&script)
+        .unwrap()
+        .with_error_policy(ErrorPolicy::ContinueOnAll);
+
+    assert_eq!(interpreter.next().unwrap().unwrap(), Request::None);
+    assert!(interpreter.complete(Ok(())).is_ok());
+
+    let mut tests_run = 0;
+    let error = loop {
+        let request = match interpreter.next().unwrap() {
+            Ok(request) => request,
+            Err(error) => break error,
+        };
+        assert!(matches!(request, Request::TCUTransact(_)));
+        tests_run += 1;
+
+        let test = FailedTest {
+            measurement: 20,
+            expected: 0..=10,
+            message: String::from("FAIL"),
+            mask: None,
+        };
+        let error = Error::from_failed_test(ParsedExpr::from_kind_default(Expr::Flush), test);
+        assert!(interpreter.complete(Err(error)).is_ok());
+    };
+
+    assert_eq!(tests_run, 2);
+    assert!(matches!(
+        error.reason(),
+        ErrorReason::MaxFailuresExceeded {
+            threshold: 2,
+            actual: 2
+        }
+    ));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_arithmetic() {
+    let script = r#"
+ADDVAR TOTAL, COUNT, 5
+SUBVAR TOTAL, TOTAL, 1
+MULVAR TOTAL, TOTAL, COUNT
+DIVVAR TOTAL, TOTAL, 2
+COMMENT "Total ${TOTAL}"
+"#;
+
+    let params = HashMap::from([(String::from("COUNT"), 3)]);
+    let requests: Vec<Request> = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_params(params)
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+
+    assert_eq!(requests[4], Request::GuiPrint(String::from("Total 10")));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_arithmetic_overflow() {
+    let script = "MULVAR TOTAL, $10000, $10000";
+
+    let error = Interpreter::try_from_str(script)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap_err();
+
+    assert!(matches!(
+        error.reason(),
+        ErrorReason::ArithmeticOverflow { .. }
+    ));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_arithmetic_divide_by_zero() {
+    let script = "DIVVAR TOTAL, 10, ZERO";
+
+    let params = HashMap::from([(String::from("ZERO"), 0)]);
+    let error = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_params(params)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap_err();
+
+    assert!(matches!(error.reason(), ErrorReason::DivisionByZero { .. }));
+    assert_eq!(error.reason().span(), Some(&(0..22)));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_test_group() {
+    let script = "TESTGROUP \"Power supply\"\nHPMODE\nENDTESTGROUP";
+
+    let mut interpreter = Interpreter::try_from_str(script).unwrap();
+    assert_eq!(interpreter.test_group(), None);
+
+    assert_eq!(
+        interpreter.next().unwrap().unwrap(),
+        Request::Checkpoint(String::from("Power supply"))
+    );
+    assert_eq!(interpreter.next().unwrap().unwrap(), Request::None);
+    assert_eq!(interpreter.test_group(), Some("Power supply"));
+
+    assert_eq!(
+        interpreter.next().unwrap().unwrap(),
+        Request::Checkpoint(String::from("End of Power supply"))
+    );
+    assert_eq!(interpreter.test_group(), None);
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_warn_test() {
+    let script = r#"
+WARNTEST 1, 0, 10, 0, "Measured {value}"
+IFTESTFAIL
+    COMMENT "Failed"
+ENDIF
+    "#;
+
+    let mut interpreter = Interpreter::try_from_str(script).unwrap();
+    let Request::TCUTransact(_) = interpreter.next().unwrap().unwrap() else {
+        panic!("Expected TCU transaction");
+    };
+
+    let test = FailedTest {
+        measurement: 20,
+        expected: 0..=10,
+        message: String::from("Measured {value}"),
+        mask: None,
+    };
+    let error = Error::from_failed_test(ParsedExpr::from_kind_default(Expr::Flush), test);
+
+    // The failure only produces a warning so shouldn't be passed on.
+    assert!(interpreter.complete(Err(error)).is_ok());
+    assert_eq!(interpreter.warnings(), ["Measured 20"]);
+
+    let requests: Vec<Request> = interpreter.map(|r| r.unwrap()).collect();
+    assert_eq!(
+        requests,
+        [
+            Request::GuiPrint(String::from("[WARNING] Measured 20")),
+            Request::None,
+            Request::GuiPrint(String::from("Failed")),
+            Request::None,
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_error_policy_continue_on_io() {
+    let script = "PRINT \"Hello\"\nTCUTEST 1, 0, 10, 0, \"FAIL\"";
+
+    let mut interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_error_policy(ErrorPolicy::ContinueOnIO);
+
+    let expr = ParsedExpr::from_kind_default(Expr::Flush);
+
+    // IO errors are accumulated and execution continues.
+    interpreter.next().unwrap().unwrap();
+    let error = Error::from_io_error(expr.clone(), std::io::Error::other("Write failed"));
+    assert!(interpreter.complete(Err(error)).is_ok());
+    assert_eq!(interpreter.errors().len(), 1);
+
+    // Failed tests still stop the script.
+    interpreter.next().unwrap().unwrap();
+    let test = FailedTest {
+        measurement: 20,
+        expected: 0..=10,
+        message: String::from("FAIL"),
+        mask: None,
+    };
+    let error = interpreter.complete(Err(Error::from_failed_test(expr, test)));
+    assert!(error.is_err());
+    assert_eq!(interpreter.errors().len(), 1);
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_error_policy_continue_on_all() {
+    let script = "TCUTEST 1, 0, 10, 0, \"FAIL\"\nCOMMENT \"Continued\"";
+
+    let mut interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_error_policy(ErrorPolicy::ContinueOnAll);
+
+    interpreter.next().unwrap().unwrap();
+    let test = FailedTest {
+        measurement: 20,
+        expected: 0..=10,
+        message: String::from("FAIL"),
+        mask: None,
+    };
+    let error = Error::from_failed_test(ParsedExpr::from_kind_default(Expr::Flush), test);
+    assert!(interpreter.complete(Err(error)).is_ok());
+
+    assert_eq!(
+        interpreter.next().unwrap().unwrap(),
+        Request::GuiPrint(String::from("Continued"))
+    );
+
+    let [error] = interpreter.errors() else {
+        panic!("Expected a single error");
+    };
+    assert!(matches!(error.reason(), ErrorReason::TestFailure { .. }));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_repeat() {
+    let script = r#"
+REPEAT COUNT
+    COMMENT "Outer ${COUNT}"
+    REPEAT 2
+        COMMENT "Inner"
+    ENDREPEAT
+ENDREPEAT
+REPEAT 0
+    COMMENT "Skipped"
+ENDREPEAT
+COMMENT "Done"
+    "#;
+
+    let params = HashMap::from([(String::from("COUNT"), 2)]);
+    let interpreter = Interpreter::try_from_str(script)
+        .unwrap()
+        .with_params(params)
+        .unwrap();
+
+    let comments: Vec<Request> = interpreter
+        .map(|r| r.unwrap())
+        .filter(|r| matches!(r, Request::GuiPrint(_)))
+        .collect();
+    assert_eq!(
+        comments,
+        [
+            Request::GuiPrint(String::from("Outer 2")),
+            Request::GuiPrint(String::from("Inner")),
+            Request::GuiPrint(String::from("Inner")),
+            Request::GuiPrint(String::from("Outer 2")),
+            Request::GuiPrint(String::from("Inner")),
+            Request::GuiPrint(String::from("Inner")),
+            Request::GuiPrint(String::from("Done")),
+        ]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_repeat_undefined_count() {
+    let script = "REPEAT COUNT\nCOMMENT \"Body\"\nENDREPEAT";
+
+    let error = Interpreter::try_from_str(script)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap_err();
+
+    assert!(matches!(
+        error.reason(),
+        ErrorReason::UndefinedVariable { name, .. } if name == "COUNT"
+    ));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_try_from_reader() {
+    let script: &[u8] = b"COMMENT \"Read\"";
+
+    let mut interpreter = Interpreter::try_from_reader(script).unwrap();
+    assert_eq!(
+        interpreter.next().unwrap().unwrap(),
+        Request::GuiPrint(String::from("Read"))
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_try_from_path() {
+    let path = std::env::temp_dir().join("gallivant_test_try_from_path.txt");
+    std::fs::write(&path, "HPMODE\nNOTACOMMAND").unwrap();
+
+    let errors = Interpreter::try_from_path(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    let [error] = &errors[..] else {
+        panic!("Expected a single error. Got: {errors:?}");
+    };
+    assert_eq!(
+        error.notes(),
+        [ErrorNote::Note(format!("In {}:2:1", path.display()).into())]
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_try_from_path_missing() {
+    let path = std::env::temp_dir().join("gallivant_test_missing_script.txt");
+
+    let errors = Interpreter::try_from_path(&path).unwrap_err();
+    let [error] = &errors[..] else {
+        panic!("Expected a single error. Got: {errors:?}");
+    };
+    assert!(matches!(
+        error.reason(),
+        ErrorReason::ScriptRead { path: Some(p), .. } if *p == path
+    ));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_error_filename() {
+    let path = std::env::temp_dir().join("gallivant_test_error_filename.txt");
+    let script = "COMMENT \"Start\"\nASSERT VOLTAGE, 0, 10, \"FAIL\"";
+    std::fs::write(&path, script).unwrap();
+
+    let mut interpreter = Interpreter::try_from_path(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    interpreter.next().unwrap().unwrap();
+    interpreter.complete(Ok(())).unwrap();
+    let error = interpreter.next().unwrap().unwrap_err();
+    let error = interpreter.complete(Err(error)).unwrap_err();
+    let filename = path.display().to_string();
+    assert_eq!(error.filename(), Some(filename.as_str()));
+
+    let mut buffer = Vec::new();
+    error
+        .to_report(script)
+        .write_for_stdout(ariadne::Source::from(script), &mut buffer)
+        .unwrap();
+
+    let output = String::from_utf8(buffer).unwrap();
+    assert!(
+        output.contains(&format!("{filename}: line 2, column 1: ")),
+        "{output}"
+    );
+}
+
+////////////////////////////////////////////////////////////////
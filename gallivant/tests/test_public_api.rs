@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use gallivant::{evaluate, parse_from_str, ErrorReason, EvalState, FrontendRequest};
+
+type Request = FrontendRequest;
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_parse_and_evaluate() {
+    let script = "HPMODE\nCOMMENT \"Start\"\nWAIT 100\nTCUCLOSE 6";
+
+    let ast = parse_from_str(script).unwrap();
+    assert_eq!(ast.len(), 4);
+
+    let mut state = EvalState::new();
+    let requests: Vec<Request> = ast
+        .iter()
+        .map(|expr| evaluate(expr, &mut state).unwrap())
+        .collect();
+
+    assert!(state.hpmode());
+    assert!(matches!(
+        &requests[..],
+        [
+            Request::None,
+            Request::GuiPrint(_),
+            Request::Wait(_),
+            Request::TCUTransact(_),
+        ]
+    ));
+    assert_eq!(requests[2], Request::Wait(Duration::from_millis(100)));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_set_variable() {
+    let ast = parse_from_str("ECHO LIMIT\nDIVVAR HALF, LIMIT, 0").unwrap();
+
+    let mut state = EvalState::new();
+    state.set_variable("LIMIT", 5).unwrap();
+    assert_eq!(state.variable("LIMIT"), Some(5));
+    assert_eq!(
+        evaluate(&ast[0], &mut state).unwrap(),
+        Request::GuiPrint(String::from("LIMIT = 5 ($0005)"))
+    );
+
+    let error = evaluate(&ast[1], &mut state).unwrap_err();
+    assert!(matches!(error.reason(), ErrorReason::DivisionByZero { .. }));
+    assert_eq!(state.variable("HALF"), None);
+
+    for name in ["", "MY LIMIT", "LIMIT\t"] {
+        let error = state.set_variable(name, 1).unwrap_err();
+        assert!(
+            matches!(error.reason(), ErrorReason::InvalidVariableName { name: invalid } if invalid == name),
+            "{error:?}"
+        );
+    }
+    assert_eq!(state.variable("LIMIT"), Some(5));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_parse_error() {
+    let errors = parse_from_str("WAIT 100\nNOTACOMMAND").unwrap_err();
+
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(errors[0].reason(), ErrorReason::SyntaxError(_)));
+    assert_eq!(errors[0].reason().span(), Some(&(9..20)));
+}
+
+////////////////////////////////////////////////////////////////
@@ -0,0 +1,69 @@
+use gallivant::{
+    serde_support::{FrontendRequest, Transaction},
+    TransactionStatus,
+};
+
+type Request = FrontendRequest;
+
+mod common;
+use common::{interpret_script, mocks::PortMock};
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_transaction_round_trip() {
+    let script = r#"TCUTEST 1, 10, 20, 0, "measurement out of range""#;
+    let requests = interpret_script(script);
+
+    let Request::TCUTransact(transaction) = requests[0].clone() else {
+        panic!("Expected TCU transaction but found {:?}", requests[0]);
+    };
+
+    let json = serde_json::to_string(&transaction).unwrap();
+    let deserialized: Transaction = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, transaction);
+
+    // A transaction that has already made progress should resume from the same point.
+    let mut port = PortMock::new();
+    let Ok(TransactionStatus::Ongoing(transaction)) = transaction.process(&mut port) else {
+        panic!("Expected ongoing transaction");
+    };
+
+    let json = serde_json::to_string(&transaction).unwrap();
+    let deserialized: Transaction = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, transaction);
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_request_round_trip() {
+    let script = r#"
+        COMMENT "starting"
+        OPENDIALOG "Connect the printer"
+        TCUCLOSE 6
+        "#;
+
+    for request in interpret_script(script) {
+        let json = serde_json::to_string(&request).unwrap();
+        let deserialized: Request = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, request);
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_span_format() {
+    let script = r#"TCUCLOSE 6"#;
+    let requests = interpret_script(script);
+
+    let Request::TCUTransact(transaction) = requests[0].clone() else {
+        panic!("Expected TCU transaction but found {:?}", requests[0]);
+    };
+
+    let json = serde_json::to_value(&transaction).unwrap();
+    assert_eq!(json["expression"]["span"], serde_json::json!([0, 10]));
+}
+
+////////////////////////////////////////////////////////////////
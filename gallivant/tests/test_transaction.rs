@@ -0,0 +1,542 @@
+use std::collections::{HashMap, HashSet};
+
+use gallivant::{
+    Device, ErrorReason, FailedTest, FrontendRequest, PendingTransaction, Transaction,
+    TransactionPhase, TransactionStatus,
+};
+
+type Request = FrontendRequest;
+
+mod common;
+use common::{interpret_script, mocks::PortMock};
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_echo_mismatch() {
+    let script = r#"TCUCLOSE 6"#;
+    let requests = interpret_script(script);
+    assert!(matches!(requests[..], [Request::TCUTransact(_)]));
+
+    if let Request::TCUTransact(mut transaction) = requests[0].clone() {
+        let mut port = PortMock::new();
+
+        if let Ok(TransactionStatus::Ongoing(tr)) = transaction.process(&mut port) {
+            transaction = tr;
+        } else {
+            panic!()
+        }
+
+        // Corrupted echo.
+        port.rxdata.extend(b"C07\r");
+        let error = transaction.process(&mut port).unwrap_err();
+
+        if let ErrorReason::EchoMismatch { sent, received, .. } = error.reason() {
+            assert_eq!(sent, b"C06\r");
+            assert_eq!(received, b"C07\r");
+        } else {
+            panic!("Expected echo mismatch. Got: {error:?}");
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_echo_length_mismatch() {
+    let script = r#"TCUCLOSE 6"#;
+    let requests = interpret_script(script);
+
+    let Request::TCUTransact(transaction) = requests[0].clone() else {
+        panic!("Expected TCU transaction but found {:?}", requests[0]);
+    };
+
+    let mut port = PortMock::new();
+    port.truncate_echo_at(2);
+
+    let Ok(TransactionStatus::Ongoing(transaction)) = transaction.process(&mut port) else {
+        panic!("Expected ongoing transaction");
+    };
+
+    // Echo cut short as "C0\r".
+    let error = transaction.process(&mut port).unwrap_err();
+    let ErrorReason::EchoLengthMismatch {
+        expected, received, ..
+    } = error.reason()
+    else {
+        panic!("Expected echo length mismatch. Got: {error:?}");
+    };
+    assert_eq!((*expected, *received), (4, 3));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_write_error() {
+    let script = r#"TCUCLOSE 6"#;
+    let requests = interpret_script(script);
+
+    let Request::TCUTransact(transaction) = requests[0].clone() else {
+        panic!("Expected TCU transaction but found {:?}", requests[0]);
+    };
+
+    let mut port = PortMock::new();
+    port.write_error = Some(std::io::ErrorKind::BrokenPipe);
+
+    let error = transaction.process(&mut port).unwrap_err();
+    let ErrorReason::IOError { error, .. } = error.reason() else {
+        panic!("Expected IO error. Got: {error:?}");
+    };
+    assert_eq!(error.kind(), std::io::ErrorKind::BrokenPipe);
+    assert!(port.txdata.is_empty());
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_partial_write() {
+    let script = r#"TCUCLOSE 6"#;
+    let requests = interpret_script(script);
+
+    let Request::TCUTransact(transaction) = requests[0].clone() else {
+        panic!("Expected TCU transaction but found {:?}", requests[0]);
+    };
+
+    // The port times out after accepting only part of the transaction.
+    let mut port = PortMock::new();
+    port.write_capacity = Some(2);
+
+    let Ok(TransactionStatus::Ongoing(transaction)) = transaction.process(&mut port) else {
+        panic!("Expected ongoing transaction");
+    };
+    assert_eq!(port.txdata, b"C0");
+    assert!(matches!(
+        transaction.clone().into_phase(),
+        TransactionPhase::Write(_)
+    ));
+
+    // Nothing more is accepted so the write fails.
+    let error = transaction.clone().process(&mut port).unwrap_err();
+    let ErrorReason::IOError { error, .. } = error.reason() else {
+        panic!("Expected IO error. Got: {error:?}");
+    };
+    assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+
+    // The rest is written once the port accepts it.
+    port.write_capacity = None;
+    let Ok(TransactionStatus::Ongoing(transaction)) = transaction.process(&mut port) else {
+        panic!("Expected ongoing transaction");
+    };
+    assert_eq!(port.txdata, b"C06\r");
+
+    port.rxdata.extend(b"C06\r");
+    assert!(matches!(
+        transaction.process(&mut port),
+        Ok(TransactionStatus::Success(None))
+    ));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_measurement_parse_error() {
+    let script = r#"TCUTEST 3, 1000, 12000, 1, "FAIL""#;
+    let requests = interpret_script(script);
+    assert!(matches!(requests[..], [Request::TCUTransact(_)]));
+
+    if let Request::TCUTransact(mut transaction) = requests[0].clone() {
+        let mut port = PortMock::new();
+
+        if let Ok(TransactionStatus::Ongoing(tr)) = transaction.process(&mut port) {
+            transaction = tr;
+        } else {
+            panic!()
+        }
+
+        // Echo followed by an invalid measurement.
+        port.rxdata.extend(&port.txdata);
+        port.rxdata.extend(b"GHIJ\r");
+
+        let error = transaction.process(&mut port).unwrap_err();
+        if let ErrorReason::MeasurementParse { raw, .. } = error.reason() {
+            assert_eq!(raw, b"GHIJ\r");
+        } else {
+            panic!("Expected measurement parse error. Got: {error:?}");
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_exact_measurement() {
+    let script = r#"TCUEXACT 8, $29, 0, "wrong version""#;
+
+    for (measurement, passed) in [(b"29\r", true), (b"28\r", false)] {
+        let requests = interpret_script(script);
+        let Request::TCUTransact(transaction) = requests[0].clone() else {
+            panic!("Expected TCU transaction but found {:?}", requests[0]);
+        };
+
+        let mut port = PortMock::new();
+        let Ok(TransactionStatus::Ongoing(transaction)) = transaction.process(&mut port) else {
+            panic!("Expected ongoing transaction");
+        };
+
+        port.rxdata.extend(&port.txdata);
+        port.rxdata.extend(measurement);
+
+        let result = transaction.process(&mut port);
+        if passed {
+            assert!(matches!(result, Ok(TransactionStatus::Success(Some(0x29)))));
+            continue;
+        }
+
+        let error = result.unwrap_err();
+        let ErrorReason::TestFailure { test, .. } = error.reason() else {
+            panic!("Expected test failure. Got: {error:?}");
+        };
+
+        assert_eq!(
+            *test,
+            FailedTest {
+                measurement: 0x28,
+                expected: 0x29..=0x29,
+                message: String::from("wrong version"),
+                mask: None,
+            }
+        );
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_failed_test_measurement() {
+    let script = r#"TCUTEST 3, 1000, 2000, 0, "FAIL""#;
+    let requests = interpret_script(script);
+
+    let Request::TCUTransact(transaction) = requests[0].clone() else {
+        panic!("Expected TCU transaction but found {:?}", requests[0]);
+    };
+
+    let mut port = PortMock::new();
+    let Ok(TransactionStatus::Ongoing(transaction)) = transaction.process(&mut port) else {
+        panic!("Expected ongoing transaction");
+    };
+
+    // Echo followed by a measurement of 2500.
+    port.rxdata.extend(&port.txdata);
+    port.rxdata.extend(b"09C4\r");
+
+    let error = transaction.process(&mut port).unwrap_err();
+    let ErrorReason::TestFailure { test, .. } = error.reason() else {
+        panic!("Expected test failure. Got: {error:?}");
+    };
+
+    assert_eq!(
+        *test,
+        FailedTest {
+            measurement: 2500,
+            expected: 1000..=2000,
+            message: String::from("FAIL"),
+            mask: None,
+        }
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_no_echo() {
+    let script =
+        "SETNOECHO ON\nTCUTEST 3, 1000, 2000, 0, \"FAIL\"\nTCUCLOSE 6\nSETNOECHO OFF\nTCUCLOSE 6";
+    let requests = interpret_script(script);
+
+    let [Request::None, Request::TCUTransact(test), Request::TCUTransact(close), Request::None, Request::TCUTransact(echoed)] =
+        &requests[..]
+    else {
+        panic!("Expected three TCU transactions but found {requests:?}");
+    };
+
+    // Only the measurement is returned.
+    let mut port = PortMock::new();
+    let Ok(TransactionStatus::Ongoing(test)) = test.clone().process(&mut port) else {
+        panic!("Expected ongoing transaction");
+    };
+    port.rxdata.extend(b"05DC\r");
+    assert_eq!(
+        test.process(&mut port).unwrap(),
+        TransactionStatus::Success(Some(0x05DC))
+    );
+
+    assert!(!close.expects_response());
+    assert_eq!(
+        close.clone().process(&mut port).unwrap(),
+        TransactionStatus::Success(None)
+    );
+
+    assert!(echoed.expects_response());
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_separate_write_and_read() {
+    let script = r#"TCUTEST 3, 1000, 12000, 1, "FAIL""#;
+    let requests = interpret_script(script);
+
+    let Request::TCUTransact(transaction) = requests[0].clone() else {
+        panic!("Expected TCU transaction. Got: {:?}", requests[0]);
+    };
+
+    // Write to multiple devices before reading from any of them.
+    let mut ports = [PortMock::new(), PortMock::new()];
+    let pending: Vec<PendingTransaction> = ports
+        .iter_mut()
+        .map(|port| transaction.clone().write(port).unwrap())
+        .collect();
+
+    assert!(ports.iter().all(|port| port.txdata == b"M03\r"));
+
+    for (port, pending) in ports.iter_mut().zip(pending) {
+        // Nothing to read yet.
+        let status = pending.read(port).unwrap();
+        let TransactionStatus::Ongoing(transaction) = status else {
+            panic!("Expected ongoing transaction. Got: {status:?}");
+        };
+
+        port.rxdata.extend(b"M03\r3E8\r");
+        let TransactionPhase::Read(pending) = transaction.into_phase() else {
+            panic!("Expected transaction to be in the read phase");
+        };
+        assert_eq!(
+            pending.read(port).unwrap(),
+            TransactionStatus::Success(Some(0x3E8))
+        );
+    }
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_retry_returns_to_write() {
+    let script = r#"TCUTEST 3, 1000, 12000, 1, "FAIL""#;
+    let requests = interpret_script(script);
+
+    let Request::TCUTransact(transaction) = requests[0].clone() else {
+        panic!("Expected TCU transaction. Got: {:?}", requests[0]);
+    };
+
+    let mut port = PortMock::new();
+    let pending = transaction.write(&mut port).unwrap();
+
+    // Measurement out of range so the test is retried.
+    port.rxdata.extend(b"M03\r1\r");
+    let TransactionStatus::Ongoing(transaction) = pending.read(&mut port).unwrap() else {
+        panic!("Expected the test to be retried");
+    };
+
+    let TransactionPhase::Write(transaction) = transaction.into_phase() else {
+        panic!("Expected transaction to be in the write phase");
+    };
+    let pending = transaction.write(&mut port).unwrap();
+
+    port.rxdata.extend(b"M03\r3E8\r");
+    assert_eq!(
+        pending.read(&mut port).unwrap(),
+        TransactionStatus::Success(Some(0x3E8))
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_outlier_warning() {
+    let script = r#"TCUTEST 3, 1000, 1100, 4, "Voltage""#;
+    let requests = interpret_script(script);
+
+    let Request::TCUTransact(transaction) = requests[0].clone() else {
+        panic!("Expected TCU transaction. Got: {:?}", requests[0]);
+    };
+
+    // Send the transaction and respond with a measurement.
+    let measure = |transaction: Transaction, measurement: &[u8]| {
+        let mut port = PortMock::new();
+        let pending = transaction.write(&mut port).unwrap();
+
+        port.rxdata.extend(b"M03\r");
+        port.rxdata.extend(measurement);
+        pending.read(&mut port).unwrap()
+    };
+
+    let transaction = transaction.with_max_std_dev(10.0);
+
+    // Consistent measurements outside of the range are retried without a warning.
+    let TransactionStatus::Ongoing(transaction) = measure(transaction, b"64\r") else {
+        panic!("Expected the test to be retried");
+    };
+
+    let TransactionStatus::Ongoing(transaction) = measure(transaction, b"65\r") else {
+        panic!("Expected the test to be retried");
+    };
+
+    // A measurement far from the others triggers a warning containing each sample.
+    let status = measure(transaction, b"200\r");
+    let TransactionStatus::Warning(transaction, request) = status else {
+        panic!("Expected a warning. Got: {status:?}");
+    };
+
+    let Request::GuiPrint(message) = *request else {
+        panic!("Expected the warning to be printed. Got: {request:?}");
+    };
+
+    assert!(message.ends_with("100, 101, 512"), "{message}");
+
+    // Further retries don't repeat the warning.
+    let status = measure(transaction, b"10\r");
+    assert!(
+        matches!(status, TransactionStatus::Ongoing(_)),
+        "Expected the test to be retried without a warning. Got: {status:?}"
+    );
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_nan_max_std_dev() {
+    let script = r#"TCUTEST 3, 1000, 1100, 4, "Voltage""#;
+    let requests = interpret_script(script);
+
+    let Request::TCUTransact(transaction) = requests[0].clone() else {
+        panic!("Expected TCU transaction. Got: {:?}", requests[0]);
+    };
+
+    // A threshold that isn't finite is ignored, so the transaction still equals itself.
+    let with_nan = transaction.clone().with_max_std_dev(f32::NAN);
+    assert_eq!(with_nan, transaction);
+    assert_eq!(with_nan, with_nan.clone());
+
+    let mut transactions = HashSet::new();
+    assert!(transactions.insert(with_nan.clone()));
+    assert!(!transactions.insert(with_nan));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_bytes_received() {
+    let script = r#"TCUTEST 3, 1000, 12000, 1, "FAIL""#;
+    let requests = interpret_script(script);
+
+    let Request::TCUTransact(transaction) = requests[0].clone() else {
+        panic!("Expected TCU transaction. Got: {:?}", requests[0]);
+    };
+
+    let mut port = PortMock::new();
+    let pending = transaction.write(&mut port).unwrap();
+    assert_eq!(pending.transaction().bytes(), b"M03\r");
+    assert_eq!(pending.transaction().bytes_received(), b"");
+
+    // Measurement out of range so the complete exchange is kept for the retry.
+    port.rxdata.extend(b"M03\r1\r");
+    let TransactionStatus::Ongoing(transaction) = pending.read(&mut port).unwrap() else {
+        panic!("Expected the test to be retried");
+    };
+
+    assert_eq!(transaction.bytes(), b"M03\r");
+    assert_eq!(transaction.bytes_received(), b"M03\r1\r");
+
+    // Writing again starts a new exchange.
+    let pending = transaction.write(&mut port).unwrap();
+    assert_eq!(pending.transaction().bytes_received(), b"");
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_device() {
+    let script =
+        "TCUTEST 3, 1000, 12000, 1, \"FAIL\"\nUSBOPEN\nUSBPRINTERTEST 4, 133, 987, 5, \"FAIL\"";
+    let requests = interpret_script(script);
+
+    let [Request::TCUTransact(tcu), Request::PrinterOpen, Request::PrinterTransact(printer)] =
+        &requests[..]
+    else {
+        panic!("Expected TCU and printer transactions. Got: {requests:?}");
+    };
+
+    assert_eq!(tcu.device(), Device::TCU);
+    assert_eq!(printer.device(), Device::Printer);
+
+    // Transactions can be routed to ports by device.
+    let ports = HashMap::from([(Device::TCU, "COM1"), (Device::Printer, "COM2")]);
+    assert_eq!(ports[&tcu.device()], "COM1");
+    assert_eq!(ports[&printer.device()], "COM2");
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_usbtest_matches_usbprintertest() {
+    let script =
+        "USBOPEN\nUSBTEST 3, 1000, 12000, 1, \"FAIL\"\nUSBPRINTERTEST 3, 1000, 12000, 1, \"FAIL\"";
+    let requests = interpret_script(script);
+
+    let [Request::PrinterOpen, Request::PrinterTransact(usbtest), Request::PrinterTransact(usbprintertest)] =
+        &requests[..]
+    else {
+        panic!("Expected two printer transactions but found {requests:?}");
+    };
+
+    assert_eq!(usbtest.bytes(), usbprintertest.bytes());
+    assert_eq!(usbtest.bytes(), [0x1B, 0x00, b'M', 3]);
+    assert_eq!(usbtest.test(), usbprintertest.test());
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_duplicate_transactions() {
+    let script = "TCUCLOSE 6\nTCUCLOSE 6\nTCUCLOSE 7";
+    let requests = interpret_script(script);
+
+    let [Request::TCUTransact(first), Request::TCUTransact(second), Request::TCUTransact(third)] =
+        &requests[..]
+    else {
+        panic!("Expected three TCU transactions but found {requests:?}");
+    };
+
+    let mut transactions = HashSet::new();
+    assert!(transactions.insert(first.clone()));
+    assert!(!transactions.insert(second.clone()));
+    assert!(transactions.insert(third.clone()));
+}
+
+////////////////////////////////////////////////////////////////
+
+#[test]
+fn test_print_raw_carriage_return_echo() {
+    let script = "PRINTRAW $41 $0D $42";
+    let requests = interpret_script(script);
+
+    let Request::TCUTransact(transaction) = requests[0].clone() else {
+        panic!("Expected TCU transaction but found {:?}", requests[0]);
+    };
+    assert_eq!(transaction.bytes(), b"A\rB\r");
+
+    let mut port = PortMock::new();
+    let Ok(TransactionStatus::Ongoing(transaction)) = transaction.process(&mut port) else {
+        panic!("Expected ongoing transaction");
+    };
+
+    // Only the first \r of the echo received so far.
+    port.rxdata.extend(b"A\r");
+    let Ok(TransactionStatus::Ongoing(transaction)) = transaction.process(&mut port) else {
+        panic!("Expected ongoing transaction");
+    };
+
+    port.rxdata.extend(b"B\r");
+    let status = transaction.process(&mut port).unwrap();
+    assert!(matches!(status, TransactionStatus::Success(None)));
+}